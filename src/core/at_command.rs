@@ -0,0 +1,308 @@
+//! AT 指令事务层
+//!
+//! 在会话链路上发送 `AT...` 并等待最终结果码 (`OK`/`ERROR`/`+CME ERROR: n` 等)：
+//! - 回显 (ATE1) 的指令行自动跳过
+//! - 最终结果码之前的行作为中间响应返回
+//! - 主动上报 (URC，如 `+CREG:`、`RING`) 按前缀识别，通过回调单独分发；
+//!   与当前指令同名的前缀 (AT+CREG? 的 `+CREG:`) 视为响应
+//! - `AT+CMGS` 等指令的 `> ` 输入提示作为最终结果返回
+//!
+//! 应用内保持一个长期存在的引擎 (`SharedAtEngine`)，两次指令之间到达的 URC 也能被分发。
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::session_link::{SerialLink, SessionLink};
+
+/// 常见模组的 URC 前缀 (蜂窝、ESP-AT、BLE)
+pub const DEFAULT_URC_PREFIXES: &[&str] = &[
+    "RING",
+    "+CRING:",
+    "+CLIP:",
+    "+CREG:",
+    "+CGREG:",
+    "+CEREG:",
+    "+CMTI:",
+    "+CMT:",
+    "+CDS:",
+    "+CUSD:",
+    "+CPIN:",
+    "+QIURC:",
+    "+QIND:",
+    "+IPD,",
+    "WIFI CONNECTED",
+    "WIFI GOT IP",
+    "WIFI DISCONNECT",
+    "+BLECONN:",
+    "+BLEDISCONN:",
+    "RDY",
+    "ready",
+];
+
+/// 默认指令超时
+pub const DEFAULT_AT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 最终结果码
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "detail", rename_all = "snake_case")]
+pub enum AtFinal {
+    Ok,
+    Error,
+    /// `+CME ERROR: <err>` (设备/网络错误，数字或文本)
+    CmeError(String),
+    /// `+CMS ERROR: <err>` (短信错误)
+    CmsError(String),
+    NoCarrier,
+    Busy,
+    NoAnswer,
+    NoDialtone,
+    /// `CONNECT [<text>]`，进入数据模式
+    Connect(Option<String>),
+    /// `> ` 输入提示，等待发送数据 (以 Ctrl-Z 结束)
+    Prompt,
+}
+
+impl AtFinal {
+    /// 识别最终结果码行
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        Some(match line {
+            "OK" => Self::Ok,
+            "ERROR" => Self::Error,
+            "NO CARRIER" => Self::NoCarrier,
+            "BUSY" => Self::Busy,
+            "NO ANSWER" => Self::NoAnswer,
+            "NO DIALTONE" | "NO DIAL TONE" => Self::NoDialtone,
+            "CONNECT" => Self::Connect(None),
+            _ => {
+                if let Some(err) = line.strip_prefix("+CME ERROR:") {
+                    Self::CmeError(err.trim().to_string())
+                } else if let Some(err) = line.strip_prefix("+CMS ERROR:") {
+                    Self::CmsError(err.trim().to_string())
+                } else if let Some(text) = line.strip_prefix("CONNECT ") {
+                    Self::Connect(Some(text.trim().to_string()))
+                } else {
+                    return None;
+                }
+            }
+        })
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Ok | Self::Connect(_) | Self::Prompt)
+    }
+}
+
+/// 一次 AT 事务的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct AtResponse {
+    pub command: String,
+    pub result: AtFinal,
+    /// 中间响应行 (不含回显、URC 与最终结果码)
+    pub lines: Vec<String>,
+    /// 事务期间收到的 URC
+    pub urcs: Vec<String>,
+    pub elapsed_ms: u64,
+}
+
+/// 指令的响应前缀：`AT+CSQ` → `+CSQ:`，`AT^SYSINFO` → `^SYSINFO:`
+fn response_prefix(command: &str) -> Option<String> {
+    let body = command.trim();
+    let body = body.get(..2).filter(|p| p.eq_ignore_ascii_case("AT")).map(|_| &body[2..])?;
+    let mut chars = body.chars();
+    let lead = chars.next().filter(|c| matches!(c, '+' | '^' | '$' | '#' | '%'))?;
+    let name: String = chars.take_while(|c| c.is_ascii_alphanumeric() || *c == '_').collect();
+    if name.is_empty() {
+        return None;
+    }
+    Some(format!("{}{}:", lead, name.to_ascii_uppercase()))
+}
+
+/// 应用内共享的会话 AT 引擎 (未使用过或会话已断开时为 None)
+pub type SharedAtEngine = Arc<Mutex<Option<AtEngine<SessionLink>>>>;
+
+/// AT 指令引擎
+pub struct AtEngine<L: SerialLink> {
+    link: L,
+    urc_prefixes: Vec<String>,
+    /// 未成行的接收数据
+    buffer: Vec<u8>,
+}
+
+impl<L: SerialLink> AtEngine<L> {
+    pub fn new(link: L) -> Self {
+        Self {
+            link,
+            urc_prefixes: DEFAULT_URC_PREFIXES.iter().map(|p| p.to_string()).collect(),
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn set_urc_prefixes(&mut self, prefixes: Vec<String>) {
+        self.urc_prefixes = prefixes;
+    }
+
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+
+    fn is_urc(&self, line: &str, own_prefix: Option<&str>) -> bool {
+        if own_prefix.is_some_and(|p| line.starts_with(p)) {
+            return false;
+        }
+        self.urc_prefixes.iter().any(|p| line.starts_with(p.as_str()))
+    }
+
+    /// 从缓冲区取出一行 (去除行尾，跳过空行)；`> ` 提示单独成行返回
+    fn take_line(&mut self) -> Option<String> {
+        loop {
+            let start = self.buffer.iter().position(|&b| b != b'\r' && b != b'\n')?;
+            if start > 0 {
+                self.buffer.drain(..start);
+            }
+            if self.buffer.starts_with(b">") {
+                let end = if self.buffer.get(1) == Some(&b' ') { 2 } else { 1 };
+                self.buffer.drain(..end);
+                return Some(">".to_string());
+            }
+            let end = self.buffer.iter().position(|&b| b == b'\r' || b == b'\n')?;
+            let line: Vec<u8> = self.buffer.drain(..end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+    }
+
+    /// 读取下一行，超过 `deadline` 返回 None
+    fn read_line(&mut self, deadline: Instant) -> Result<Option<String>> {
+        let mut chunk = [0u8; 256];
+        loop {
+            if let Some(line) = self.take_line() {
+                return Ok(Some(line));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let n = self.link.read(&mut chunk, remaining)?;
+            if n == 0 && remaining.is_zero() {
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// 分发两次事务之间收到的 URC (不阻塞)；不匹配 URC 前缀的行 (迟到的响应、终端输出) 丢弃
+    pub fn poll_urcs(&mut self, on_urc: &mut dyn FnMut(&str)) -> Result<Vec<String>> {
+        let mut urcs = Vec::new();
+        while let Some(line) = self.read_line(Instant::now())? {
+            if self.is_urc(&line, None) {
+                on_urc(&line);
+                urcs.push(line);
+            }
+        }
+        Ok(urcs)
+    }
+
+    /// 发送一条指令并等待最终结果码，超时返回错误
+    pub fn send(&mut self, command: &str, timeout: Duration, on_urc: &mut dyn FnMut(&str)) -> Result<AtResponse> {
+        let command = command.trim_end_matches(['\r', '\n']);
+        // 上一事务之后到达的行都是主动上报
+        let mut urcs = self.poll_urcs(on_urc)?;
+
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let own_prefix = response_prefix(command);
+        self.link.write_all(format!("{}\r", command).as_bytes())?;
+
+        let mut lines = Vec::new();
+        let mut echo_pending = true;
+        loop {
+            let line = match self.read_line(deadline)? {
+                Some(line) => line,
+                None => bail!("Timeout waiting for response to {}", command),
+            };
+            // 回显只可能出现在第一行
+            if echo_pending {
+                echo_pending = false;
+                if line.eq_ignore_ascii_case(command.trim()) {
+                    continue;
+                }
+            }
+            let result = if line == ">" { Some(AtFinal::Prompt) } else { AtFinal::parse(&line) };
+            if let Some(result) = result {
+                return Ok(AtResponse {
+                    command: command.to_string(),
+                    result,
+                    lines,
+                    urcs,
+                    elapsed_ms: started.elapsed().as_millis() as u64,
+                });
+            }
+            if self.is_urc(&line, own_prefix.as_deref()) {
+                on_urc(&line);
+                urcs.push(line);
+            } else {
+                lines.push(line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+
+    #[test]
+    fn test_parse_final() {
+        assert_eq!(AtFinal::parse("OK"), Some(AtFinal::Ok));
+        assert_eq!(AtFinal::parse("+CME ERROR: 10"), Some(AtFinal::CmeError("10".to_string())));
+        assert_eq!(AtFinal::parse("CONNECT 115200"), Some(AtFinal::Connect(Some("115200".to_string()))));
+        assert_eq!(AtFinal::parse("+CSQ: 23,99"), None);
+        assert_eq!(response_prefix("AT+CREG?").as_deref(), Some("+CREG:"));
+        assert_eq!(response_prefix("at^sysinfo").as_deref(), Some("^SYSINFO:"));
+        assert_eq!(response_prefix("ATE0"), None);
+    }
+
+    #[test]
+    fn test_transaction_with_echo_and_urc() {
+        let (mut module, link) = pipe_pair();
+        let mut engine = AtEngine::new(link);
+        let device = std::thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            let n = module.read(&mut buf, Duration::from_secs(1)).unwrap();
+            assert_eq!(&buf[..n], b"AT+CREG?\r");
+            module
+                .write_all(b"AT+CREG?\r\r\n+CMTI: \"SM\",3\r\n+CREG: 0,1\r\n\r\nOK\r\n")
+                .unwrap();
+            module.write_all(b"\r\nRING\r\nstray output\r\n").unwrap();
+            let n = module.read(&mut buf, Duration::from_secs(1)).unwrap();
+            assert_eq!(&buf[..n], b"AT+CMGS=\"10086\"\r");
+            module.write_all(b"\r\n> ").unwrap();
+        });
+        let mut routed = Vec::new();
+        let response = engine
+            .send("AT+CREG?", Duration::from_secs(1), &mut |urc| routed.push(urc.to_string()))
+            .unwrap();
+        assert_eq!(response.result, AtFinal::Ok);
+        assert_eq!(response.lines, vec!["+CREG: 0,1"]);
+        assert_eq!(response.urcs, vec!["+CMTI: \"SM\",3"]);
+
+        // 事务之间的 URC 与 `> ` 提示
+        std::thread::sleep(Duration::from_millis(20));
+        let response = engine
+            .send("AT+CMGS=\"10086\"", Duration::from_secs(1), &mut |urc| routed.push(urc.to_string()))
+            .unwrap();
+        device.join().unwrap();
+        assert_eq!(response.result, AtFinal::Prompt);
+        assert_eq!(response.urcs, vec!["RING"]);
+        assert_eq!(routed, vec!["+CMTI: \"SM\",3", "RING"]);
+
+        assert!(engine.send("AT", Duration::from_millis(50), &mut |_| {}).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! 抓包记录 (Capture)
+//!
+//! 为串口收发数据打上纳秒级时间戳，形成统一的事件记录 (`CaptureRecord`)，
+//! 并提供 JSON Lines 格式的存储/读取。pcapng 导出见 `pcapng` 模块。
+
+use anyhow::{Result, anyhow, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::protocol_decoder::DecodedMessage;
+
+/// 数据方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// 设备 -> 本机
+    Rx,
+    /// 本机 -> 设备
+    Tx,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Rx => "RX",
+            Direction::Tx => "TX",
+        }
+    }
+}
+
+/// 记录内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureEvent {
+    /// 收发的原始数据
+    Data { direction: Direction, data: Vec<u8> },
+    /// 用户标记 (书签/注释)
+    Marker { text: String },
+    /// 协议解码结果 (见 `protocol_decoder`)
+    Decoded { protocol: String, direction: Direction, summary: String, fields: serde_json::Value },
+}
+
+/// 带时间戳的事件记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Unix 纪元起的纳秒数
+    pub timestamp_ns: u64,
+    /// 端口名 (如 "COM3")
+    pub port: String,
+    #[serde(flatten)]
+    pub event: CaptureEvent,
+}
+
+impl CaptureRecord {
+    pub fn data(port: &str, direction: Direction, data: &[u8]) -> Self {
+        Self {
+            timestamp_ns: now_ns(),
+            port: port.to_string(),
+            event: CaptureEvent::Data { direction, data: data.to_vec() },
+        }
+    }
+
+    pub fn marker(port: &str, text: &str) -> Self {
+        Self {
+            timestamp_ns: now_ns(),
+            port: port.to_string(),
+            event: CaptureEvent::Marker { text: text.to_string() },
+        }
+    }
+
+    pub fn decoded(port: &str, message: &DecodedMessage) -> Self {
+        Self {
+            timestamp_ns: message.timestamp_ns,
+            port: port.to_string(),
+            event: CaptureEvent::Decoded {
+                protocol: message.protocol.clone(),
+                direction: message.direction,
+                summary: message.summary.clone(),
+                fields: message.fields.clone(),
+            },
+        }
+    }
+}
+
+/// 当前时间 (Unix 纳秒)
+pub fn now_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// 抓包输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureFormat {
+    /// 每行一个 JSON 记录，便于回放与转换
+    Jsonl,
+    /// Wireshark pcapng
+    Pcapng,
+}
+
+impl CaptureFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(CaptureFormat::Jsonl),
+            "pcapng" => Ok(CaptureFormat::Pcapng),
+            _ => Err(anyhow!("Unsupported capture format: {}", s)),
+        }
+    }
+}
+
+/// 抓包输出端
+pub trait CaptureSink: Send {
+    fn write_record(&mut self, record: &CaptureRecord) -> Result<()>;
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// 按格式创建抓包文件
+pub fn create_sink(path: &Path, format: CaptureFormat) -> Result<Box<dyn CaptureSink>> {
+    match format {
+        CaptureFormat::Jsonl => Ok(Box::new(JsonlCaptureWriter::create(path)?)),
+        CaptureFormat::Pcapng => Ok(Box::new(super::pcapng::PcapngWriter::create(path)?)),
+    }
+}
+
+/// 去除数据中 ANSI 转义序列后再写入的包装 (按 端口/方向 分别保留解析状态)
+pub struct StripEscapesSink {
+    inner: Box<dyn CaptureSink>,
+    strippers: HashMap<(String, Direction), super::vt_parser::EscapeStripper>,
+}
+
+impl StripEscapesSink {
+    pub fn new(inner: Box<dyn CaptureSink>) -> Self {
+        Self { inner, strippers: HashMap::new() }
+    }
+}
+
+impl CaptureSink for StripEscapesSink {
+    fn write_record(&mut self, record: &CaptureRecord) -> Result<()> {
+        match &record.event {
+            CaptureEvent::Data { direction, data } => {
+                let stripped = self.strippers
+                    .entry((record.port.clone(), *direction))
+                    .or_default()
+                    .strip(data);
+                if stripped.is_empty() {
+                    return Ok(());
+                }
+                self.inner.write_record(&CaptureRecord {
+                    timestamp_ns: record.timestamp_ns,
+                    port: record.port.clone(),
+                    event: CaptureEvent::Data { direction: *direction, data: stripped },
+                })
+            }
+            CaptureEvent::Marker { .. } | CaptureEvent::Decoded { .. } => self.inner.write_record(record),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// JSON Lines 抓包写入器
+pub struct JsonlCaptureWriter {
+    writer: BufWriter<File>,
+}
+
+impl JsonlCaptureWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create capture file {:?}", path))?;
+        Ok(Self { writer: BufWriter::new(file) })
+    }
+}
+
+impl CaptureSink for JsonlCaptureWriter {
+    fn write_record(&mut self, record: &CaptureRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// 读取 JSON Lines 抓包文件
+pub fn read_jsonl(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open capture file {:?}", path))?;
+    let mut records = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: CaptureRecord = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid capture record at line {}", idx + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 按扩展名读取抓包文件 (.pcapng 或 JSON Lines)
+pub fn read_capture(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let is_pcapng = path.extension()
+        .map(|ext| ext.eq_ignore_ascii_case("pcapng"))
+        .unwrap_or(false);
+    if is_pcapng {
+        super::pcapng::read_pcapng(path)
+    } else {
+        read_jsonl(path)
+    }
+}
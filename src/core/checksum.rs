@@ -0,0 +1,297 @@
+//! 校验和 / CRC 工具
+//!
+//! 常用 CRC-8/16/32 变体 (参数取自 CRC 目录 reveng) 以及 XOR、累加和、LRC。
+//! 协议模块 (Modbus、XMODEM、ZMODEM) 与发送时自动追加校验共用此处的实现。
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+/// CRC 参数 (输入输出同为反射或同为不反射)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrcParams {
+    pub width: u32,
+    pub poly: u32,
+    pub init: u32,
+    pub reflected: bool,
+    pub xorout: u32,
+}
+
+impl CrcParams {
+    pub const CRC8: Self = Self { width: 8, poly: 0x07, init: 0, reflected: false, xorout: 0 };
+    pub const CRC8_MAXIM: Self = Self { width: 8, poly: 0x31, init: 0, reflected: true, xorout: 0 };
+    pub const CRC16_MODBUS: Self = Self { width: 16, poly: 0x8005, init: 0xFFFF, reflected: true, xorout: 0 };
+    pub const CRC16_IBM: Self = Self { width: 16, poly: 0x8005, init: 0, reflected: true, xorout: 0 };
+    pub const CRC16_CCITT_FALSE: Self = Self { width: 16, poly: 0x1021, init: 0xFFFF, reflected: false, xorout: 0 };
+    pub const CRC16_XMODEM: Self = Self { width: 16, poly: 0x1021, init: 0, reflected: false, xorout: 0 };
+    pub const CRC16_KERMIT: Self = Self { width: 16, poly: 0x1021, init: 0, reflected: true, xorout: 0 };
+    pub const CRC16_MCRF4XX: Self = Self { width: 16, poly: 0x1021, init: 0xFFFF, reflected: true, xorout: 0 };
+    pub const CRC32: Self = Self { width: 32, poly: 0x04C1_1DB7, init: 0xFFFF_FFFF, reflected: true, xorout: 0xFFFF_FFFF };
+    pub const CRC32C: Self = Self { width: 32, poly: 0x1EDC_6F41, init: 0xFFFF_FFFF, reflected: true, xorout: 0xFFFF_FFFF };
+
+    fn mask(&self) -> u32 {
+        if self.width >= 32 { u32::MAX } else { (1 << self.width) - 1 }
+    }
+
+    /// 逐位计算
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        let mask = self.mask();
+        if self.reflected {
+            let poly = self.poly.reverse_bits() >> (32 - self.width);
+            let mut crc = self.init.reverse_bits() >> (32 - self.width);
+            for &b in data {
+                crc ^= b as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 { (crc >> 1) ^ poly } else { crc >> 1 };
+                }
+            }
+            (crc ^ self.xorout) & mask
+        } else {
+            let top = 1u32 << (self.width - 1);
+            let mut crc = self.init;
+            for &b in data {
+                crc ^= (b as u32) << (self.width - 8);
+                for _ in 0..8 {
+                    crc = if crc & top != 0 { (crc << 1) ^ self.poly } else { crc << 1 };
+                }
+                crc &= mask;
+            }
+            (crc ^ self.xorout) & mask
+        }
+    }
+}
+
+/// Modbus RTU CRC16 (帧尾按小端追加)
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+    CrcParams::CRC16_MODBUS.compute(data) as u16
+}
+
+/// CRC16-CCITT (XMODEM: 多项式 0x1021，初值 0)
+pub fn crc16_xmodem(data: &[u8]) -> u16 {
+    CrcParams::CRC16_XMODEM.compute(data) as u16
+}
+
+/// CRC-16/MCRF4XX (MAVLink 的 "X.25" 校验)
+pub fn crc16_mcrf4xx(data: &[u8]) -> u16 {
+    CrcParams::CRC16_MCRF4XX.compute(data) as u16
+}
+
+/// CRC32 (IEEE 802.3)
+pub fn crc32(data: &[u8]) -> u32 {
+    CrcParams::CRC32.compute(data)
+}
+
+/// 校验算法 (反序列化经 `parse`，接受 "CRC-16/MODBUS" 等写法)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChecksumAlgorithm {
+    Crc8,
+    Crc8Maxim,
+    Crc16Modbus,
+    Crc16Ibm,
+    Crc16CcittFalse,
+    Crc16Xmodem,
+    Crc16Kermit,
+    Crc16Mcrf4xx,
+    Crc32,
+    #[serde(rename = "crc32c")]
+    Crc32C,
+    /// 逐字节异或 (BCC)
+    Xor8,
+    /// 累加和取低 8 位
+    Sum8,
+    /// 累加和取低 16 位
+    Sum16,
+    /// 纵向冗余校验：累加和的补码 (Modbus ASCII)
+    Lrc,
+}
+
+impl ChecksumAlgorithm {
+    pub const ALL: [ChecksumAlgorithm; 14] = [
+        Self::Crc8,
+        Self::Crc8Maxim,
+        Self::Crc16Modbus,
+        Self::Crc16Ibm,
+        Self::Crc16CcittFalse,
+        Self::Crc16Xmodem,
+        Self::Crc16Kermit,
+        Self::Crc16Mcrf4xx,
+        Self::Crc32,
+        Self::Crc32C,
+        Self::Xor8,
+        Self::Sum8,
+        Self::Sum16,
+        Self::Lrc,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self> {
+        let normalized = s.trim().to_ascii_lowercase().replace(['_', '/', ' '], "-");
+        let normalized = normalized.replace("crc-", "crc");
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == normalized)
+            .ok_or_else(|| anyhow!("Unknown checksum algorithm: {}", s))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Crc8 => "crc8",
+            Self::Crc8Maxim => "crc8-maxim",
+            Self::Crc16Modbus => "crc16-modbus",
+            Self::Crc16Ibm => "crc16-ibm",
+            Self::Crc16CcittFalse => "crc16-ccitt-false",
+            Self::Crc16Xmodem => "crc16-xmodem",
+            Self::Crc16Kermit => "crc16-kermit",
+            Self::Crc16Mcrf4xx => "crc16-mcrf4xx",
+            Self::Crc32 => "crc32",
+            Self::Crc32C => "crc32c",
+            Self::Xor8 => "xor8",
+            Self::Sum8 => "sum8",
+            Self::Sum16 => "sum16",
+            Self::Lrc => "lrc",
+        }
+    }
+
+    fn crc_params(&self) -> Option<CrcParams> {
+        Some(match self {
+            Self::Crc8 => CrcParams::CRC8,
+            Self::Crc8Maxim => CrcParams::CRC8_MAXIM,
+            Self::Crc16Modbus => CrcParams::CRC16_MODBUS,
+            Self::Crc16Ibm => CrcParams::CRC16_IBM,
+            Self::Crc16CcittFalse => CrcParams::CRC16_CCITT_FALSE,
+            Self::Crc16Xmodem => CrcParams::CRC16_XMODEM,
+            Self::Crc16Kermit => CrcParams::CRC16_KERMIT,
+            Self::Crc16Mcrf4xx => CrcParams::CRC16_MCRF4XX,
+            Self::Crc32 => CrcParams::CRC32,
+            Self::Crc32C => CrcParams::CRC32C,
+            _ => return None,
+        })
+    }
+
+    /// 结果字节数
+    pub fn width_bytes(&self) -> usize {
+        match self {
+            Self::Xor8 | Self::Sum8 | Self::Lrc => 1,
+            Self::Sum16 => 2,
+            other => other.crc_params().map(|p| p.width as usize / 8).unwrap_or(1),
+        }
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        if let Some(params) = self.crc_params() {
+            return params.compute(data);
+        }
+        match self {
+            Self::Xor8 => data.iter().fold(0u8, |acc, &b| acc ^ b) as u32,
+            Self::Sum8 => data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) as u32,
+            Self::Sum16 => data.iter().fold(0u16, |acc, &b| acc.wrapping_add(b as u16)) as u32,
+            Self::Lrc => data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)).wrapping_neg() as u32,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ChecksumAlgorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::parse(&name).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 多字节校验值的字节序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+/// 发送时追加校验的声明，如 `{ algorithm: "crc16-modbus", byteOrder: "little" }`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumSpec {
+    pub algorithm: ChecksumAlgorithm,
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+    /// 开头不参与计算的字节数 (如帧头 0xAA 0x55)
+    #[serde(default)]
+    pub skip: usize,
+}
+
+impl ChecksumSpec {
+    pub fn new(algorithm: ChecksumAlgorithm, byte_order: ByteOrder) -> Self {
+        Self { algorithm, byte_order, skip: 0 }
+    }
+
+    /// 计算校验值并编码为字节
+    pub fn checksum_bytes(&self, data: &[u8]) -> Vec<u8> {
+        let covered = data.get(self.skip..).unwrap_or(&[]);
+        let value = self.algorithm.compute(covered);
+        let width = self.algorithm.width_bytes();
+        match self.byte_order {
+            ByteOrder::Big => value.to_be_bytes()[4 - width..].to_vec(),
+            ByteOrder::Little => value.to_le_bytes()[..width].to_vec(),
+        }
+    }
+
+    /// 返回追加了校验值的数据
+    pub fn append(&self, data: &[u8]) -> Vec<u8> {
+        let mut out = data.to_vec();
+        out.extend_from_slice(&self.checksum_bytes(data));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_values() {
+        // 标准校验串 "123456789" 的目录校验值
+        let expected = [
+            (ChecksumAlgorithm::Crc8, 0xF4),
+            (ChecksumAlgorithm::Crc8Maxim, 0xA1),
+            (ChecksumAlgorithm::Crc16Modbus, 0x4B37),
+            (ChecksumAlgorithm::Crc16Ibm, 0xBB3D),
+            (ChecksumAlgorithm::Crc16CcittFalse, 0x29B1),
+            (ChecksumAlgorithm::Crc16Xmodem, 0x31C3),
+            (ChecksumAlgorithm::Crc16Kermit, 0x2189),
+            (ChecksumAlgorithm::Crc16Mcrf4xx, 0x6F91),
+            (ChecksumAlgorithm::Crc32, 0xCBF4_3926),
+            (ChecksumAlgorithm::Crc32C, 0xE306_9283),
+            (ChecksumAlgorithm::Xor8, 0x31),
+            (ChecksumAlgorithm::Sum8, 0xDD),
+            (ChecksumAlgorithm::Sum16, 0x01DD),
+            (ChecksumAlgorithm::Lrc, 0x23),
+        ];
+        for (algorithm, value) in expected {
+            assert_eq!(algorithm.compute(b"123456789"), value, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_append_modbus_little_endian() {
+        let spec = ChecksumSpec::new(ChecksumAlgorithm::Crc16Modbus, ByteOrder::Little);
+        assert_eq!(
+            spec.append(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]),
+            vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]
+        );
+    }
+
+    #[test]
+    fn test_spec_deserialize_and_parse() {
+        let spec: ChecksumSpec = serde_json::from_str(r#"{"algorithm":"crc16-modbus","byteOrder":"little","skip":1}"#).unwrap();
+        assert_eq!(spec.algorithm, ChecksumAlgorithm::Crc16Modbus);
+        assert_eq!(spec.checksum_bytes(&[0xFF, 0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), vec![0x84, 0x0A]);
+        assert_eq!(ChecksumAlgorithm::parse("CRC-16/MODBUS").unwrap(), ChecksumAlgorithm::Crc16Modbus);
+        assert_eq!(ChecksumAlgorithm::parse("crc32c").unwrap(), ChecksumAlgorithm::Crc32C);
+        assert!(ChecksumAlgorithm::parse("md5").is_err());
+
+        let spec: ChecksumSpec = serde_json::from_str(r#"{"algorithm":"CRC-16/MODBUS"}"#).unwrap();
+        assert_eq!(spec.algorithm, ChecksumAlgorithm::Crc16Modbus);
+        assert_eq!(serde_json::to_string(&ChecksumAlgorithm::Crc32C).unwrap(), r#""crc32c""#);
+        assert!(serde_json::from_str::<ChecksumAlgorithm>(r#""md5""#).is_err());
+    }
+}
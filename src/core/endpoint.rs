@@ -0,0 +1,68 @@
+//! 虚拟设备端点
+//!
+//! 回放、模拟器等"假设备"功能需要把数据送到第三方工具可以打开的位置：
+//! 虚拟串口对 (com0com) 的一端，或 Unix 下新建的 pty。
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use serialport::SerialPort;
+use std::time::Duration;
+use log::info;
+
+/// 端点类型
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Endpoint {
+    /// 打开指定串口 (通常是虚拟串口对的一端)
+    Port { port_name: String, baud_rate: u32 },
+    /// 新建 pty，第三方工具打开其从设备路径 (仅 Unix)
+    Pty,
+}
+
+/// 已打开的端点
+pub struct OpenedEndpoint {
+    pub port: Box<dyn SerialPort>,
+    /// 第三方工具应打开的设备路径
+    pub device_path: Option<String>,
+    /// pty 从设备句柄：保持打开，避免主设备在无人连接时读写失败
+    _pty_slave: Option<Box<dyn SerialPort>>,
+}
+
+impl Endpoint {
+    pub fn open(&self, timeout: Duration) -> Result<OpenedEndpoint> {
+        match self {
+            Endpoint::Port { port_name, baud_rate } => {
+                let port = serialport::new(port_name, *baud_rate)
+                    .timeout(timeout)
+                    .open()
+                    .map_err(|e| anyhow!("Failed to open {}: {}", port_name, e))?;
+                Ok(OpenedEndpoint {
+                    port,
+                    device_path: Some(port_name.clone()),
+                    _pty_slave: None,
+                })
+            }
+            Endpoint::Pty => open_pty(timeout),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_pty(timeout: Duration) -> Result<OpenedEndpoint> {
+    let (mut master, slave) = serialport::TTYPort::pair()
+        .map_err(|e| anyhow!("Failed to create pty: {}", e))?;
+    master.set_timeout(timeout).map_err(|e| anyhow!("Failed to configure pty: {}", e))?;
+    let device_path = slave.name();
+    info!("pty created: {:?}", device_path);
+    Ok(OpenedEndpoint {
+        port: Box::new(master),
+        device_path,
+        _pty_slave: Some(Box::new(slave)),
+    })
+}
+
+#[cfg(not(unix))]
+fn open_pty(_timeout: Duration) -> Result<OpenedEndpoint> {
+    info!("pty requested on a non-Unix platform");
+    Err(anyhow!("pty is only available on Unix; use a virtual port pair instead"))
+}
@@ -0,0 +1,640 @@
+//! ESP32 / ESP8266 ROM Bootloader 协议 (esptool 串口协议)
+//!
+//! 在已打开的会话上烧录乐鑫芯片：
+//! - DTR/RTS 自动复位进入下载模式 (经典 EN/IO0 双三极管电路)
+//! - SLIP 封帧的命令/应答，SYNC 握手后按魔数识别芯片
+//! - FLASH_BEGIN 擦除并写入，SPI_FLASH_MD5 校验
+//! - 完成后经 RTS 硬复位运行新固件，恢复会话原波特率继续监视
+
+use anyhow::{Result, anyhow, bail};
+use md5::{Digest, Md5};
+use serde::{Serialize, Deserialize};
+use std::time::{Duration, Instant};
+
+use super::file_transfer::{TransferControl, TransferPhase, TransferProgress, TransferStats};
+use super::firmware_image::Segment;
+use super::session_link::SerialLink;
+
+pub const SLIP_END: u8 = 0xC0;
+pub const SLIP_ESC: u8 = 0xDB;
+const SLIP_ESC_END: u8 = 0xDC;
+const SLIP_ESC_ESC: u8 = 0xDD;
+
+pub const OP_FLASH_BEGIN: u8 = 0x02;
+pub const OP_FLASH_DATA: u8 = 0x03;
+pub const OP_FLASH_END: u8 = 0x04;
+pub const OP_SYNC: u8 = 0x08;
+pub const OP_WRITE_REG: u8 = 0x09;
+pub const OP_READ_REG: u8 = 0x0A;
+pub const OP_SPI_SET_PARAMS: u8 = 0x0B;
+pub const OP_SPI_ATTACH: u8 = 0x0D;
+pub const OP_CHANGE_BAUDRATE: u8 = 0x0F;
+pub const OP_SPI_FLASH_MD5: u8 = 0x13;
+
+/// 数据命令校验和初值
+const CHECKSUM_SEED: u8 = 0xEF;
+/// ROM 写入块大小
+pub const FLASH_WRITE_SIZE: usize = 0x400;
+const FLASH_SECTOR_SIZE: u32 = 0x1000;
+const CHIP_DETECT_MAGIC_REG: u32 = 0x4000_1000;
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+/// 擦除与 MD5 计算按数据量放宽超时
+const ERASE_TIMEOUT_PER_MB: Duration = Duration::from_secs(30);
+const MD5_TIMEOUT_PER_MB: Duration = Duration::from_secs(8);
+
+/// 芯片型号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EspChip {
+    Esp8266,
+    Esp32,
+    Esp32S2,
+    Esp32S3,
+    Esp32C2,
+    Esp32C3,
+    Esp32C6,
+    Esp32H2,
+}
+
+impl EspChip {
+    /// 由 CHIP_DETECT_MAGIC 寄存器值识别
+    pub fn from_magic(magic: u32) -> Option<Self> {
+        Some(match magic {
+            0xFFF0_C101 => Self::Esp8266,
+            0x00F0_1D83 => Self::Esp32,
+            0x0000_07C6 => Self::Esp32S2,
+            0x0000_0009 => Self::Esp32S3,
+            0x6F51_306F | 0x7C41_A06F => Self::Esp32C2,
+            0x6921_506F | 0x1B31_506F | 0x4881_606F | 0x4361_606F => Self::Esp32C3,
+            0x2CE0_806F => Self::Esp32C6,
+            0xD7B7_3E80 => Self::Esp32H2,
+            _ => return None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Esp8266 => "ESP8266",
+            Self::Esp32 => "ESP32",
+            Self::Esp32S2 => "ESP32-S2",
+            Self::Esp32S3 => "ESP32-S3",
+            Self::Esp32C2 => "ESP32-C2",
+            Self::Esp32C3 => "ESP32-C3",
+            Self::Esp32C6 => "ESP32-C6",
+            Self::Esp32H2 => "ESP32-H2",
+        }
+    }
+
+    /// ROM 的 FLASH_BEGIN 是否带"加密写入"参数
+    fn begin_has_encrypt_flag(&self) -> bool {
+        !matches!(self, Self::Esp8266 | Self::Esp32)
+    }
+}
+
+/// 烧录参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EspFlashOptions {
+    /// 通过 DTR/RTS 自动进入下载模式
+    #[serde(default = "default_true")]
+    pub auto_reset: bool,
+    /// 握手后切换到的下载波特率 (ESP8266 ROM 不支持)
+    #[serde(default)]
+    pub baud_rate: Option<u32>,
+    /// Flash 容量 (字节)，用于 SPI_SET_PARAMS
+    #[serde(default = "default_flash_size")]
+    pub flash_size: u32,
+    /// 写入后用 MD5 校验
+    #[serde(default = "default_true")]
+    pub verify: bool,
+    /// 完成后不复位 (保持在下载模式)
+    #[serde(default)]
+    pub no_reset: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_flash_size() -> u32 {
+    4 * 1024 * 1024
+}
+
+impl Default for EspFlashOptions {
+    fn default() -> Self {
+        Self {
+            auto_reset: true,
+            baud_rate: None,
+            flash_size: default_flash_size(),
+            verify: true,
+            no_reset: false,
+        }
+    }
+}
+
+/// SLIP 封帧
+pub fn slip_encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 8);
+    out.push(SLIP_END);
+    for &b in data {
+        match b {
+            SLIP_END => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_END]),
+            SLIP_ESC => out.extend_from_slice(&[SLIP_ESC, SLIP_ESC_ESC]),
+            b => out.push(b),
+        }
+    }
+    out.push(SLIP_END);
+    out
+}
+
+/// 数据命令 (FLASH_DATA 等) 的校验和
+pub fn data_checksum(data: &[u8]) -> u32 {
+    data.iter().fold(CHECKSUM_SEED, |acc, &b| acc ^ b) as u32
+}
+
+/// 命令包：方向 0x00 + 操作码 + 长度 + 校验 + 数据
+pub fn build_command(op: u8, data: &[u8], checksum: u32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(data.len() + 8);
+    packet.push(0x00);
+    packet.push(op);
+    packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    packet.extend_from_slice(&checksum.to_le_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// ESP8266 ROM 的擦除长度缺陷补偿 (与 esptool 相同)
+pub fn esp8266_erase_size(offset: u32, size: u32) -> u32 {
+    const SECTORS_PER_BLOCK: u32 = 16;
+    let num_sectors = size.div_ceil(FLASH_SECTOR_SIZE);
+    let start_sector = offset / FLASH_SECTOR_SIZE;
+    let head_sectors = (SECTORS_PER_BLOCK - start_sector % SECTORS_PER_BLOCK).min(num_sectors);
+    if num_sectors < 2 * head_sectors {
+        num_sectors.div_ceil(2) * FLASH_SECTOR_SIZE
+    } else {
+        (num_sectors - head_sectors) * FLASH_SECTOR_SIZE
+    }
+}
+
+/// 检查各段起始地址按 4 KiB 扇区对齐且互不重叠 (FLASH_BEGIN 按扇区擦除，
+/// 未对齐或重叠的段会擦掉相邻段已写入的内容)
+pub fn validate_segments(segments: &[Segment]) -> Result<()> {
+    let mut ranges = Vec::with_capacity(segments.len());
+    for segment in segments {
+        if segment.address & (FLASH_SECTOR_SIZE - 1) != 0 {
+            bail!("Segment at 0x{:08X} is not aligned to a 4 KiB sector", segment.address);
+        }
+        let end = u32::try_from(segment.data.len())
+            .ok()
+            .and_then(|len| segment.address.checked_add(len))
+            .ok_or_else(|| anyhow!("Segment at 0x{:08X} exceeds the address space", segment.address))?;
+        ranges.push((segment.address, end));
+    }
+    ranges.sort_unstable();
+    for pair in ranges.windows(2) {
+        let ((start, end), (next, _)) = (pair[0], pair[1]);
+        if end > next {
+            bail!("Segment at 0x{:08X}..0x{:08X} overlaps segment at 0x{:08X}", start, end, next);
+        }
+    }
+    Ok(())
+}
+
+fn scaled_timeout(per_mb: Duration, size: usize) -> Duration {
+    let scaled = per_mb.mul_f64(size as f64 / (1024.0 * 1024.0));
+    scaled.max(DEFAULT_TIMEOUT)
+}
+
+/// 应答
+#[derive(Debug, Clone)]
+struct Response {
+    value: u32,
+    data: Vec<u8>,
+}
+
+/// ROM Bootloader 客户端
+pub struct EspLoader<L: SerialLink> {
+    link: L,
+    chip: Option<EspChip>,
+    /// 会话原波特率，结束时恢复
+    saved_baud: Option<u32>,
+}
+
+impl<L: SerialLink> EspLoader<L> {
+    pub fn new(link: L) -> Self {
+        Self { link, chip: None, saved_baud: None }
+    }
+
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    pub fn chip(&self) -> Option<EspChip> {
+        self.chip
+    }
+
+    /// 经典自动下载电路：EN 拉低、IO0 拉高 → 释放 EN 同时拉低 IO0 → 释放 IO0
+    pub fn enter_download_mode(&mut self) -> Result<()> {
+        self.link.set_dtr(false)?;
+        self.link.set_rts(true)?;
+        std::thread::sleep(Duration::from_millis(100));
+        self.link.set_dtr(true)?;
+        self.link.set_rts(false)?;
+        std::thread::sleep(Duration::from_millis(50));
+        self.link.set_dtr(false)?;
+        self.link.clear_input();
+        Ok(())
+    }
+
+    /// 经 RTS 拉低 EN 硬复位，运行用户程序
+    pub fn hard_reset(&mut self) -> Result<()> {
+        self.link.set_rts(true)?;
+        std::thread::sleep(Duration::from_millis(100));
+        self.link.set_rts(false)
+    }
+
+    /// 读取一个 SLIP 帧；遇到非法转义时丢弃当前帧，重新同步到下一个 0xC0 (同 esptool)
+    fn read_frame(&mut self, deadline: Instant) -> Result<Option<Vec<u8>>> {
+        let mut frame = Vec::new();
+        let mut in_frame = false;
+        let mut escaped = false;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let b = match self.link.read_byte(remaining)? {
+                Some(b) => b,
+                None => return Ok(None),
+            };
+            if !in_frame {
+                in_frame = b == SLIP_END;
+                continue;
+            }
+            match (escaped, b) {
+                (false, SLIP_END) if frame.is_empty() => {}
+                (false, SLIP_END) => return Ok(Some(frame)),
+                (false, SLIP_ESC) => escaped = true,
+                (true, SLIP_ESC_END) => {
+                    frame.push(SLIP_END);
+                    escaped = false;
+                }
+                (true, SLIP_ESC_ESC) => {
+                    frame.push(SLIP_ESC);
+                    escaped = false;
+                }
+                (true, other) => {
+                    frame.clear();
+                    escaped = false;
+                    in_frame = other == SLIP_END;
+                }
+                (false, b) => frame.push(b),
+            }
+        }
+    }
+
+    /// 发送命令并等待同一操作码的应答；`payload_len` 为状态字节之前的应答数据长度
+    fn command(&mut self, op: u8, data: &[u8], checksum: u32, timeout: Duration, payload_len: usize) -> Result<Response> {
+        self.link.write_all(&slip_encode(&build_command(op, data, checksum)))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            let frame = self
+                .read_frame(deadline)?
+                .ok_or_else(|| anyhow!("Timeout waiting for response to command 0x{:02X}", op))?;
+            // 忽略非应答帧与其它命令 (如多余的 SYNC 应答)
+            if frame.len() < 8 || frame[0] != 0x01 || frame[1] != op {
+                continue;
+            }
+            let size = u16::from_le_bytes([frame[2], frame[3]]) as usize;
+            let value = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+            let data = frame[8..].get(..size).unwrap_or(&frame[8..]).to_vec();
+            let status = data.get(payload_len).copied().unwrap_or(0);
+            if status != 0 {
+                let error = data.get(payload_len + 1).copied().unwrap_or(0);
+                bail!("Command 0x{:02X} failed: status {} error 0x{:02X}", op, status, error);
+            }
+            return Ok(Response { value, data });
+        }
+    }
+
+    /// SYNC 握手 (ROM 会回多个应答，多余的在后续命令中被忽略)
+    pub fn sync(&mut self) -> Result<()> {
+        let mut payload = vec![0x07, 0x07, 0x12, 0x20];
+        payload.extend_from_slice(&[0x55; 32]);
+        for _ in 0..10 {
+            self.link.clear_input();
+            if self.command(OP_SYNC, &payload, 0, Duration::from_millis(100), 0).is_ok() {
+                std::thread::sleep(Duration::from_millis(50));
+                self.link.clear_input();
+                return Ok(());
+            }
+        }
+        bail!("Failed to sync with ESP ROM bootloader (is the chip in download mode?)")
+    }
+
+    pub fn read_reg(&mut self, address: u32) -> Result<u32> {
+        Ok(self.command(OP_READ_REG, &words(&[address]), 0, DEFAULT_TIMEOUT, 0)?.value)
+    }
+
+    pub fn detect_chip(&mut self) -> Result<EspChip> {
+        let magic = self.read_reg(CHIP_DETECT_MAGIC_REG)?;
+        let chip = EspChip::from_magic(magic).ok_or_else(|| anyhow!("Unknown chip magic 0x{:08X}", magic))?;
+        self.chip = Some(chip);
+        Ok(chip)
+    }
+
+    /// 切换波特率 (ROM 第二个参数为 0)
+    pub fn change_baud(&mut self, baud_rate: u32) -> Result<()> {
+        if self.chip == Some(EspChip::Esp8266) {
+            bail!("ESP8266 ROM does not support changing baud rate");
+        }
+        self.command(OP_CHANGE_BAUDRATE, &words(&[baud_rate, 0]), 0, DEFAULT_TIMEOUT, 0)?;
+        if self.saved_baud.is_none() {
+            self.saved_baud = Some(self.link.baud_rate()?);
+        }
+        self.link.set_baud_rate(baud_rate)?;
+        std::thread::sleep(Duration::from_millis(50));
+        self.link.clear_input();
+        Ok(())
+    }
+
+    pub fn restore_baud(&mut self) -> Result<()> {
+        match self.saved_baud.take() {
+            Some(baud) => self.link.set_baud_rate(baud),
+            None => Ok(()),
+        }
+    }
+
+    /// 连接 SPI Flash 并设置容量 (ESP8266 ROM 不需要)
+    pub fn attach_flash(&mut self, flash_size: u32) -> Result<()> {
+        if self.chip == Some(EspChip::Esp8266) {
+            return Ok(());
+        }
+        self.command(OP_SPI_ATTACH, &[0u8; 8], 0, DEFAULT_TIMEOUT, 0)?;
+        let params = words(&[0, flash_size, 64 * 1024, FLASH_SECTOR_SIZE, 256, 0xFFFF]);
+        self.command(OP_SPI_SET_PARAMS, &params, 0, DEFAULT_TIMEOUT, 0)?;
+        Ok(())
+    }
+
+    /// FLASH_BEGIN：擦除 `[offset, offset + size)` 并准备按块写入
+    pub fn flash_begin(&mut self, offset: u32, size: u32) -> Result<u32> {
+        let blocks = (size as usize).div_ceil(FLASH_WRITE_SIZE) as u32;
+        let erase_size = match self.chip {
+            Some(EspChip::Esp8266) => esp8266_erase_size(offset, size),
+            _ => size,
+        };
+        let mut params = vec![erase_size, blocks, FLASH_WRITE_SIZE as u32, offset];
+        if self.chip.is_some_and(|c| c.begin_has_encrypt_flag()) {
+            params.push(0);
+        }
+        let timeout = scaled_timeout(ERASE_TIMEOUT_PER_MB, size as usize);
+        self.command(OP_FLASH_BEGIN, &words(&params), 0, timeout, 0)?;
+        Ok(blocks)
+    }
+
+    /// 擦除区域 (ROM 没有独立擦除命令，以不写数据的 FLASH_BEGIN 实现)
+    pub fn erase_region(&mut self, offset: u32, size: u32) -> Result<()> {
+        if (offset | size) & (FLASH_SECTOR_SIZE - 1) != 0 {
+            bail!("Erase region must be aligned to 4 KiB sectors");
+        }
+        self.flash_begin(offset, size)?;
+        Ok(())
+    }
+
+    /// FLASH_DATA：写入一块 (不足块大小以 0xFF 补齐)
+    pub fn flash_block(&mut self, data: &[u8], seq: u32) -> Result<()> {
+        let mut block = data.to_vec();
+        block.resize(FLASH_WRITE_SIZE, 0xFF);
+        let mut payload = words(&[block.len() as u32, seq, 0, 0]);
+        payload.extend_from_slice(&block);
+        self.command(OP_FLASH_DATA, &payload, data_checksum(&block), DEFAULT_TIMEOUT, 0)?;
+        Ok(())
+    }
+
+    /// FLASH_END；`reboot` 为 false 时停留在 Bootloader
+    pub fn flash_end(&mut self, reboot: bool) -> Result<()> {
+        self.command(OP_FLASH_END, &words(&[u32::from(!reboot)]), 0, DEFAULT_TIMEOUT, 0)?;
+        Ok(())
+    }
+
+    /// 计算 Flash 区域的 MD5 (ROM 以 32 个十六进制字符返回)
+    pub fn flash_md5(&mut self, offset: u32, size: u32) -> Result<String> {
+        let timeout = scaled_timeout(MD5_TIMEOUT_PER_MB, size as usize);
+        let response = self.command(OP_SPI_FLASH_MD5, &words(&[offset, size, 0, 0]), 0, timeout, 32)?;
+        let digest = response.data.get(..32).ok_or_else(|| anyhow!("Short MD5 response"))?;
+        Ok(String::from_utf8_lossy(digest).to_ascii_lowercase())
+    }
+
+    /// 连接：自动复位 → 同步 → 识别芯片 → 切换波特率 → 连接 Flash
+    pub fn connect(&mut self, options: &EspFlashOptions) -> Result<EspChip> {
+        if options.auto_reset {
+            self.enter_download_mode()?;
+        }
+        self.sync()?;
+        let chip = self.detect_chip()?;
+        if let Some(baud) = options.baud_rate {
+            self.change_baud(baud)?;
+        }
+        self.attach_flash(options.flash_size)?;
+        Ok(chip)
+    }
+
+    /// 烧录多个数据段 (各自的地址即 Flash 偏移)，完成后复位运行
+    pub fn flash(
+        &mut self,
+        segments: &[Segment],
+        options: &EspFlashOptions,
+        control: &TransferControl,
+        progress: &mut dyn FnMut(&TransferProgress),
+    ) -> Result<EspChip> {
+        validate_segments(segments)?;
+        let result = self.flash_inner(segments, options, control, progress);
+        let restored = self.restore_baud();
+        // 失败或取消时同样复位，不让芯片停在下载模式
+        let reset = if options.no_reset { Ok(()) } else { self.hard_reset() };
+        let chip = result?;
+        restored?;
+        reset?;
+        Ok(chip)
+    }
+
+    fn flash_inner(
+        &mut self,
+        segments: &[Segment],
+        options: &EspFlashOptions,
+        control: &TransferControl,
+        progress: &mut dyn FnMut(&TransferProgress),
+    ) -> Result<EspChip> {
+        let mut stats = TransferStats::default();
+        let mut report = |phase, index: usize, bytes: u64, total: u64, stats: &TransferStats| {
+            progress(&TransferProgress {
+                phase,
+                file_name: None,
+                bytes,
+                total: Some(total),
+                file_index: index,
+                file_count: Some(segments.len()),
+                stats: stats.clone(),
+            });
+        };
+
+        report(TransferPhase::Waiting, 0, 0, 0, &stats);
+        let chip = self.connect(options)?;
+
+        for (index, segment) in segments.iter().enumerate() {
+            // 按 4 字节对齐，MD5 覆盖实际写入的内容
+            let mut data = segment.data.clone();
+            data.resize(data.len().div_ceil(4) * 4, 0xFF);
+            let total = data.len() as u64;
+
+            self.flash_begin(segment.address, data.len() as u32)?;
+            for (seq, block) in data.chunks(FLASH_WRITE_SIZE).enumerate() {
+                if control.is_cancelled() {
+                    report(TransferPhase::Cancelled, index, stats.total_bytes, total, &stats);
+                    bail!("Transfer cancelled");
+                }
+                self.flash_block(block, seq as u32)?;
+                stats.blocks += 1;
+                stats.total_bytes += block.len() as u64;
+                let written = ((seq + 1) * FLASH_WRITE_SIZE).min(data.len()) as u64;
+                report(TransferPhase::Transferring, index, written, total, &stats);
+            }
+
+            if options.verify {
+                let expected = format!("{:x}", Md5::digest(&data));
+                let actual = self.flash_md5(segment.address, data.len() as u32)?;
+                if actual != expected {
+                    report(TransferPhase::Failed, index, total, total, &stats);
+                    bail!("MD5 mismatch at 0x{:08X}: expected {}, got {}", segment.address, expected, actual);
+                }
+            }
+        }
+
+        if options.no_reset {
+            self.flash_end(false)?;
+        }
+        // 完成时汇报所有段的写入总量
+        let total = stats.total_bytes;
+        report(TransferPhase::Completed, segments.len().saturating_sub(1), total, total, &stats);
+        Ok(chip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_slip_and_packets() {
+        assert_eq!(slip_encode(&[0x01, 0xC0, 0xDB]), vec![0xC0, 0x01, 0xDB, 0xDC, 0xDB, 0xDD, 0xC0]);
+        let packet = build_command(OP_READ_REG, &words(&[CHIP_DETECT_MAGIC_REG]), 0);
+        assert_eq!(packet, vec![0x00, 0x0A, 0x04, 0x00, 0, 0, 0, 0, 0x00, 0x10, 0x00, 0x40]);
+        assert_eq!(data_checksum(&[0x01, 0x02]), 0xEF ^ 0x03);
+        assert_eq!(EspChip::from_magic(0x00F0_1D83), Some(EspChip::Esp32));
+    }
+
+    #[test]
+    fn test_read_frame_resyncs_after_bad_escape() {
+        let (mut device, host) = pipe_pair();
+        device
+            .write_all(&[0xC0, 0x01, SLIP_ESC, 0x11, 0x22, 0xC0, 0x01, SLIP_ESC, SLIP_ESC_END, 0xC0])
+            .unwrap();
+        let mut loader = EspLoader::new(host);
+        let frame = loader.read_frame(Instant::now() + Duration::from_millis(500)).unwrap();
+        assert_eq!(frame, Some(vec![0x01, 0xC0]));
+    }
+
+    #[test]
+    fn test_esp8266_erase_size() {
+        // 与 esptool 的 get_erase_size 一致
+        assert_eq!(esp8266_erase_size(0, 0x1000), 0x1000);
+        assert_eq!(esp8266_erase_size(0, 0x40000), 0x30000);
+        assert_eq!(esp8266_erase_size(0x10000, 0x2000), 0x1000);
+    }
+
+    /// 模拟 ESP32 ROM：记录写入的 Flash 内容
+    fn fake_rom(link: impl SerialLink) -> BTreeMap<u32, Vec<u8>> {
+        let mut flash: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+        let mut begin = (0u32, 0u32);
+        let mut reader = EspLoader::new(link);
+        while let Ok(Some(frame)) = reader.read_frame(Instant::now() + Duration::from_millis(500)) {
+            let op = frame[1];
+            let data = &frame[8..];
+            let word = |i: usize| u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
+            let mut value = 0u32;
+            let mut payload = Vec::new();
+            match op {
+                OP_READ_REG => value = 0x00F0_1D83,
+                OP_FLASH_BEGIN => {
+                    assert_eq!(data.len(), 16);
+                    begin = (word(3), word(0));
+                }
+                OP_FLASH_DATA => {
+                    let len = word(0) as usize;
+                    let block = &data[16..16 + len];
+                    assert_eq!(u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]), data_checksum(block));
+                    let address = begin.0 + word(1) * FLASH_WRITE_SIZE as u32;
+                    flash.insert(address, block.to_vec());
+                }
+                OP_SPI_FLASH_MD5 => {
+                    let mut content = Vec::new();
+                    for block in flash.range(word(0)..word(0) + word(1)).map(|(_, b)| b) {
+                        content.extend_from_slice(block);
+                    }
+                    content.truncate(word(1) as usize);
+                    payload = format!("{:x}", Md5::digest(&content)).into_bytes();
+                }
+                _ => {}
+            }
+            let mut reply = vec![0x01, op];
+            payload.extend_from_slice(&[0, 0, 0, 0]);
+            reply.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            reply.extend_from_slice(&value.to_le_bytes());
+            reply.extend_from_slice(&payload);
+            reader.link.write_all(&slip_encode(&reply)).unwrap();
+        }
+        flash
+    }
+
+    #[test]
+    fn test_flash_with_md5() {
+        let (host, device) = pipe_pair();
+        let device = std::thread::spawn(move || fake_rom(device));
+
+        let data: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let segments = vec![
+            Segment { address: 0x10000, data: data.clone() },
+            Segment { address: 0x20000, data: vec![0x5A; 10] },
+        ];
+        let options = EspFlashOptions { auto_reset: false, no_reset: true, ..Default::default() };
+        let mut loader = EspLoader::new(host);
+        let mut last = None;
+        let chip = loader
+            .flash(&segments, &options, &TransferControl::new(), &mut |p| last = Some(p.clone()))
+            .unwrap();
+        drop(loader);
+        let flash = device.join().unwrap();
+
+        assert_eq!(chip, EspChip::Esp32);
+        assert_eq!(flash.len(), 4);
+        assert_eq!(&flash[&0x10000][..], &data[..FLASH_WRITE_SIZE]);
+        assert_eq!(flash[&0x10800][..3000 - 2048], data[2048..]);
+        assert_eq!(flash[&0x20000][..10], [0x5A; 10]);
+        let last = last.unwrap();
+        assert_eq!(last.phase, TransferPhase::Completed);
+        assert_eq!((last.bytes, last.total), (3000 + 12, Some(3000 + 12)));
+    }
+
+    #[test]
+    fn test_validate_segments() {
+        let segment = |address, len| Segment { address, data: vec![0; len] };
+        assert!(validate_segments(&[segment(0x1000, 0x1000), segment(0x0, 0x1000)]).is_ok());
+        assert!(validate_segments(&[segment(0x1800, 16)]).is_err());
+        assert!(validate_segments(&[segment(0x10000, 0x1001), segment(0x11000, 16)]).is_err());
+        assert!(validate_segments(&[segment(0xFFFF_F000, 0x1001)]).is_err());
+    }
+}
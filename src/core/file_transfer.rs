@@ -0,0 +1,134 @@
+//! 文件传输公共部分
+//!
+//! XMODEM/YMODEM/ZMODEM 等协议共用的进度、统计与取消控制。
+//! 协议实现运行在阻塞线程上，通过回调上报进度，通过 `TransferControl` 响应取消。
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 传输阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferPhase {
+    /// 等待对端启动 (如接收方的 'C' / NAK)
+    Waiting,
+    Transferring,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// 进度 (`transfer-progress` 事件载荷)
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    pub phase: TransferPhase,
+    pub file_name: Option<String>,
+    /// 当前文件已传输字节数
+    pub bytes: u64,
+    /// 当前文件总字节数 (XMODEM 接收时未知)
+    pub total: Option<u64>,
+    pub file_index: usize,
+    pub file_count: Option<usize>,
+    pub stats: TransferStats,
+}
+
+/// 重传统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransferStats {
+    pub blocks: u64,
+    pub retries: u64,
+    pub crc_errors: u64,
+    pub timeouts: u64,
+    /// 全部文件累计字节数
+    pub total_bytes: u64,
+}
+
+/// 接收到的文件
+#[derive(Debug, Clone)]
+pub struct ReceivedFile {
+    /// XMODEM 不携带文件名
+    pub name: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// 待发送的文件
+#[derive(Debug, Clone)]
+pub struct OutgoingFile {
+    pub name: String,
+    pub data: Vec<u8>,
+    /// 修改时间 (Unix 秒)，YMODEM/ZMODEM 头中携带
+    pub modified: Option<u64>,
+}
+
+impl OutgoingFile {
+    pub fn load(path: &std::path::Path) -> anyhow::Result<Self> {
+        use anyhow::Context;
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "file".to_string());
+        let modified = std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        Ok(Self { name, data, modified })
+    }
+}
+
+/// 取消控制 (跨线程共享)
+#[derive(Debug, Clone, Default)]
+pub struct TransferControl {
+    cancelled: Arc<AtomicBool>,
+    active: Arc<AtomicBool>,
+}
+
+impl TransferControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 标记传输开始；已有传输进行中时返回 false
+    pub fn begin(&self) -> bool {
+        if self.active.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.cancelled.store(false, Ordering::SeqCst);
+        true
+    }
+
+    pub fn finish(&self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    /// 同 `begin`，返回的守卫在离开作用域 (包括 panic) 时调用 `finish`
+    pub fn try_begin(&self) -> Option<TransferGuard> {
+        self.begin().then(|| TransferGuard { control: self.clone() })
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+}
+
+/// 占用会话链路的守卫，drop 时结束占用
+#[derive(Debug)]
+pub struct TransferGuard {
+    control: TransferControl,
+}
+
+impl Drop for TransferGuard {
+    fn drop(&mut self) {
+        self.control.finish();
+    }
+}
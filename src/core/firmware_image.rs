@@ -0,0 +1,496 @@
+//! 固件镜像
+//!
+//! 将 Intel HEX、Motorola S-record、ELF (按程序头 PT_LOAD) 或原始二进制 (指定基地址)
+//! 解析为按地址排列的数据段，供 Bootloader 烧录与发送文件使用；支持间隙填充与校验。
+
+use anyhow::{Result, anyhow, bail, Context};
+use serde::Serialize;
+use std::path::Path;
+
+use super::checksum::crc32;
+use super::hex::parse_hex;
+
+/// 填充为连续数据的最大跨度；STM32 主 Flash 与选项字节等相距很远的段不做整体填充
+pub const MAX_CONTIGUOUS_SPAN: u64 = 16 * 1024 * 1024;
+
+/// 连续地址的数据段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn end(&self) -> u64 {
+        self.address as u64 + self.data.len() as u64
+    }
+}
+
+/// 镜像格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageFormat {
+    IntelHex,
+    Srec,
+    Elf,
+    Binary,
+}
+
+impl ImageFormat {
+    /// 按扩展名判断，未知扩展名按二进制处理
+    pub fn from_path(path: &Path) -> Self {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        match ext.as_str() {
+            "hex" | "ihx" | "ihex" => Self::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => Self::Srec,
+            "elf" | "axf" | "out" => Self::Elf,
+            _ => Self::Binary,
+        }
+    }
+
+    /// 优先按内容识别 (ELF 魔数、HEX/SREC 记录头)，否则按扩展名
+    pub fn detect(path: &Path, data: &[u8]) -> Self {
+        if data.starts_with(b"\x7FELF") {
+            return Self::Elf;
+        }
+        let first_line = data.split(|&b| b == b'\n').next().unwrap_or(&[]);
+        let is_text = !first_line.is_empty() && first_line.iter().all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace());
+        if is_text && first_line.starts_with(b":") {
+            return Self::IntelHex;
+        }
+        if is_text && first_line.len() > 1 && first_line[0] == b'S' && first_line[1].is_ascii_digit() {
+            return Self::Srec;
+        }
+        Self::from_path(path)
+    }
+}
+
+/// 固件镜像：按地址升序、互不重叠的数据段
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareImage {
+    pub segments: Vec<Segment>,
+    /// 入口地址 (HEX 的记录类型 03/05)
+    pub entry: Option<u32>,
+}
+
+impl FirmwareImage {
+    pub fn from_binary(data: Vec<u8>, base_address: u32) -> Self {
+        let mut image = Self::default();
+        if !data.is_empty() {
+            image.segments.push(Segment { address: base_address, data });
+        }
+        image
+    }
+
+    /// 解析 Intel HEX 文本
+    pub fn parse_intel_hex(text: &str) -> Result<Self> {
+        let mut image = Self::default();
+        let mut upper: u32 = 0;
+        let mut segment_base: u32 = 0;
+        let mut pieces = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hex = line
+                .strip_prefix(':')
+                .ok_or_else(|| anyhow!("Line {}: missing ':'", line_no))?;
+            let bytes = parse_hex(hex).with_context(|| format!("Line {}", line_no))?;
+            if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+                bail!("Line {}: invalid record length", line_no);
+            }
+            if bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != 0 {
+                bail!("Line {}: checksum mismatch", line_no);
+            }
+            let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+            let payload = &bytes[4..bytes.len() - 1];
+            match bytes[3] {
+                0x00 => {
+                    let address = upper.wrapping_add(segment_base).wrapping_add(offset);
+                    pieces.push(Segment { address, data: payload.to_vec() });
+                }
+                0x01 => break,
+                0x02 if payload.len() == 2 => {
+                    segment_base = (u16::from_be_bytes([payload[0], payload[1]]) as u32) << 4;
+                    upper = 0;
+                }
+                0x04 if payload.len() == 2 => {
+                    upper = (u16::from_be_bytes([payload[0], payload[1]]) as u32) << 16;
+                    segment_base = 0;
+                }
+                0x03 if payload.len() == 4 => {
+                    let cs = u16::from_be_bytes([payload[0], payload[1]]) as u32;
+                    let ip = u16::from_be_bytes([payload[2], payload[3]]) as u32;
+                    image.entry = Some((cs << 4) + ip);
+                }
+                0x05 if payload.len() == 4 => {
+                    image.entry = Some(u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]));
+                }
+                other => bail!("Line {}: unsupported record type {:02X}", line_no, other),
+            }
+        }
+
+        image.segments = merge_segments(pieces)?;
+        Ok(image)
+    }
+
+    /// 解析 Motorola S-record 文本
+    pub fn parse_srec(text: &str) -> Result<Self> {
+        let mut image = Self::default();
+        let mut pieces = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if !line.is_ascii() {
+                bail!("Line {}: non-ASCII characters", line_no);
+            }
+            let record = line.as_bytes();
+            if record.len() < 4 || record[0] != b'S' {
+                bail!("Line {}: missing 'S'", line_no);
+            }
+            let kind = record[1];
+            let bytes = parse_hex(&line[2..]).with_context(|| format!("Line {}", line_no))?;
+            if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+                bail!("Line {}: invalid record length", line_no);
+            }
+            let (body, checksum) = bytes.split_at(bytes.len() - 1);
+            if !body.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != checksum[0] {
+                bail!("Line {}: checksum mismatch", line_no);
+            }
+            let address_len = match kind {
+                b'0' | b'1' | b'5' | b'9' => 2,
+                b'2' | b'6' | b'8' => 3,
+                b'3' | b'7' => 4,
+                other => bail!("Line {}: unsupported record type S{}", line_no, other as char),
+            };
+            if body.len() < 1 + address_len {
+                bail!("Line {}: record too short", line_no);
+            }
+            let address = body[1..1 + address_len].iter().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            let payload = &body[1 + address_len..];
+            match kind {
+                b'1' | b'2' | b'3' => pieces.push(Segment { address, data: payload.to_vec() }),
+                b'7' | b'8' | b'9' => image.entry = Some(address),
+                // S0 头记录、S5/S6 记录数
+                _ => {}
+            }
+        }
+
+        image.segments = merge_segments(pieces)?;
+        Ok(image)
+    }
+
+    /// 按 ELF 程序头加载 (PT_LOAD 段，以物理地址 p_paddr 为烧录地址)
+    pub fn parse_elf(data: &[u8]) -> Result<Self> {
+        if !data.starts_with(b"\x7FELF") || data.len() < 52 {
+            bail!("Not an ELF file");
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            other => bail!("Invalid ELF class {}", other),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            other => bail!("Invalid ELF data encoding {}", other),
+        };
+        let read = |offset: usize, size: usize| -> Result<u64> {
+            let bytes = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| anyhow!("ELF header truncated at 0x{:X}", offset))?;
+            let value = if big_endian {
+                bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64)
+            } else {
+                bytes.iter().rev().fold(0u64, |acc, &b| (acc << 8) | b as u64)
+            };
+            Ok(value)
+        };
+        let word = if is_64 { 8 } else { 4 };
+
+        let (entry, phoff, phentsize, phnum) = if is_64 {
+            (read(24, 8)?, read(32, 8)?, read(54, 2)?, read(56, 2)?)
+        } else {
+            (read(24, 4)?, read(28, 4)?, read(42, 2)?, read(44, 2)?)
+        };
+
+        let mut pieces = Vec::new();
+        let min_phentsize = if is_64 { 56 } else { 32 };
+        if phnum > 0 && phentsize < min_phentsize {
+            bail!("Invalid program header size {}", phentsize);
+        }
+        let phoff = usize::try_from(phoff).map_err(|_| anyhow!("Program header offset 0x{:X} out of range", phoff))?;
+        for i in 0..phnum as usize {
+            let base = i
+                .checked_mul(phentsize as usize)
+                .and_then(|rel| rel.checked_add(phoff))
+                .ok_or_else(|| anyhow!("Program header {} out of range", i))?;
+            if read(base, 4)? != 1 {
+                continue;
+            }
+            // 32 位: type offset vaddr paddr filesz memsz flags align
+            // 64 位: type flags offset vaddr paddr filesz memsz align
+            let (offset, paddr, filesz) = if is_64 {
+                (read(base + 8, word)?, read(base + 24, word)?, read(base + 32, word)?)
+            } else {
+                (read(base + 4, word)?, read(base + 12, word)?, read(base + 16, word)?)
+            };
+            if filesz == 0 {
+                continue;
+            }
+            let bytes = usize::try_from(offset)
+                .ok()
+                .zip(usize::try_from(filesz).ok())
+                .and_then(|(start, len)| data.get(start..start.checked_add(len)?))
+                .ok_or_else(|| anyhow!("Program header {} exceeds file size", i))?;
+            let address = u32::try_from(paddr).map_err(|_| anyhow!("Segment address 0x{:X} exceeds 32 bits", paddr))?;
+            pieces.push(Segment { address, data: bytes.to_vec() });
+        }
+
+        Ok(Self { segments: merge_segments(pieces)?, entry: u32::try_from(entry).ok() })
+    }
+
+    /// 读取镜像文件；二进制文件使用 `base_address` (默认 0)
+    pub fn load(path: &Path, base_address: Option<u32>) -> Result<Self> {
+        let data = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+        Self::from_bytes(ImageFormat::detect(path, &data), data, base_address)
+    }
+
+    pub fn from_bytes(format: ImageFormat, data: Vec<u8>, base_address: Option<u32>) -> Result<Self> {
+        match format {
+            ImageFormat::IntelHex => {
+                let text = String::from_utf8(data).map_err(|_| anyhow!("Intel HEX file is not valid text"))?;
+                Self::parse_intel_hex(&text)
+            }
+            ImageFormat::Srec => {
+                let text = String::from_utf8(data).map_err(|_| anyhow!("S-record file is not valid text"))?;
+                Self::parse_srec(&text)
+            }
+            ImageFormat::Elf => Self::parse_elf(&data),
+            ImageFormat::Binary => Ok(Self::from_binary(data, base_address.unwrap_or(0))),
+        }
+    }
+
+    /// 全部数据字节数
+    pub fn total_len(&self) -> usize {
+        self.segments.iter().map(|s| s.data.len()).sum()
+    }
+
+    pub fn start_address(&self) -> Option<u32> {
+        self.segments.first().map(|s| s.address)
+    }
+
+    /// 结束地址 (不含)
+    pub fn end_address(&self) -> Option<u64> {
+        self.segments.last().map(|s| s.end())
+    }
+
+    /// 起止地址跨度 (含空隙)
+    pub fn span(&self) -> u64 {
+        match (self.start_address(), self.end_address()) {
+            (Some(start), Some(end)) => end - start as u64,
+            _ => 0,
+        }
+    }
+
+    /// 从起始地址到结束地址的连续数据，段间空隙以 `fill` 填充；跨度超过 `MAX_CONTIGUOUS_SPAN` 时报错
+    pub fn to_contiguous(&self, fill: u8) -> Result<Vec<u8>> {
+        let Some(start) = self.start_address() else {
+            return Ok(Vec::new());
+        };
+        let span = self.span();
+        if span > MAX_CONTIGUOUS_SPAN {
+            bail!("Image spans 0x{:X} bytes, too large to fill gaps", span);
+        }
+        let mut out = Vec::with_capacity(span as usize);
+        for segment in &self.segments {
+            out.resize((segment.address - start) as usize, fill);
+            out.extend_from_slice(&segment.data);
+        }
+        Ok(out)
+    }
+
+    /// 将全部数据段合并为一个，空隙以 `fill` 填充
+    pub fn fill_gaps(&mut self, fill: u8) -> Result<()> {
+        if let Some(address) = self.start_address() {
+            let data = self.to_contiguous(fill)?;
+            self.segments = vec![Segment { address, data }];
+        }
+        Ok(())
+    }
+
+    /// 镜像概要；跨度过大时不计算整体 CRC，只给出各段 CRC
+    pub fn info(&self, format: ImageFormat, fill: u8) -> ImageInfo {
+        ImageInfo {
+            format,
+            entry: self.entry,
+            start_address: self.start_address(),
+            end_address: self.end_address(),
+            data_size: self.total_len(),
+            span_size: self.span(),
+            crc32: self.to_contiguous(fill).ok().map(|data| crc32(&data)),
+            segments: self
+                .segments
+                .iter()
+                .map(|s| SegmentInfo { address: s.address, size: s.data.len(), crc32: crc32(&s.data) })
+                .collect(),
+        }
+    }
+}
+
+/// 数据段概要
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentInfo {
+    pub address: u32,
+    pub size: usize,
+    pub crc32: u32,
+}
+
+/// 镜像概要 (`inspect_firmware_image` 的返回值)
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub entry: Option<u32>,
+    pub start_address: Option<u32>,
+    pub end_address: Option<u64>,
+    /// 各段数据字节数之和
+    pub data_size: usize,
+    /// 起止地址跨度 (含空隙)
+    pub span_size: u64,
+    /// 填充空隙后整个跨度的 CRC32 (跨度超过 `MAX_CONTIGUOUS_SPAN` 时为 None)
+    pub crc32: Option<u32>,
+    pub segments: Vec<SegmentInfo>,
+}
+
+/// 排序并合并相邻数据段，地址重叠时报错
+fn merge_segments(mut pieces: Vec<Segment>) -> Result<Vec<Segment>> {
+    pieces.sort_by_key(|s| s.address);
+    let mut merged: Vec<Segment> = Vec::new();
+    for piece in pieces {
+        if piece.data.is_empty() {
+            continue;
+        }
+        if let Some(last) = merged.last_mut() {
+            if last.end() > piece.address as u64 {
+                bail!("Overlapping data at 0x{:08X}", piece.address);
+            }
+            if last.end() == piece.address as u64 {
+                last.data.extend_from_slice(&piece.data);
+                continue;
+            }
+        }
+        merged.push(piece);
+    }
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_intel_hex() {
+        let text = ":020000040800F2\n\
+                    :0400000001020304F2\n\
+                    :0400040005060708DE\n\
+                    :0400100011121314A2\n\
+                    :0400000508000131BD\n\
+                    :00000001FF\n";
+        let image = FirmwareImage::parse_intel_hex(text).unwrap();
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.segments[0], Segment { address: 0x0800_0000, data: vec![1, 2, 3, 4, 5, 6, 7, 8] });
+        assert_eq!(image.segments[1].address, 0x0800_0010);
+        assert_eq!(image.entry, Some(0x0800_0131));
+        assert_eq!(image.total_len(), 12);
+    }
+
+    #[test]
+    fn test_parse_srec_and_gap_fill() {
+        let text = "S00600004844521B\n\
+                    S107000001020304EE\n\
+                    S1070008AABBCCDDE2\n\
+                    S9030000FC\n";
+        let mut image = FirmwareImage::parse_srec(text).unwrap();
+        assert_eq!(image.segments.len(), 2);
+        assert_eq!(image.entry, Some(0));
+        assert_eq!(image.to_contiguous(0xFF).unwrap(), vec![1, 2, 3, 4, 0xFF, 0xFF, 0xFF, 0xFF, 0xAA, 0xBB, 0xCC, 0xDD]);
+        let info = image.info(ImageFormat::Srec, 0xFF);
+        assert_eq!((info.data_size, info.span_size), (8, 12));
+        assert!(info.crc32.is_some());
+        image.fill_gaps(0xFF).unwrap();
+        assert_eq!(image.segments.len(), 1);
+    }
+
+    #[test]
+    fn test_sparse_image_info() {
+        // 主 Flash 与选项字节相距约 400 MB，不应分配整个跨度
+        let mut image = FirmwareImage::from_binary(vec![0x11; 16], 0x0800_0000);
+        image.segments.push(Segment { address: 0x1FFF_C000, data: vec![0xAA, 0x55] });
+        let info = image.info(ImageFormat::IntelHex, 0xFF);
+        assert_eq!(info.span_size, 0x1FFF_C002 - 0x0800_0000);
+        assert_eq!(info.crc32, None);
+        assert_eq!(info.segments.len(), 2);
+        assert!(image.to_contiguous(0xFF).is_err());
+    }
+
+    #[test]
+    fn test_parse_elf32() {
+        // 最小 ELF32 LE：一个 PT_LOAD 段 (vaddr 0x20000000，paddr 0x08000000)
+        let mut elf = vec![0u8; 0x60];
+        elf[..4].copy_from_slice(b"\x7FELF");
+        elf[4] = 1;
+        elf[5] = 1;
+        elf[24..28].copy_from_slice(&0x0800_0009u32.to_le_bytes());
+        elf[28..32].copy_from_slice(&52u32.to_le_bytes());
+        elf[42..44].copy_from_slice(&32u16.to_le_bytes());
+        elf[44..46].copy_from_slice(&1u16.to_le_bytes());
+        let ph = 52;
+        elf[ph..ph + 4].copy_from_slice(&1u32.to_le_bytes());
+        elf[ph + 4..ph + 8].copy_from_slice(&0x54u32.to_le_bytes());
+        elf[ph + 8..ph + 12].copy_from_slice(&0x2000_0000u32.to_le_bytes());
+        elf[ph + 12..ph + 16].copy_from_slice(&0x0800_0000u32.to_le_bytes());
+        elf[ph + 16..ph + 20].copy_from_slice(&4u32.to_le_bytes());
+        elf[0x54..0x58].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+
+        assert_eq!(ImageFormat::detect(Path::new("fw.bin"), &elf), ImageFormat::Elf);
+        let image = FirmwareImage::parse_elf(&elf).unwrap();
+        assert_eq!(image.segments, vec![Segment { address: 0x0800_0000, data: vec![0xDE, 0xAD, 0xBE, 0xEF] }]);
+        assert_eq!(image.entry, Some(0x0800_0009));
+    }
+
+    #[test]
+    fn test_hex_errors() {
+        assert!(FirmwareImage::parse_intel_hex(":0400000001020304F3\n").is_err());
+        assert!(FirmwareImage::parse_intel_hex(":0400000001020304F2\n:020002001122C9\n").is_err());
+        assert!(FirmwareImage::parse_intel_hex(":04é0000001020304F2\n").is_err());
+        assert!(FirmwareImage::parse_srec("Sé07000001020304EE\n").is_err());
+    }
+
+    #[test]
+    fn test_elf_bad_offsets() {
+        let mut elf = vec![0u8; 0x60];
+        elf[..4].copy_from_slice(b"\x7FELF");
+        elf[4] = 1;
+        elf[5] = 1;
+        elf[28..32].copy_from_slice(&u32::MAX.to_le_bytes());
+        elf[42..44].copy_from_slice(&32u16.to_le_bytes());
+        elf[44..46].copy_from_slice(&2u16.to_le_bytes());
+        assert!(FirmwareImage::parse_elf(&elf).is_err());
+
+        // 段数据偏移 + 长度溢出
+        elf[28..32].copy_from_slice(&52u32.to_le_bytes());
+        elf[44..46].copy_from_slice(&1u16.to_le_bytes());
+        elf[52..56].copy_from_slice(&1u32.to_le_bytes());
+        elf[56..60].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        elf[68..72].copy_from_slice(&0x20u32.to_le_bytes());
+        assert!(FirmwareImage::parse_elf(&elf).is_err());
+    }
+}
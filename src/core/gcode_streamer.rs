@@ -0,0 +1,569 @@
+//! G-code 流式发送 (GRBL / Marlin)
+//!
+//! - GRBL 字符计数协议：控制器 RX 缓冲区 (默认 128 字节) 中未应答行的总长度不超过缓冲区大小，
+//!   每个 `ok` / `error:N` 释放最早一行，可连续塞满缓冲区以保持运动平滑
+//! - Marlin 应答握手：一次只发一行，收到 `ok` 再发下一行；`Error:` 行记入错误，随后的 `ok` 释放该行
+//! - `ALARM:N` 立即终止；`error:N` 记录 (可配置为终止)；有未应答行且超过应答超时无任何应答时终止
+//! - 暂停/继续/取消：GRBL 下暂停发送 `!` (进给保持)、继续发送 `~`、取消发送 Ctrl-X (软复位)；
+//!   Marlin 只是停止/恢复送行。其它实时字节 (如 `?` 状态查询) 可在发送过程中插入
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::session_link::SerialLink;
+
+/// GRBL 实时命令
+pub const REALTIME_STATUS: u8 = b'?';
+pub const REALTIME_FEED_HOLD: u8 = b'!';
+pub const REALTIME_CYCLE_START: u8 = b'~';
+pub const REALTIME_SOFT_RESET: u8 = 0x18;
+
+/// GRBL 默认串口接收缓冲区
+pub const GRBL_RX_BUFFER_SIZE: usize = 128;
+
+/// 读应答的轮询间隔 (期间检查暂停/取消/实时字节)
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 应答单行最大长度，超出部分丢弃
+const MAX_LINE: usize = 256;
+
+/// 默认应答超时 (Marlin 下 `G28`、`G4` 等长命令完成后才应答)
+const DEFAULT_RESPONSE_TIMEOUT_MS: u64 = 30_000;
+
+/// 流控方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowMode {
+    /// GRBL 字符计数
+    CharacterCounting,
+    /// 逐行等待 `ok` (Marlin 等)
+    SendResponse,
+}
+
+/// 发送参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GcodeStreamOptions {
+    pub mode: FlowMode,
+    /// 控制器接收缓冲区大小 (字符计数模式)
+    pub buffer_size: usize,
+    /// 遇到 `error:N` / `Error:` 时终止
+    pub stop_on_error: bool,
+    /// 有未应答行时最长多久收不到任何应答 (ms)，0 表示不限
+    pub response_timeout_ms: u64,
+}
+
+impl Default for GcodeStreamOptions {
+    fn default() -> Self {
+        Self {
+            mode: FlowMode::CharacterCounting,
+            buffer_size: GRBL_RX_BUFFER_SIZE,
+            stop_on_error: false,
+            response_timeout_ms: DEFAULT_RESPONSE_TIMEOUT_MS,
+        }
+    }
+}
+
+/// 程序中的一行 (已去除注释与空白)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GcodeLine {
+    /// 源文件行号 (从 1 开始)
+    pub source_line: usize,
+    pub text: String,
+}
+
+/// 待发送的 G-code 程序
+#[derive(Debug, Clone, Default)]
+pub struct GcodeProgram {
+    pub name: String,
+    pub lines: Vec<GcodeLine>,
+}
+
+impl GcodeProgram {
+    /// 解析程序文本：去除 `;` 行尾注释与 `( )` 括号注释、首尾空白，跳过空行与 `%` 程序界定符
+    pub fn parse(name: &str, text: &str) -> Self {
+        let lines = text
+            .lines()
+            .enumerate()
+            .filter_map(|(i, raw)| {
+                let text = strip_comments(raw);
+                (!text.is_empty() && text != "%").then(|| GcodeLine { source_line: i + 1, text })
+            })
+            .collect();
+        Self { name: name.to_string(), lines }
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {:?}: {}", path, e))?;
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        Ok(Self::parse(&name, &text))
+    }
+}
+
+fn strip_comments(line: &str) -> String {
+    let line = line.split(';').next().unwrap_or("");
+    let mut out = String::with_capacity(line.len());
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+/// GRBL 1.1 错误码说明
+pub fn grbl_error_message(code: u32) -> Option<&'static str> {
+    Some(match code {
+        1 => "G-code words consist of a letter and a value. Letter was not found.",
+        2 => "Numeric value format is not valid or missing an expected value.",
+        3 => "Grbl '$' system command was not recognized or supported.",
+        4 => "Negative value received for an expected positive value.",
+        5 => "Homing cycle is not enabled via settings.",
+        6 => "Minimum step pulse time must be greater than 3usec.",
+        7 => "EEPROM read failed. Reset and restored to default values.",
+        8 => "Grbl '$' command cannot be used unless Grbl is IDLE.",
+        9 => "G-code locked out during alarm or jog state.",
+        10 => "Soft limits cannot be enabled without homing also enabled.",
+        11 => "Max characters per line exceeded. Line was not processed and executed.",
+        12 => "Grbl '$' setting value exceeds the maximum step rate supported.",
+        13 => "Safety door detected as opened and door state initiated.",
+        14 => "Build info or startup line exceeded EEPROM line length limit.",
+        15 => "Jog target exceeds machine travel. Command ignored.",
+        16 => "Jog command with no '=' or contains prohibited g-code.",
+        17 => "Laser mode requires PWM output.",
+        20 => "Unsupported or invalid g-code command found in block.",
+        21 => "More than one g-code command from same modal group found in block.",
+        22 => "Feed rate has not yet been set or is undefined.",
+        23 => "G-code command in block requires an integer value.",
+        24 => "Two G-code commands that both require the use of the XYZ axis words were detected in the block.",
+        25 => "A G-code word was repeated in the block.",
+        26 => "A G-code command implicitly or explicitly requires XYZ axis words in the block, but none were detected.",
+        27 => "N line number value is not within the valid range of 1 - 9,999,999.",
+        28 => "A G-code command was sent, but is missing some required P or L value words in the line.",
+        29 => "Grbl supports six work coordinate systems G54-G59. G59.1, G59.2, and G59.3 are not supported.",
+        30 => "The G53 G-code command requires either a G0 seek or G1 feed motion mode to be active.",
+        31 => "There are unused axis words in the block and G80 motion mode cancel is active.",
+        32 => "A G2 or G3 arc was commanded but there are no XYZ axis words in the selected plane to trace the arc.",
+        33 => "The motion command has an invalid target.",
+        34 => "Arc radius value is invalid.",
+        35 => "A G2 or G3 arc, traced with the offset definition, is missing the IJK offset word in the selected plane.",
+        36 => "There are unused, leftover G-code words that aren't used by any command in the block.",
+        37 => "The G43.1 dynamic tool length offset command cannot apply an offset to an axis other than its configured axis.",
+        38 => "Tool number greater than max supported value.",
+        _ => return None,
+    })
+}
+
+/// GRBL 1.1 报警码说明
+pub fn grbl_alarm_message(code: u32) -> Option<&'static str> {
+    Some(match code {
+        1 => "Hard limit triggered. Machine position is likely lost due to sudden and immediate halt.",
+        2 => "G-code motion target exceeds machine travel.",
+        3 => "Reset while in motion. Machine position is likely lost.",
+        4 => "Probe fail. The probe is not in the expected initial state before starting probe cycle.",
+        5 => "Probe fail. Probe did not contact the workpiece within the programmed travel.",
+        6 => "Homing fail. Reset during active homing cycle.",
+        7 => "Homing fail. Safety door was opened during active homing cycle.",
+        8 => "Homing fail. Cycle failed to clear limit switch when pulling off.",
+        9 => "Homing fail. Could not find limit switch within search distance.",
+        10 => "Homing fail. On dual axis machines, could not find the second limit switch for self-squaring.",
+        _ => return None,
+    })
+}
+
+/// 发送阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamPhase {
+    Streaming,
+    Paused,
+    Completed,
+    Cancelled,
+    /// 控制器报警，已停止
+    Alarm,
+    /// 因错误终止 (`stop_on_error`)
+    Failed,
+    /// 超过 `response_timeout_ms` 未收到应答，已停止
+    TimedOut,
+}
+
+/// 一条控制器报错
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GcodeError {
+    /// 出错行的源文件行号
+    pub source_line: usize,
+    pub line: String,
+    /// 控制器原始应答
+    pub response: String,
+    /// 已知错误码的说明
+    pub message: Option<String>,
+}
+
+/// 进度 (`gcode-progress` 事件载荷)
+#[derive(Debug, Clone, Serialize)]
+pub struct GcodeProgress {
+    pub phase: StreamPhase,
+    pub program: String,
+    pub total_lines: usize,
+    pub lines_sent: usize,
+    /// 已收到 `ok` / `error` 应答的行数
+    pub lines_acked: usize,
+    /// 控制器缓冲区中未应答的字节数
+    pub buffered_bytes: usize,
+    pub errors: Vec<GcodeError>,
+    /// `ALARM:N` 应答及说明
+    pub alarm: Option<String>,
+    pub elapsed_ms: u64,
+}
+
+/// 暂停/取消/实时字节控制 (跨线程共享)
+#[derive(Debug, Clone, Default)]
+pub struct StreamControl {
+    active: Arc<AtomicBool>,
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    realtime: Arc<Mutex<Vec<u8>>>,
+}
+
+impl StreamControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 标记发送开始；已有发送进行中时返回 false
+    pub fn begin(&self) -> bool {
+        if self.active.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.cancelled.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+        if let Ok(mut queue) = self.realtime.lock() {
+            queue.clear();
+        }
+        true
+    }
+
+    pub fn finish(&self) {
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// 在下一次轮询时插入实时字节 (不占控制器行缓冲区)
+    pub fn send_realtime(&self, byte: u8) {
+        if let Ok(mut queue) = self.realtime.lock() {
+            queue.push(byte);
+        }
+    }
+
+    fn take_realtime(&self) -> Vec<u8> {
+        self.realtime.lock().map(|mut q| std::mem::take(&mut *q)).unwrap_or_default()
+    }
+}
+
+/// 控制器应答分类
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Response {
+    Ok,
+    /// GRBL `error:N`，代替 `ok` 释放一行
+    GrblError(Option<u32>),
+    /// Marlin `Error:...`，随后仍有 `ok`
+    MarlinError,
+    Alarm(Option<u32>),
+    /// 状态报告、`[MSG:...]`、欢迎信息等
+    Other,
+}
+
+fn classify(line: &str) -> Response {
+    if line == "ok" || line.starts_with("ok ") {
+        Response::Ok
+    } else if let Some(code) = line.strip_prefix("error:") {
+        Response::GrblError(code.trim().parse().ok())
+    } else if line.starts_with("Error:") || line.starts_with("!!") {
+        Response::MarlinError
+    } else if let Some(code) = line.strip_prefix("ALARM:") {
+        Response::Alarm(code.trim().parse().ok())
+    } else {
+        Response::Other
+    }
+}
+
+/// 按行读取控制器应答
+struct ResponseReader {
+    line: Vec<u8>,
+}
+
+impl ResponseReader {
+    fn poll(&mut self, link: &mut impl SerialLink, timeout: Duration) -> Result<Vec<String>> {
+        let mut buf = [0u8; 256];
+        let n = link.read(&mut buf, timeout)?;
+        let mut lines = Vec::new();
+        for &b in &buf[..n] {
+            match b {
+                b'\n' => {
+                    let text = String::from_utf8_lossy(&std::mem::take(&mut self.line)).trim().to_string();
+                    if !text.is_empty() {
+                        lines.push(text);
+                    }
+                }
+                b'\r' => {}
+                b if self.line.len() < MAX_LINE => self.line.push(b),
+                _ => {}
+            }
+        }
+        Ok(lines)
+    }
+}
+
+/// 在链路上发送程序，直到全部行得到应答、报警、取消或 (配置时) 出错
+pub fn stream(
+    link: &mut impl SerialLink,
+    program: &GcodeProgram,
+    options: &GcodeStreamOptions,
+    control: &StreamControl,
+    on_progress: &mut dyn FnMut(&GcodeProgress),
+) -> Result<GcodeProgress> {
+    let grbl = options.mode == FlowMode::CharacterCounting;
+    if grbl {
+        if let Some(line) = program.lines.iter().find(|l| l.text.len() + 1 > options.buffer_size) {
+            bail!("Line {} is longer than the {}-byte controller buffer", line.source_line, options.buffer_size);
+        }
+    }
+
+    let started = Instant::now();
+    let mut progress = GcodeProgress {
+        phase: StreamPhase::Streaming,
+        program: program.name.clone(),
+        total_lines: program.lines.len(),
+        lines_sent: 0,
+        lines_acked: 0,
+        buffered_bytes: 0,
+        errors: Vec::new(),
+        alarm: None,
+        elapsed_ms: 0,
+    };
+    // 已发送未应答的行：(行序号, 字节数)
+    let mut in_flight: VecDeque<(usize, usize)> = VecDeque::new();
+    let mut reader = ResponseReader { line: Vec::new() };
+    let response_timeout = Duration::from_millis(options.response_timeout_ms);
+    // 最近一次收到应答 (或开始等待应答) 的时间
+    let mut last_response = Instant::now();
+    link.clear_input();
+    on_progress(&progress);
+
+    loop {
+        if control.cancelled.load(Ordering::SeqCst) {
+            if grbl {
+                link.write_all(&[REALTIME_SOFT_RESET])?;
+            }
+            progress.phase = StreamPhase::Cancelled;
+            break;
+        }
+
+        let mut realtime = control.take_realtime();
+        let paused = control.paused.load(Ordering::SeqCst);
+        let phase = if paused { StreamPhase::Paused } else { StreamPhase::Streaming };
+        if phase != progress.phase {
+            if grbl {
+                realtime.push(if paused { REALTIME_FEED_HOLD } else { REALTIME_CYCLE_START });
+            }
+            progress.phase = phase;
+            on_progress(&progress);
+        }
+        if !realtime.is_empty() {
+            link.write_all(&realtime)?;
+        }
+
+        // 暂停或无未应答行时不计超时
+        if paused || in_flight.is_empty() {
+            last_response = Instant::now();
+        }
+        if !paused {
+            while let Some(line) = program.lines.get(progress.lines_sent) {
+                let len = line.text.len() + 1;
+                let fits = if grbl {
+                    progress.buffered_bytes + len <= options.buffer_size
+                } else {
+                    in_flight.is_empty()
+                };
+                if !fits {
+                    break;
+                }
+                link.write_all(format!("{}\n", line.text).as_bytes())?;
+                in_flight.push_back((progress.lines_sent, len));
+                progress.buffered_bytes += len;
+                progress.lines_sent += 1;
+            }
+        }
+
+        if progress.lines_sent == program.lines.len() && in_flight.is_empty() {
+            progress.phase = StreamPhase::Completed;
+            break;
+        }
+
+        let mut changed = false;
+        let responses = reader.poll(link, POLL_INTERVAL)?;
+        if !responses.is_empty() {
+            last_response = Instant::now();
+        } else if !response_timeout.is_zero() && !in_flight.is_empty() && last_response.elapsed() >= response_timeout {
+            progress.phase = StreamPhase::TimedOut;
+            break;
+        }
+        for response in responses {
+            let kind = classify(&response);
+            let error = match kind {
+                Response::GrblError(code) => Some(code.and_then(grbl_error_message)),
+                Response::MarlinError => Some(None),
+                _ => None,
+            };
+            if let (Some(message), Some(&(index, _))) = (error, in_flight.front()) {
+                let line = &program.lines[index];
+                progress.errors.push(GcodeError {
+                    source_line: line.source_line,
+                    line: line.text.clone(),
+                    response: response.clone(),
+                    message: message.map(str::to_string),
+                });
+                changed = true;
+            }
+            match kind {
+                Response::Ok | Response::GrblError(_) => {
+                    if let Some((_, len)) = in_flight.pop_front() {
+                        progress.buffered_bytes -= len;
+                        progress.lines_acked += 1;
+                        changed = true;
+                    }
+                }
+                Response::Alarm(code) => {
+                    let message = code.and_then(grbl_alarm_message);
+                    progress.alarm = Some(match message {
+                        Some(message) => format!("{} ({})", response, message),
+                        None => response.clone(),
+                    });
+                    progress.phase = StreamPhase::Alarm;
+                }
+                Response::MarlinError | Response::Other => {}
+            }
+        }
+        if progress.phase == StreamPhase::Alarm {
+            break;
+        }
+        if options.stop_on_error && !progress.errors.is_empty() {
+            progress.phase = StreamPhase::Failed;
+            break;
+        }
+        if changed {
+            progress.elapsed_ms = started.elapsed().as_millis() as u64;
+            on_progress(&progress);
+        }
+    }
+
+    progress.elapsed_ms = started.elapsed().as_millis() as u64;
+    on_progress(&progress);
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+
+    #[test]
+    fn test_parse_program() {
+        let program = GcodeProgram::parse("job.nc", "%\n; header\nG21 (mm) G90\n\n  G1 X10 F500 ; move\n(only comment)\n%\n");
+        assert_eq!(
+            program.lines,
+            vec![
+                GcodeLine { source_line: 3, text: "G21  G90".to_string() },
+                GcodeLine { source_line: 5, text: "G1 X10 F500".to_string() },
+            ]
+        );
+        assert_eq!(classify("ok"), Response::Ok);
+        assert_eq!(classify("error:20"), Response::GrblError(Some(20)));
+        assert_eq!(classify("ALARM:1"), Response::Alarm(Some(1)));
+        assert_eq!(classify("<Idle|MPos:0.000,0.000,0.000|FS:0,0>"), Response::Other);
+    }
+
+    /// 模拟 GRBL：检查未应答字节不超过 128，第 `bad` 行应答 error:20，其余 ok
+    fn fake_grbl(mut link: impl SerialLink, bad: usize) -> Vec<String> {
+        let mut received = Vec::new();
+        let mut pending: VecDeque<usize> = VecDeque::new();
+        let mut line = Vec::new();
+        let mut buf = [0u8; 64];
+        loop {
+            let n = link.read(&mut buf, Duration::from_millis(300)).unwrap();
+            if n == 0 {
+                break;
+            }
+            for &b in &buf[..n] {
+                line.push(b);
+                if b == b'\n' {
+                    pending.push_back(line.len());
+                    received.push(String::from_utf8(std::mem::take(&mut line)).unwrap().trim().to_string());
+                }
+            }
+            assert!(pending.iter().sum::<usize>() + line.len() <= GRBL_RX_BUFFER_SIZE);
+            while pending.pop_front().is_some() {
+                let acked = received.len() - pending.len();
+                let reply = if acked == bad { "error:20\r\n" } else { "ok\r\n" };
+                link.write_all(reply.as_bytes()).unwrap();
+            }
+        }
+        received
+    }
+
+    #[test]
+    fn test_character_counting_stream() {
+        let text: String = (0..40).map(|i| format!("G1 X{} Y{} F1000 ; step {}\n", i, i * 2, i)).collect();
+        let program = GcodeProgram::parse("job.nc", &text);
+        let (mut host, device) = pipe_pair();
+        let device = std::thread::spawn(move || fake_grbl(device, 5));
+
+        let mut events = 0;
+        let result = stream(&mut host, &program, &GcodeStreamOptions::default(), &StreamControl::new(), &mut |_| events += 1)
+            .unwrap();
+        drop(host);
+        let received = device.join().unwrap();
+
+        assert_eq!(result.phase, StreamPhase::Completed);
+        assert_eq!((result.lines_sent, result.lines_acked, result.buffered_bytes), (40, 40, 0));
+        assert_eq!(received.len(), 40);
+        assert_eq!(received[4], "G1 X4 Y8 F1000");
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].source_line, 5);
+        assert!(result.errors[0].message.as_deref().unwrap().starts_with("Unsupported"));
+        assert!(events > 2);
+    }
+
+    #[test]
+    fn test_response_timeout() {
+        let program = GcodeProgram::parse("job.nc", "G1 X1\nG1 X2\n");
+        let (mut host, _device) = pipe_pair();
+        let options = GcodeStreamOptions { mode: FlowMode::SendResponse, response_timeout_ms: 100, ..Default::default() };
+        let result = stream(&mut host, &program, &options, &StreamControl::new(), &mut |_| {}).unwrap();
+        assert_eq!(result.phase, StreamPhase::TimedOut);
+        assert_eq!((result.lines_sent, result.lines_acked), (1, 0));
+    }
+}
@@ -0,0 +1,34 @@
+//! 十六进制文本解析
+//!
+//! 触发规则、模拟器、报文模板与固件镜像共用，输入可能来自用户或文件，非法字符一律报错而不是 panic。
+
+use anyhow::{Result, anyhow};
+
+/// 解析十六进制字符串 ("AA 55", "aa55", "0xAA,0x55")
+pub fn parse_hex(s: &str) -> Result<Vec<u8>> {
+    let mut digits = Vec::new();
+    for c in s.replace("0x", "").replace("0X", "").chars() {
+        if c.is_whitespace() || c == ',' {
+            continue;
+        }
+        let digit = c.to_digit(16).ok_or_else(|| anyhow!("Invalid hex digit '{}' in '{}'", c, s))?;
+        digits.push(digit as u8);
+    }
+    if digits.len() & 1 == 1 {
+        return Err(anyhow!("Odd number of hex digits in '{}'", s));
+    }
+    Ok(digits.chunks(2).map(|pair| (pair[0] << 4) | pair[1]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("AA 55 0x01,02").unwrap(), vec![0xAA, 0x55, 0x01, 0x02]);
+        assert!(parse_hex("ABC").is_err());
+        assert!(parse_hex("AA é5").is_err());
+        assert!(parse_hex("ÄÄ").is_err());
+    }
+}
@@ -0,0 +1,261 @@
+//! 行组装与换行符规范化
+//!
+//! 将解码后的 RX 文本按会话配置的行结束符切分为完整的行，
+//! 每行的时间戳取自该行第一个字符到达的时间；空闲超时后强制输出未结束的行。
+
+use serde::{Serialize, Deserialize};
+
+/// 行结束符
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LineEnding {
+    Lf,
+    Cr,
+    CrLf,
+    /// CR、LF、CRLF 均视为一次换行
+    Any,
+    /// 自定义结束序列 (如 ";" 或 "\r\r")
+    Custom { sequence: String },
+}
+
+/// 行组装配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineAssemblerConfig {
+    #[serde(default = "default_ending")]
+    pub ending: LineEnding,
+    /// 空闲多久后输出未结束的行 (ms)，0 表示不超时
+    #[serde(default = "default_idle_flush")]
+    pub idle_flush_ms: u64,
+    /// 单行最大长度，超出后强制断行
+    #[serde(default = "default_max_line_len")]
+    pub max_line_len: usize,
+    /// 去除行内的 ANSI 转义序列
+    #[serde(default)]
+    pub strip_escapes: bool,
+}
+
+fn default_ending() -> LineEnding {
+    LineEnding::Any
+}
+
+fn default_idle_flush() -> u64 {
+    200
+}
+
+fn default_max_line_len() -> usize {
+    4096
+}
+
+impl Default for LineAssemblerConfig {
+    fn default() -> Self {
+        Self {
+            ending: default_ending(),
+            idle_flush_ms: default_idle_flush(),
+            max_line_len: default_max_line_len(),
+            strip_escapes: false,
+        }
+    }
+}
+
+/// 组装完成的一行 (`serial-line` 事件载荷)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssembledLine {
+    /// 不含结束符的行内容
+    pub text: String,
+    /// 行首字符到达时间 (Unix 纳秒)
+    pub timestamp_ns: u64,
+    /// false 表示由空闲超时或长度上限强制输出
+    pub terminated: bool,
+}
+
+/// 行组装器
+pub struct LineAssembler {
+    config: LineAssemblerConfig,
+    current: String,
+    started_ns: Option<u64>,
+    last_input_ns: u64,
+    /// Any 模式下刚遇到 CR，若下一个字符是 LF 则吞掉
+    pending_cr: bool,
+}
+
+impl Default for LineAssembler {
+    fn default() -> Self {
+        Self::new(LineAssemblerConfig::default())
+    }
+}
+
+impl LineAssembler {
+    pub fn new(config: LineAssemblerConfig) -> Self {
+        Self {
+            config,
+            current: String::new(),
+            started_ns: None,
+            last_input_ns: 0,
+            pending_cr: false,
+        }
+    }
+
+    pub fn config(&self) -> &LineAssemblerConfig {
+        &self.config
+    }
+
+    /// 更换配置 (丢弃未完成的行)
+    pub fn set_config(&mut self, config: LineAssemblerConfig) {
+        *self = Self::new(config);
+    }
+
+    /// 喂入一块文本，`now_ns` 为该块到达时间
+    ///
+    /// `text` 为空表示这块字节全部留在解码器中 (被拆开的多字节字符)，
+    /// 该字符属于当前行，行首时间按这块字节的到达时间记录。
+    pub fn feed(&mut self, text: &str, now_ns: u64) -> Vec<AssembledLine> {
+        let mut lines = Vec::new();
+        self.last_input_ns = now_ns;
+        if text.is_empty() {
+            self.started_ns.get_or_insert(now_ns);
+            return lines;
+        }
+
+        for c in text.chars() {
+            if self.pending_cr {
+                self.pending_cr = false;
+                if c == '\n' {
+                    continue;
+                }
+            }
+
+            let complete = match &self.config.ending {
+                LineEnding::Lf => c == '\n',
+                LineEnding::Cr => c == '\r',
+                LineEnding::Any => {
+                    if c == '\r' {
+                        self.pending_cr = true;
+                    }
+                    c == '\r' || c == '\n'
+                }
+                LineEnding::CrLf => {
+                    self.push_char(c, now_ns);
+                    self.strip_suffix("\r\n")
+                }
+                LineEnding::Custom { sequence } => {
+                    let sequence = sequence.clone();
+                    self.push_char(c, now_ns);
+                    !sequence.is_empty() && self.strip_suffix(&sequence)
+                }
+            };
+
+            if complete {
+                // 行首即为结束符时也记录一个时间戳 (空行)
+                self.started_ns.get_or_insert(now_ns);
+                lines.push(self.take_line(true));
+                continue;
+            }
+
+            if matches!(self.config.ending, LineEnding::Lf | LineEnding::Cr | LineEnding::Any) {
+                self.push_char(c, now_ns);
+            }
+            if self.current.len() >= self.config.max_line_len {
+                lines.push(self.take_line(false));
+            }
+        }
+        lines
+    }
+
+    /// 空闲超时检查：距上次输入超过 `idle_flush_ms` 时输出未结束的行
+    pub fn flush_idle(&mut self, now_ns: u64) -> Option<AssembledLine> {
+        if self.config.idle_flush_ms == 0 || self.current.is_empty() {
+            return None;
+        }
+        let idle_ns = now_ns.saturating_sub(self.last_input_ns);
+        if idle_ns >= self.config.idle_flush_ms.saturating_mul(1_000_000) {
+            Some(self.take_line(false))
+        } else {
+            None
+        }
+    }
+
+    fn push_char(&mut self, c: char, now_ns: u64) {
+        self.started_ns.get_or_insert(now_ns);
+        self.current.push(c);
+    }
+
+    fn strip_suffix(&mut self, suffix: &str) -> bool {
+        if self.current.ends_with(suffix) {
+            let len = self.current.len() - suffix.len();
+            self.current.truncate(len);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn take_line(&mut self, terminated: bool) -> AssembledLine {
+        let mut text = std::mem::take(&mut self.current);
+        if self.config.strip_escapes {
+            text = super::vt_parser::strip_escapes(&text);
+        }
+        AssembledLine {
+            text,
+            timestamp_ns: self.started_ns.take().unwrap_or(self.last_input_ns),
+            terminated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lines: &[AssembledLine]) -> Vec<&str> {
+        lines.iter().map(|l| l.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_any_ending_with_split_crlf() {
+        let mut asm = LineAssembler::default();
+        let lines = asm.feed("boot\r", 10);
+        assert_eq!(texts(&lines), vec!["boot"]);
+        // CRLF 被切在两块之间，LF 不应产生额外空行
+        let lines = asm.feed("\nok\n\n", 20);
+        assert_eq!(texts(&lines), vec!["ok", ""]);
+    }
+
+    #[test]
+    fn test_timestamp_from_first_char_and_idle_flush() {
+        let mut asm = LineAssembler::new(LineAssemblerConfig {
+            ending: LineEnding::CrLf,
+            idle_flush_ms: 100,
+            ..Default::default()
+        });
+        assert!(asm.feed("AT+C", 1_000).is_empty());
+        let lines = asm.feed("SQ\r\n+CSQ", 5_000);
+        assert_eq!(lines[0].text, "AT+CSQ");
+        assert_eq!(lines[0].timestamp_ns, 1_000);
+        assert!(asm.flush_idle(50_000_000).is_none());
+        let line = asm.flush_idle(200_000_000).unwrap();
+        assert_eq!(line.text, "+CSQ");
+        assert_eq!(line.timestamp_ns, 5_000);
+        assert!(!line.terminated);
+    }
+
+    #[test]
+    fn test_timestamp_from_buffered_first_byte() {
+        let mut asm = LineAssembler::new(LineAssemblerConfig { idle_flush_ms: u64::MAX, ..Default::default() });
+        // "温" 的首字节单独到达，解码器暂存后文本为空
+        assert!(asm.feed("", 1_000).is_empty());
+        let lines = asm.feed("温度\n", 2_000);
+        assert_eq!(lines[0].text, "温度");
+        assert_eq!(lines[0].timestamp_ns, 1_000);
+        assert!(asm.feed("x", 3_000).is_empty());
+        assert!(asm.flush_idle(u64::MAX - 1).is_none());
+    }
+
+    #[test]
+    fn test_custom_terminator() {
+        let mut asm = LineAssembler::new(LineAssemblerConfig {
+            ending: LineEnding::Custom { sequence: ";".to_string() },
+            ..Default::default()
+        });
+        assert_eq!(texts(&asm.feed("a=1;b=2;c", 0)), vec!["a=1", "b=2"]);
+    }
+}
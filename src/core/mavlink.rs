@@ -0,0 +1,1208 @@
+//! MAVLink v1/v2 解析与发送
+//!
+//! - 在 RX 流中按起始符 (v1 `0xFE` / v2 `0xFD`) 切帧，按 CRC_EXTRA 校验 CRC；
+//!   v2 签名在配置密钥后校验 (SHA-256 前 48 位)
+//! - 内置 common 方言全部消息的 CRC_EXTRA 与常用消息的字段定义 (其 CRC_EXTRA 与字段线序由定义推导)
+//! - 按 系统/组件/消息 统计收包数、丢包 (序号跳变) 与实时频率
+//! - 由字段值 (JSON) 编码消息并组帧发送，序号自增，可选签名
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::capture::Direction;
+use super::checksum::crc16_mcrf4xx;
+use super::protocol_decoder::{DecodedMessage, DecoderFactory, ProtocolDecoder};
+use super::hex::parse_hex;
+
+pub const MAGIC_V1: u8 = 0xFE;
+pub const MAGIC_V2: u8 = 0xFD;
+/// v2 不兼容标志：帧尾带签名
+const INCOMPAT_SIGNED: u8 = 0x01;
+const SIGNATURE_LEN: usize = 13;
+/// 签名时间戳起点 2015-01-01 00:00:00 UTC (Unix 秒)
+const SIGNING_EPOCH_SECS: u64 = 1_420_070_400;
+/// 频率统计窗口
+const RATE_WINDOW_NS: u64 = 5_000_000_000;
+
+/// common 方言常用消息：`(ID, 名称, 字段)`，字段按 XML 顺序，`|` 之后为扩展字段
+const COMMON_MESSAGES: &[(u32, &str, &str)] = &[
+    (0, "HEARTBEAT", "uint8_t type, uint8_t autopilot, uint8_t base_mode, uint32_t custom_mode, uint8_t system_status, uint8_t mavlink_version"),
+    (1, "SYS_STATUS", "uint32_t onboard_control_sensors_present, uint32_t onboard_control_sensors_enabled, \
+        uint32_t onboard_control_sensors_health, uint16_t load, uint16_t voltage_battery, int16_t current_battery, \
+        int8_t battery_remaining, uint16_t drop_rate_comm, uint16_t errors_comm, uint16_t errors_count1, \
+        uint16_t errors_count2, uint16_t errors_count3, uint16_t errors_count4 | \
+        uint32_t onboard_control_sensors_present_extended, uint32_t onboard_control_sensors_enabled_extended, \
+        uint32_t onboard_control_sensors_health_extended"),
+    (2, "SYSTEM_TIME", "uint64_t time_unix_usec, uint32_t time_boot_ms"),
+    (4, "PING", "uint64_t time_usec, uint32_t seq, uint8_t target_system, uint8_t target_component"),
+    (11, "SET_MODE", "uint8_t target_system, uint8_t base_mode, uint32_t custom_mode"),
+    (20, "PARAM_REQUEST_READ", "uint8_t target_system, uint8_t target_component, char[16] param_id, int16_t param_index"),
+    (21, "PARAM_REQUEST_LIST", "uint8_t target_system, uint8_t target_component"),
+    (22, "PARAM_VALUE", "char[16] param_id, float param_value, uint8_t param_type, uint16_t param_count, uint16_t param_index"),
+    (23, "PARAM_SET", "uint8_t target_system, uint8_t target_component, char[16] param_id, float param_value, uint8_t param_type"),
+    (24, "GPS_RAW_INT", "uint64_t time_usec, uint8_t fix_type, int32_t lat, int32_t lon, int32_t alt, uint16_t eph, \
+        uint16_t epv, uint16_t vel, uint16_t cog, uint8_t satellites_visible | int32_t alt_ellipsoid, uint32_t h_acc, \
+        uint32_t v_acc, uint32_t vel_acc, uint32_t hdg_acc, uint16_t yaw"),
+    (27, "RAW_IMU", "uint64_t time_usec, int16_t xacc, int16_t yacc, int16_t zacc, int16_t xgyro, int16_t ygyro, \
+        int16_t zgyro, int16_t xmag, int16_t ymag, int16_t zmag | uint8_t id, int16_t temperature"),
+    (29, "SCALED_PRESSURE", "uint32_t time_boot_ms, float press_abs, float press_diff, int16_t temperature | \
+        int16_t temperature_press_diff"),
+    (30, "ATTITUDE", "uint32_t time_boot_ms, float roll, float pitch, float yaw, float rollspeed, float pitchspeed, float yawspeed"),
+    (31, "ATTITUDE_QUATERNION", "uint32_t time_boot_ms, float q1, float q2, float q3, float q4, float rollspeed, \
+        float pitchspeed, float yawspeed | float[4] repr_offset_q"),
+    (32, "LOCAL_POSITION_NED", "uint32_t time_boot_ms, float x, float y, float z, float vx, float vy, float vz"),
+    (33, "GLOBAL_POSITION_INT", "uint32_t time_boot_ms, int32_t lat, int32_t lon, int32_t alt, int32_t relative_alt, \
+        int16_t vx, int16_t vy, int16_t vz, uint16_t hdg"),
+    (36, "SERVO_OUTPUT_RAW", "uint32_t time_usec, uint8_t port, uint16_t servo1_raw, uint16_t servo2_raw, \
+        uint16_t servo3_raw, uint16_t servo4_raw, uint16_t servo5_raw, uint16_t servo6_raw, uint16_t servo7_raw, \
+        uint16_t servo8_raw | uint16_t servo9_raw, uint16_t servo10_raw, uint16_t servo11_raw, uint16_t servo12_raw, \
+        uint16_t servo13_raw, uint16_t servo14_raw, uint16_t servo15_raw, uint16_t servo16_raw"),
+    (42, "MISSION_CURRENT", "uint16_t seq | uint16_t total, uint8_t mission_state, uint8_t mission_mode"),
+    (65, "RC_CHANNELS", "uint32_t time_boot_ms, uint8_t chancount, uint16_t chan1_raw, uint16_t chan2_raw, \
+        uint16_t chan3_raw, uint16_t chan4_raw, uint16_t chan5_raw, uint16_t chan6_raw, uint16_t chan7_raw, \
+        uint16_t chan8_raw, uint16_t chan9_raw, uint16_t chan10_raw, uint16_t chan11_raw, uint16_t chan12_raw, \
+        uint16_t chan13_raw, uint16_t chan14_raw, uint16_t chan15_raw, uint16_t chan16_raw, uint16_t chan17_raw, \
+        uint16_t chan18_raw, uint8_t rssi"),
+    (66, "REQUEST_DATA_STREAM", "uint8_t target_system, uint8_t target_component, uint8_t req_stream_id, \
+        uint16_t req_message_rate, uint8_t start_stop"),
+    (69, "MANUAL_CONTROL", "uint8_t target, int16_t x, int16_t y, int16_t z, int16_t r, uint16_t buttons | \
+        uint16_t buttons2, uint8_t enabled_extensions, int16_t s, int16_t t"),
+    (74, "VFR_HUD", "float airspeed, float groundspeed, int16_t heading, uint16_t throttle, float alt, float climb"),
+    (75, "COMMAND_INT", "uint8_t target_system, uint8_t target_component, uint8_t frame, uint16_t command, \
+        uint8_t current, uint8_t autocontinue, float param1, float param2, float param3, float param4, int32_t x, \
+        int32_t y, float z"),
+    (76, "COMMAND_LONG", "uint8_t target_system, uint8_t target_component, uint16_t command, uint8_t confirmation, \
+        float param1, float param2, float param3, float param4, float param5, float param6, float param7"),
+    (77, "COMMAND_ACK", "uint16_t command, uint8_t result | uint8_t progress, int32_t result_param2, \
+        uint8_t target_system, uint8_t target_component"),
+    (111, "TIMESYNC", "int64_t tc1, int64_t ts1 | uint8_t target_system, uint8_t target_component"),
+    (147, "BATTERY_STATUS", "uint8_t id, uint8_t battery_function, uint8_t type, int16_t temperature, \
+        uint16_t[10] voltages, int16_t current_battery, int32_t current_consumed, int32_t energy_consumed, \
+        int8_t battery_remaining | int32_t time_remaining, uint8_t charge_state, uint16_t[4] voltages_ext, \
+        uint8_t mode, uint32_t fault_bitmask"),
+    (148, "AUTOPILOT_VERSION", "uint64_t capabilities, uint32_t flight_sw_version, uint32_t middleware_sw_version, \
+        uint32_t os_sw_version, uint32_t board_version, uint8_t[8] flight_custom_version, \
+        uint8_t[8] middleware_custom_version, uint8_t[8] os_custom_version, uint16_t vendor_id, uint16_t product_id, \
+        uint64_t uid | uint8_t[18] uid2"),
+    (245, "EXTENDED_SYS_STATE", "uint8_t vtol_state, uint8_t landed_state"),
+    (253, "STATUSTEXT", "uint8_t severity, char[50] text | uint16_t id, uint8_t chunk_seq"),
+];
+
+/// common 方言全部消息的 CRC_EXTRA：`(ID, 名称, CRC_EXTRA)`，未内置字段定义的消息只校验不解码
+const COMMON_CRC_EXTRA: &[(u32, &str, u8)] = &[
+    (0, "HEARTBEAT", 50), (1, "SYS_STATUS", 124), (2, "SYSTEM_TIME", 137), (4, "PING", 237),
+    (5, "CHANGE_OPERATOR_CONTROL", 217), (6, "CHANGE_OPERATOR_CONTROL_ACK", 104), (7, "AUTH_KEY", 119),
+    (8, "LINK_NODE_STATUS", 117), (11, "SET_MODE", 89), (20, "PARAM_REQUEST_READ", 214),
+    (21, "PARAM_REQUEST_LIST", 159), (22, "PARAM_VALUE", 220), (23, "PARAM_SET", 168), (24, "GPS_RAW_INT", 24),
+    (25, "GPS_STATUS", 23), (26, "SCALED_IMU", 170), (27, "RAW_IMU", 144), (28, "RAW_PRESSURE", 67),
+    (29, "SCALED_PRESSURE", 115), (30, "ATTITUDE", 39), (31, "ATTITUDE_QUATERNION", 246),
+    (32, "LOCAL_POSITION_NED", 185), (33, "GLOBAL_POSITION_INT", 104), (34, "RC_CHANNELS_SCALED", 237),
+    (35, "RC_CHANNELS_RAW", 244), (36, "SERVO_OUTPUT_RAW", 222), (37, "MISSION_REQUEST_PARTIAL_LIST", 212),
+    (38, "MISSION_WRITE_PARTIAL_LIST", 9), (39, "MISSION_ITEM", 254), (40, "MISSION_REQUEST", 230),
+    (41, "MISSION_SET_CURRENT", 28), (42, "MISSION_CURRENT", 28), (43, "MISSION_REQUEST_LIST", 132),
+    (44, "MISSION_COUNT", 221), (45, "MISSION_CLEAR_ALL", 232), (46, "MISSION_ITEM_REACHED", 11),
+    (47, "MISSION_ACK", 153), (48, "SET_GPS_GLOBAL_ORIGIN", 41), (49, "GPS_GLOBAL_ORIGIN", 39),
+    (50, "PARAM_MAP_RC", 78), (51, "MISSION_REQUEST_INT", 196), (54, "SAFETY_SET_ALLOWED_AREA", 15),
+    (55, "SAFETY_ALLOWED_AREA", 3), (61, "ATTITUDE_QUATERNION_COV", 167), (62, "NAV_CONTROLLER_OUTPUT", 183),
+    (63, "GLOBAL_POSITION_INT_COV", 119), (64, "LOCAL_POSITION_NED_COV", 191), (65, "RC_CHANNELS", 118),
+    (66, "REQUEST_DATA_STREAM", 148), (67, "DATA_STREAM", 21), (69, "MANUAL_CONTROL", 243),
+    (70, "RC_CHANNELS_OVERRIDE", 124), (73, "MISSION_ITEM_INT", 38), (74, "VFR_HUD", 20), (75, "COMMAND_INT", 158),
+    (76, "COMMAND_LONG", 152), (77, "COMMAND_ACK", 143), (80, "COMMAND_CANCEL", 14), (81, "MANUAL_SETPOINT", 106),
+    (82, "SET_ATTITUDE_TARGET", 49), (83, "ATTITUDE_TARGET", 22), (84, "SET_POSITION_TARGET_LOCAL_NED", 143),
+    (85, "POSITION_TARGET_LOCAL_NED", 140), (86, "SET_POSITION_TARGET_GLOBAL_INT", 5),
+    (87, "POSITION_TARGET_GLOBAL_INT", 150), (89, "LOCAL_POSITION_NED_SYSTEM_GLOBAL_OFFSET", 231),
+    (90, "HIL_STATE", 183), (91, "HIL_CONTROLS", 63), (92, "HIL_RC_INPUTS_RAW", 54),
+    (93, "HIL_ACTUATOR_CONTROLS", 47), (100, "OPTICAL_FLOW", 175), (101, "GLOBAL_VISION_POSITION_ESTIMATE", 102),
+    (102, "VISION_POSITION_ESTIMATE", 158), (103, "VISION_SPEED_ESTIMATE", 208),
+    (104, "VICON_POSITION_ESTIMATE", 56), (105, "HIGHRES_IMU", 93), (106, "OPTICAL_FLOW_RAD", 138),
+    (107, "HIL_SENSOR", 108), (108, "SIM_STATE", 32), (109, "RADIO_STATUS", 185),
+    (110, "FILE_TRANSFER_PROTOCOL", 84), (111, "TIMESYNC", 34), (112, "CAMERA_TRIGGER", 174), (113, "HIL_GPS", 124),
+    (114, "HIL_OPTICAL_FLOW", 237), (115, "HIL_STATE_QUATERNION", 4), (116, "SCALED_IMU2", 76),
+    (117, "LOG_REQUEST_LIST", 128), (118, "LOG_ENTRY", 56), (119, "LOG_REQUEST_DATA", 116), (120, "LOG_DATA", 134),
+    (121, "LOG_ERASE", 237), (122, "LOG_REQUEST_END", 203), (123, "GPS_INJECT_DATA", 250), (124, "GPS2_RAW", 87),
+    (125, "POWER_STATUS", 203), (126, "SERIAL_CONTROL", 220), (127, "GPS_RTK", 25), (128, "GPS2_RTK", 226),
+    (129, "SCALED_IMU3", 46), (130, "DATA_TRANSMISSION_HANDSHAKE", 29), (131, "ENCAPSULATED_DATA", 223),
+    (132, "DISTANCE_SENSOR", 85), (133, "TERRAIN_REQUEST", 6), (134, "TERRAIN_DATA", 229),
+    (135, "TERRAIN_CHECK", 203), (136, "TERRAIN_REPORT", 1), (137, "SCALED_PRESSURE2", 195),
+    (138, "ATT_POS_MOCAP", 109), (139, "SET_ACTUATOR_CONTROL_TARGET", 168), (140, "ACTUATOR_CONTROL_TARGET", 181),
+    (141, "ALTITUDE", 47), (142, "RESOURCE_REQUEST", 72), (143, "SCALED_PRESSURE3", 131),
+    (144, "FOLLOW_TARGET", 127), (146, "CONTROL_SYSTEM_STATE", 103), (147, "BATTERY_STATUS", 154),
+    (148, "AUTOPILOT_VERSION", 178), (149, "LANDING_TARGET", 200), (162, "FENCE_STATUS", 189),
+    (192, "MAG_CAL_REPORT", 36), (225, "EFI_STATUS", 208), (230, "ESTIMATOR_STATUS", 163), (231, "WIND_COV", 105),
+    (232, "GPS_INPUT", 151), (233, "GPS_RTCM_DATA", 35), (234, "HIGH_LATENCY", 150), (235, "HIGH_LATENCY2", 179),
+    (241, "VIBRATION", 90), (242, "HOME_POSITION", 104), (243, "SET_HOME_POSITION", 85),
+    (244, "MESSAGE_INTERVAL", 95), (245, "EXTENDED_SYS_STATE", 130), (246, "ADSB_VEHICLE", 184),
+    (247, "COLLISION", 81), (248, "V2_EXTENSION", 8), (249, "MEMORY_VECT", 204), (250, "DEBUG_VECT", 49),
+    (251, "NAMED_VALUE_FLOAT", 170), (252, "NAMED_VALUE_INT", 44), (253, "STATUSTEXT", 83), (254, "DEBUG", 46),
+    (256, "SETUP_SIGNING", 71), (257, "BUTTON_CHANGE", 131), (258, "PLAY_TUNE", 187),
+    (259, "CAMERA_INFORMATION", 92), (260, "CAMERA_SETTINGS", 146), (261, "STORAGE_INFORMATION", 179),
+    (262, "CAMERA_CAPTURE_STATUS", 12), (263, "CAMERA_IMAGE_CAPTURED", 133), (264, "FLIGHT_INFORMATION", 49),
+    (265, "MOUNT_ORIENTATION", 26), (266, "LOGGING_DATA", 193), (267, "LOGGING_DATA_ACKED", 35),
+    (268, "LOGGING_ACK", 14), (269, "VIDEO_STREAM_INFORMATION", 109), (270, "VIDEO_STREAM_STATUS", 59),
+    (271, "CAMERA_FOV_STATUS", 22), (280, "GIMBAL_MANAGER_INFORMATION", 70), (281, "GIMBAL_MANAGER_STATUS", 48),
+    (282, "GIMBAL_MANAGER_SET_ATTITUDE", 123), (283, "GIMBAL_DEVICE_INFORMATION", 74),
+    (284, "GIMBAL_DEVICE_SET_ATTITUDE", 99), (285, "GIMBAL_DEVICE_ATTITUDE_STATUS", 137),
+    (286, "AUTOPILOT_STATE_FOR_GIMBAL_DEVICE", 210), (287, "GIMBAL_MANAGER_SET_PITCHYAW", 1),
+    (288, "GIMBAL_MANAGER_SET_MANUAL_CONTROL", 20), (290, "ESC_INFO", 251), (291, "ESC_STATUS", 10),
+    (299, "WIFI_CONFIG_AP", 19), (301, "AIS_VESSEL", 243), (310, "UAVCAN_NODE_STATUS", 28),
+    (311, "UAVCAN_NODE_INFO", 95), (320, "PARAM_EXT_REQUEST_READ", 243), (321, "PARAM_EXT_REQUEST_LIST", 88),
+    (322, "PARAM_EXT_VALUE", 243), (323, "PARAM_EXT_SET", 78), (324, "PARAM_EXT_ACK", 132),
+    (330, "OBSTACLE_DISTANCE", 23), (331, "ODOMETRY", 91), (332, "TRAJECTORY_REPRESENTATION_WAYPOINTS", 236),
+    (333, "TRAJECTORY_REPRESENTATION_BEZIER", 231), (334, "CELLULAR_STATUS", 72), (335, "ISBD_LINK_STATUS", 225),
+    (336, "CELLULAR_CONFIG", 245), (339, "RAW_RPM", 199), (340, "UTM_GLOBAL_POSITION", 99),
+    (350, "DEBUG_FLOAT_ARRAY", 232), (360, "ORBIT_EXECUTION_STATUS", 11), (370, "SMART_BATTERY_INFO", 75),
+    (373, "GENERATOR_STATUS", 117), (375, "ACTUATOR_OUTPUT_STATUS", 251), (380, "TIME_ESTIMATE_TO_TARGET", 232),
+    (385, "TUNNEL", 147), (386, "CAN_FRAME", 132), (387, "CANFD_FRAME", 4), (388, "CAN_FILTER_MODIFY", 8),
+    (390, "ONBOARD_COMPUTER_STATUS", 156), (396, "COMPONENT_METADATA", 182), (397, "PLAY_TUNE_V2", 110),
+    (398, "SUPPORTED_TUNES", 183), (399, "EVENT", 160), (400, "CURRENT_EVENT_SEQUENCE", 106),
+    (401, "REQUEST_EVENT", 33), (402, "RESPONSE_EVENT_ERROR", 77), (9000, "WHEEL_DISTANCE", 113),
+    (9005, "WINCH_STATUS", 117), (12900, "OPEN_DRONE_ID_BASIC_ID", 114), (12901, "OPEN_DRONE_ID_LOCATION", 254),
+    (12902, "OPEN_DRONE_ID_AUTHENTICATION", 140), (12903, "OPEN_DRONE_ID_SELF_ID", 249),
+    (12904, "OPEN_DRONE_ID_SYSTEM", 77), (12905, "OPEN_DRONE_ID_OPERATOR_ID", 49),
+    (12915, "OPEN_DRONE_ID_MESSAGE_PACK", 94), (12918, "OPEN_DRONE_ID_ARM_STATUS", 139),
+    (12919, "OPEN_DRONE_ID_SYSTEM_UPDATE", 7), (12920, "HYGROMETER_SENSOR", 20),
+];
+
+/// 字段基本类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+    Char,
+}
+
+impl FieldType {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "uint8_t" => Self::U8,
+            "int8_t" => Self::I8,
+            "uint16_t" => Self::U16,
+            "int16_t" => Self::I16,
+            "uint32_t" => Self::U32,
+            "int32_t" => Self::I32,
+            "uint64_t" => Self::U64,
+            "int64_t" => Self::I64,
+            "float" => Self::F32,
+            "double" => Self::F64,
+            "char" => Self::Char,
+            other => bail!("Unknown MAVLink type {}", other),
+        })
+    }
+
+    fn c_name(&self) -> &'static str {
+        match self {
+            Self::U8 => "uint8_t",
+            Self::I8 => "int8_t",
+            Self::U16 => "uint16_t",
+            Self::I16 => "int16_t",
+            Self::U32 => "uint32_t",
+            Self::I32 => "int32_t",
+            Self::U64 => "uint64_t",
+            Self::I64 => "int64_t",
+            Self::F32 => "float",
+            Self::F64 => "double",
+            Self::Char => "char",
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::U8 | Self::I8 | Self::Char => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+
+    fn read(&self, b: &[u8]) -> Value {
+        match self {
+            Self::U8 | Self::Char => Value::from(b[0]),
+            Self::I8 => Value::from(b[0] as i8),
+            Self::U16 => Value::from(u16::from_le_bytes([b[0], b[1]])),
+            Self::I16 => Value::from(i16::from_le_bytes([b[0], b[1]])),
+            Self::U32 => Value::from(u32::from_le_bytes(b[..4].try_into().unwrap())),
+            Self::I32 => Value::from(i32::from_le_bytes(b[..4].try_into().unwrap())),
+            Self::U64 => Value::from(u64::from_le_bytes(b[..8].try_into().unwrap())),
+            Self::I64 => Value::from(i64::from_le_bytes(b[..8].try_into().unwrap())),
+            Self::F32 => Value::from(f32::from_le_bytes(b[..4].try_into().unwrap()) as f64),
+            Self::F64 => Value::from(f64::from_le_bytes(b[..8].try_into().unwrap())),
+        }
+    }
+
+    fn write(&self, value: &Value, out: &mut [u8]) -> Result<()> {
+        let int = || -> Result<i128> {
+            match value {
+                Value::Bool(b) => Ok(*b as i128),
+                Value::Number(n) => n
+                    .as_i64()
+                    .map(|v| v as i128)
+                    .or_else(|| n.as_u64().map(|v| v as i128))
+                    .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i128))
+                    .ok_or_else(|| anyhow!("Expected an integer, got {}", n)),
+                other => bail!("Expected an integer, got {}", other),
+            }
+        };
+        let float = || value.as_f64().ok_or_else(|| anyhow!("Expected a number, got {}", value));
+        let (min, max): (i128, i128) = match self {
+            Self::U8 | Self::Char => (0, u8::MAX as i128),
+            Self::I8 => (i8::MIN as i128, i8::MAX as i128),
+            Self::U16 => (0, u16::MAX as i128),
+            Self::I16 => (i16::MIN as i128, i16::MAX as i128),
+            Self::U32 => (0, u32::MAX as i128),
+            Self::I32 => (i32::MIN as i128, i32::MAX as i128),
+            Self::U64 => (0, u64::MAX as i128),
+            Self::I64 => (i64::MIN as i128, i64::MAX as i128),
+            Self::F32 => {
+                out.copy_from_slice(&(float()? as f32).to_le_bytes());
+                return Ok(());
+            }
+            Self::F64 => {
+                out.copy_from_slice(&float()?.to_le_bytes());
+                return Ok(());
+            }
+        };
+        let v = int()?;
+        if v < min || v > max {
+            bail!("Value {} out of range for {}", v, self.c_name());
+        }
+        out.copy_from_slice(&(v as u64).to_le_bytes()[..self.size()]);
+        Ok(())
+    }
+}
+
+/// 字段定义
+#[derive(Debug, Clone)]
+struct FieldDef {
+    name: String,
+    ty: FieldType,
+    /// 数组长度，标量为 0
+    array: usize,
+    extension: bool,
+    /// 线序中的字节偏移
+    offset: usize,
+}
+
+impl FieldDef {
+    fn len(&self) -> usize {
+        self.ty.size() * self.array.max(1)
+    }
+}
+
+/// 消息定义
+#[derive(Debug, Clone)]
+pub struct MessageDef {
+    pub id: u32,
+    pub name: String,
+    pub crc_extra: u8,
+    /// 不含扩展字段的长度 (v1 负载长度)
+    pub base_len: usize,
+    /// 含扩展字段的长度
+    pub max_len: usize,
+    /// XML 顺序
+    fields: Vec<FieldDef>,
+}
+
+impl MessageDef {
+    fn parse(id: u32, name: &str, spec: &str) -> Result<Self> {
+        let (base, extensions) = spec.split_once('|').unwrap_or((spec, ""));
+        let mut fields = Vec::new();
+        for (part, extension) in [(base, false), (extensions, true)] {
+            for decl in part.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+                let (ty, field_name) = decl.split_once(' ').ok_or_else(|| anyhow!("Invalid field {}", decl))?;
+                let (ty, array) = match ty.split_once('[') {
+                    Some((ty, len)) => (ty, len.trim_end_matches(']').parse()?),
+                    None => (ty, 0),
+                };
+                fields.push(FieldDef {
+                    name: field_name.trim().to_string(),
+                    ty: FieldType::parse(ty)?,
+                    array,
+                    extension,
+                    offset: 0,
+                });
+            }
+        }
+        // 线序：基础字段按类型宽度降序 (稳定排序)，扩展字段按原顺序追加
+        let mut order: Vec<usize> = (0..fields.len()).filter(|&i| !fields[i].extension).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(fields[i].ty.size()));
+        let base_order = order.clone();
+        order.extend((0..fields.len()).filter(|&i| fields[i].extension));
+
+        let mut offset = 0;
+        for &i in &order {
+            fields[i].offset = offset;
+            offset += fields[i].len();
+        }
+        let base_len = fields.iter().filter(|f| !f.extension).map(FieldDef::len).sum();
+
+        let mut seed = format!("{} ", name).into_bytes();
+        for &i in &base_order {
+            let f = &fields[i];
+            seed.extend_from_slice(format!("{} {} ", f.ty.c_name(), f.name).as_bytes());
+            if f.array > 0 {
+                seed.push(f.array as u8);
+            }
+        }
+        let crc = crc16_mcrf4xx(&seed);
+        Ok(Self {
+            id,
+            name: name.to_string(),
+            crc_extra: (crc as u8) ^ (crc >> 8) as u8,
+            base_len,
+            max_len: offset,
+            fields,
+        })
+    }
+
+    /// 解码负载 (v2 截断的尾部零字节补齐)
+    pub fn decode(&self, payload: &[u8]) -> Map<String, Value> {
+        let mut padded = payload.to_vec();
+        padded.resize(self.max_len.max(payload.len()), 0);
+        let mut object = Map::new();
+        for f in &self.fields {
+            let bytes = &padded[f.offset..f.offset + f.len()];
+            let value = if f.ty == FieldType::Char && f.array > 0 {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                Value::String(String::from_utf8_lossy(&bytes[..end]).to_string())
+            } else if f.array > 0 {
+                Value::Array(bytes.chunks(f.ty.size()).map(|c| f.ty.read(c)).collect())
+            } else {
+                f.ty.read(bytes)
+            };
+            object.insert(f.name.clone(), value);
+        }
+        object
+    }
+
+    /// 由字段值编码负载，缺省字段为 0；`extensions` 为 false 时按 v1 省略扩展字段
+    pub fn encode(&self, values: &Value, extensions: bool) -> Result<Vec<u8>> {
+        let object = values.as_object().ok_or_else(|| anyhow!("Expected an object of field values"))?;
+        if let Some(unknown) = object.keys().find(|k| !self.fields.iter().any(|f| &f.name == *k)) {
+            bail!("{} has no field {}", self.name, unknown);
+        }
+        let mut payload = vec![0u8; self.max_len];
+        for f in &self.fields {
+            let Some(value) = object.get(&f.name) else { continue };
+            let out = &mut payload[f.offset..f.offset + f.len()];
+            let result = match (value, f.array) {
+                (Value::String(text), n) if f.ty == FieldType::Char && n > 0 => {
+                    if text.len() > n {
+                        Err(anyhow!("Text longer than {} bytes", n))
+                    } else {
+                        out[..text.len()].copy_from_slice(text.as_bytes());
+                        Ok(())
+                    }
+                }
+                (Value::Array(items), n) if n > 0 => {
+                    if items.len() > n {
+                        Err(anyhow!("At most {} elements", n))
+                    } else {
+                        items
+                            .iter()
+                            .zip(out.chunks_mut(f.ty.size()))
+                            .try_for_each(|(item, chunk)| f.ty.write(item, chunk))
+                    }
+                }
+                (value, 0) => f.ty.write(value, out),
+                _ => Err(anyhow!("Expected an array")),
+            };
+            result.map_err(|e| anyhow!("{}.{}: {}", self.name, f.name, e))?;
+        }
+        payload.truncate(if extensions { self.max_len } else { self.base_len });
+        Ok(payload)
+    }
+
+    /// 单行摘要 (XML 顺序)
+    fn summarize(&self, fields: &Map<String, Value>) -> String {
+        let parts: Vec<String> = self
+            .fields
+            .iter()
+            .filter_map(|f| fields.get(&f.name).map(|v| format!("{}={}", f.name, v)))
+            .collect();
+        format!("{} {}", self.name, parts.join(" "))
+    }
+}
+
+/// 消息定义集合
+pub struct Dialect {
+    by_id: HashMap<u32, MessageDef>,
+    by_name: HashMap<String, u32>,
+    /// 全部已知消息的 (名称, CRC_EXTRA)
+    crc_extra: HashMap<u32, (&'static str, u8)>,
+}
+
+impl Dialect {
+    pub fn get(&self, id: u32) -> Option<&MessageDef> {
+        self.by_id.get(&id)
+    }
+
+    /// 可校验 CRC 的消息 (含无字段定义的)
+    pub fn crc_extra(&self, id: u32) -> Option<(&str, u8)> {
+        self.crc_extra.get(&id).copied()
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&MessageDef> {
+        self.by_name.get(&name.to_ascii_uppercase()).and_then(|id| self.by_id.get(id))
+    }
+
+    /// 按 ID 排序的消息名
+    pub fn message_names(&self) -> Vec<&str> {
+        let mut defs: Vec<&MessageDef> = self.by_id.values().collect();
+        defs.sort_by_key(|d| d.id);
+        defs.into_iter().map(|d| d.name.as_str()).collect()
+    }
+}
+
+/// 内置 common 方言
+pub fn common() -> &'static Dialect {
+    static COMMON: OnceLock<Dialect> = OnceLock::new();
+    COMMON.get_or_init(|| {
+        let mut dialect = Dialect {
+            by_id: HashMap::new(),
+            by_name: HashMap::new(),
+            crc_extra: COMMON_CRC_EXTRA.iter().map(|&(id, name, crc)| (id, (name, crc))).collect(),
+        };
+        for &(id, name, spec) in COMMON_MESSAGES {
+            let def = MessageDef::parse(id, name, spec).expect("valid built-in MAVLink definition");
+            dialect.by_name.insert(def.name.clone(), id);
+            dialect.by_id.insert(id, def);
+        }
+        dialect
+    })
+}
+
+/// v2 签名块
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Signature {
+    pub link_id: u8,
+    /// 自 2015-01-01 起的 10 微秒数
+    pub timestamp: u64,
+    pub signature: [u8; 6],
+}
+
+/// 一帧 MAVLink
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MavlinkFrame {
+    /// 1 或 2
+    pub version: u8,
+    pub sequence: u8,
+    pub system_id: u8,
+    pub component_id: u8,
+    pub message_id: u32,
+    pub incompat_flags: u8,
+    pub compat_flags: u8,
+    pub payload: Vec<u8>,
+    pub crc: u16,
+    pub signature: Option<Signature>,
+}
+
+/// 解码后的帧 (`DecodedMessage::fields`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedFrame {
+    #[serde(flatten)]
+    pub frame: MavlinkFrame,
+    /// 消息名 (方言中未定义时为空)
+    pub name: Option<String>,
+    /// 方言外的消息无法校验 CRC，为空
+    pub crc_ok: Option<bool>,
+    /// 配置了签名密钥且帧带签名时的校验结果
+    pub signature_ok: Option<bool>,
+    pub fields: Map<String, Value>,
+}
+
+/// 切帧结果
+enum Parsed {
+    Incomplete,
+    /// 起始符后的帧头不合法，跳过起始符
+    Invalid,
+    Frame(MavlinkFrame, usize),
+}
+
+fn frame_crc(header: &[u8], payload: &[u8], crc_extra: u8) -> u16 {
+    let mut data = Vec::with_capacity(header.len() + payload.len());
+    data.extend_from_slice(&header[1..]);
+    data.extend_from_slice(payload);
+    data.push(crc_extra);
+    crc16_mcrf4xx(&data)
+}
+
+/// 从 `buf` 开头 (起始符处) 切出一帧
+fn parse_frame(buf: &[u8]) -> Parsed {
+    let (header_len, v2) = match buf.first() {
+        Some(&MAGIC_V1) => (6, false),
+        Some(&MAGIC_V2) => (10, true),
+        _ => return Parsed::Invalid,
+    };
+    if buf.len() < header_len {
+        return Parsed::Incomplete;
+    }
+    let payload_len = buf[1] as usize;
+    let incompat_flags = if v2 { buf[2] } else { 0 };
+    if incompat_flags & !INCOMPAT_SIGNED != 0 {
+        return Parsed::Invalid;
+    }
+    let signature_len = if incompat_flags & INCOMPAT_SIGNED != 0 { SIGNATURE_LEN } else { 0 };
+    let total = header_len + payload_len + 2 + signature_len;
+    if buf.len() < total {
+        return Parsed::Incomplete;
+    }
+    let header = &buf[..header_len];
+    let payload = buf[header_len..header_len + payload_len].to_vec();
+    let crc_at = header_len + payload_len;
+    let signature = (signature_len > 0).then(|| {
+        let s = &buf[crc_at + 2..total];
+        let mut timestamp = [0u8; 8];
+        timestamp[..6].copy_from_slice(&s[1..7]);
+        Signature {
+            link_id: s[0],
+            timestamp: u64::from_le_bytes(timestamp),
+            signature: s[7..13].try_into().unwrap(),
+        }
+    });
+    let frame = if v2 {
+        MavlinkFrame {
+            version: 2,
+            incompat_flags,
+            compat_flags: header[3],
+            sequence: header[4],
+            system_id: header[5],
+            component_id: header[6],
+            message_id: u32::from_le_bytes([header[7], header[8], header[9], 0]),
+            payload,
+            crc: u16::from_le_bytes([buf[crc_at], buf[crc_at + 1]]),
+            signature,
+        }
+    } else {
+        MavlinkFrame {
+            version: 1,
+            incompat_flags: 0,
+            compat_flags: 0,
+            sequence: header[2],
+            system_id: header[3],
+            component_id: header[4],
+            message_id: header[5] as u32,
+            payload,
+            crc: u16::from_le_bytes([buf[crc_at], buf[crc_at + 1]]),
+            signature: None,
+        }
+    };
+    Parsed::Frame(frame, total)
+}
+
+impl MavlinkFrame {
+    fn header(&self) -> Vec<u8> {
+        if self.version == 1 {
+            vec![MAGIC_V1, self.payload.len() as u8, self.sequence, self.system_id, self.component_id, self.message_id as u8]
+        } else {
+            let id = self.message_id.to_le_bytes();
+            vec![
+                MAGIC_V2,
+                self.payload.len() as u8,
+                self.incompat_flags,
+                self.compat_flags,
+                self.sequence,
+                self.system_id,
+                self.component_id,
+                id[0],
+                id[1],
+                id[2],
+            ]
+        }
+    }
+
+    /// 按 CRC_EXTRA 计算校验
+    pub fn compute_crc(&self, crc_extra: u8) -> u16 {
+        frame_crc(&self.header(), &self.payload, crc_extra)
+    }
+
+    /// 签名：SHA-256(密钥 + 帧头 + 负载 + CRC + 链路 ID + 时间戳) 的前 6 字节
+    fn compute_signature(&self, key: &[u8; 32], link_id: u8, timestamp: u64) -> [u8; 6] {
+        let mut hasher = Sha256::new();
+        hasher.update(key);
+        hasher.update(self.header());
+        hasher.update(&self.payload);
+        hasher.update(self.crc.to_le_bytes());
+        hasher.update([link_id]);
+        hasher.update(&timestamp.to_le_bytes()[..6]);
+        hasher.finalize()[..6].try_into().unwrap()
+    }
+
+    pub fn verify_signature(&self, key: &[u8; 32]) -> Option<bool> {
+        let s = self.signature.as_ref()?;
+        Some(self.compute_signature(key, s.link_id, s.timestamp) == s.signature)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.header();
+        out.extend_from_slice(&self.payload);
+        out.extend_from_slice(&self.crc.to_le_bytes());
+        if let Some(s) = &self.signature {
+            out.push(s.link_id);
+            out.extend_from_slice(&s.timestamp.to_le_bytes()[..6]);
+            out.extend_from_slice(&s.signature);
+        }
+        out
+    }
+
+    /// 按方言解码负载并校验
+    pub fn decode(self, dialect: &Dialect, key: Option<&[u8; 32]>) -> DecodedFrame {
+        let def = dialect.get(self.message_id);
+        let known = dialect.crc_extra(self.message_id);
+        DecodedFrame {
+            name: known.map(|(name, _)| name.to_string()),
+            crc_ok: known.map(|(_, crc_extra)| self.compute_crc(crc_extra) == self.crc),
+            signature_ok: key.and_then(|k| self.verify_signature(k)),
+            fields: def.map(|d| d.decode(&self.payload)).unwrap_or_default(),
+            frame: self,
+        }
+    }
+}
+
+impl DecodedFrame {
+    pub fn summary(&self) -> String {
+        let f = &self.frame;
+        let body = match (common().get(f.message_id), &self.name) {
+            (Some(def), _) if !self.fields.is_empty() => def.summarize(&self.fields),
+            (_, Some(name)) => format!("{} {} bytes", name, f.payload.len()),
+            _ => format!("MSG#{} {} bytes", f.message_id, f.payload.len()),
+        };
+        let mut text = format!("v{} {}:{} seq={} {}", f.version, f.system_id, f.component_id, f.sequence, body);
+        if self.crc_ok == Some(false) {
+            text.push_str(" (bad CRC)");
+        }
+        if self.signature_ok == Some(false) {
+            text.push_str(" (bad signature)");
+        }
+        text
+    }
+}
+
+/// 解析 64 位 hex 密钥
+pub fn parse_signing_key(hex: &str) -> Result<[u8; 32]> {
+    parse_hex(hex)?
+        .try_into()
+        .map_err(|_| anyhow!("MAVLink signing key must be 32 bytes"))
+}
+
+/// 当前签名时间戳
+fn signing_timestamp() -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    now.as_micros().saturating_sub(SIGNING_EPOCH_SECS as u128 * 1_000_000) as u64 / 10
+}
+
+/// 发送参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MavlinkSendOptions {
+    /// 本机系统 ID (地面站惯例 255)
+    pub system_id: u8,
+    /// 本机组件 ID (MAV_COMP_ID_MISSIONPLANNER = 190)
+    pub component_id: u8,
+    /// 协议版本 1 或 2
+    pub version: u8,
+    /// 32 字节 hex 密钥，给出时对 v2 帧签名
+    pub signing_key: Option<String>,
+    pub link_id: u8,
+}
+
+impl Default for MavlinkSendOptions {
+    fn default() -> Self {
+        Self { system_id: 255, component_id: 190, version: 2, signing_key: None, link_id: 0 }
+    }
+}
+
+/// 消息发送端：维护序号与签名时间戳
+#[derive(Debug, Default)]
+pub struct MavlinkSender {
+    sequence: u8,
+    last_timestamp: u64,
+}
+
+impl MavlinkSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 按消息名与字段值组帧
+    pub fn frame(&mut self, message: &str, fields: &Value, options: &MavlinkSendOptions) -> Result<Vec<u8>> {
+        let def = common().by_name(message).ok_or_else(|| anyhow!("Unknown MAVLink message: {}", message))?;
+        let key = options.signing_key.as_deref().map(parse_signing_key).transpose()?;
+        let frame = match options.version {
+            1 if def.id > 255 => bail!("{} needs MAVLink 2", def.name),
+            1 => self.build(def, def.encode(fields, false)?, 1, options, None),
+            2 => {
+                let mut payload = def.encode(fields, true)?;
+                // v2 去掉尾部零字节 (至少保留 1 字节)
+                while payload.len() > 1 && payload.last() == Some(&0) {
+                    payload.pop();
+                }
+                self.build(def, payload, 2, options, key.as_ref())
+            }
+            other => bail!("Unsupported MAVLink version {}", other),
+        };
+        Ok(frame.to_bytes())
+    }
+
+    fn build(&mut self, def: &MessageDef, payload: Vec<u8>, version: u8, options: &MavlinkSendOptions, key: Option<&[u8; 32]>) -> MavlinkFrame {
+        let mut frame = MavlinkFrame {
+            version,
+            sequence: self.sequence,
+            system_id: options.system_id,
+            component_id: options.component_id,
+            message_id: def.id,
+            incompat_flags: if key.is_some() { INCOMPAT_SIGNED } else { 0 },
+            compat_flags: 0,
+            payload,
+            crc: 0,
+            signature: None,
+        };
+        self.sequence = self.sequence.wrapping_add(1);
+        frame.crc = frame.compute_crc(def.crc_extra);
+        if let Some(key) = key {
+            // 时间戳必须单调递增
+            let timestamp = signing_timestamp().max(self.last_timestamp + 1);
+            self.last_timestamp = timestamp;
+            let signature = frame.compute_signature(key, options.link_id, timestamp);
+            frame.signature = Some(Signature { link_id: options.link_id, timestamp, signature });
+        }
+        frame
+    }
+}
+
+/// 单个消息的统计
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageRate {
+    pub message_id: u32,
+    pub name: Option<String>,
+    pub count: u64,
+    pub rate_hz: f64,
+}
+
+/// 单个 系统/组件 的统计
+#[derive(Debug, Clone, Serialize)]
+pub struct ComponentStats {
+    pub system_id: u8,
+    pub component_id: u8,
+    pub received: u64,
+    /// 按序号跳变估计的丢包数
+    pub lost: u64,
+    pub rate_hz: f64,
+    pub messages: Vec<MessageRate>,
+}
+
+/// 统计快照
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MavlinkStatsSnapshot {
+    pub crc_errors: u64,
+    pub components: Vec<ComponentStats>,
+}
+
+#[derive(Debug, Default)]
+struct RateEntry {
+    count: u64,
+    /// 窗口内的到达时间
+    times: VecDeque<u64>,
+}
+
+impl RateEntry {
+    fn record(&mut self, now_ns: u64) {
+        self.count += 1;
+        self.times.push_back(now_ns);
+        self.trim(now_ns);
+    }
+
+    fn trim(&mut self, now_ns: u64) {
+        while self.times.front().is_some_and(|&t| now_ns.saturating_sub(t) > RATE_WINDOW_NS) {
+            self.times.pop_front();
+        }
+    }
+
+    /// 窗口内首末到达间隔估算频率
+    fn rate(&mut self, now_ns: u64) -> f64 {
+        self.trim(now_ns);
+        match (self.times.front(), self.times.back()) {
+            (Some(&first), Some(&last)) if last > first => {
+                (self.times.len() - 1) as f64 / ((last - first) as f64 / 1e9)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ComponentEntry {
+    last_sequence: Option<u8>,
+    received: u64,
+    lost: u64,
+    all: RateEntry,
+    messages: BTreeMap<u32, RateEntry>,
+}
+
+/// 收包统计
+#[derive(Debug, Default)]
+pub struct MavlinkStats {
+    crc_errors: u64,
+    components: BTreeMap<(u8, u8), ComponentEntry>,
+}
+
+impl MavlinkStats {
+    pub fn record(&mut self, frame: &DecodedFrame, timestamp_ns: u64) {
+        if frame.crc_ok == Some(false) {
+            self.crc_errors += 1;
+            return;
+        }
+        let f = &frame.frame;
+        let entry = self.components.entry((f.system_id, f.component_id)).or_default();
+        if let Some(last) = entry.last_sequence {
+            // 序号相同视为重复帧，不计丢包
+            let step = f.sequence.wrapping_sub(last);
+            if step != 0 {
+                entry.lost += step.wrapping_sub(1) as u64;
+            }
+        }
+        entry.last_sequence = Some(f.sequence);
+        entry.received += 1;
+        entry.all.record(timestamp_ns);
+        entry.messages.entry(f.message_id).or_default().record(timestamp_ns);
+    }
+
+    /// 切帧时丢弃的 CRC 错误帧
+    pub fn record_crc_errors(&mut self, count: u64) {
+        self.crc_errors += count;
+    }
+
+    pub fn snapshot(&mut self, now_ns: u64) -> MavlinkStatsSnapshot {
+        let dialect = common();
+        let components = self
+            .components
+            .iter_mut()
+            .map(|(&(system_id, component_id), entry)| ComponentStats {
+                system_id,
+                component_id,
+                received: entry.received,
+                lost: entry.lost,
+                rate_hz: entry.all.rate(now_ns),
+                messages: entry
+                    .messages
+                    .iter_mut()
+                    .map(|(&message_id, rate)| MessageRate {
+                        message_id,
+                        name: dialect.get(message_id).map(|d| d.name.clone()),
+                        count: rate.count,
+                        rate_hz: rate.rate(now_ns),
+                    })
+                    .collect(),
+            })
+            .collect();
+        MavlinkStatsSnapshot { crc_errors: self.crc_errors, components }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// 解码器与前端共享的统计
+pub type SharedMavlinkStats = Arc<Mutex<MavlinkStats>>;
+
+/// 注册表中的解码器名称
+pub const DECODER_NAME: &str = "mavlink";
+
+/// 流式切帧：缓冲 RX 数据，CRC 错误时后移一字节重新同步；
+/// 方言外的消息无法校验，仅当帧后紧跟起始符 (或数据结束) 时作为未解码帧接受
+#[derive(Debug, Default)]
+pub struct MavlinkParser {
+    buffer: Vec<u8>,
+    start_ns: u64,
+    crc_errors: u64,
+}
+
+impl MavlinkParser {
+    pub fn feed(&mut self, data: &[u8], now_ns: u64, key: Option<&[u8; 32]>) -> Vec<(DecodedFrame, Vec<u8>, u64)> {
+        if self.buffer.is_empty() {
+            self.start_ns = now_ns;
+        }
+        self.buffer.extend_from_slice(data);
+        let mut frames = Vec::new();
+        loop {
+            let Some(start) = self.buffer.iter().position(|&b| b == MAGIC_V1 || b == MAGIC_V2) else {
+                self.buffer.clear();
+                break;
+            };
+            self.buffer.drain(..start);
+            match parse_frame(&self.buffer) {
+                Parsed::Incomplete => break,
+                Parsed::Invalid => {
+                    self.buffer.remove(0);
+                }
+                Parsed::Frame(frame, len) => {
+                    let decoded = frame.decode(common(), key);
+                    let accept = match decoded.crc_ok {
+                        Some(ok) => ok,
+                        None => matches!(self.buffer.get(len), None | Some(&MAGIC_V1) | Some(&MAGIC_V2)),
+                    };
+                    if !accept {
+                        // 可能是负载中的伪起始符
+                        if decoded.crc_ok == Some(false) {
+                            self.crc_errors += 1;
+                        }
+                        self.buffer.remove(0);
+                        continue;
+                    }
+                    let raw: Vec<u8> = self.buffer.drain(..len).collect();
+                    frames.push((decoded, raw, self.start_ns));
+                    self.start_ns = now_ns;
+                }
+            }
+        }
+        frames
+    }
+
+    /// 取出并清零上次以来的 CRC 错误数
+    pub fn take_crc_errors(&mut self) -> u64 {
+        std::mem::take(&mut self.crc_errors)
+    }
+}
+
+/// MAVLink 协议解码器：RX 按流切帧，TX 每次写入视为完整帧
+pub struct MavlinkDecoder {
+    stats: SharedMavlinkStats,
+    key: Option<[u8; 32]>,
+    rx: MavlinkParser,
+}
+
+impl MavlinkDecoder {
+    pub fn new(stats: SharedMavlinkStats, key: Option<[u8; 32]>) -> Self {
+        Self { stats, key, rx: MavlinkParser::default() }
+    }
+
+    fn message(&self, direction: Direction, decoded: DecodedFrame, raw: Vec<u8>, timestamp_ns: u64) -> DecodedMessage {
+        DecodedMessage {
+            protocol: DECODER_NAME.to_string(),
+            direction,
+            timestamp_ns,
+            raw,
+            valid: decoded.crc_ok != Some(false) && decoded.signature_ok != Some(false),
+            summary: decoded.summary(),
+            fields: serde_json::to_value(&decoded).unwrap_or_default(),
+        }
+    }
+}
+
+/// 注册表工厂：参数 `{ "signingKey": "<64 位 hex>" }` 可选；统计独立于前端
+pub fn decoder_factory(options: &Value) -> Result<Box<dyn ProtocolDecoder>> {
+    shared_decoder_factory(SharedMavlinkStats::default())(options)
+}
+
+/// 与前端共享统计的注册表工厂
+pub fn shared_decoder_factory(stats: SharedMavlinkStats) -> DecoderFactory {
+    Box::new(move |options| {
+        let key = options.get("signingKey").and_then(Value::as_str).map(parse_signing_key).transpose()?;
+        Ok(Box::new(MavlinkDecoder::new(stats.clone(), key)) as Box<dyn ProtocolDecoder>)
+    })
+}
+
+impl ProtocolDecoder for MavlinkDecoder {
+    fn name(&self) -> &str {
+        DECODER_NAME
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        match direction {
+            Direction::Rx => {
+                let frames = self.rx.feed(data, timestamp_ns, self.key.as_ref());
+                let crc_errors = self.rx.take_crc_errors();
+                if let Ok(mut stats) = self.stats.lock() {
+                    stats.record_crc_errors(crc_errors);
+                    for (decoded, _, ts) in &frames {
+                        stats.record(decoded, *ts);
+                    }
+                }
+                frames
+                    .into_iter()
+                    .map(|(decoded, raw, ts)| self.message(direction, decoded, raw, ts))
+                    .collect()
+            }
+            Direction::Tx => match parse_frame(data) {
+                Parsed::Frame(frame, _) => {
+                    let decoded = frame.decode(common(), self.key.as_ref());
+                    vec![self.message(direction, decoded, data.to_vec(), timestamp_ns)]
+                }
+                _ => Vec::new(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_crc_extra_matches_common_xml() {
+        let expected = [
+            ("HEARTBEAT", 50),
+            ("SYS_STATUS", 124),
+            ("SYSTEM_TIME", 137),
+            ("PING", 237),
+            ("SET_MODE", 89),
+            ("PARAM_REQUEST_READ", 214),
+            ("PARAM_REQUEST_LIST", 159),
+            ("PARAM_VALUE", 220),
+            ("PARAM_SET", 168),
+            ("GPS_RAW_INT", 24),
+            ("RAW_IMU", 144),
+            ("SCALED_PRESSURE", 115),
+            ("ATTITUDE", 39),
+            ("ATTITUDE_QUATERNION", 246),
+            ("LOCAL_POSITION_NED", 185),
+            ("GLOBAL_POSITION_INT", 104),
+            ("SERVO_OUTPUT_RAW", 222),
+            ("MISSION_CURRENT", 28),
+            ("RC_CHANNELS", 118),
+            ("REQUEST_DATA_STREAM", 148),
+            ("MANUAL_CONTROL", 243),
+            ("VFR_HUD", 20),
+            ("COMMAND_INT", 158),
+            ("COMMAND_LONG", 152),
+            ("COMMAND_ACK", 143),
+            ("TIMESYNC", 34),
+            ("BATTERY_STATUS", 154),
+            ("AUTOPILOT_VERSION", 178),
+            ("EXTENDED_SYS_STATE", 130),
+            ("STATUSTEXT", 83),
+        ];
+        for (name, crc_extra) in expected {
+            assert_eq!(common().by_name(name).unwrap().crc_extra, crc_extra, "{}", name);
+        }
+        for def in common().by_id.values() {
+            assert_eq!(common().crc_extra(def.id), Some((def.name.as_str(), def.crc_extra)), "{}", def.name);
+        }
+        let heartbeat = common().by_name("heartbeat").unwrap();
+        assert_eq!((heartbeat.base_len, heartbeat.max_len), (9, 9));
+        assert_eq!(common().by_name("GPS_RAW_INT").unwrap().max_len, 52);
+    }
+
+    fn frame_of(bytes: &[u8]) -> MavlinkFrame {
+        match parse_frame(bytes) {
+            Parsed::Frame(frame, _) => frame,
+            _ => panic!("not a frame"),
+        }
+    }
+
+    #[test]
+    fn test_send_and_parse_round_trip() {
+        let mut sender = MavlinkSender::new();
+        let options = MavlinkSendOptions::default();
+        let heartbeat = json!({ "type": 6, "autopilot": 8, "system_status": 4, "mavlink_version": 3 });
+        let v2 = sender.frame("HEARTBEAT", &heartbeat, &options).unwrap();
+        let v1 = sender
+            .frame("COMMAND_LONG", &json!({ "target_system": 1, "command": 400, "param1": 1.0 }), &MavlinkSendOptions {
+                version: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(v1.len(), 6 + 33 + 2);
+
+        let mut stream = vec![0x00, MAGIC_V2, 0x01, 0x80];
+        stream.extend_from_slice(&v2);
+        stream.extend_from_slice(&v1[..10]);
+        let mut parser = MavlinkParser::default();
+        let first = parser.feed(&stream, 1, None);
+        assert_eq!(first.len(), 1);
+        let (decoded, raw, _) = &first[0];
+        assert_eq!(raw, &v2);
+        assert_eq!(decoded.crc_ok, Some(true));
+        assert_eq!(decoded.frame.system_id, 255);
+        assert_eq!(decoded.fields["type"], json!(6));
+        assert_eq!(decoded.fields["custom_mode"], json!(0));
+
+        let second = parser.feed(&v1[10..], 2, None);
+        assert_eq!(second[0].0.name.as_deref(), Some("COMMAND_LONG"));
+        assert_eq!(second[0].0.frame.sequence, 1);
+        assert_eq!(second[0].0.fields["command"], json!(400));
+        assert!(second[0].0.summary().starts_with("v1 255:190 seq=1 COMMAND_LONG target_system=1"));
+
+        // 方言外的消息后紧跟起始符时作为未解码帧接受，否则视为未同步
+        let mut unknown = v2.clone();
+        unknown[7..10].copy_from_slice(&[0xEE, 0xEE, 0xEE]);
+        let mut stream = unknown.clone();
+        stream.extend_from_slice(&v2);
+        let frames = parser.feed(&stream, 3, None);
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].0.frame.message_id, frames[0].0.crc_ok), (0xEEEEEE, None));
+        assert!(frames[0].0.summary().ends_with("MSG#15658734 9 bytes"));
+        assert_eq!(frames[1].1, v2);
+        let mut noise = unknown.clone();
+        noise.push(0x00);
+        noise.extend_from_slice(&v2);
+        let frames = parser.feed(&noise, 4, None);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].1, v2);
+
+        // 无字段定义但已知 CRC_EXTRA 的消息 (VIBRATION) 校验后按名称显示
+        let mut vibration = MavlinkFrame { message_id: 241, payload: vec![0; 32], ..frame_of(&v2) };
+        vibration.crc = vibration.compute_crc(90);
+        let frames = parser.feed(&vibration.to_bytes(), 5, None);
+        assert_eq!(frames[0].0.crc_ok, Some(true));
+        assert!(frames[0].0.summary().ends_with("VIBRATION 32 bytes"));
+
+        assert!(sender.frame("HEARTBEAT", &json!({ "bogus": 1 }), &options).is_err());
+        assert!(sender.frame("HEARTBEAT", &json!({ "type": 300 }), &options).is_err());
+    }
+
+    #[test]
+    fn test_signing_and_stats() {
+        let key_hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+        let key = parse_signing_key(key_hex).unwrap();
+        let mut sender = MavlinkSender::new();
+        let options = MavlinkSendOptions { signing_key: Some(key_hex.to_string()), system_id: 1, component_id: 1, ..Default::default() };
+        let stats = SharedMavlinkStats::default();
+        let mut decoder = MavlinkDecoder::new(stats.clone(), Some(key));
+
+        let mut stream = Vec::new();
+        for _ in 0..3 {
+            stream.extend(sender.frame("ATTITUDE", &json!({ "roll": 0.5 }), &options).unwrap());
+        }
+        // 跳过序号 3
+        sender.frame("ATTITUDE", &json!({}), &options).unwrap();
+        let mut tampered = sender.frame("ATTITUDE", &json!({}), &options).unwrap();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 0xFF;
+
+        let messages = decoder.feed(Direction::Rx, &stream, 1_000_000_000);
+        let late = decoder.feed(Direction::Rx, &tampered, 1_500_000_000);
+        assert_eq!(messages.len(), 3);
+        assert!(messages.iter().all(|m| m.valid));
+        assert_eq!(messages[0].fields["signature_ok"], json!(true));
+        assert!(!late[0].valid);
+        assert!(late[0].summary.ends_with("(bad signature)"));
+
+        // 重复序号不计丢包，CRC 错误帧丢弃但计数
+        assert_eq!(decoder.feed(Direction::Rx, &tampered, 1_500_000_000).len(), 1);
+        let mut corrupted = tampered.clone();
+        corrupted[12] ^= 0xFF;
+        assert!(decoder.feed(Direction::Rx, &corrupted, 1_500_000_000).is_empty());
+
+        let snapshot = stats.lock().unwrap().snapshot(1_500_000_000);
+        assert_eq!(snapshot.crc_errors, 1);
+        let component = &snapshot.components[0];
+        assert_eq!((component.system_id, component.received, component.lost), (1, 5, 1));
+        assert_eq!(component.messages[0].name.as_deref(), Some("ATTITUDE"));
+        assert!((component.rate_hz - 8.0).abs() < 1e-9);
+    }
+}
@@ -4,3 +4,31 @@ pub mod port_sharing_manager;
 
 pub mod admin_service;
 pub mod ipc;
+pub mod capture;
+pub mod pcapng;
+pub mod replay;
+pub mod hex;
+pub mod trigger;
+pub mod endpoint;
+pub mod simulator;
+pub mod text_decoder;
+pub mod vt_parser;
+pub mod line_assembler;
+pub mod session_link;
+pub mod checksum;
+pub mod modbus;
+pub mod file_transfer;
+pub mod xmodem;
+pub mod zmodem;
+pub mod at_command;
+pub mod firmware_image;
+pub mod stm32_bootloader;
+pub mod esp_loader;
+pub mod packet_schema;
+pub mod protocol_decoder;
+pub mod nmea;
+pub mod mavlink;
+pub mod slcan;
+pub mod socketcan;
+pub mod gcode_streamer;
+pub mod scpi;
@@ -0,0 +1,344 @@
+//! Modbus TCP → RTU 网关
+//!
+//! 在本地 TCP 端口监听 Modbus TCP (MBAP) 请求，转换为 RTU 帧在当前会话串口上发出。
+//! 多个 TCP 客户端的请求进入同一队列，由单个工作线程串行执行，保证总线上同一时刻只有一个事务；
+//! 从站无应答时回复异常码 0x0B，每个事务在会话抓包中记录一条标记。
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use log::{info, error};
+
+use super::*;
+use super::master::ModbusMaster;
+use crate::core::file_transfer::TransferGuard;
+use crate::core::session_link::SessionLink;
+
+/// MBAP 头长度 (事务号 2 + 协议号 2 + 长度 2 + 单元号 1)
+pub const MBAP_HEADER_LEN: usize = 7;
+
+/// 客户端读超时：空闲时据此检查停止标志，帧中途停顿超过此时间则断开
+const CLIENT_READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// MBAP 请求
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MbapFrame {
+    pub transaction_id: u16,
+    pub unit: u8,
+    pub pdu: Vec<u8>,
+}
+
+impl MbapFrame {
+    /// 由 7 字节头与后续 PDU 组成帧；头中的长度字段包含单元号
+    pub fn parse_header(header: &[u8; MBAP_HEADER_LEN]) -> Result<(u16, u8, usize)> {
+        let transaction_id = u16::from_be_bytes([header[0], header[1]]);
+        let protocol_id = u16::from_be_bytes([header[2], header[3]]);
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if protocol_id != 0 {
+            return Err(anyhow!("Unsupported MBAP protocol id: {}", protocol_id));
+        }
+        if !(2..=254).contains(&length) {
+            return Err(anyhow!("Invalid MBAP length: {}", length));
+        }
+        Ok((transaction_id, header[6], length - 1))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(MBAP_HEADER_LEN + self.pdu.len());
+        out.extend_from_slice(&self.transaction_id.to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(&((self.pdu.len() + 1) as u16).to_be_bytes());
+        out.push(self.unit);
+        out.extend_from_slice(&self.pdu);
+        out
+    }
+
+    /// 从流中读取一帧；对端关闭返回 None
+    pub fn read_from(stream: &mut impl Read) -> Result<Option<Self>> {
+        let mut header = [0u8; MBAP_HEADER_LEN];
+        match stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let (transaction_id, unit, pdu_len) = Self::parse_header(&header)?;
+        let mut pdu = vec![0u8; pdu_len];
+        stream.read_exact(&mut pdu)?;
+        Ok(Some(Self { transaction_id, unit, pdu }))
+    }
+}
+
+/// 将 RTU 事务结果转换为应答 PDU：从站异常原样返回，其余错误按网关异常处理
+pub fn response_pdu(function: u8, result: Result<Vec<u8>>) -> Vec<u8> {
+    match result {
+        Ok(pdu) => pdu,
+        Err(e) => {
+            let code = match e.downcast_ref::<ModbusException>() {
+                Some(exception) => exception.code,
+                None => ExceptionCode::GatewayTargetFailedToRespond,
+            };
+            vec![function | EXCEPTION_FLAG, code.to_u8()]
+        }
+    }
+}
+
+/// 排队中的事务
+struct Transaction {
+    request: MbapFrame,
+    reply: Sender<Option<MbapFrame>>,
+}
+
+/// 客户端线程及其连接 (停止时关闭连接以唤醒阻塞的读)
+type Connections = Arc<Mutex<Vec<(JoinHandle<()>, TcpStream)>>>;
+
+/// 网关状态
+#[derive(Debug, Clone, Serialize)]
+pub struct GatewayStatus {
+    pub running: bool,
+    pub listen_addr: Option<String>,
+    pub clients: usize,
+    pub transactions: usize,
+    pub errors: usize,
+}
+
+/// Modbus TCP 网关管理器
+pub struct ModbusGateway {
+    running: Arc<AtomicBool>,
+    listen_addr: Option<SocketAddr>,
+    clients: Arc<AtomicUsize>,
+    transactions: Arc<AtomicUsize>,
+    errors: Arc<AtomicUsize>,
+    /// 事务线程与监听线程
+    workers: Vec<JoinHandle<()>>,
+    connections: Connections,
+}
+
+impl Default for ModbusGateway {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModbusGateway {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            listen_addr: None,
+            clients: Arc::new(AtomicUsize::new(0)),
+            transactions: Arc::new(AtomicUsize::new(0)),
+            errors: Arc::new(AtomicUsize::new(0)),
+            workers: Vec::new(),
+            connections: Connections::default(),
+        }
+    }
+
+    /// 在 `bind_addr` (如 "127.0.0.1:502") 上监听，事务在 `link` 所属会话上执行；
+    /// `busy` 为会话链路的占用守卫，事务线程退出时释放
+    pub fn start(
+        &mut self,
+        link: SessionLink,
+        bind_addr: &str,
+        response_timeout: Duration,
+        busy: Option<TransferGuard>,
+    ) -> Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(anyhow!("Modbus gateway already running"));
+        }
+        // 回收已自行退出的上一次线程
+        self.stop_workers();
+
+        let listener = TcpListener::bind(bind_addr)
+            .map_err(|e| anyhow!("Failed to listen on {}: {}", bind_addr, e))?;
+        listener.set_nonblocking(true)?;
+        let mut master = ModbusMaster::new(link)?;
+        master.set_response_timeout(response_timeout);
+
+        self.listen_addr = Some(listener.local_addr()?);
+        self.clients = Arc::new(AtomicUsize::new(0));
+        self.transactions = Arc::new(AtomicUsize::new(0));
+        self.errors = Arc::new(AtomicUsize::new(0));
+        self.running = Arc::new(AtomicBool::new(true));
+
+        let (queue_tx, queue_rx) = mpsc::channel::<Transaction>();
+
+        // 串行事务线程
+        let running = self.running.clone();
+        let transactions = self.transactions.clone();
+        let errors = self.errors.clone();
+        self.workers.push(std::thread::spawn(move || {
+            let _busy = busy;
+            run_queue(master, queue_rx, running, transactions, errors);
+        }));
+
+        // 监听线程
+        let running = self.running.clone();
+        let clients = self.clients.clone();
+        let connections = self.connections.clone();
+        info!("Modbus TCP gateway listening on {:?}", self.listen_addr);
+        self.workers.push(std::thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, peer)) => {
+                        info!("Modbus TCP client connected: {}", peer);
+                        let handle = match stream.try_clone() {
+                            Ok(handle) => handle,
+                            Err(e) => {
+                                error!("Modbus TCP client {} error: {}", peer, e);
+                                continue;
+                            }
+                        };
+                        let queue_tx = queue_tx.clone();
+                        let running = running.clone();
+                        let clients = clients.clone();
+                        clients.fetch_add(1, Ordering::SeqCst);
+                        let worker = std::thread::spawn(move || {
+                            if let Err(e) = serve_client(stream, queue_tx, running) {
+                                error!("Modbus TCP client {} error: {}", peer, e);
+                            }
+                            clients.fetch_sub(1, Ordering::SeqCst);
+                            info!("Modbus TCP client disconnected: {}", peer);
+                        });
+                        if let Ok(mut connections) = connections.lock() {
+                            connections.retain(|(worker, _)| !worker.is_finished());
+                            connections.push((worker, handle));
+                        }
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                    Err(e) => {
+                        error!("Modbus TCP accept error: {}", e);
+                        break;
+                    }
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+            info!("Modbus TCP gateway stopped");
+        }));
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_workers();
+        self.listen_addr = None;
+        Ok(())
+    }
+
+    /// 通知当前线程退出，关闭客户端连接并等待全部线程结束
+    fn stop_workers(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        let connections = self.connections.lock().map(|mut c| std::mem::take(&mut *c)).unwrap_or_default();
+        for (worker, stream) in connections {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            let _ = worker.join();
+        }
+    }
+
+    pub fn status(&self) -> GatewayStatus {
+        GatewayStatus {
+            running: self.running.load(Ordering::SeqCst),
+            listen_addr: self.listen_addr.map(|a| a.to_string()),
+            clients: self.clients.load(Ordering::SeqCst),
+            transactions: self.transactions.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn run_queue(
+    mut master: ModbusMaster<SessionLink>,
+    queue: Receiver<Transaction>,
+    running: Arc<AtomicBool>,
+    transactions: Arc<AtomicUsize>,
+    errors: Arc<AtomicUsize>,
+) {
+    while running.load(Ordering::SeqCst) {
+        let Transaction { request, reply } = match queue.recv_timeout(Duration::from_millis(100)) {
+            Ok(t) => t,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let function = request.pdu[0];
+        let result = master.transact(request.unit, &request.pdu);
+        transactions.fetch_add(1, Ordering::SeqCst);
+
+        let outcome = match &result {
+            Ok(_) => "ok".to_string(),
+            Err(e) => {
+                errors.fetch_add(1, Ordering::SeqCst);
+                e.to_string()
+            }
+        };
+        master.link().mark(&format!(
+            "Modbus TCP tid={} unit={} fc=0x{:02X}: {}",
+            request.transaction_id, request.unit, function, outcome
+        ));
+
+        // 广播请求无应答
+        let response = (request.unit != 0).then(|| MbapFrame {
+            transaction_id: request.transaction_id,
+            unit: request.unit,
+            pdu: response_pdu(function, result),
+        });
+        let _ = reply.send(response);
+    }
+}
+
+fn serve_client(mut stream: TcpStream, queue: Sender<Transaction>, running: Arc<AtomicBool>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(CLIENT_READ_TIMEOUT))?;
+    while running.load(Ordering::SeqCst) {
+        // 空闲时等待下一帧首字节，超时后重新检查停止标志
+        match stream.peek(&mut [0u8; 1]) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.into()),
+        }
+        let request = match MbapFrame::read_from(&mut stream)? {
+            Some(r) => r,
+            None => break,
+        };
+        let (reply_tx, reply_rx) = mpsc::channel();
+        queue
+            .send(Transaction { request, reply: reply_tx })
+            .map_err(|_| anyhow!("Gateway stopped"))?;
+        if let Ok(Some(response)) = reply_rx.recv() {
+            stream.write_all(&response.encode())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mbap_round_trip() {
+        let frame = MbapFrame { transaction_id: 0x1234, unit: 1, pdu: vec![0x03, 0x00, 0x00, 0x00, 0x02] };
+        let bytes = frame.encode();
+        assert_eq!(&bytes[..7], &[0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0x01]);
+        let parsed = MbapFrame::read_from(&mut bytes.as_slice()).unwrap().unwrap();
+        assert_eq!(parsed, frame);
+        assert!(MbapFrame::read_from(&mut [].as_slice()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_response_pdu_exceptions() {
+        let slave_exception = anyhow::Error::from(ModbusException { function: 0x03, code: ExceptionCode::IllegalDataAddress });
+        assert_eq!(response_pdu(0x03, Err(slave_exception)), vec![0x83, 0x02]);
+        assert_eq!(response_pdu(0x03, Err(anyhow!("No response from unit 1"))), vec![0x83, 0x0B]);
+    }
+}
@@ -0,0 +1,380 @@
+//! Modbus RTU 主站
+//!
+//! 在 `SerialLink` 上按请求/应答方式访问从站。每次请求前等待 t3.5 帧间隔，
+//! 应答按功能码确定长度后校验 CRC；异常应答转换为 `ModbusException`。
+
+use anyhow::{Result, anyhow, bail};
+use std::time::{Duration, Instant};
+
+use super::*;
+use crate::core::session_link::SerialLink;
+
+/// 默认应答超时
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Modbus RTU 主站
+pub struct ModbusMaster<L: SerialLink> {
+    link: L,
+    response_timeout: Duration,
+    inter_frame: Duration,
+    inter_char: Duration,
+    last_frame_end: Option<Instant>,
+}
+
+impl<L: SerialLink> ModbusMaster<L> {
+    /// 创建主站，帧间隔按链路当前波特率计算
+    pub fn new(link: L) -> Result<Self> {
+        let baud_rate = link.baud_rate()?;
+        Ok(Self {
+            link,
+            response_timeout: DEFAULT_RESPONSE_TIMEOUT,
+            inter_frame: inter_frame_delay(baud_rate),
+            inter_char: inter_char_timeout(baud_rate),
+            last_frame_end: None,
+        })
+    }
+
+    pub fn set_response_timeout(&mut self, timeout: Duration) {
+        self.response_timeout = timeout;
+    }
+
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+
+    /// 发送请求 PDU 并返回应答 PDU (不含地址与 CRC)
+    ///
+    /// 广播地址 0 不等待应答，返回空 PDU。
+    pub fn transact(&mut self, unit: u8, request: &[u8]) -> Result<Vec<u8>> {
+        if request.is_empty() || request.len() > 253 {
+            bail!("Invalid PDU length: {}", request.len());
+        }
+        let function = request[0];
+
+        // 帧间静默
+        if let Some(last) = self.last_frame_end {
+            let elapsed = last.elapsed();
+            if elapsed < self.inter_frame {
+                std::thread::sleep(self.inter_frame - elapsed);
+            }
+        }
+
+        self.link.clear_input();
+        self.link.write_all(&build_rtu_frame(unit, request))?;
+
+        if unit == 0 {
+            self.last_frame_end = Some(Instant::now());
+            return Ok(Vec::new());
+        }
+
+        let result = self.read_response(unit, function);
+        self.last_frame_end = Some(Instant::now());
+        result
+    }
+
+    fn read_response(&mut self, unit: u8, function: u8) -> Result<Vec<u8>> {
+        let mut frame = vec![0u8; 2];
+        self.link
+            .read_exact(&mut frame, self.response_timeout)
+            .map_err(|_| anyhow!("No response from unit {}", unit))?;
+
+        if frame[0] != unit {
+            bail!("Unexpected unit address in response: {} (expected {})", frame[0], unit);
+        }
+
+        // 剩余长度 (含 CRC)
+        let remaining = if frame[1] == function | EXCEPTION_FLAG {
+            1 + 2
+        } else if frame[1] != function {
+            bail!("Unexpected function code in response: 0x{:02X}", frame[1]);
+        } else {
+            match function {
+                FC_READ_COILS
+                | FC_READ_DISCRETE_INPUTS
+                | FC_READ_HOLDING_REGISTERS
+                | FC_READ_INPUT_REGISTERS
+                | FC_READ_WRITE_MULTIPLE_REGISTERS => {
+                    let count = self.read_tail(1)?[0] as usize;
+                    frame.push(count as u8);
+                    count + 2
+                }
+                FC_WRITE_SINGLE_COIL
+                | FC_WRITE_SINGLE_REGISTER
+                | FC_WRITE_MULTIPLE_COILS
+                | FC_WRITE_MULTIPLE_REGISTERS => 4 + 2,
+                // 未知功能码 (如网关透传)：读到静默为止，以 CRC 判定帧结束
+                _ => return self.read_until_silence(frame),
+            }
+        };
+        let tail = self.read_tail(remaining)?;
+        frame.extend_from_slice(&tail);
+
+        let (_, pdu) = split_rtu_frame(&frame).ok_or_else(|| anyhow!("CRC mismatch in response"))?;
+        if pdu[0] & EXCEPTION_FLAG != 0 {
+            return Err(ModbusException {
+                function,
+                code: ExceptionCode::from_u8(pdu[1]),
+            }
+            .into());
+        }
+        Ok(pdu.to_vec())
+    }
+
+    fn read_until_silence(&mut self, mut frame: Vec<u8>) -> Result<Vec<u8>> {
+        let gap = self.inter_frame.max(Duration::from_millis(20));
+        while frame.len() < MAX_RTU_FRAME {
+            match self.link.read_byte(gap)? {
+                Some(b) => frame.push(b),
+                None => break,
+            }
+        }
+        let (_, pdu) = split_rtu_frame(&frame).ok_or_else(|| anyhow!("CRC mismatch in response"))?;
+        Ok(pdu.to_vec())
+    }
+
+    /// 读取帧的剩余部分；字节间隔按 t1.5 放宽到整帧时长，避免 USB 转串口的分包延迟误判
+    fn read_tail(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        let timeout = self.inter_char * (len as u32 + 1) + self.response_timeout;
+        self.link
+            .read_exact(&mut buf, timeout)
+            .map_err(|_| anyhow!("Incomplete response frame"))?;
+        Ok(buf)
+    }
+
+    fn read_bits(&mut self, unit: u8, function: u8, address: u16, quantity: u16) -> Result<Vec<bool>> {
+        if quantity == 0 || quantity > 2000 {
+            bail!("Invalid quantity: {}", quantity);
+        }
+        let mut request = vec![function];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&quantity.to_be_bytes());
+        let pdu = self.transact(unit, &request)?;
+        let data = byte_count_payload(&pdu, (quantity as usize).div_ceil(8))?;
+        Ok(unpack_bits(data, quantity as usize))
+    }
+
+    fn read_registers(&mut self, unit: u8, function: u8, address: u16, quantity: u16) -> Result<Vec<u16>> {
+        if quantity == 0 || quantity > 125 {
+            bail!("Invalid quantity: {}", quantity);
+        }
+        let mut request = vec![function];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&quantity.to_be_bytes());
+        let pdu = self.transact(unit, &request)?;
+        let data = byte_count_payload(&pdu, quantity as usize * 2)?;
+        Ok(bytes_to_registers(data))
+    }
+
+    /// FC 01
+    pub fn read_coils(&mut self, unit: u8, address: u16, quantity: u16) -> Result<Vec<bool>> {
+        self.read_bits(unit, FC_READ_COILS, address, quantity)
+    }
+
+    /// FC 02
+    pub fn read_discrete_inputs(&mut self, unit: u8, address: u16, quantity: u16) -> Result<Vec<bool>> {
+        self.read_bits(unit, FC_READ_DISCRETE_INPUTS, address, quantity)
+    }
+
+    /// FC 03
+    pub fn read_holding_registers(&mut self, unit: u8, address: u16, quantity: u16) -> Result<Vec<u16>> {
+        self.read_registers(unit, FC_READ_HOLDING_REGISTERS, address, quantity)
+    }
+
+    /// FC 04
+    pub fn read_input_registers(&mut self, unit: u8, address: u16, quantity: u16) -> Result<Vec<u16>> {
+        self.read_registers(unit, FC_READ_INPUT_REGISTERS, address, quantity)
+    }
+
+    /// FC 05
+    pub fn write_single_coil(&mut self, unit: u8, address: u16, value: bool) -> Result<()> {
+        let mut request = vec![FC_WRITE_SINGLE_COIL];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(if value { &[0xFF, 0x00] } else { &[0x00, 0x00] });
+        self.write_echo(unit, &request)
+    }
+
+    /// FC 06
+    pub fn write_single_register(&mut self, unit: u8, address: u16, value: u16) -> Result<()> {
+        let mut request = vec![FC_WRITE_SINGLE_REGISTER];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&value.to_be_bytes());
+        self.write_echo(unit, &request)
+    }
+
+    /// FC 15
+    pub fn write_multiple_coils(&mut self, unit: u8, address: u16, values: &[bool]) -> Result<()> {
+        if values.is_empty() || values.len() > 1968 {
+            bail!("Invalid quantity: {}", values.len());
+        }
+        let packed = pack_bits(values);
+        let mut request = vec![FC_WRITE_MULTIPLE_COILS];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        request.push(packed.len() as u8);
+        request.extend_from_slice(&packed);
+        self.write_echo(unit, &request)
+    }
+
+    /// FC 16
+    pub fn write_multiple_registers(&mut self, unit: u8, address: u16, values: &[u16]) -> Result<()> {
+        if values.is_empty() || values.len() > 123 {
+            bail!("Invalid quantity: {}", values.len());
+        }
+        let mut request = vec![FC_WRITE_MULTIPLE_REGISTERS];
+        request.extend_from_slice(&address.to_be_bytes());
+        request.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        request.push((values.len() * 2) as u8);
+        request.extend_from_slice(&registers_to_bytes(values));
+        self.write_echo(unit, &request)
+    }
+
+    /// FC 23: 先写后读
+    pub fn read_write_multiple_registers(
+        &mut self,
+        unit: u8,
+        read_address: u16,
+        read_quantity: u16,
+        write_address: u16,
+        values: &[u16],
+    ) -> Result<Vec<u16>> {
+        if read_quantity == 0 || read_quantity > 125 {
+            bail!("Invalid read quantity: {}", read_quantity);
+        }
+        if values.is_empty() || values.len() > 121 {
+            bail!("Invalid write quantity: {}", values.len());
+        }
+        let mut request = vec![FC_READ_WRITE_MULTIPLE_REGISTERS];
+        request.extend_from_slice(&read_address.to_be_bytes());
+        request.extend_from_slice(&read_quantity.to_be_bytes());
+        request.extend_from_slice(&write_address.to_be_bytes());
+        request.extend_from_slice(&(values.len() as u16).to_be_bytes());
+        request.push((values.len() * 2) as u8);
+        request.extend_from_slice(&registers_to_bytes(values));
+        let pdu = self.transact(unit, &request)?;
+        let data = byte_count_payload(&pdu, read_quantity as usize * 2)?;
+        Ok(bytes_to_registers(data))
+    }
+
+    /// 写类功能码：应答回显地址与数量/值
+    fn write_echo(&mut self, unit: u8, request: &[u8]) -> Result<()> {
+        let pdu = self.transact(unit, request)?;
+        if unit != 0 && pdu.get(..5) != request.get(..5) {
+            bail!("Unexpected write response");
+        }
+        Ok(())
+    }
+}
+
+/// 校验 `[fc, byte_count, data...]` 并返回 data
+fn byte_count_payload(pdu: &[u8], expected: usize) -> Result<&[u8]> {
+    match pdu.get(1) {
+        Some(&count) if count as usize == expected && pdu.len() == expected + 2 => Ok(&pdu[2..]),
+        _ => Err(anyhow!("Unexpected byte count in response")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serialport::Parity;
+    use std::collections::VecDeque;
+
+    /// 预置应答的模拟链路
+    struct MockLink {
+        written: Vec<u8>,
+        responses: VecDeque<Vec<u8>>,
+        rx: VecDeque<u8>,
+    }
+
+    impl MockLink {
+        fn new(responses: Vec<Vec<u8>>) -> Self {
+            Self {
+                written: Vec::new(),
+                responses: responses.into(),
+                rx: VecDeque::new(),
+            }
+        }
+    }
+
+    impl SerialLink for MockLink {
+        fn write_all(&mut self, data: &[u8]) -> Result<()> {
+            self.written.extend_from_slice(data);
+            if let Some(resp) = self.responses.pop_front() {
+                self.rx.extend(resp);
+            }
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+            let n = buf.len().min(self.rx.len());
+            for (dst, src) in buf.iter_mut().zip(self.rx.drain(..n)) {
+                *dst = src;
+            }
+            Ok(n)
+        }
+
+        fn clear_input(&mut self) {
+            self.rx.clear();
+        }
+
+        fn baud_rate(&self) -> Result<u32> {
+            Ok(115200)
+        }
+
+        fn set_baud_rate(&mut self, _baud_rate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn parity(&self) -> Result<Parity> {
+            Ok(Parity::None)
+        }
+
+        fn set_parity(&mut self, _parity: Parity) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_dtr(&mut self, _level: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_rts(&mut self, _level: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_read_holding_registers() {
+        let response = build_rtu_frame(1, &[0x03, 0x04, 0x00, 0x0A, 0x01, 0x02]);
+        let mut master = ModbusMaster::new(MockLink::new(vec![response])).unwrap();
+        let values = master.read_holding_registers(1, 0, 2).unwrap();
+        assert_eq!(values, vec![0x000A, 0x0102]);
+        assert_eq!(master.into_inner().written, build_rtu_frame(1, &[0x03, 0x00, 0x00, 0x00, 0x02]));
+    }
+
+    #[test]
+    fn test_exception_response() {
+        let response = build_rtu_frame(1, &[0x83, 0x02]);
+        let mut master = ModbusMaster::new(MockLink::new(vec![response])).unwrap();
+        let err = master.read_holding_registers(1, 100, 1).unwrap_err();
+        let exception = err.downcast_ref::<ModbusException>().unwrap();
+        assert_eq!(exception.code, ExceptionCode::IllegalDataAddress);
+    }
+
+    #[test]
+    fn test_write_multiple_coils_and_bad_crc() {
+        let request = [0x0F, 0x00, 0x13, 0x00, 0x0A];
+        let mut master = ModbusMaster::new(MockLink::new(vec![build_rtu_frame(1, &request)])).unwrap();
+        let values = [true, false, true, true, false, false, true, true, true, false];
+        master.write_multiple_coils(1, 0x13, &values).unwrap();
+
+        let mut corrupted = build_rtu_frame(1, &[0x06, 0x00, 0x01, 0x00, 0x03]);
+        corrupted[3] ^= 0xFF;
+        let mut master = ModbusMaster::new(MockLink::new(vec![corrupted])).unwrap();
+        assert!(master.write_single_register(1, 1, 3).is_err());
+    }
+}
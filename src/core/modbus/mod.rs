@@ -0,0 +1,202 @@
+//! Modbus RTU
+//!
+//! 公共部分：功能码、异常码、CRC16、RTU 帧编解码与帧间隔计算。
+//! - `master`: 主站 (在活动会话上发起读写)
+//! - `sniffer`: 总线监听，被动切帧与请求/应答配对
+//! - `slave`: 从站模拟器 (寄存器表)
+//! - `gateway`: Modbus TCP → RTU 网关
+
+pub mod master;
+pub mod sniffer;
+pub mod slave;
+pub mod gateway;
+
+use serde::{Serialize, Deserialize};
+use std::time::Duration;
+
+use crate::core::checksum::crc16_modbus;
+
+pub const FC_READ_COILS: u8 = 0x01;
+pub const FC_READ_DISCRETE_INPUTS: u8 = 0x02;
+pub const FC_READ_HOLDING_REGISTERS: u8 = 0x03;
+pub const FC_READ_INPUT_REGISTERS: u8 = 0x04;
+pub const FC_WRITE_SINGLE_COIL: u8 = 0x05;
+pub const FC_WRITE_SINGLE_REGISTER: u8 = 0x06;
+pub const FC_WRITE_MULTIPLE_COILS: u8 = 0x0F;
+pub const FC_WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+pub const FC_READ_WRITE_MULTIPLE_REGISTERS: u8 = 0x17;
+
+/// 异常响应的功能码标志位
+pub const EXCEPTION_FLAG: u8 = 0x80;
+
+/// RTU 帧最大长度 (地址 + PDU 253 + CRC)
+pub const MAX_RTU_FRAME: usize = 256;
+
+/// Modbus 异常码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExceptionCode {
+    IllegalFunction,
+    IllegalDataAddress,
+    IllegalDataValue,
+    ServerDeviceFailure,
+    Acknowledge,
+    ServerDeviceBusy,
+    MemoryParityError,
+    GatewayPathUnavailable,
+    GatewayTargetFailedToRespond,
+    Unknown(u8),
+}
+
+impl ExceptionCode {
+    pub fn from_u8(code: u8) -> Self {
+        match code {
+            0x01 => ExceptionCode::IllegalFunction,
+            0x02 => ExceptionCode::IllegalDataAddress,
+            0x03 => ExceptionCode::IllegalDataValue,
+            0x04 => ExceptionCode::ServerDeviceFailure,
+            0x05 => ExceptionCode::Acknowledge,
+            0x06 => ExceptionCode::ServerDeviceBusy,
+            0x08 => ExceptionCode::MemoryParityError,
+            0x0A => ExceptionCode::GatewayPathUnavailable,
+            0x0B => ExceptionCode::GatewayTargetFailedToRespond,
+            other => ExceptionCode::Unknown(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ExceptionCode::IllegalFunction => 0x01,
+            ExceptionCode::IllegalDataAddress => 0x02,
+            ExceptionCode::IllegalDataValue => 0x03,
+            ExceptionCode::ServerDeviceFailure => 0x04,
+            ExceptionCode::Acknowledge => 0x05,
+            ExceptionCode::ServerDeviceBusy => 0x06,
+            ExceptionCode::MemoryParityError => 0x08,
+            ExceptionCode::GatewayPathUnavailable => 0x0A,
+            ExceptionCode::GatewayTargetFailedToRespond => 0x0B,
+            ExceptionCode::Unknown(code) => code,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ExceptionCode::IllegalFunction => "Illegal function",
+            ExceptionCode::IllegalDataAddress => "Illegal data address",
+            ExceptionCode::IllegalDataValue => "Illegal data value",
+            ExceptionCode::ServerDeviceFailure => "Server device failure",
+            ExceptionCode::Acknowledge => "Acknowledge",
+            ExceptionCode::ServerDeviceBusy => "Server device busy",
+            ExceptionCode::MemoryParityError => "Memory parity error",
+            ExceptionCode::GatewayPathUnavailable => "Gateway path unavailable",
+            ExceptionCode::GatewayTargetFailedToRespond => "Gateway target device failed to respond",
+            ExceptionCode::Unknown(_) => "Unknown exception",
+        }
+    }
+}
+
+/// 从站返回的异常响应
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Modbus exception 0x{:02X} on function 0x{function:02X}: {}", .code.to_u8(), .code.description())]
+pub struct ModbusException {
+    pub function: u8,
+    pub code: ExceptionCode,
+}
+
+/// 组装 RTU 帧: 地址 + PDU + CRC(LE)
+pub fn build_rtu_frame(unit: u8, pdu: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(pdu.len() + 3);
+    frame.push(unit);
+    frame.extend_from_slice(pdu);
+    let crc = crc16_modbus(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// 校验 RTU 帧 CRC，成功返回 (地址, PDU)
+pub fn split_rtu_frame(frame: &[u8]) -> Option<(u8, &[u8])> {
+    if frame.len() < 4 {
+        return None;
+    }
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    if crc16_modbus(body).to_le_bytes() != [crc[0], crc[1]] {
+        return None;
+    }
+    Some((body[0], &body[1..]))
+}
+
+/// `chars` 个字符的传输时间 (每字符按 11 位: 起始 + 8 数据 + 校验/停止 + 停止)
+fn chars_time(chars: f64, baud_rate: u32) -> Duration {
+    Duration::from_micros((chars * 11_000_000.0 / baud_rate.max(1) as f64) as u64)
+}
+
+/// 帧间隔 t3.5；波特率高于 19200 时按规范固定为 1.75ms
+pub fn inter_frame_delay(baud_rate: u32) -> Duration {
+    if baud_rate > 19200 {
+        Duration::from_micros(1750)
+    } else {
+        chars_time(3.5, baud_rate)
+    }
+}
+
+/// 字符间隔 t1.5；波特率高于 19200 时固定为 750us
+pub fn inter_char_timeout(baud_rate: u32) -> Duration {
+    if baud_rate > 19200 {
+        Duration::from_micros(750)
+    } else {
+        chars_time(1.5, baud_rate)
+    }
+}
+
+/// 将线圈状态打包为字节 (LSB 在前)
+pub fn pack_bits(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, &bit) in bits.iter().enumerate() {
+        if bit {
+            bytes[i / 8] |= 1 << (i % 8);
+        }
+    }
+    bytes
+}
+
+/// 从字节中解出 `count` 个位
+pub fn unpack_bits(bytes: &[u8], count: usize) -> Vec<bool> {
+    (0..count)
+        .map(|i| bytes.get(i / 8).map(|b| b & (1 << (i % 8)) != 0).unwrap_or(false))
+        .collect()
+}
+
+/// 大端寄存器序列
+pub fn registers_to_bytes(values: &[u16]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_be_bytes()).collect()
+}
+
+pub fn bytes_to_registers(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc16() {
+        // 01 03 00 00 00 01 -> CRC 0x0A84 (帧尾 84 0A)
+        let frame = build_rtu_frame(0x01, &[0x03, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(frame, vec![0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]);
+        assert_eq!(split_rtu_frame(&frame), Some((0x01, &frame[1..6])));
+        assert_eq!(split_rtu_frame(&frame[..7]), None);
+    }
+
+    #[test]
+    fn test_bits() {
+        let bits = vec![true, false, true, true, false, false, false, false, true];
+        assert_eq!(pack_bits(&bits), vec![0x0D, 0x01]);
+        assert_eq!(unpack_bits(&[0x0D, 0x01], 9), bits);
+    }
+
+    #[test]
+    fn test_timing() {
+        assert_eq!(inter_frame_delay(9600), Duration::from_micros(4010));
+        assert_eq!(inter_frame_delay(115200), Duration::from_micros(1750));
+    }
+}
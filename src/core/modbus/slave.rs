@@ -0,0 +1,494 @@
+//! Modbus RTU 从站模拟器
+//!
+//! 从 YAML 或 CSV 加载寄存器表，在串口 / 虚拟串口对 / pty 上应答主站请求。
+//! 运行期间寄存器表可从应用中读取和修改，主站写入时通过回调通知。
+//!
+//! YAML:
+//! ```yaml
+//! unit: 1
+//! coils: { 0: true, 1: false }
+//! discrete_inputs: { 0: true }
+//! holding_registers: { 0: 100, 1: 0x1234 }
+//! input_registers: { 0: 250 }
+//! ```
+//!
+//! CSV (表头可选，table 取 coil / discrete_input / holding / input)：
+//! ```text
+//! table,address,value
+//! holding,0,100
+//! coil,3,1
+//! ```
+
+use anyhow::{Result, anyhow, Context};
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use log::{info, error};
+
+use super::*;
+use super::sniffer::frame_len;
+use crate::core::endpoint::Endpoint;
+
+/// 寄存器表类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegisterTable {
+    Coil,
+    DiscreteInput,
+    HoldingRegister,
+    InputRegister,
+}
+
+impl RegisterTable {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+            "coil" | "coils" => Ok(RegisterTable::Coil),
+            "discrete_input" | "discrete_inputs" | "discrete" => Ok(RegisterTable::DiscreteInput),
+            "holding" | "holding_register" | "holding_registers" => Ok(RegisterTable::HoldingRegister),
+            "input" | "input_register" | "input_registers" => Ok(RegisterTable::InputRegister),
+            _ => Err(anyhow!("Unknown register table: {}", s)),
+        }
+    }
+}
+
+/// 寄存器表；未定义的地址按非法地址 (异常码 02) 应答
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RegisterMap {
+    #[serde(default)]
+    pub coils: BTreeMap<u16, bool>,
+    #[serde(default)]
+    pub discrete_inputs: BTreeMap<u16, bool>,
+    #[serde(default)]
+    pub holding_registers: BTreeMap<u16, u16>,
+    #[serde(default)]
+    pub input_registers: BTreeMap<u16, u16>,
+}
+
+/// 从站配置文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlaveProfile {
+    #[serde(default = "default_unit")]
+    pub unit: u8,
+    #[serde(flatten)]
+    pub map: RegisterMap,
+}
+
+fn default_unit() -> u8 {
+    1
+}
+
+impl SlaveProfile {
+    /// 按扩展名加载 YAML 或 CSV (CSV 不含从站地址，使用默认值 1)
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read register map {:?}", path))?;
+        let is_csv = path.extension().map(|e| e.eq_ignore_ascii_case("csv")).unwrap_or(false);
+        if is_csv {
+            Ok(Self { unit: default_unit(), map: RegisterMap::parse_csv(&content)? })
+        } else {
+            serde_yaml::from_str(&content).map_err(|e| anyhow!("Invalid register map: {}", e))
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<u16> {
+    let s = s.trim();
+    let value = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse::<u16>(),
+    };
+    value.map_err(|_| anyhow!("Invalid number: {}", s))
+}
+
+impl RegisterMap {
+    pub fn parse_csv(content: &str) -> Result<Self> {
+        let mut map = RegisterMap::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').collect();
+            if fields.len() < 3 {
+                return Err(anyhow!("Line {}: expected table,address,value", i + 1));
+            }
+            // 表头
+            if i == 0 && parse_number(fields[1]).is_err() {
+                continue;
+            }
+            let table = RegisterTable::parse(fields[0]).with_context(|| format!("Line {}", i + 1))?;
+            let address = parse_number(fields[1]).with_context(|| format!("Line {}", i + 1))?;
+            let value = match fields[2].trim().to_lowercase().as_str() {
+                "true" | "on" => 1,
+                "false" | "off" => 0,
+                other => parse_number(other).with_context(|| format!("Line {}", i + 1))?,
+            };
+            map.set(table, address, value);
+        }
+        Ok(map)
+    }
+
+    /// 设置单个值 (线圈/离散输入非 0 即为 ON)，地址不存在时新建
+    pub fn set(&mut self, table: RegisterTable, address: u16, value: u16) {
+        match table {
+            RegisterTable::Coil => { self.coils.insert(address, value != 0); }
+            RegisterTable::DiscreteInput => { self.discrete_inputs.insert(address, value != 0); }
+            RegisterTable::HoldingRegister => { self.holding_registers.insert(address, value); }
+            RegisterTable::InputRegister => { self.input_registers.insert(address, value); }
+        }
+    }
+
+    fn read_bits(table: &BTreeMap<u16, bool>, address: u16, quantity: u16) -> Result<Vec<bool>, ExceptionCode> {
+        if quantity == 0 || quantity > 2000 {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+        (0..quantity)
+            .map(|i| {
+                let addr = address.checked_add(i).ok_or(ExceptionCode::IllegalDataAddress)?;
+                table.get(&addr).copied().ok_or(ExceptionCode::IllegalDataAddress)
+            })
+            .collect()
+    }
+
+    fn read_registers(table: &BTreeMap<u16, u16>, address: u16, quantity: u16) -> Result<Vec<u16>, ExceptionCode> {
+        if quantity == 0 || quantity > 125 {
+            return Err(ExceptionCode::IllegalDataValue);
+        }
+        (0..quantity)
+            .map(|i| {
+                let addr = address.checked_add(i).ok_or(ExceptionCode::IllegalDataAddress)?;
+                table.get(&addr).copied().ok_or(ExceptionCode::IllegalDataAddress)
+            })
+            .collect()
+    }
+
+    /// 检查写入范围内的地址均已定义
+    fn check_range<T>(table: &BTreeMap<u16, T>, address: u16, quantity: usize) -> Result<(), ExceptionCode> {
+        for i in 0..quantity {
+            let addr = address.checked_add(i as u16).ok_or(ExceptionCode::IllegalDataAddress)?;
+            if !table.contains_key(&addr) {
+                return Err(ExceptionCode::IllegalDataAddress);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 寄存器变化 (主站写入或应用修改)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegisterChange {
+    pub table: RegisterTable,
+    pub address: u16,
+    /// 线圈以 0/1 表示
+    pub value: u16,
+}
+
+/// 处理一个请求 PDU，返回应答 PDU 或异常码；写入产生的变化追加到 `changes`
+pub fn handle_request(map: &mut RegisterMap, pdu: &[u8], changes: &mut Vec<RegisterChange>) -> Result<Vec<u8>, ExceptionCode> {
+    let function = pdu[0];
+    let field = |at: usize| -> Result<u16, ExceptionCode> {
+        pdu.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or(ExceptionCode::IllegalDataValue)
+    };
+
+    let mut response = vec![function];
+    match function {
+        FC_READ_COILS | FC_READ_DISCRETE_INPUTS => {
+            let table = if function == FC_READ_COILS { &map.coils } else { &map.discrete_inputs };
+            let bits = RegisterMap::read_bits(table, field(1)?, field(3)?)?;
+            let packed = pack_bits(&bits);
+            response.push(packed.len() as u8);
+            response.extend_from_slice(&packed);
+        }
+        FC_READ_HOLDING_REGISTERS | FC_READ_INPUT_REGISTERS => {
+            let table = if function == FC_READ_HOLDING_REGISTERS { &map.holding_registers } else { &map.input_registers };
+            let values = RegisterMap::read_registers(table, field(1)?, field(3)?)?;
+            response.push((values.len() * 2) as u8);
+            response.extend_from_slice(&registers_to_bytes(&values));
+        }
+        FC_WRITE_SINGLE_COIL => {
+            let (address, raw) = (field(1)?, field(3)?);
+            let value = match raw {
+                0xFF00 => true,
+                0x0000 => false,
+                _ => return Err(ExceptionCode::IllegalDataValue),
+            };
+            RegisterMap::check_range(&map.coils, address, 1)?;
+            map.coils.insert(address, value);
+            changes.push(RegisterChange { table: RegisterTable::Coil, address, value: value as u16 });
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        FC_WRITE_SINGLE_REGISTER => {
+            let (address, value) = (field(1)?, field(3)?);
+            RegisterMap::check_range(&map.holding_registers, address, 1)?;
+            map.holding_registers.insert(address, value);
+            changes.push(RegisterChange { table: RegisterTable::HoldingRegister, address, value });
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        FC_WRITE_MULTIPLE_COILS => {
+            let (address, quantity) = (field(1)?, field(3)?);
+            let data = pdu.get(6..).ok_or(ExceptionCode::IllegalDataValue)?;
+            if quantity == 0 || quantity > 1968 || data.len() != (quantity as usize).div_ceil(8) {
+                return Err(ExceptionCode::IllegalDataValue);
+            }
+            RegisterMap::check_range(&map.coils, address, quantity as usize)?;
+            for (i, value) in unpack_bits(data, quantity as usize).into_iter().enumerate() {
+                let addr = address + i as u16;
+                map.coils.insert(addr, value);
+                changes.push(RegisterChange { table: RegisterTable::Coil, address: addr, value: value as u16 });
+            }
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        FC_WRITE_MULTIPLE_REGISTERS => {
+            let (address, quantity) = (field(1)?, field(3)?);
+            let data = pdu.get(6..).ok_or(ExceptionCode::IllegalDataValue)?;
+            if quantity == 0 || quantity > 123 || data.len() != quantity as usize * 2 {
+                return Err(ExceptionCode::IllegalDataValue);
+            }
+            RegisterMap::check_range(&map.holding_registers, address, quantity as usize)?;
+            write_registers(map, address, &bytes_to_registers(data), changes);
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        FC_READ_WRITE_MULTIPLE_REGISTERS => {
+            let (read_address, read_quantity) = (field(1)?, field(3)?);
+            let (write_address, write_quantity) = (field(5)?, field(7)?);
+            let data = pdu.get(10..).ok_or(ExceptionCode::IllegalDataValue)?;
+            if write_quantity == 0 || write_quantity > 121 || data.len() != write_quantity as usize * 2 {
+                return Err(ExceptionCode::IllegalDataValue);
+            }
+            RegisterMap::check_range(&map.holding_registers, write_address, write_quantity as usize)?;
+            // 读范围需在写入前校验，避免异常应答时已修改寄存器
+            RegisterMap::read_registers(&map.holding_registers, read_address, read_quantity)?;
+            // 规范要求先写后读
+            write_registers(map, write_address, &bytes_to_registers(data), changes);
+            let values = RegisterMap::read_registers(&map.holding_registers, read_address, read_quantity)?;
+            response.push((values.len() * 2) as u8);
+            response.extend_from_slice(&registers_to_bytes(&values));
+        }
+        _ => return Err(ExceptionCode::IllegalFunction),
+    }
+    Ok(response)
+}
+
+fn write_registers(map: &mut RegisterMap, address: u16, values: &[u16], changes: &mut Vec<RegisterChange>) {
+    for (i, &value) in values.iter().enumerate() {
+        let addr = address + i as u16;
+        map.holding_registers.insert(addr, value);
+        changes.push(RegisterChange { table: RegisterTable::HoldingRegister, address: addr, value });
+    }
+}
+
+/// 处理一帧 RTU 请求，返回应答帧 (非本机地址、广播或 CRC 错误时不应答)
+pub fn handle_frame(unit: u8, map: &mut RegisterMap, frame: &[u8], changes: &mut Vec<RegisterChange>) -> Option<Vec<u8>> {
+    let (address, pdu) = split_rtu_frame(frame)?;
+    if address != unit && address != 0 {
+        return None;
+    }
+    let response = match handle_request(map, pdu, changes) {
+        Ok(response) => response,
+        Err(code) => vec![pdu[0] | EXCEPTION_FLAG, code.to_u8()],
+    };
+    (address != 0).then(|| build_rtu_frame(unit, &response))
+}
+
+/// 从站模拟器状态
+#[derive(Debug, Clone, Serialize)]
+pub struct ModbusSlaveStatus {
+    pub running: bool,
+    pub unit: u8,
+    pub device_path: Option<String>,
+    pub requests_handled: usize,
+}
+
+/// 寄存器变化回调
+pub type ChangeCallback = Box<dyn Fn(&RegisterChange) + Send>;
+
+/// 从站模拟器管理器
+pub struct ModbusSlaveManager {
+    running: Arc<AtomicBool>,
+    requests_handled: Arc<AtomicUsize>,
+    unit: u8,
+    device_path: Option<String>,
+    map: Arc<Mutex<RegisterMap>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Default for ModbusSlaveManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModbusSlaveManager {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            requests_handled: Arc::new(AtomicUsize::new(0)),
+            unit: default_unit(),
+            device_path: None,
+            map: Arc::new(Mutex::new(RegisterMap::default())),
+            worker: None,
+        }
+    }
+
+    /// 加载寄存器表并在端点上启动；`unit` 覆盖配置文件中的从站地址
+    pub fn start(&mut self, map_path: &Path, unit: Option<u8>, endpoint: &Endpoint, on_change: Option<ChangeCallback>) -> Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(anyhow!("Modbus slave already running"));
+        }
+        // 回收已自行退出的上一次线程
+        self.stop_worker();
+
+        let profile = SlaveProfile::load(map_path)?;
+        let unit = unit.unwrap_or(profile.unit);
+        if unit == 0 || unit > 247 {
+            return Err(anyhow!("Invalid unit address: {}", unit));
+        }
+        let mut opened = endpoint.open(Duration::from_millis(20))?;
+
+        self.unit = unit;
+        self.device_path = opened.device_path.clone();
+        *self.map.lock().unwrap() = profile.map;
+        self.requests_handled = Arc::new(AtomicUsize::new(0));
+        self.running = Arc::new(AtomicBool::new(true));
+
+        let running = self.running.clone();
+        let requests_handled = self.requests_handled.clone();
+        let map = self.map.clone();
+
+        info!("Modbus slave {} started on {:?}", unit, self.device_path);
+
+        self.worker = Some(std::thread::spawn(move || {
+            let mut buf = [0u8; 256];
+            let mut pending: Vec<u8> = Vec::new();
+            while running.load(Ordering::SeqCst) {
+                match opened.port.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        pending.extend_from_slice(&buf[..n]);
+                        while let Some(len) = frame_len(&pending) {
+                            let frame: Vec<u8> = pending.drain(..len).collect();
+                            let mut changes = Vec::new();
+                            let response = handle_frame(unit, &mut map.lock().unwrap(), &frame, &mut changes);
+                            if let Some(callback) = &on_change {
+                                changes.iter().for_each(callback);
+                            }
+                            let Some(response) = response else { continue };
+                            if let Err(e) = opened.port.write_all(&response).and_then(|_| opened.port.flush()) {
+                                error!("Modbus slave write error: {}", e);
+                                running.store(false, Ordering::SeqCst);
+                                break;
+                            }
+                            requests_handled.fetch_add(1, Ordering::SeqCst);
+                        }
+                        if pending.len() > MAX_RTU_FRAME {
+                            pending.clear();
+                        }
+                    }
+                    Ok(_) => {}
+                    // 总线静默：丢弃不完整或损坏的帧
+                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => pending.clear(),
+                    Err(e) => {
+                        error!("Modbus slave read error: {}", e);
+                        break;
+                    }
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+            info!("Modbus slave thread exited");
+        }));
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_worker();
+        self.device_path = None;
+        Ok(())
+    }
+
+    /// 通知当前线程退出并等待其结束
+    fn stop_worker(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    pub fn status(&self) -> ModbusSlaveStatus {
+        ModbusSlaveStatus {
+            running: self.running.load(Ordering::SeqCst),
+            unit: self.unit,
+            device_path: self.device_path.clone(),
+            requests_handled: self.requests_handled.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 当前寄存器表快照
+    pub fn registers(&self) -> RegisterMap {
+        self.map.lock().unwrap().clone()
+    }
+
+    /// 修改寄存器 (运行中立即生效)，返回与主站写入相同格式的变化记录
+    pub fn set_register(&self, table: RegisterTable, address: u16, value: u16) -> RegisterChange {
+        self.map.lock().unwrap().set(table, address, value);
+        let value = match table {
+            RegisterTable::Coil | RegisterTable::DiscreteInput => u16::from(value != 0),
+            RegisterTable::HoldingRegister | RegisterTable::InputRegister => value,
+        };
+        RegisterChange { table, address, value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_map() -> RegisterMap {
+        let profile: SlaveProfile = serde_yaml::from_str(
+            "unit: 5\ncoils: { 0: true, 1: false, 2: true }\nholding_registers: { 10: 100, 11: 0x1234 }\n",
+        )
+        .unwrap();
+        assert_eq!(profile.unit, 5);
+        profile.map
+    }
+
+    #[test]
+    fn test_read_and_write_registers() {
+        let mut map = sample_map();
+        let mut changes = Vec::new();
+        let response = handle_frame(5, &mut map, &build_rtu_frame(5, &[0x03, 0x00, 0x0A, 0x00, 0x02]), &mut changes);
+        assert_eq!(response, Some(build_rtu_frame(5, &[0x03, 0x04, 0x00, 0x64, 0x12, 0x34])));
+
+        let request = build_rtu_frame(5, &[0x06, 0x00, 0x0B, 0x00, 0x07]);
+        assert_eq!(handle_frame(5, &mut map, &request, &mut changes), Some(request.clone()));
+        assert_eq!(map.holding_registers[&11], 7);
+        assert_eq!(changes, vec![RegisterChange { table: RegisterTable::HoldingRegister, address: 11, value: 7 }]);
+
+        // 其它从站地址不应答
+        assert_eq!(handle_frame(6, &mut map, &request, &mut changes), None);
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let mut map = sample_map();
+        let mut changes = Vec::new();
+        let response = handle_frame(5, &mut map, &build_rtu_frame(5, &[0x01, 0x00, 0x02, 0x00, 0x02]), &mut changes);
+        assert_eq!(response, Some(build_rtu_frame(5, &[0x81, 0x02])));
+        let response = handle_frame(5, &mut map, &build_rtu_frame(5, &[0x2B, 0x0E]), &mut changes);
+        assert_eq!(response, Some(build_rtu_frame(5, &[0xAB, 0x01])));
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let map = RegisterMap::parse_csv("table,address,value\nholding,0,0x10\ncoil,3,on\ninput,1,42\n").unwrap();
+        assert_eq!(map.holding_registers[&0], 16);
+        assert!(map.coils[&3]);
+        assert_eq!(map.input_registers[&1], 42);
+    }
+}
@@ -0,0 +1,449 @@
+//! Modbus RTU 被动解析 (总线监听)
+//!
+//! 通过端口共享旁听总线时，RX 流中同时包含主站请求与从站应答。
+//! 按 3.5 字符静默间隔与功能码推算的帧长切帧，校验 CRC 后解码，
+//! 并将应答与最近一次同地址、同功能码的请求配对。
+
+use anyhow::{Result, bail};
+use serde::Serialize;
+use std::time::Duration;
+
+use super::*;
+use crate::core::capture::Direction;
+use crate::core::protocol_decoder::{DecodedMessage, ProtocolDecoder};
+
+/// 帧方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameRole {
+    Request,
+    Response,
+    /// CRC 错误或无法识别
+    Unknown,
+}
+
+/// 解码后的 PDU
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModbusPdu {
+    /// FC 01-04 请求
+    ReadRequest { address: u16, quantity: u16 },
+    /// FC 01/02 应答
+    ReadBitsResponse { values: Vec<bool> },
+    /// FC 03/04/23 应答
+    ReadRegistersResponse { values: Vec<u16> },
+    /// FC 05 请求/应答
+    WriteSingleCoil { address: u16, value: bool },
+    /// FC 06 请求/应答
+    WriteSingleRegister { address: u16, value: u16 },
+    /// FC 15 请求
+    WriteMultipleCoils { address: u16, values: Vec<bool> },
+    /// FC 16 请求
+    WriteMultipleRegisters { address: u16, values: Vec<u16> },
+    /// FC 15/16 应答
+    WriteMultipleResponse { address: u16, quantity: u16 },
+    /// FC 23 请求
+    ReadWriteRequest { read_address: u16, read_quantity: u16, write_address: u16, values: Vec<u16> },
+    Exception { code: ExceptionCode, description: String },
+    /// 未支持的功能码，保留原始数据
+    Other { data: Vec<u8> },
+}
+
+/// 解析出的一帧 (经 `into_message` 转为 `decoded-message` 事件载荷)
+#[derive(Debug, Clone, Serialize)]
+pub struct SniffedFrame {
+    /// 帧首字节到达时间 (Unix 纳秒)
+    pub timestamp_ns: u64,
+    pub raw: Vec<u8>,
+    pub crc_ok: bool,
+    pub role: FrameRole,
+    pub unit: Option<u8>,
+    pub function: Option<u8>,
+    pub pdu: Option<ModbusPdu>,
+    /// 应答对应的起始地址 (取自配对的请求)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u16>,
+    /// 配对请求的时间戳
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_timestamp_ns: Option<u64>,
+}
+
+impl SniffedFrame {
+    /// 单行摘要，如 `unit 1 FC03 request: read 10 @ 0`
+    pub fn summary(&self) -> String {
+        if !self.crc_ok {
+            return format!("CRC error ({} bytes)", self.raw.len());
+        }
+        let role = match self.role {
+            FrameRole::Request => "request",
+            FrameRole::Response => "response",
+            FrameRole::Unknown => "unknown",
+        };
+        let detail = match &self.pdu {
+            Some(ModbusPdu::ReadRequest { address, quantity }) => format!("read {} @ {}", quantity, address),
+            Some(ModbusPdu::ReadBitsResponse { values }) => format!("bits {:?}", values),
+            Some(ModbusPdu::ReadRegistersResponse { values }) => match self.address {
+                Some(address) => format!("registers @ {} {:?}", address, values),
+                None => format!("registers {:?}", values),
+            },
+            Some(ModbusPdu::WriteSingleCoil { address, value }) => format!("coil {} = {}", address, value),
+            Some(ModbusPdu::WriteSingleRegister { address, value }) => format!("register {} = {}", address, value),
+            Some(ModbusPdu::WriteMultipleCoils { address, values }) => format!("coils {} = {:?}", address, values),
+            Some(ModbusPdu::WriteMultipleRegisters { address, values }) => format!("registers {} = {:?}", address, values),
+            Some(ModbusPdu::WriteMultipleResponse { address, quantity }) => format!("wrote {} @ {}", quantity, address),
+            Some(ModbusPdu::ReadWriteRequest { read_address, read_quantity, write_address, values }) => {
+                format!("read {} @ {}, write {} = {:?}", read_quantity, read_address, write_address, values)
+            }
+            Some(ModbusPdu::Exception { description, .. }) => format!("exception: {}", description),
+            Some(ModbusPdu::Other { data }) => format!("{} bytes", data.len()),
+            None => String::new(),
+        };
+        format!("unit {} FC{:02X} {}: {}", self.unit.unwrap_or(0), self.function.unwrap_or(0), role, detail)
+    }
+
+    fn into_message(self) -> DecodedMessage {
+        DecodedMessage {
+            protocol: DECODER_NAME.to_string(),
+            direction: Direction::Rx,
+            timestamp_ns: self.timestamp_ns,
+            valid: self.crc_ok,
+            summary: self.summary(),
+            fields: serde_json::to_value(&self).unwrap_or_default(),
+            raw: self.raw,
+        }
+    }
+}
+
+/// 注册表中的解码器名称
+pub const DECODER_NAME: &str = "modbus-rtu";
+
+/// 注册表工厂：参数 `{ "baudRate": 9600 }` 或 `{ "gapMs": 5.0 }`
+pub fn decoder_factory(options: &serde_json::Value) -> Result<Box<dyn ProtocolDecoder>> {
+    let sniffer = if let Some(ms) = options.get("gapMs").and_then(|v| v.as_f64()) {
+        ModbusSniffer::with_gap(Duration::from_secs_f64(ms / 1000.0))
+    } else if let Some(baud_rate) = options.get("baudRate").and_then(|v| v.as_u64()) {
+        ModbusSniffer::new(baud_rate as u32)
+    } else {
+        bail!("modbus-rtu decoder needs baudRate or gapMs");
+    };
+    Ok(Box::new(sniffer))
+}
+
+/// 等待应答的请求
+struct PendingRequest {
+    unit: u8,
+    function: u8,
+    timestamp_ns: u64,
+    address: u16,
+    quantity: u16,
+}
+
+/// Modbus RTU 监听解析器
+pub struct ModbusSniffer {
+    gap_ns: u64,
+    buffer: Vec<u8>,
+    buffer_start_ns: u64,
+    last_rx_ns: u64,
+    pending: Option<PendingRequest>,
+}
+
+impl ModbusSniffer {
+    /// 按波特率计算帧间隔
+    pub fn new(baud_rate: u32) -> Self {
+        Self::with_gap(inter_frame_delay(baud_rate))
+    }
+
+    /// 自定义帧间隔 (USB 转串口的分包延迟通常远大于 t3.5)
+    pub fn with_gap(gap: Duration) -> Self {
+        Self {
+            gap_ns: gap.as_nanos() as u64,
+            buffer: Vec::new(),
+            buffer_start_ns: 0,
+            last_rx_ns: 0,
+            pending: None,
+        }
+    }
+
+    /// 喂入一块 RX 数据，`now_ns` 为到达时间
+    pub fn feed(&mut self, data: &[u8], now_ns: u64) -> Vec<SniffedFrame> {
+        let mut frames = Vec::new();
+        if data.is_empty() {
+            return frames;
+        }
+        // 静默超过帧间隔：之前的残留数据独立成帧
+        if !self.buffer.is_empty() && now_ns.saturating_sub(self.last_rx_ns) > self.gap_ns {
+            self.extract(true, &mut frames);
+        }
+        if self.buffer.is_empty() {
+            self.buffer_start_ns = now_ns;
+        }
+        self.buffer.extend_from_slice(data);
+        self.last_rx_ns = now_ns;
+        self.extract(false, &mut frames);
+        frames
+    }
+
+    /// 空闲检查：静默超过帧间隔后输出残留数据
+    pub fn flush_idle(&mut self, now_ns: u64) -> Vec<SniffedFrame> {
+        let mut frames = Vec::new();
+        if !self.buffer.is_empty() && now_ns.saturating_sub(self.last_rx_ns) > self.gap_ns {
+            self.extract(true, &mut frames);
+        }
+        frames
+    }
+
+    /// 从缓冲区头部切出完整帧；`boundary` 为 true 时剩余数据作为一帧输出
+    fn extract(&mut self, boundary: bool, frames: &mut Vec<SniffedFrame>) {
+        while !self.buffer.is_empty() {
+            match frame_len(&self.buffer) {
+                Some(len) => {
+                    let raw: Vec<u8> = self.buffer.drain(..len).collect();
+                    let frame = self.decode(raw, self.buffer_start_ns);
+                    frames.push(frame);
+                    // 同一块中的后续帧沿用该块到达时间
+                    self.buffer_start_ns = self.last_rx_ns;
+                }
+                None if boundary || self.buffer.len() >= MAX_RTU_FRAME => {
+                    let raw = std::mem::take(&mut self.buffer);
+                    frames.push(self.decode(raw, self.buffer_start_ns));
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn decode(&mut self, raw: Vec<u8>, timestamp_ns: u64) -> SniffedFrame {
+        let mut frame = SniffedFrame {
+            timestamp_ns,
+            crc_ok: false,
+            role: FrameRole::Unknown,
+            unit: raw.first().copied(),
+            function: raw.get(1).copied(),
+            pdu: None,
+            address: None,
+            request_timestamp_ns: None,
+            raw,
+        };
+        let (unit, pdu) = match split_rtu_frame(&frame.raw) {
+            Some((unit, pdu)) => (unit, pdu.to_vec()),
+            None => return frame,
+        };
+        frame.crc_ok = true;
+        let function = pdu[0];
+
+        let matches_pending = self
+            .pending
+            .as_ref()
+            .is_some_and(|p| p.unit == unit && p.function == function & !EXCEPTION_FLAG);
+
+        if matches_pending || function & EXCEPTION_FLAG != 0 {
+            if let Some(decoded) = decode_response(&pdu, self.pending.as_ref().filter(|_| matches_pending)) {
+                if matches_pending {
+                    let request = self.pending.take().unwrap();
+                    frame.address = Some(request.address);
+                    frame.request_timestamp_ns = Some(request.timestamp_ns);
+                }
+                frame.role = FrameRole::Response;
+                frame.pdu = Some(decoded);
+                return frame;
+            }
+        }
+
+        if let Some((decoded, address, quantity)) = decode_request(&pdu) {
+            frame.role = FrameRole::Request;
+            frame.pdu = Some(decoded);
+            // 广播请求无应答
+            self.pending = (unit != 0).then_some(PendingRequest {
+                unit,
+                function,
+                timestamp_ns,
+                address,
+                quantity,
+            });
+            return frame;
+        }
+
+        // 未配对的应答 (如监听开始时请求已错过)
+        if let Some(decoded) = decode_response(&pdu, None) {
+            frame.role = FrameRole::Response;
+            frame.pdu = Some(decoded);
+        } else {
+            frame.pdu = Some(ModbusPdu::Other { data: pdu[1..].to_vec() });
+        }
+        frame
+    }
+}
+
+/// 总线监听只解析 RX 方向
+impl ProtocolDecoder for ModbusSniffer {
+    fn name(&self) -> &str {
+        DECODER_NAME
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        if direction != Direction::Rx {
+            return Vec::new();
+        }
+        ModbusSniffer::feed(self, data, timestamp_ns).into_iter().map(SniffedFrame::into_message).collect()
+    }
+
+    fn flush_idle(&mut self, now_ns: u64) -> Vec<DecodedMessage> {
+        ModbusSniffer::flush_idle(self, now_ns).into_iter().map(SniffedFrame::into_message).collect()
+    }
+}
+
+/// 根据功能码推算帧长，仅返回 CRC 校验通过的长度
+pub(super) fn frame_len(buf: &[u8]) -> Option<usize> {
+    if buf.len() < 4 {
+        return None;
+    }
+    let byte = |i: usize| buf.get(i).map(|&b| b as usize);
+    let function = buf[1];
+    let mut candidates = Vec::with_capacity(2);
+    if function & EXCEPTION_FLAG != 0 {
+        candidates.push(Some(5));
+    } else {
+        match function {
+            FC_READ_COILS | FC_READ_DISCRETE_INPUTS | FC_READ_HOLDING_REGISTERS | FC_READ_INPUT_REGISTERS => {
+                candidates.push(Some(8));
+                candidates.push(byte(2).map(|n| 5 + n));
+            }
+            FC_WRITE_SINGLE_COIL | FC_WRITE_SINGLE_REGISTER => candidates.push(Some(8)),
+            FC_WRITE_MULTIPLE_COILS | FC_WRITE_MULTIPLE_REGISTERS => {
+                candidates.push(byte(6).map(|n| 9 + n));
+                candidates.push(Some(8));
+            }
+            FC_READ_WRITE_MULTIPLE_REGISTERS => {
+                candidates.push(byte(10).map(|n| 13 + n));
+                candidates.push(byte(2).map(|n| 5 + n));
+            }
+            _ => {}
+        }
+    }
+    candidates
+        .into_iter()
+        .flatten()
+        .find(|&len| len <= buf.len() && split_rtu_frame(&buf[..len]).is_some())
+        .or_else(|| split_rtu_frame(buf).map(|_| buf.len()))
+}
+
+fn be16(pdu: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([pdu[at], pdu[at + 1]])
+}
+
+/// 按请求格式解码，返回 (PDU, 起始地址, 数量)
+fn decode_request(pdu: &[u8]) -> Option<(ModbusPdu, u16, u16)> {
+    let function = pdu[0];
+    match function {
+        FC_READ_COILS | FC_READ_DISCRETE_INPUTS | FC_READ_HOLDING_REGISTERS | FC_READ_INPUT_REGISTERS
+            if pdu.len() == 5 =>
+        {
+            let (address, quantity) = (be16(pdu, 1), be16(pdu, 3));
+            Some((ModbusPdu::ReadRequest { address, quantity }, address, quantity))
+        }
+        FC_WRITE_SINGLE_COIL if pdu.len() == 5 => {
+            let address = be16(pdu, 1);
+            Some((ModbusPdu::WriteSingleCoil { address, value: pdu[3] == 0xFF }, address, 1))
+        }
+        FC_WRITE_SINGLE_REGISTER if pdu.len() == 5 => {
+            let address = be16(pdu, 1);
+            Some((ModbusPdu::WriteSingleRegister { address, value: be16(pdu, 3) }, address, 1))
+        }
+        FC_WRITE_MULTIPLE_COILS if pdu.len() > 6 && pdu.len() == 6 + pdu[5] as usize => {
+            let (address, quantity) = (be16(pdu, 1), be16(pdu, 3));
+            let values = unpack_bits(&pdu[6..], quantity as usize);
+            Some((ModbusPdu::WriteMultipleCoils { address, values }, address, quantity))
+        }
+        FC_WRITE_MULTIPLE_REGISTERS if pdu.len() > 6 && pdu.len() == 6 + pdu[5] as usize => {
+            let (address, quantity) = (be16(pdu, 1), be16(pdu, 3));
+            let values = bytes_to_registers(&pdu[6..]);
+            Some((ModbusPdu::WriteMultipleRegisters { address, values }, address, quantity))
+        }
+        FC_READ_WRITE_MULTIPLE_REGISTERS if pdu.len() > 10 && pdu.len() == 10 + pdu[9] as usize => {
+            let (read_address, read_quantity) = (be16(pdu, 1), be16(pdu, 3));
+            let pdu_value = ModbusPdu::ReadWriteRequest {
+                read_address,
+                read_quantity,
+                write_address: be16(pdu, 5),
+                values: bytes_to_registers(&pdu[10..]),
+            };
+            Some((pdu_value, read_address, read_quantity))
+        }
+        _ => None,
+    }
+}
+
+/// 按应答格式解码；有配对请求时按请求数量截取线圈
+fn decode_response(pdu: &[u8], request: Option<&PendingRequest>) -> Option<ModbusPdu> {
+    let function = pdu[0];
+    if function & EXCEPTION_FLAG != 0 {
+        let code = ExceptionCode::from_u8(*pdu.get(1)?);
+        return (pdu.len() == 2).then(|| ModbusPdu::Exception {
+            code,
+            description: code.description().to_string(),
+        });
+    }
+    let byte_counted = pdu.len() >= 2 && pdu.len() == 2 + pdu[1] as usize;
+    match function {
+        FC_READ_COILS | FC_READ_DISCRETE_INPUTS if byte_counted => {
+            let count = request.map(|r| r.quantity as usize).unwrap_or((pdu.len() - 2) * 8);
+            Some(ModbusPdu::ReadBitsResponse { values: unpack_bits(&pdu[2..], count) })
+        }
+        FC_READ_HOLDING_REGISTERS | FC_READ_INPUT_REGISTERS | FC_READ_WRITE_MULTIPLE_REGISTERS if byte_counted => {
+            Some(ModbusPdu::ReadRegistersResponse { values: bytes_to_registers(&pdu[2..]) })
+        }
+        FC_WRITE_SINGLE_COIL | FC_WRITE_SINGLE_REGISTER => decode_request(pdu).map(|(p, _, _)| p),
+        FC_WRITE_MULTIPLE_COILS | FC_WRITE_MULTIPLE_REGISTERS if pdu.len() == 5 => {
+            Some(ModbusPdu::WriteMultipleResponse { address: be16(pdu, 1), quantity: be16(pdu, 3) })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_response_in_one_chunk() {
+        let mut sniffer = ModbusSniffer::new(9600);
+        let mut data = build_rtu_frame(1, &[0x03, 0x00, 0x10, 0x00, 0x02]);
+        data.extend(build_rtu_frame(1, &[0x03, 0x04, 0x00, 0x01, 0x00, 0x02]));
+        let frames = sniffer.feed(&data, 1_000);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].role, FrameRole::Request);
+        assert_eq!(frames[0].pdu, Some(ModbusPdu::ReadRequest { address: 0x10, quantity: 2 }));
+        assert_eq!(frames[1].role, FrameRole::Response);
+        assert_eq!(frames[1].address, Some(0x10));
+        assert_eq!(frames[1].pdu, Some(ModbusPdu::ReadRegistersResponse { values: vec![1, 2] }));
+    }
+
+    #[test]
+    fn test_split_frame_and_exception() {
+        let mut sniffer = ModbusSniffer::new(9600);
+        let request = build_rtu_frame(2, &[0x01, 0x00, 0x00, 0x00, 0x0A]);
+        assert!(sniffer.feed(&request[..3], 0).is_empty());
+        let frames = sniffer.feed(&request[3..], 1_000_000);
+        assert_eq!(frames[0].role, FrameRole::Request);
+
+        let frames = sniffer.feed(&build_rtu_frame(2, &[0x81, 0x02]), 20_000_000);
+        assert_eq!(frames[0].role, FrameRole::Response);
+        assert_eq!(frames[0].request_timestamp_ns, Some(0));
+        assert!(matches!(
+            frames[0].pdu,
+            Some(ModbusPdu::Exception { code: ExceptionCode::IllegalDataAddress, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bad_crc_flushed_on_gap() {
+        let mut sniffer = ModbusSniffer::new(9600);
+        let mut frame = build_rtu_frame(1, &[0x06, 0x00, 0x01, 0x00, 0x03]);
+        frame[4] ^= 0x55;
+        assert!(sniffer.feed(&frame, 0).is_empty());
+        let frames = sniffer.flush_idle(10_000_000);
+        assert_eq!(frames.len(), 1);
+        assert!(!frames[0].crc_ok);
+        assert_eq!(frames[0].role, FrameRole::Unknown);
+    }
+}
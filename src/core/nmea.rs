@@ -0,0 +1,776 @@
+//! NMEA 0183 解析 (GNSS 模块)
+//!
+//! 校验 `*hh` 校验和，将 GGA/RMC/GSA/GSV/VTG/GLL 解析为结构体，其余语句保留原始字段。
+//! `FixTracker` 汇总各语句维护滚动定位状态 (位置、卫星、DOP、定位类型)，
+//! 作为 `nmea-0183` 协议解码器接入注册表时与前端共享同一份状态。
+
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use super::capture::Direction;
+use super::protocol_decoder::{DecodedMessage, ProtocolDecoder};
+
+/// 单行最大长度 (规范为 82 字节，留出余量给私有语句)
+const MAX_LINE: usize = 256;
+
+/// UTC 时间
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct UtcTime {
+    pub hour: u8,
+    pub minute: u8,
+    pub second: f32,
+}
+
+/// UTC 日期
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UtcDate {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+/// GGA 定位质量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixQuality {
+    #[default]
+    Invalid,
+    Gps,
+    Dgps,
+    Pps,
+    Rtk,
+    FloatRtk,
+    Estimated,
+    Manual,
+    Simulation,
+}
+
+impl FixQuality {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Self::Gps,
+            2 => Self::Dgps,
+            3 => Self::Pps,
+            4 => Self::Rtk,
+            5 => Self::FloatRtk,
+            6 => Self::Estimated,
+            7 => Self::Manual,
+            8 => Self::Simulation,
+            _ => Self::Invalid,
+        }
+    }
+}
+
+/// GSA 定位类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixType {
+    #[default]
+    NoFix,
+    Fix2d,
+    Fix3d,
+}
+
+/// 定位数据 (Global Positioning System Fix Data)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gga {
+    pub time: Option<UtcTime>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub quality: FixQuality,
+    pub satellites: Option<u8>,
+    pub hdop: Option<f32>,
+    /// 海拔 (米)
+    pub altitude: Option<f64>,
+    /// 大地水准面差距 (米)
+    pub geoid_separation: Option<f64>,
+    pub dgps_age: Option<f32>,
+    pub dgps_station: Option<u16>,
+}
+
+/// 推荐最小定位信息
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rmc {
+    pub time: Option<UtcTime>,
+    /// 状态 A (有效)
+    pub valid: bool,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub speed_knots: Option<f64>,
+    /// 真北航向 (度)
+    pub course: Option<f64>,
+    pub date: Option<UtcDate>,
+    /// 磁偏角 (度，西为负)
+    pub magnetic_variation: Option<f64>,
+    /// NMEA 2.3+ 模式指示 (A/D/E/N...)
+    pub mode: Option<char>,
+}
+
+/// DOP 与参与定位的卫星
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gsa {
+    /// 2D/3D 自动切换
+    pub auto: bool,
+    pub fix_type: FixType,
+    pub prns: Vec<u16>,
+    pub pdop: Option<f32>,
+    pub hdop: Option<f32>,
+    pub vdop: Option<f32>,
+    /// NMEA 4.1+ 系统 ID
+    pub system_id: Option<u8>,
+}
+
+/// 可见卫星
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SatelliteInfo {
+    pub prn: u16,
+    /// 仰角 (度)
+    pub elevation: Option<u8>,
+    /// 方位角 (度)
+    pub azimuth: Option<u16>,
+    /// 载噪比 (dB-Hz)，未跟踪时为空
+    pub snr: Option<u8>,
+}
+
+/// 可见卫星 (多条语句组成一组)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gsv {
+    pub total_messages: u8,
+    pub message_number: u8,
+    pub satellites_in_view: u8,
+    pub satellites: Vec<SatelliteInfo>,
+}
+
+/// 地面速度与航向
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Vtg {
+    pub course_true: Option<f64>,
+    pub course_magnetic: Option<f64>,
+    pub speed_knots: Option<f64>,
+    pub speed_kmh: Option<f64>,
+    pub mode: Option<char>,
+}
+
+/// 地理位置
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Gll {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub time: Option<UtcTime>,
+    pub valid: bool,
+    pub mode: Option<char>,
+}
+
+/// 语句内容
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NmeaMessage {
+    Gga(Gga),
+    Rmc(Rmc),
+    Gsa(Gsa),
+    Gsv(Gsv),
+    Vtg(Vtg),
+    Gll(Gll),
+    /// 未支持的语句 (含私有 `$P...`)，保留原始字段
+    Other { fields: Vec<String> },
+}
+
+/// 一条 NMEA 语句
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NmeaSentence {
+    /// 发送方 (GP/GL/GA/GB/BD/GN...)，私有语句为空
+    pub talker: String,
+    /// 语句类型 (GGA/RMC...)，私有语句为完整地址 (如 PUBX)
+    pub sentence_type: String,
+    /// 校验和是否存在
+    pub has_checksum: bool,
+    pub message: NmeaMessage,
+}
+
+/// 计算 `$` 与 `*` 之间字符的异或校验
+pub fn checksum(body: &str) -> u8 {
+    body.bytes().fold(0, |acc, b| acc ^ b)
+}
+
+/// 解析一行语句 (可带行尾)；校验和不符时返回错误，缺省校验和时接受
+pub fn parse_sentence(line: &str) -> Result<NmeaSentence> {
+    let line = line.trim_end_matches(['\r', '\n']).trim();
+    let body = line
+        .strip_prefix('$')
+        .or_else(|| line.strip_prefix('!'))
+        .ok_or_else(|| anyhow!("Not an NMEA sentence: {}", line))?;
+    let (body, has_checksum) = match body.rsplit_once('*') {
+        Some((body, hex)) => {
+            let expected = u8::from_str_radix(hex.trim(), 16).map_err(|_| anyhow!("Invalid checksum field: {}", hex))?;
+            let actual = checksum(body);
+            if actual != expected {
+                bail!("Checksum mismatch: expected {:02X}, computed {:02X}", expected, actual);
+            }
+            (body, true)
+        }
+        None => (body, false),
+    };
+    let mut parts = body.split(',');
+    let address = parts.next().unwrap_or_default();
+    // 噪声中的非 ASCII 字节不能按字节切分地址
+    if !address.is_ascii() {
+        bail!("Invalid address field: {}", address);
+    }
+    let fields: Vec<&str> = parts.collect();
+    let (talker, sentence_type) = if address.starts_with('P') || address.len() < 5 {
+        (String::new(), address.to_string())
+    } else {
+        (address[..2].to_string(), address[2..].to_string())
+    };
+    let message = match sentence_type.as_str() {
+        "GGA" => NmeaMessage::Gga(parse_gga(&fields)),
+        "RMC" => NmeaMessage::Rmc(parse_rmc(&fields)),
+        "GSA" => NmeaMessage::Gsa(parse_gsa(&fields)),
+        "GSV" => NmeaMessage::Gsv(parse_gsv(&fields)?),
+        "VTG" => NmeaMessage::Vtg(parse_vtg(&fields)),
+        "GLL" => NmeaMessage::Gll(parse_gll(&fields)),
+        _ => NmeaMessage::Other { fields: fields.iter().map(|f| f.to_string()).collect() },
+    };
+    Ok(NmeaSentence { talker, sentence_type, has_checksum, message })
+}
+
+fn field<'a>(fields: &[&'a str], index: usize) -> Option<&'a str> {
+    fields.get(index).map(|f| f.trim()).filter(|f| !f.is_empty())
+}
+
+fn num<T: std::str::FromStr>(fields: &[&str], index: usize) -> Option<T> {
+    field(fields, index)?.parse().ok()
+}
+
+fn flag(fields: &[&str], index: usize) -> Option<char> {
+    field(fields, index)?.chars().next()
+}
+
+/// `hhmmss.ss`
+fn parse_time(fields: &[&str], index: usize) -> Option<UtcTime> {
+    let s = field(fields, index)?;
+    Some(UtcTime {
+        hour: s.get(0..2)?.parse().ok()?,
+        minute: s.get(2..4)?.parse().ok()?,
+        second: s.get(4..)?.parse().ok()?,
+    })
+}
+
+/// `ddmmyy`
+fn parse_date(fields: &[&str], index: usize) -> Option<UtcDate> {
+    let s = field(fields, index)?;
+    let year: u16 = s.get(4..6)?.parse().ok()?;
+    Some(UtcDate {
+        day: s.get(0..2)?.parse().ok()?,
+        month: s.get(2..4)?.parse().ok()?,
+        // 两位年份按 1980 起算 (GPS 纪元)
+        year: if year >= 80 { 1900 + year } else { 2000 + year },
+    })
+}
+
+/// `(d)ddmm.mmmm` + 半球 → 十进制度
+fn parse_coordinate(fields: &[&str], index: usize) -> Option<f64> {
+    let raw: f64 = num(fields, index)?;
+    let degrees = (raw / 100.0).trunc();
+    let value = degrees + (raw - degrees * 100.0) / 60.0;
+    match flag(fields, index + 1)? {
+        'N' | 'E' => Some(value),
+        'S' | 'W' => Some(-value),
+        _ => None,
+    }
+}
+
+fn parse_gga(f: &[&str]) -> Gga {
+    Gga {
+        time: parse_time(f, 0),
+        latitude: parse_coordinate(f, 1),
+        longitude: parse_coordinate(f, 3),
+        quality: FixQuality::from_u8(num(f, 5).unwrap_or(0)),
+        satellites: num(f, 6),
+        hdop: num(f, 7),
+        altitude: num(f, 8),
+        geoid_separation: num(f, 10),
+        dgps_age: num(f, 12),
+        dgps_station: num(f, 13),
+    }
+}
+
+fn parse_rmc(f: &[&str]) -> Rmc {
+    let variation: Option<f64> = num(f, 9);
+    Rmc {
+        time: parse_time(f, 0),
+        valid: flag(f, 1) == Some('A'),
+        latitude: parse_coordinate(f, 2),
+        longitude: parse_coordinate(f, 4),
+        speed_knots: num(f, 6),
+        course: num(f, 7),
+        date: parse_date(f, 8),
+        magnetic_variation: variation.map(|v| if flag(f, 10) == Some('W') { -v } else { v }),
+        mode: flag(f, 11),
+    }
+}
+
+fn parse_gsa(f: &[&str]) -> Gsa {
+    Gsa {
+        auto: flag(f, 0) == Some('A'),
+        fix_type: match num::<u8>(f, 1) {
+            Some(2) => FixType::Fix2d,
+            Some(3) => FixType::Fix3d,
+            _ => FixType::NoFix,
+        },
+        prns: (2..14).filter_map(|i| num(f, i)).collect(),
+        pdop: num(f, 14),
+        hdop: num(f, 15),
+        vdop: num(f, 16),
+        system_id: num(f, 17),
+    }
+}
+
+fn parse_gsv(f: &[&str]) -> Result<Gsv> {
+    let header = |i| num::<u8>(f, i).ok_or_else(|| anyhow!("Invalid GSV header"));
+    // 每颗卫星 4 个字段，NMEA 4.1 末尾可能多一个信号 ID
+    let satellites = f[3.min(f.len())..]
+        .chunks(4)
+        .filter(|chunk| chunk.len() == 4)
+        .filter_map(|chunk| {
+            Some(SatelliteInfo {
+                prn: num(chunk, 0)?,
+                elevation: num(chunk, 1),
+                azimuth: num(chunk, 2),
+                snr: num(chunk, 3),
+            })
+        })
+        .collect();
+    Ok(Gsv {
+        total_messages: header(0)?,
+        message_number: header(1)?,
+        satellites_in_view: header(2)?,
+        satellites,
+    })
+}
+
+fn parse_vtg(f: &[&str]) -> Vtg {
+    Vtg {
+        course_true: num(f, 0),
+        course_magnetic: num(f, 2),
+        speed_knots: num(f, 4),
+        speed_kmh: num(f, 6),
+        mode: flag(f, 8),
+    }
+}
+
+fn parse_gll(f: &[&str]) -> Gll {
+    Gll {
+        latitude: parse_coordinate(f, 0),
+        longitude: parse_coordinate(f, 2),
+        time: parse_time(f, 4),
+        valid: flag(f, 5) == Some('A'),
+        mode: flag(f, 6),
+    }
+}
+
+impl NmeaSentence {
+    /// 单行摘要，如 `GPGGA 48.117300,11.516667 gps sats=8 hdop=0.9 alt=545.4m`
+    pub fn summary(&self) -> String {
+        let position = |lat: Option<f64>, lon: Option<f64>| match (lat, lon) {
+            (Some(lat), Some(lon)) => format!("{:.6},{:.6}", lat, lon),
+            _ => "no position".to_string(),
+        };
+        let opt = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+        let detail = match &self.message {
+            NmeaMessage::Gga(g) => format!(
+                "{} {:?} sats={} hdop={} alt={}m",
+                position(g.latitude, g.longitude),
+                g.quality,
+                opt(g.satellites.map(|v| v.to_string())),
+                opt(g.hdop.map(|v| v.to_string())),
+                opt(g.altitude.map(|v| v.to_string())),
+            ),
+            NmeaMessage::Rmc(r) => format!(
+                "{} {} {}kn {}°",
+                if r.valid { "valid" } else { "void" },
+                position(r.latitude, r.longitude),
+                opt(r.speed_knots.map(|v| v.to_string())),
+                opt(r.course.map(|v| v.to_string())),
+            ),
+            NmeaMessage::Gsa(g) => format!(
+                "{:?} prns={:?} pdop={} hdop={} vdop={}",
+                g.fix_type,
+                g.prns,
+                opt(g.pdop.map(|v| v.to_string())),
+                opt(g.hdop.map(|v| v.to_string())),
+                opt(g.vdop.map(|v| v.to_string())),
+            ),
+            NmeaMessage::Gsv(g) => format!(
+                "{}/{} in view={} prns={:?}",
+                g.message_number,
+                g.total_messages,
+                g.satellites_in_view,
+                g.satellites.iter().map(|s| s.prn).collect::<Vec<_>>(),
+            ),
+            NmeaMessage::Vtg(v) => format!(
+                "{}° {}km/h",
+                opt(v.course_true.map(|c| c.to_string())),
+                opt(v.speed_kmh.map(|s| s.to_string())),
+            ),
+            NmeaMessage::Gll(g) => format!(
+                "{} {}",
+                if g.valid { "valid" } else { "void" },
+                position(g.latitude, g.longitude),
+            ),
+            NmeaMessage::Other { fields } => fields.join(","),
+        };
+        format!("{}{} {}", self.talker, self.sentence_type, detail)
+    }
+}
+
+/// 可见卫星 (附所属系统与是否参与定位)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackedSatellite {
+    /// 发送方 (GP/GL/GA/GB...)
+    pub system: String,
+    #[serde(flatten)]
+    pub info: SatelliteInfo,
+    pub used: bool,
+}
+
+/// 滚动定位状态 (`gnss-fix` 事件载荷)
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FixState {
+    pub time: Option<UtcTime>,
+    pub date: Option<UtcDate>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub quality: FixQuality,
+    pub fix_type: FixType,
+    pub satellites_used: Option<u8>,
+    pub satellites: Vec<TrackedSatellite>,
+    pub hdop: Option<f32>,
+    pub vdop: Option<f32>,
+    pub pdop: Option<f32>,
+    pub speed_knots: Option<f64>,
+    pub course: Option<f64>,
+    /// 最后一次更新时间 (Unix 纳秒)
+    pub updated_ns: u64,
+}
+
+/// 定位状态汇总
+#[derive(Debug, Default)]
+pub struct FixTracker {
+    state: FixState,
+    /// 各系统最近一组完整的 GSV
+    in_view: BTreeMap<String, Vec<SatelliteInfo>>,
+    /// 正在接收的 GSV 分组
+    pending_gsv: BTreeMap<String, Vec<SatelliteInfo>>,
+    /// 各系统 (按发送方归一) 参与定位的 PRN；不同系统的 PRN 可能重号
+    used: BTreeMap<String, Vec<u16>>,
+    /// 当前这一串连续 GSA 的序号 (无系统 ID 的 `$GNGSA` 按此推断系统)
+    gsa_index: usize,
+    changed: bool,
+}
+
+/// NMEA 4.1 GSA 系统 ID 对应的发送方
+fn system_talker(system_id: u8) -> Option<&'static str> {
+    match system_id {
+        1 => Some("GP"),
+        2 => Some("GL"),
+        3 => Some("GA"),
+        4 => Some("GB"),
+        5 => Some("GQ"),
+        6 => Some("GI"),
+        _ => None,
+    }
+}
+
+/// 无系统 ID 时多系统接收机逐个系统输出 `$GNGSA` 的顺序
+const GN_GSA_ORDER: [&str; 4] = ["GP", "GL", "GA", "GB"];
+
+/// 归一发送方 (北斗有 GB/BD 两种写法)
+fn system_key(talker: &str) -> &str {
+    if talker == "BD" { "GB" } else { talker }
+}
+
+impl FixTracker {
+    pub fn update(&mut self, sentence: &NmeaSentence, timestamp_ns: u64) {
+        if !matches!(sentence.message, NmeaMessage::Gsa(_)) {
+            self.gsa_index = 0;
+        }
+        let state = &mut self.state;
+        match &sentence.message {
+            NmeaMessage::Gga(g) => {
+                state.time = g.time.or(state.time);
+                state.latitude = g.latitude;
+                state.longitude = g.longitude;
+                state.altitude = g.altitude;
+                state.quality = g.quality;
+                state.satellites_used = g.satellites;
+                state.hdop = g.hdop.or(state.hdop);
+            }
+            NmeaMessage::Rmc(r) => {
+                state.time = r.time.or(state.time);
+                state.date = r.date.or(state.date);
+                if r.valid {
+                    state.latitude = r.latitude;
+                    state.longitude = r.longitude;
+                }
+                state.speed_knots = r.speed_knots;
+                state.course = r.course;
+            }
+            NmeaMessage::Gsa(g) => {
+                state.fix_type = g.fix_type;
+                state.pdop = g.pdop;
+                state.hdop = g.hdop;
+                state.vdop = g.vdop;
+                let key = match g.system_id.and_then(system_talker) {
+                    Some(talker) => talker,
+                    None if sentence.talker == "GN" => {
+                        GN_GSA_ORDER.get(self.gsa_index).copied().unwrap_or("GN")
+                    }
+                    None => system_key(&sentence.talker),
+                };
+                self.gsa_index += 1;
+                self.used.insert(key.to_string(), g.prns.clone());
+                self.rebuild_satellites();
+            }
+            NmeaMessage::Gsv(g) => {
+                let pending = self.pending_gsv.entry(sentence.talker.clone()).or_default();
+                if g.message_number <= 1 {
+                    pending.clear();
+                }
+                pending.extend(g.satellites.iter().cloned());
+                if g.message_number < g.total_messages {
+                    return;
+                }
+                let complete = self.pending_gsv.remove(&sentence.talker).unwrap_or_default();
+                self.in_view.insert(sentence.talker.clone(), complete);
+                self.rebuild_satellites();
+            }
+            NmeaMessage::Vtg(v) => {
+                state.course = v.course_true.or(state.course);
+                state.speed_knots = v.speed_knots.or(state.speed_knots);
+            }
+            NmeaMessage::Gll(g) => {
+                state.time = g.time.or(state.time);
+                if g.valid {
+                    state.latitude = g.latitude;
+                    state.longitude = g.longitude;
+                }
+            }
+            NmeaMessage::Other { .. } => return,
+        }
+        self.state.updated_ns = timestamp_ns;
+        self.changed = true;
+    }
+
+    fn rebuild_satellites(&mut self) {
+        let used = &self.used;
+        self.state.satellites = self
+            .in_view
+            .iter()
+            .flat_map(|(system, satellites)| {
+                let used = used.get(system_key(system));
+                satellites.iter().map(move |info| TrackedSatellite {
+                    system: system.clone(),
+                    info: info.clone(),
+                    used: used.is_some_and(|prns| prns.contains(&info.prn)),
+                })
+            })
+            .collect();
+    }
+
+    pub fn state(&self) -> &FixState {
+        &self.state
+    }
+
+    /// 自上次调用以来有更新时返回当前状态
+    pub fn take_update(&mut self) -> Option<FixState> {
+        std::mem::take(&mut self.changed).then(|| self.state.clone())
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// 解码器与前端共享的定位状态
+pub type SharedFixTracker = Arc<Mutex<FixTracker>>;
+
+/// 注册表中的解码器名称
+pub const DECODER_NAME: &str = "nmea-0183";
+
+/// 注册表工厂：使用独立的定位状态 (与前端共享时由调用方以 `NmeaDecoder::new` 另行注册)
+pub fn decoder_factory(_options: &serde_json::Value) -> Result<Box<dyn ProtocolDecoder>> {
+    Ok(Box::new(NmeaDecoder::new(SharedFixTracker::default())))
+}
+
+/// NMEA 协议解码器：按行切分 RX 数据，校验并解析语句，同时更新定位状态
+pub struct NmeaDecoder {
+    tracker: SharedFixTracker,
+    line: Vec<u8>,
+    line_start_ns: u64,
+}
+
+impl NmeaDecoder {
+    pub fn new(tracker: SharedFixTracker) -> Self {
+        Self { tracker, line: Vec::new(), line_start_ns: 0 }
+    }
+
+    fn decode_line(&mut self, timestamp_ns: u64) -> Option<DecodedMessage> {
+        let raw = std::mem::take(&mut self.line);
+        let text = String::from_utf8_lossy(&raw);
+        let text = text.trim();
+        if !text.starts_with('$') && !text.starts_with('!') {
+            return None;
+        }
+        let (valid, summary, fields) = match parse_sentence(text) {
+            Ok(sentence) => {
+                if let Ok(mut tracker) = self.tracker.lock() {
+                    tracker.update(&sentence, timestamp_ns);
+                }
+                (true, sentence.summary(), serde_json::to_value(&sentence).unwrap_or_default())
+            }
+            Err(e) => (false, format!("{} ({})", text, e), serde_json::Value::Null),
+        };
+        Some(DecodedMessage {
+            protocol: DECODER_NAME.to_string(),
+            direction: Direction::Rx,
+            timestamp_ns,
+            raw: text.as_bytes().to_vec(),
+            valid,
+            summary,
+            fields,
+        })
+    }
+}
+
+/// 只解析 RX 方向 (TX 为发给模块的配置语句)
+impl ProtocolDecoder for NmeaDecoder {
+    fn name(&self) -> &str {
+        DECODER_NAME
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        let mut messages = Vec::new();
+        if direction != Direction::Rx {
+            return messages;
+        }
+        for &b in data {
+            if self.line.is_empty() {
+                self.line_start_ns = timestamp_ns;
+            }
+            if b == b'\n' {
+                messages.extend(self.decode_line(self.line_start_ns));
+            } else if self.line.len() < MAX_LINE {
+                self.line.push(b);
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPOCH: &str = "$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n\
+        $GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A\r\n\
+        $GPGSA,A,3,04,05,,09,12,,,24,,,,,2.5,1.3,2.1*39\r\n\
+        $GPGSV,2,1,08,01,40,083,46,02,17,308,41,12,07,344,39,14,22,228,45*75\r\n\
+        $GPGSV,2,2,08,04,77,150,,05,12,050,30,09,25,120,33,24,60,270,48*75\r\n\
+        $GPVTG,054.7,T,034.4,M,005.5,N,010.2,K,A*25\r\n";
+
+    #[test]
+    fn test_parse_sentences() {
+        let gga = parse_sentence("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47").unwrap();
+        assert_eq!(gga.talker, "GP");
+        let NmeaMessage::Gga(g) = gga.message else { panic!("not GGA") };
+        assert!((g.latitude.unwrap() - 48.1173).abs() < 1e-6);
+        assert!((g.longitude.unwrap() - 11.516_666).abs() < 1e-6);
+        assert_eq!(g.quality, FixQuality::Gps);
+        assert_eq!(g.satellites, Some(8));
+        assert_eq!(g.time, Some(UtcTime { hour: 12, minute: 35, second: 19.0 }));
+
+        let rmc = parse_sentence("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A").unwrap();
+        let NmeaMessage::Rmc(r) = rmc.message else { panic!("not RMC") };
+        assert_eq!(r.date, Some(UtcDate { year: 1994, month: 3, day: 23 }));
+        assert_eq!(r.magnetic_variation, Some(-3.1));
+
+        let gll = parse_sentence("$GPGLL,4916.45,N,12311.12,W,225444,A,A*5C").unwrap();
+        let NmeaMessage::Gll(g) = gll.message else { panic!("not GLL") };
+        assert!(g.valid && g.longitude.unwrap() < 0.0);
+
+        assert!(parse_sentence("$GPGGA,123519,4807.038,N*00").is_err());
+        let private = parse_sentence("$PUBX,00,081350.00").unwrap();
+        assert_eq!(private.sentence_type, "PUBX");
+        assert!(!private.has_checksum);
+    }
+
+    #[test]
+    fn test_noisy_sentence_rejected() {
+        assert!(parse_sentence("$G\u{e9}GGA,123519").is_err());
+        let mut decoder = NmeaDecoder::new(SharedFixTracker::default());
+        let messages = decoder.feed(Direction::Rx, b"$\xC3\xA9GGAX,1\r\n$\xFF\xFEGPGGA,2\r\n", 1);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| !m.valid));
+    }
+
+    #[test]
+    fn test_decoder_tracks_fix() {
+        let tracker = SharedFixTracker::default();
+        let mut decoder = NmeaDecoder::new(tracker.clone());
+        let (head, tail) = EPOCH.as_bytes().split_at(40);
+        let mut messages = decoder.feed(Direction::Rx, head, 1);
+        messages.extend(decoder.feed(Direction::Rx, tail, 2));
+        assert_eq!(messages.len(), 6);
+        assert!(messages.iter().all(|m| m.valid));
+        assert_eq!(messages[0].timestamp_ns, 1);
+        assert!(messages[0].summary.starts_with("GPGGA 48.117300,11.516667 Gps sats=8"));
+
+        let mut tracker = tracker.lock().unwrap();
+        let state = tracker.take_update().unwrap();
+        assert!(tracker.take_update().is_none());
+        assert_eq!(state.fix_type, FixType::Fix3d);
+        assert_eq!(state.satellites_used, Some(8));
+        assert_eq!(state.hdop, Some(1.3));
+        assert_eq!(state.speed_knots, Some(5.5));
+        assert_eq!(state.satellites.len(), 8);
+        let used: Vec<u16> = state.satellites.iter().filter(|s| s.used).map(|s| s.info.prn).collect();
+        assert_eq!(used, vec![12, 4, 5, 9, 24]);
+        assert_eq!(state.satellites[4].info.snr, None);
+    }
+
+    fn sentence(body: &str) -> String {
+        format!("${}*{:02X}\r\n", body, checksum(body))
+    }
+
+    #[test]
+    fn test_multi_system_used_satellites() {
+        // GPS 与 Galileo 的 PRN 重号：按系统区分是否参与定位
+        let gsv = sentence("GPGSV,1,1,03,04,40,083,46,05,17,308,41,07,07,344,39")
+            + &sentence("GAGSV,1,1,02,04,77,150,30,07,12,050,30");
+        let with_ids = sentence("GNGSA,A,3,04,05,,,,,,,,,,,2.5,1.3,2.1,1")
+            + &sentence("GNGSA,A,3,07,,,,,,,,,,,,2.5,1.3,2.1,3")
+            + &gsv;
+        // 无系统 ID 时按输出顺序 (GPS, GLONASS, Galileo, ...) 推断
+        let by_order = sentence("GNGSA,A,3,04,05,,,,,,,,,,,2.5,1.3,2.1")
+            + &sentence("GNGSA,A,3,,,,,,,,,,,,,2.5,1.3,2.1")
+            + &sentence("GNGSA,A,3,07,,,,,,,,,,,,2.5,1.3,2.1")
+            + &gsv;
+
+        for input in [with_ids, by_order] {
+            let tracker = SharedFixTracker::default();
+            let mut decoder = NmeaDecoder::new(tracker.clone());
+            let messages = decoder.feed(Direction::Rx, input.as_bytes(), 1);
+            assert!(messages.iter().all(|m| m.valid));
+            let state = tracker.lock().unwrap().take_update().unwrap();
+            let used: Vec<(&str, u16)> = state.satellites.iter()
+                .filter(|s| s.used)
+                .map(|s| (s.system.as_str(), s.info.prn))
+                .collect();
+            assert_eq!(used, vec![("GA", 7), ("GP", 4), ("GP", 5)]);
+        }
+    }
+}
@@ -0,0 +1,1328 @@
+//! 自定义二进制协议描述 (Packet Schema)
+//!
+//! 用 YAML 描述帧结构，按描述将 RX 帧解码为字段树，或由字段值编码 TX 帧：
+//!
+//! ```yaml
+//! name: sensor
+//! endian: big
+//! fields:
+//!   - { name: sync, type: u16, const: 0xAA55 }
+//!   - { name: len, type: u8 }
+//!   - { name: kind, type: u8, enum: { 1: status, 2: reading } }
+//!   - { name: flags, type: u8, bits: { ready: 0, mode: "1-3" } }
+//!   - { name: status, type: status_t, if: "kind == status" }
+//!   - { name: reading, type: reading_t, length: "len - 2", if: "kind == reading" }
+//!   - { name: crc, type: u16, endian: little, checksum: { algorithm: crc16-modbus, from: len } }
+//! structs:
+//!   status_t:
+//!     - { name: battery, type: u8 }
+//!   reading_t:
+//!     - { name: count, type: u8 }
+//!     - { name: samples, type: i16, count: count }
+//!     - { name: label, type: string, length: rest }
+//! ```
+//!
+//! - 类型：`u8`..`u64`、`i8`..`i64`、`f32`/`f64`、`bytes`、`string` 或 `structs` 中的结构体名
+//! - `length` (bytes/string/结构体的字节数) 与 `count` (数组元素数) 可为常数、
+//!   之前的字段 (`len`、`len - 2`、`len * 2`) 或 `rest` (所在区域剩余字节，扣除其后的定长字段)
+//! - `if` 支持 `==`、`!=`、`<`、`<=`、`>`、`>=`，右侧为数值或枚举名；位域以 `字段.子字段` 引用
+//! - `checksum` 从 `from` 字段 (默认帧首) 起算到校验字段之前，字节序随字段
+//! - 开头的 `const` 字段作为流式切帧的同步头
+
+use anyhow::{Result, anyhow, bail, Context};
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use super::capture::Direction;
+use super::checksum::{ByteOrder, ChecksumAlgorithm};
+use super::protocol_decoder::{DecodedMessage, ProtocolDecoder};
+use super::hex::parse_hex;
+
+/// 结构体最大嵌套深度 (防止自引用的结构体无限递归)
+const MAX_DEPTH: usize = 16;
+/// 流式解码缓冲上限，超出后丢弃
+const MAX_FRAME: usize = 64 * 1024;
+/// 帧内静默超过该时长视为帧结束 (`rest` 长度的帧据此收尾)
+const DEFAULT_IDLE_GAP_NS: u64 = 20_000_000;
+
+/// 长度/数量：常数或表达式字符串
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SizeSpec {
+    Fixed(usize),
+    Expr(String),
+}
+
+/// 位域：单个位 (解码为 bool) 或 `"lo-hi"` 位段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BitSpec {
+    Bit(u32),
+    Range(String),
+}
+
+/// 校验字段声明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldChecksum {
+    pub algorithm: ChecksumAlgorithm,
+    /// 校验覆盖的起始字段，默认帧首
+    #[serde(default)]
+    pub from: Option<String>,
+}
+
+/// 字段定义
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// 覆盖报文默认字节序
+    #[serde(default)]
+    pub endian: Option<ByteOrder>,
+    #[serde(default)]
+    pub length: Option<SizeSpec>,
+    #[serde(default)]
+    pub count: Option<SizeSpec>,
+    /// 固定值 (数值，string 为文本，bytes 为 hex)
+    #[serde(rename = "const", default)]
+    pub constant: Option<Value>,
+    #[serde(rename = "enum", default)]
+    pub enum_map: BTreeMap<i64, String>,
+    #[serde(default)]
+    pub bits: BTreeMap<String, BitSpec>,
+    #[serde(rename = "if", default)]
+    pub condition: Option<String>,
+    #[serde(default)]
+    pub checksum: Option<FieldChecksum>,
+}
+
+/// 报文描述
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PacketSchema {
+    pub name: String,
+    #[serde(default)]
+    pub endian: ByteOrder,
+    pub fields: Vec<FieldDef>,
+    #[serde(default)]
+    pub structs: HashMap<String, Vec<FieldDef>>,
+}
+
+/// 字段的基本类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind<'a> {
+    Int { size: usize, signed: bool },
+    Float { size: usize },
+    Bytes,
+    Str,
+    Struct(&'a str),
+}
+
+/// 解析后的 `length`/`count`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Size {
+    Fixed(usize),
+    /// `field * scale + offset`
+    Ref { field: String, scale: i64, offset: i64 },
+    Rest,
+}
+
+impl Size {
+    fn parse(spec: &SizeSpec) -> Result<Self> {
+        let expr = match spec {
+            SizeSpec::Fixed(n) => return Ok(Size::Fixed(*n)),
+            SizeSpec::Expr(expr) => expr.trim(),
+        };
+        if expr == "rest" {
+            return Ok(Size::Rest);
+        }
+        if let Some(n) = parse_int(expr) {
+            return usize::try_from(n).map(Size::Fixed).map_err(|_| anyhow!("Negative size: {}", expr));
+        }
+        for (op, sign) in [('+', 1), ('-', -1), ('*', 0)] {
+            if let Some((field, operand)) = expr.split_once(op) {
+                let n = parse_int(operand.trim()).ok_or_else(|| anyhow!("Invalid size expression: {}", expr))?;
+                let field = field.trim().to_string();
+                return Ok(match sign {
+                    0 => Size::Ref { field, scale: n, offset: 0 },
+                    _ => Size::Ref {
+                        field,
+                        scale: 1,
+                        offset: n.checked_mul(sign).ok_or_else(|| anyhow!("Invalid size expression: {}", expr))?,
+                    },
+                });
+            }
+        }
+        if !is_identifier(expr) {
+            bail!("Invalid size expression: {}", expr);
+        }
+        Ok(Size::Ref { field: expr.to_string(), scale: 1, offset: 0 })
+    }
+}
+
+/// 解析后的 `if` 条件
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition {
+    field: String,
+    op: &'static str,
+    /// 数值或枚举名
+    rhs: String,
+}
+
+impl Condition {
+    fn parse(expr: &str) -> Result<Self> {
+        for op in ["==", "!=", ">=", "<=", ">", "<"] {
+            if let Some((field, rhs)) = expr.split_once(op) {
+                let field = field.trim().to_string();
+                if !is_identifier(&field) {
+                    bail!("Invalid condition: {}", expr);
+                }
+                return Ok(Self { field, op, rhs: rhs.trim().to_string() });
+            }
+        }
+        let field = expr.trim();
+        if !is_identifier(field) {
+            bail!("Invalid condition: {}", expr);
+        }
+        Ok(Self { field: field.to_string(), op: "!=", rhs: "0".to_string() })
+    }
+
+    fn eval(&self, scope: &Scope) -> Result<bool> {
+        let entry = scope.get(&self.field)?;
+        let (lhs, rhs) = match parse_int(&self.rhs) {
+            Some(rhs) => (entry.value, rhs),
+            // 按枚举名比较：相同名字视为相等
+            None => {
+                let equal = entry.label.as_deref() == Some(self.rhs.as_str());
+                (equal as i64, 1)
+            }
+        };
+        Ok(match self.op {
+            "==" => lhs == rhs,
+            "!=" => lhs != rhs,
+            ">=" => lhs >= rhs,
+            "<=" => lhs <= rhs,
+            ">" => lhs > rhs,
+            _ => lhs < rhs,
+        })
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// 十进制 / `0x` / `0b` 整数
+fn parse_int(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (negative, body) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let value = if let Some(hex) = body.strip_prefix("0x").or_else(|| body.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = body.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else {
+        body.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// 位段 `(最低位, 位数)`
+fn bit_range(spec: &BitSpec) -> Result<(u32, u32)> {
+    match spec {
+        BitSpec::Bit(bit) if *bit < 64 => Ok((*bit, 1)),
+        BitSpec::Bit(bit) => bail!("Bit {} out of range", bit),
+        BitSpec::Range(range) => {
+            let (lo, hi) = range
+                .split_once("..")
+                .or_else(|| range.split_once('-'))
+                .ok_or_else(|| anyhow!("Invalid bit range: {}", range))?;
+            let lo: u32 = lo.trim().parse().map_err(|_| anyhow!("Invalid bit range: {}", range))?;
+            let hi: u32 = hi.trim().parse().map_err(|_| anyhow!("Invalid bit range: {}", range))?;
+            if hi < lo || hi >= 64 {
+                bail!("Invalid bit range: {}", range);
+            }
+            Ok((lo, hi - lo + 1))
+        }
+    }
+}
+
+/// 编码时待计算的校验字段
+struct ChecksumFixup {
+    from: usize,
+    at: usize,
+    size: usize,
+    algorithm: ChecksumAlgorithm,
+    endian: ByteOrder,
+}
+
+/// 已解码/编码的数值字段，供长度引用与条件判断
+struct ScopeEntry {
+    name: String,
+    value: i64,
+    label: Option<String>,
+    /// 在帧中的位置 (编码时回填长度用)
+    offset: usize,
+    size: usize,
+    endian: ByteOrder,
+    /// 编码时未给值，等待由长度/数量回填
+    auto: bool,
+}
+
+#[derive(Default)]
+struct Scope {
+    entries: Vec<ScopeEntry>,
+}
+
+impl Scope {
+    /// 取最近一次出现的同名字段
+    fn get(&self, name: &str) -> Result<&ScopeEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.name == name)
+            .ok_or_else(|| anyhow!("Unknown field reference: {}", name))
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut ScopeEntry> {
+        self.entries.iter_mut().rev().find(|e| e.name == name)
+    }
+
+    fn resolve(&self, size: &Size) -> Result<Option<usize>> {
+        match size {
+            Size::Fixed(n) => Ok(Some(*n)),
+            Size::Rest => Ok(None),
+            Size::Ref { field, scale, offset } => {
+                let value = self.get(field)?.value
+                    .checked_mul(*scale)
+                    .and_then(|v| v.checked_add(*offset))
+                    .ok_or_else(|| anyhow!("Field {} size overflows", field))?;
+                usize::try_from(value)
+                    .map(Some)
+                    .map_err(|_| anyhow!("Field {} gives negative size {}", field, value))
+            }
+        }
+    }
+}
+
+/// 解码得到的字段
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedField {
+    pub name: String,
+    /// 在帧中的字节偏移
+    pub offset: usize,
+    pub size: usize,
+    /// 数值、字符串、bytes (hex 文本)、数组或结构体对象
+    pub value: Value,
+    /// 枚举名
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// 校验字段是否匹配
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid: Option<bool>,
+    /// 结构体成员、数组元素或位域
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<DecodedField>,
+}
+
+/// 解码出的一帧 (经解码器注册表转换为 `DecodedMessage`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedPacket {
+    pub schema: String,
+    /// 帧首字节到达时间 (Unix 纳秒)
+    pub timestamp_ns: u64,
+    pub raw: Vec<u8>,
+    /// 所有校验字段均匹配 (无校验字段时为 true)
+    pub checksum_ok: bool,
+    pub fields: Vec<DecodedField>,
+    /// 单行摘要，供终端显示
+    pub summary: String,
+}
+
+/// 解码失败原因
+enum DecodeError {
+    /// 数据不足，等待更多字节
+    Incomplete,
+    Invalid(anyhow::Error),
+}
+
+impl From<anyhow::Error> for DecodeError {
+    fn from(e: anyhow::Error) -> Self {
+        DecodeError::Invalid(e)
+    }
+}
+
+type DecodeResult<T> = std::result::Result<T, DecodeError>;
+
+/// 解码游标：`end` 为当前区域 (帧或定长结构体) 的结束位置
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    end: usize,
+    /// 区域数据已完整 (否则越界视为等待更多数据)
+    complete: bool,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> DecodeResult<&[u8]> {
+        let end = match self.pos.checked_add(n) {
+            Some(end) if end <= self.end => end,
+            _ => return Err(if self.complete {
+                DecodeError::Invalid(anyhow!("Packet truncated at offset {}", self.pos))
+            } else {
+                DecodeError::Incomplete
+            }),
+        };
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+}
+
+impl PacketSchema {
+    pub fn parse(content: &str) -> Result<Self> {
+        let schema: Self = serde_yaml::from_str(content).map_err(|e| anyhow!("Invalid packet schema: {}", e))?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read packet schema {:?}", path))?;
+        Self::parse(&content)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.fields.is_empty() {
+            bail!("Packet schema {} has no fields", self.name);
+        }
+        let all = std::iter::once(&self.fields).chain(self.structs.values());
+        for field in all.flatten() {
+            let context = || format!("Field {}", field.name);
+            let kind = self.kind(field).with_context(context)?;
+            if let Some(spec) = &field.length {
+                Size::parse(spec).with_context(context)?;
+            } else if matches!(kind, Kind::Bytes) {
+                bail!("Field {}: bytes needs a length", field.name);
+            }
+            if let Some(spec) = &field.count {
+                Size::parse(spec).with_context(context)?;
+            }
+            if let Some(expr) = &field.condition {
+                Condition::parse(expr).with_context(context)?;
+            }
+            for spec in field.bits.values() {
+                let (lo, width) = bit_range(spec).with_context(context)?;
+                if let Kind::Int { size, .. } = kind {
+                    if lo + width > size as u32 * 8 {
+                        bail!("Field {}: bits {} exceed a {}-bit value", field.name, lo + width - 1, size * 8);
+                    }
+                }
+            }
+            if (!field.bits.is_empty() || field.checksum.is_some()) && !matches!(kind, Kind::Int { .. }) {
+                bail!("Field {}: bits and checksum need an integer type", field.name);
+            }
+        }
+        Ok(())
+    }
+
+    fn kind<'a>(&'a self, field: &'a FieldDef) -> Result<Kind<'a>> {
+        let int = |size, signed| Ok(Kind::Int { size, signed });
+        match field.ty.as_str() {
+            "u8" => int(1, false),
+            "u16" => int(2, false),
+            "u24" => int(3, false),
+            "u32" => int(4, false),
+            "u64" => int(8, false),
+            "i8" => int(1, true),
+            "i16" => int(2, true),
+            "i24" => int(3, true),
+            "i32" => int(4, true),
+            "i64" => int(8, true),
+            "f32" => Ok(Kind::Float { size: 4 }),
+            "f64" => Ok(Kind::Float { size: 8 }),
+            "bytes" => Ok(Kind::Bytes),
+            "string" => Ok(Kind::Str),
+            name if self.structs.contains_key(name) => Ok(Kind::Struct(name)),
+            other => Err(anyhow!("Unknown type {}", other)),
+        }
+    }
+
+    fn endian(&self, field: &FieldDef) -> ByteOrder {
+        field.endian.unwrap_or(self.endian)
+    }
+
+    /// 开头 const 字段的编码，用作流式切帧的同步头
+    pub fn sync_bytes(&self) -> Vec<u8> {
+        let mut sync = Vec::new();
+        for field in &self.fields {
+            let Some(constant) = &field.constant else { break };
+            if field.condition.is_some() || field.count.is_some() {
+                break;
+            }
+            match self.encode_scalar(field, constant) {
+                Ok(bytes) => sync.extend(bytes),
+                Err(_) => break,
+            }
+        }
+        sync
+    }
+
+    /// 最小字节数已知时返回 (用于计算 `rest`)
+    fn fixed_size(&self, field: &FieldDef, depth: usize) -> Option<usize> {
+        if field.condition.is_some() || depth > MAX_DEPTH {
+            return None;
+        }
+        let count = match &field.count {
+            None => 1,
+            Some(spec) => match Size::parse(spec).ok()? {
+                Size::Fixed(n) => n,
+                _ => return None,
+            },
+        };
+        if let Some(spec) = &field.length {
+            return match Size::parse(spec).ok()? {
+                Size::Fixed(n) => Some(n * count),
+                _ => None,
+            };
+        }
+        let one = match self.kind(field).ok()? {
+            Kind::Int { size, .. } | Kind::Float { size } => size,
+            Kind::Struct(name) => self.structs[name]
+                .iter()
+                .map(|f| self.fixed_size(f, depth + 1))
+                .sum::<Option<usize>>()?,
+            Kind::Bytes | Kind::Str => return None,
+        };
+        Some(one * count)
+    }
+
+    /// 解码一段完整的帧
+    pub fn decode(&self, data: &[u8], timestamp_ns: u64) -> Result<DecodedPacket> {
+        match self.decode_prefix(data, true, timestamp_ns) {
+            Ok((packet, _)) => Ok(packet),
+            Err(DecodeError::Invalid(e)) => Err(e),
+            Err(DecodeError::Incomplete) => Err(anyhow!("Packet truncated")),
+        }
+    }
+
+    /// 从 `data` 开头解码一帧，返回帧及其字节数
+    fn decode_prefix(&self, data: &[u8], complete: bool, timestamp_ns: u64) -> DecodeResult<(DecodedPacket, usize)> {
+        let mut reader = Reader { data, pos: 0, end: data.len(), complete };
+        let mut scope = Scope::default();
+        let fields = self.decode_fields(&self.fields, &mut reader, &mut scope, 0)?;
+        let raw = data[..reader.pos].to_vec();
+        let checksum_ok = !fields.iter().any(has_invalid);
+        let packet = DecodedPacket {
+            schema: self.name.clone(),
+            timestamp_ns,
+            summary: format!("{} {}", self.name, summarize(&fields)),
+            raw,
+            checksum_ok,
+            fields,
+        };
+        Ok((packet, reader.pos))
+    }
+
+    fn decode_fields(&self, fields: &[FieldDef], reader: &mut Reader, scope: &mut Scope, depth: usize) -> DecodeResult<Vec<DecodedField>> {
+        if depth > MAX_DEPTH {
+            return Err(anyhow!("Structs nested deeper than {}", MAX_DEPTH).into());
+        }
+        let mut decoded = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            if let Some(expr) = &field.condition {
+                if !Condition::parse(expr)?.eval(scope)? {
+                    continue;
+                }
+            }
+            let start = reader.pos;
+            // `rest` 扣除同一区域内其后定长字段的字节数
+            let rest = || -> DecodeResult<usize> {
+                if !reader.complete {
+                    return Err(DecodeError::Incomplete);
+                }
+                let trailing = fields[index + 1..]
+                    .iter()
+                    .map(|f| self.fixed_size(f, depth))
+                    .sum::<Option<usize>>()
+                    .ok_or_else(|| anyhow!("Field {}: rest must be followed by fixed-size fields", field.name))?;
+                Ok((reader.end - reader.pos).saturating_sub(trailing))
+            };
+            let length = match field.length.as_ref().map(Size::parse).transpose()? {
+                Some(Size::Rest) => Some(rest()?),
+                Some(size) => scope.resolve(&size)?,
+                None => None,
+            };
+            let node = match field.count.as_ref().map(Size::parse).transpose()? {
+                None => self.decode_one(field, &field.name, length, reader, scope, depth)?,
+                Some(size) => {
+                    // `count: rest` 读到区域结束
+                    let (count, end) = match size {
+                        Size::Rest => (usize::MAX, reader.pos + rest()?),
+                        size => (scope.resolve(&size)?.unwrap_or(0), usize::MAX),
+                    };
+                    let mut elements = Vec::new();
+                    while elements.len() < count && reader.pos < end {
+                        let name = format!("{}[{}]", field.name, elements.len());
+                        let element = self.decode_one(field, &name, length, reader, scope, depth)?;
+                        if element.size == 0 {
+                            break;
+                        }
+                        elements.push(element);
+                    }
+                    DecodedField {
+                        name: field.name.clone(),
+                        offset: start,
+                        size: reader.pos - start,
+                        value: Value::Array(elements.iter().map(|e| e.value.clone()).collect()),
+                        label: None,
+                        valid: None,
+                        children: elements,
+                    }
+                }
+            };
+            let mut node = node;
+            if let Some(checksum) = &field.checksum {
+                let from = match &checksum.from {
+                    Some(name) => find_offset(&decoded, name)
+                        .ok_or_else(|| anyhow!("Field {}: checksum start {} not found", field.name, name))?,
+                    None => 0,
+                };
+                let expected = checksum.algorithm.compute(&reader.data[from..start]);
+                node.valid = Some(node.value.as_u64() == Some(expected as u64));
+            }
+            decoded.push(node);
+        }
+        Ok(decoded)
+    }
+
+    fn decode_one(
+        &self,
+        field: &FieldDef,
+        name: &str,
+        length: Option<usize>,
+        reader: &mut Reader,
+        scope: &mut Scope,
+        depth: usize,
+    ) -> DecodeResult<DecodedField> {
+        let offset = reader.pos;
+        let endian = self.endian(field);
+        let mut label = None;
+        let mut children = Vec::new();
+        let value = match self.kind(field)? {
+            Kind::Int { size, signed } => {
+                let raw = read_uint(reader.take(size)?, endian);
+                let value = sign_extend(raw, size, signed);
+                for (bit_name, bits, width) in push_int(scope, field, raw, value)? {
+                    children.push(DecodedField {
+                        name: bit_name,
+                        offset,
+                        size,
+                        value: if width == 1 { Value::Bool(bits == 1) } else { Value::from(bits) },
+                        label: None,
+                        valid: None,
+                        children: Vec::new(),
+                    });
+                }
+                label = field.enum_map.get(&value).cloned();
+                if signed { Value::from(value) } else { Value::from(raw) }
+            }
+            Kind::Float { size } => {
+                let raw = read_uint(reader.take(size)?, endian);
+                let value = if size == 4 { f32::from_bits(raw as u32) as f64 } else { f64::from_bits(raw) };
+                Value::from(value)
+            }
+            Kind::Bytes => {
+                let bytes = reader.take(length.unwrap_or(0))?;
+                Value::String(format_hex(bytes))
+            }
+            Kind::Str => {
+                let bytes = match length {
+                    Some(n) => reader.take(n)?,
+                    // 未给长度时以 NUL 结尾
+                    None => {
+                        let len = reader.data[reader.pos..reader.end]
+                            .iter()
+                            .position(|&b| b == 0)
+                            .ok_or_else(|| if reader.complete {
+                                DecodeError::Invalid(anyhow!("Field {}: missing NUL terminator", field.name))
+                            } else {
+                                DecodeError::Incomplete
+                            })?;
+                        let bytes = reader.take(len + 1)?;
+                        &bytes[..len]
+                    }
+                };
+                Value::String(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string())
+            }
+            Kind::Struct(struct_name) => {
+                let members = &self.structs[struct_name];
+                children = match length {
+                    Some(n) => {
+                        // 先整体取出定长区域 (越界时按截断或等待处理)
+                        reader.take(n)?;
+                        let end = reader.pos;
+                        let mut region = Reader { data: reader.data, pos: offset, end, complete: true };
+                        let children = self.decode_fields(members, &mut region, scope, depth + 1)?;
+                        reader.pos = end;
+                        children
+                    }
+                    None => self.decode_fields(members, reader, scope, depth + 1)?,
+                };
+                let mut object = Map::new();
+                for child in &children {
+                    object.insert(child.name.clone(), child.value.clone());
+                }
+                Value::Object(object)
+            }
+        };
+        if let Some(constant) = &field.constant {
+            let expected = self.encode_scalar(field, constant)?;
+            if reader.data[offset..reader.pos] != expected[..] {
+                return Err(anyhow!("Field {}: constant mismatch", field.name).into());
+            }
+        }
+        Ok(DecodedField {
+            name: name.to_string(),
+            offset,
+            size: reader.pos - offset,
+            value,
+            label,
+            valid: None,
+            children,
+        })
+    }
+
+    /// 由字段值 (JSON 对象) 编码一帧；const、被引用的长度/数量字段与校验字段可省略
+    pub fn encode(&self, values: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut scope = Scope::default();
+        let mut checksums = Vec::new();
+        self.encode_fields(&self.fields, values, &mut out, &mut scope, &mut checksums, 0)?;
+        if let Some(missing) = scope.entries.iter().find(|e| e.auto) {
+            bail!("Missing value for field {}", missing.name);
+        }
+        // 校验最后计算，覆盖已回填的长度
+        for fixup in checksums {
+            let value = fixup.algorithm.compute(&out[fixup.from..fixup.at]);
+            write_uint(&mut out[fixup.at..fixup.at + fixup.size], value as u64, fixup.endian);
+        }
+        Ok(out)
+    }
+
+    fn encode_fields(
+        &self,
+        fields: &[FieldDef],
+        values: &Value,
+        out: &mut Vec<u8>,
+        scope: &mut Scope,
+        checksums: &mut Vec<ChecksumFixup>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            bail!("Structs nested deeper than {}", MAX_DEPTH);
+        }
+        let object = values.as_object().ok_or_else(|| anyhow!("Expected an object of field values"))?;
+        // 各字段在 out 中的起始位置 (校验 `from` 用)
+        let mut offsets: Vec<(&str, usize)> = Vec::new();
+        for field in fields {
+            if let Some(expr) = &field.condition {
+                if !Condition::parse(expr)?.eval(scope)? {
+                    continue;
+                }
+            }
+            let start = out.len();
+            offsets.push((&field.name, start));
+            let endian = self.endian(field);
+
+            if let Some(checksum) = &field.checksum {
+                let from = match &checksum.from {
+                    Some(name) => offsets
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .map(|(_, offset)| *offset)
+                        .ok_or_else(|| anyhow!("Field {}: checksum start {} not found", field.name, name))?,
+                    None => 0,
+                };
+                let Kind::Int { size, .. } = self.kind(field)? else { unreachable!() };
+                out.resize(start + size, 0);
+                checksums.push(ChecksumFixup { from, at: start, size, algorithm: checksum.algorithm, endian });
+                continue;
+            }
+
+            let given = field.constant.as_ref().or_else(|| object.get(&field.name));
+            match (&field.count, given) {
+                (Some(spec), Some(Value::Array(items))) => {
+                    let size = Size::parse(spec)?;
+                    fix_size(&field.name, &size, items.len(), scope, out)?;
+                    for item in items {
+                        self.encode_value(field, item, out, scope, checksums, depth)?;
+                    }
+                }
+                (Some(_), Some(_)) => bail!("Field {}: expected an array", field.name),
+                (None, Some(value)) => self.encode_value(field, value, out, scope, checksums, depth)?,
+                (_, None) => match self.kind(field)? {
+                    // 未给值的整数字段先占位，由之后的长度/数量引用回填
+                    Kind::Int { size, .. } => {
+                        out.resize(start + size, 0);
+                        scope.entries.push(ScopeEntry {
+                            name: field.name.clone(),
+                            value: 0,
+                            label: None,
+                            offset: start,
+                            size,
+                            endian,
+                            auto: true,
+                        });
+                    }
+                    _ => bail!("Missing value for field {}", field.name),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_value(
+        &self,
+        field: &FieldDef,
+        value: &Value,
+        out: &mut Vec<u8>,
+        scope: &mut Scope,
+        checksums: &mut Vec<ChecksumFixup>,
+        depth: usize,
+    ) -> Result<()> {
+        let start = out.len();
+        let kind = self.kind(field)?;
+        match kind {
+            Kind::Struct(name) => self.encode_fields(&self.structs[name], value, out, scope, checksums, depth + 1)?,
+            _ => {
+                let bytes = self.encode_scalar(field, value).with_context(|| format!("Field {}", field.name))?;
+                out.extend(bytes);
+            }
+        }
+        if let Kind::Int { size, signed } = kind {
+            let raw = read_uint(&out[start..], self.endian(field));
+            push_int(scope, field, raw, sign_extend(raw, size, signed))?;
+        }
+        if let Some(spec) = &field.length {
+            let size = Size::parse(spec)?;
+            let len = out.len() - start;
+            match &size {
+                // 定长的 bytes/string/结构体不足时补零
+                Size::Fixed(n) if len < *n => out.resize(start + n, 0),
+                _ => fix_size(&field.name, &size, len, scope, out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// 编码单个非结构体值
+    fn encode_scalar(&self, field: &FieldDef, value: &Value) -> Result<Vec<u8>> {
+        let endian = self.endian(field);
+        match self.kind(field)? {
+            Kind::Int { size, signed } => {
+                let raw = match value {
+                    Value::Object(bits) if !field.bits.is_empty() => {
+                        let mut raw = 0u64;
+                        for (bit_name, spec) in &field.bits {
+                            let (lo, width) = bit_range(spec)?;
+                            let part = match bits.get(bit_name) {
+                                Some(Value::Bool(b)) => *b as u64,
+                                Some(v) => json_int(v, field)? as u64,
+                                None => 0,
+                            };
+                            if width < 64 && part >> width != 0 {
+                                bail!("Bit field {} out of range", bit_name);
+                            }
+                            raw |= part << lo;
+                        }
+                        raw
+                    }
+                    _ => {
+                        let v = json_int(value, field)?;
+                        let bits = size as u32 * 8;
+                        let fits = bits == 64
+                            || if signed {
+                                v >= -(1i64 << (bits - 1)) && v < (1i64 << (bits - 1))
+                            } else {
+                                v >= 0 && v < (1i64 << bits)
+                            };
+                        if !fits {
+                            bail!("Value {} out of range for {}", v, field.ty);
+                        }
+                        v as u64
+                    }
+                };
+                let mut bytes = vec![0u8; size];
+                write_uint(&mut bytes, raw, endian);
+                Ok(bytes)
+            }
+            Kind::Float { size } => {
+                let v = value.as_f64().ok_or_else(|| anyhow!("Expected a number"))?;
+                let raw = if size == 4 { (v as f32).to_bits() as u64 } else { v.to_bits() };
+                let mut bytes = vec![0u8; size];
+                write_uint(&mut bytes, raw, endian);
+                Ok(bytes)
+            }
+            Kind::Bytes => match value {
+                Value::String(hex) => parse_hex(hex),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|v| v.as_u64().and_then(|b| u8::try_from(b).ok()).ok_or_else(|| anyhow!("Invalid byte {}", v)))
+                    .collect(),
+                _ => bail!("Expected hex text or a byte array"),
+            },
+            Kind::Str => {
+                let text = value.as_str().ok_or_else(|| anyhow!("Expected a string"))?;
+                let mut bytes = text.as_bytes().to_vec();
+                if field.length.is_none() {
+                    bytes.push(0);
+                }
+                Ok(bytes)
+            }
+            Kind::Struct(_) => bail!("Struct value cannot be a constant"),
+        }
+    }
+}
+
+/// 按实际长度/数量检查或回填被引用的字段
+fn fix_size(name: &str, size: &Size, actual: usize, scope: &mut Scope, out: &mut [u8]) -> Result<()> {
+    match size {
+        Size::Rest => Ok(()),
+        Size::Fixed(n) if *n == actual => Ok(()),
+        Size::Fixed(n) => bail!("Field {}: expected {} but got {}", name, n, actual),
+        Size::Ref { field, scale, offset } => {
+            let entry = scope.get_mut(field).ok_or_else(|| anyhow!("Unknown field reference: {}", field))?;
+            if !entry.auto {
+                let expected = entry.value.checked_mul(*scale).and_then(|v| v.checked_add(*offset));
+                if expected != Some(actual as i64) {
+                    bail!("Field {}: {} does not match actual size {}", name, field, actual);
+                }
+                return Ok(());
+            }
+            let wanted = (actual as i64).checked_sub(*offset).filter(|&w| w >= 0);
+            let value = match wanted {
+                Some(w) if *scale != 0 && w % scale == 0 => w / scale,
+                _ => bail!("Field {}: size {} cannot be expressed through {}", name, actual, field),
+            };
+            entry.value = value;
+            entry.auto = false;
+            write_uint(&mut out[entry.offset..entry.offset + entry.size], entry.value as u64, entry.endian);
+            Ok(())
+        }
+    }
+}
+
+impl ScopeEntry {
+    fn value(name: &str, value: i64, label: Option<String>) -> Self {
+        Self { name: name.to_string(), value, label, offset: 0, size: 0, endian: ByteOrder::Big, auto: false }
+    }
+}
+
+/// 整数值：数字、数字文本 (`0x10`)、布尔或枚举名
+fn json_int(value: &Value, field: &FieldDef) -> Result<i64> {
+    match value {
+        Value::Number(n) => n
+            .as_i64()
+            .or_else(|| n.as_u64().map(|v| v as i64))
+            .ok_or_else(|| anyhow!("Expected an integer, got {}", n)),
+        Value::Bool(b) => Ok(*b as i64),
+        Value::String(s) => parse_int(s)
+            .or_else(|| field.enum_map.iter().find(|(_, name)| *name == s).map(|(v, _)| *v))
+            .ok_or_else(|| anyhow!("Unknown value {}", s)),
+        other => bail!("Expected an integer, got {}", other),
+    }
+}
+
+fn sign_extend(raw: u64, size: usize, signed: bool) -> i64 {
+    if signed && size < 8 {
+        let shift = 64 - size as u32 * 8;
+        ((raw << shift) as i64) >> shift
+    } else {
+        raw as i64
+    }
+}
+
+/// 记录整数字段及其位域 (`字段.子字段`)，返回按最低位排序的 `(位域名, 值, 位数)`
+fn push_int(scope: &mut Scope, field: &FieldDef, raw: u64, value: i64) -> Result<Vec<(String, u64, u32)>> {
+    let mut bits = Vec::new();
+    for (name, spec) in &field.bits {
+        let (lo, width) = bit_range(spec)?;
+        bits.push((lo, name.clone(), (raw >> lo) & (u64::MAX >> (64 - width)), width));
+    }
+    bits.sort_by_key(|(lo, ..)| *lo);
+    for (_, name, part, _) in &bits {
+        scope.entries.push(ScopeEntry::value(&format!("{}.{}", field.name, name), *part as i64, None));
+    }
+    let label = field.enum_map.get(&value).cloned();
+    scope.entries.push(ScopeEntry::value(&field.name, value, label));
+    Ok(bits.into_iter().map(|(_, name, part, width)| (name, part, width)).collect())
+}
+
+fn read_uint(bytes: &[u8], endian: ByteOrder) -> u64 {
+    let fold = |acc: u64, b: &u8| (acc << 8) | *b as u64;
+    match endian {
+        ByteOrder::Big => bytes.iter().fold(0, fold),
+        ByteOrder::Little => bytes.iter().rev().fold(0, fold),
+    }
+}
+
+fn write_uint(out: &mut [u8], value: u64, endian: ByteOrder) {
+    let len = out.len();
+    for (i, b) in out.iter_mut().enumerate() {
+        let shift = match endian {
+            ByteOrder::Big => (len - 1 - i) * 8,
+            ByteOrder::Little => i * 8,
+        };
+        *b = if shift < 64 { (value >> shift) as u8 } else { 0 };
+    }
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+fn find_offset(fields: &[DecodedField], name: &str) -> Option<usize> {
+    fields.iter().find(|f| f.name == name).map(|f| f.offset)
+}
+
+fn has_invalid(field: &DecodedField) -> bool {
+    field.valid == Some(false) || field.children.iter().any(has_invalid)
+}
+
+/// 单行摘要：`kind=reading flags={ready=true mode=2} reading={count=2 samples=[10,-3]}`
+pub fn summarize(fields: &[DecodedField]) -> String {
+    fields.iter().map(summarize_field).collect::<Vec<_>>().join(" ")
+}
+
+fn summarize_field(field: &DecodedField) -> String {
+    let mut text = format!("{}={}", field.name, summarize_value(field));
+    if field.valid == Some(false) {
+        text.push_str("(bad)");
+    }
+    text
+}
+
+fn summarize_value(field: &DecodedField) -> String {
+    if let Some(label) = &field.label {
+        return label.clone();
+    }
+    match &field.value {
+        Value::Array(_) => {
+            let items: Vec<String> = field.children.iter().map(summarize_value).collect();
+            format!("[{}]", items.join(","))
+        }
+        Value::Object(_) | Value::Number(_) if !field.children.is_empty() => {
+            format!("{{{}}}", summarize(&field.children))
+        }
+        Value::String(s) if s.is_empty() || s.contains(' ') => format!("\"{}\"", s),
+        other => other.to_string().trim_matches('"').to_string(),
+    }
+}
+
+/// 流式解码器：按同步头切帧，帧不完整时等待后续数据，解码失败则后移一字节重新同步。
+/// 有同步头时校验失败的帧仍以 `checksum_ok: false` 输出
+pub struct SchemaDecoder {
+    schema: PacketSchema,
+    sync: Vec<u8>,
+    buffer: Vec<u8>,
+    /// 缓冲区首字节到达时间
+    start_ns: u64,
+    /// 缓冲区中早于最近一块到达的字节数
+    older: usize,
+    last_ns: u64,
+    gap_ns: u64,
+}
+
+impl SchemaDecoder {
+    pub fn new(schema: PacketSchema) -> Self {
+        Self {
+            sync: schema.sync_bytes(),
+            schema,
+            buffer: Vec::new(),
+            start_ns: 0,
+            older: 0,
+            last_ns: 0,
+            gap_ns: DEFAULT_IDLE_GAP_NS,
+        }
+    }
+
+    pub fn schema(&self) -> &PacketSchema {
+        &self.schema
+    }
+
+    pub fn feed(&mut self, data: &[u8], now_ns: u64) -> Vec<DecodedPacket> {
+        if self.buffer.is_empty() {
+            self.start_ns = now_ns;
+        }
+        self.older = self.buffer.len();
+        self.last_ns = now_ns;
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() > MAX_FRAME {
+            self.reset(now_ns);
+        }
+        self.drain(false)
+    }
+
+    /// 静默超过间隔后按完整帧收尾 (`rest` 长度的帧需要此步)
+    pub fn flush_idle(&mut self, now_ns: u64) -> Vec<DecodedPacket> {
+        if self.buffer.is_empty() || now_ns.saturating_sub(self.last_ns) < self.gap_ns {
+            return Vec::new();
+        }
+        let packets = self.drain(true);
+        self.reset(now_ns);
+        packets
+    }
+
+    fn drain(&mut self, complete: bool) -> Vec<DecodedPacket> {
+        let mut packets = Vec::new();
+        loop {
+            if !self.sync.is_empty() {
+                match self.buffer.windows(self.sync.len()).position(|w| w == self.sync.as_slice()) {
+                    Some(pos) => self.consume(pos),
+                    None => {
+                        // 保留可能是同步头前半部分的尾部字节
+                        let keep = self.buffer.len().min(self.sync.len() - 1);
+                        self.consume(self.buffer.len() - keep);
+                        break;
+                    }
+                }
+            }
+            if self.buffer.is_empty() {
+                break;
+            }
+            match self.schema.decode_prefix(&self.buffer, complete, self.start_ns) {
+                Ok((packet, consumed)) if packet.checksum_ok && consumed > 0 => {
+                    self.consume(consumed);
+                    packets.push(packet);
+                }
+                Ok((packet, _)) if !packet.checksum_ok && !self.sync.is_empty() => {
+                    // 可能是误同步，只跳过同步头首字节
+                    self.consume(1);
+                    packets.push(packet);
+                }
+                Err(DecodeError::Incomplete) => break,
+                _ => self.consume(1),
+            }
+        }
+        packets
+    }
+
+    /// 清空缓冲区及其时间戳记录
+    fn reset(&mut self, now_ns: u64) {
+        self.buffer.clear();
+        self.older = 0;
+        self.start_ns = now_ns;
+        self.last_ns = now_ns;
+    }
+
+    /// 丢弃缓冲区前 `n` 字节；剩余数据全部来自最近一块时改用该块的到达时间
+    fn consume(&mut self, n: usize) {
+        self.buffer.drain(..n);
+        self.older = self.older.saturating_sub(n);
+        if self.older == 0 {
+            self.start_ns = self.last_ns;
+        }
+    }
+}
+
+impl DecodedPacket {
+    fn into_message(self, direction: Direction) -> DecodedMessage {
+        DecodedMessage {
+            protocol: DECODER_NAME.to_string(),
+            direction,
+            timestamp_ns: self.timestamp_ns,
+            valid: self.checksum_ok,
+            summary: self.summary,
+            fields: serde_json::to_value(&self.fields).unwrap_or_default(),
+            raw: self.raw,
+        }
+    }
+}
+
+/// 注册表中的解码器名称
+pub const DECODER_NAME: &str = "packet-schema";
+
+/// 注册表工厂：参数 `{ "path": "sensor.yaml" }` 或内联 `{ "schema": "<yaml>" }`
+pub fn decoder_factory(options: &Value) -> Result<Box<dyn ProtocolDecoder>> {
+    let schema = match (options.get("path").and_then(Value::as_str), options.get("schema").and_then(Value::as_str)) {
+        (Some(path), _) => PacketSchema::load(Path::new(path))?,
+        (None, Some(content)) => PacketSchema::parse(content)?,
+        (None, None) => bail!("packet-schema decoder needs path or schema"),
+    };
+    Ok(Box::new(SchemaDecoder::new(schema)))
+}
+
+/// RX 按流切帧；TX 每次写入视为一整帧
+impl ProtocolDecoder for SchemaDecoder {
+    fn name(&self) -> &str {
+        DECODER_NAME
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        match direction {
+            Direction::Rx => SchemaDecoder::feed(self, data, timestamp_ns)
+                .into_iter()
+                .map(|p| p.into_message(direction))
+                .collect(),
+            Direction::Tx => self.schema.decode(data, timestamp_ns)
+                .map(|p| vec![p.into_message(direction)])
+                .unwrap_or_default(),
+        }
+    }
+
+    fn flush_idle(&mut self, now_ns: u64) -> Vec<DecodedMessage> {
+        SchemaDecoder::flush_idle(self, now_ns)
+            .into_iter()
+            .map(|p| p.into_message(Direction::Rx))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SCHEMA: &str = r#"
+name: sensor
+endian: big
+fields:
+  - { name: sync, type: u16, const: 0xAA55 }
+  - { name: len, type: u8 }
+  - { name: kind, type: u8, enum: { 1: status, 2: reading } }
+  - { name: flags, type: u8, bits: { ready: 0, mode: "1-3" } }
+  - { name: status, type: status_t, if: "kind == status" }
+  - { name: reading, type: reading_t, length: "len - 2", if: "kind == reading" }
+  - { name: crc, type: u16, endian: little, checksum: { algorithm: crc16-modbus, from: len } }
+structs:
+  status_t:
+    - { name: battery, type: u8 }
+  reading_t:
+    - { name: count, type: u8 }
+    - { name: samples, type: i16, count: count }
+    - { name: label, type: string, length: rest }
+"#;
+
+    fn reading_frame() -> Vec<u8> {
+        let mut frame = vec![0xAA, 0x55, 0x09, 0x02, 0x05, 0x02, 0x00, 0x0A, 0xFF, 0xFD, b'o', b'k'];
+        let crc = ChecksumAlgorithm::Crc16Modbus.compute(&frame[2..]) as u16;
+        frame.extend_from_slice(&crc.to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_decode_nested_conditional() {
+        let schema = PacketSchema::parse(SCHEMA).unwrap();
+        assert_eq!(schema.sync_bytes(), vec![0xAA, 0x55]);
+        let packet = schema.decode(&reading_frame(), 0).unwrap();
+        assert!(packet.checksum_ok);
+        let names: Vec<&str> = packet.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["sync", "len", "kind", "flags", "reading", "crc"]);
+        assert_eq!(packet.fields[2].label.as_deref(), Some("reading"));
+        assert_eq!(packet.fields[3].children[0].value, json!(true));
+        assert_eq!(packet.fields[3].children[1].value, json!(2));
+        assert_eq!(packet.fields[4].value, json!({ "count": 2, "samples": [10, -3], "label": "ok" }));
+        let crc = ChecksumAlgorithm::Crc16Modbus.compute(&reading_frame()[2..12]);
+        assert_eq!(
+            packet.summary,
+            format!("sensor sync=43605 len=9 kind=reading flags={{ready=true mode=2}} reading={{count=2 samples=[10,-3] label=ok}} crc={}", crc)
+        );
+
+        let mut corrupted = reading_frame();
+        corrupted[7] ^= 1;
+        assert!(!schema.decode(&corrupted, 0).unwrap().checksum_ok);
+    }
+
+    #[test]
+    fn test_encode_fills_length_and_checksum() {
+        let schema = PacketSchema::parse(SCHEMA).unwrap();
+        let values = json!({
+            "kind": "reading",
+            "flags": { "ready": true, "mode": 2 },
+            "reading": { "samples": [10, -3], "label": "ok" },
+        });
+        assert_eq!(schema.encode(&values).unwrap(), reading_frame());
+
+        let status = schema.encode(&json!({ "len": 3, "kind": 1, "flags": 0, "status": { "battery": 90 } })).unwrap();
+        let packet = schema.decode(&status, 0).unwrap();
+        assert_eq!(packet.fields[4].value, json!({ "battery": 90 }));
+        assert!(schema.encode(&json!({ "kind": 1, "flags": 0 })).is_err());
+    }
+
+    #[test]
+    fn test_stream_resync_and_split() {
+        let schema = PacketSchema::parse(SCHEMA).unwrap();
+        let mut decoder = SchemaDecoder::new(schema);
+        let frame = reading_frame();
+        let mut stream = vec![0x00, 0xAA, 0x13];
+        stream.extend_from_slice(&frame[..5]);
+        assert!(decoder.feed(&stream, 1).is_empty());
+        let packets = decoder.feed(&frame[5..], 2);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].raw, frame);
+        assert_eq!(packets[0].timestamp_ns, 1);
+
+        // 同步头后的垃圾数据校验失败，标记后跳过并找到下一帧
+        let mut stream = vec![0xAA, 0x55, 0x01, 0x00];
+        stream.extend_from_slice(&frame);
+        stream.extend_from_slice(&frame);
+        let packets = decoder.feed(&stream, 3);
+        let ok: Vec<bool> = packets.iter().map(|p| p.checksum_ok).collect();
+        assert_eq!(ok, vec![false, true, true]);
+
+        // 同一块中后续帧使用该块的到达时间
+        assert!(decoder.feed(&frame[..5], 10).is_empty());
+        let mut rest = frame[5..].to_vec();
+        rest.extend_from_slice(&frame);
+        let stamps: Vec<u64> = decoder.feed(&rest, 20).iter().map(|p| p.timestamp_ns).collect();
+        assert_eq!(stamps, vec![10, 20]);
+    }
+
+    #[test]
+    fn test_stream_overflow_resets_state() {
+        let schema = PacketSchema::parse(r#"
+name: raw
+fields:
+  - { name: sync, type: u16, const: 0xAA55 }
+  - { name: text, type: string, length: rest }
+"#).unwrap();
+        let mut decoder = SchemaDecoder::new(schema);
+        // `rest` 长度的帧在静默前一直等待，超过上限后整体丢弃
+        let mut stream = vec![0xAA, 0x55];
+        stream.resize(MAX_FRAME, b'x');
+        assert!(decoder.feed(&stream, 1).is_empty());
+        assert!(decoder.feed(b"xx", 2).is_empty());
+        assert_eq!((decoder.buffer.len(), decoder.older), (0, 0));
+        assert!(decoder.flush_idle(u64::MAX).is_empty());
+
+        assert!(decoder.feed(&[0xAA, 0x55, b'h', b'i'], 30).is_empty());
+        let packets = decoder.flush_idle(30 + DEFAULT_IDLE_GAP_NS);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].timestamp_ns, 30);
+        assert_eq!(packets[0].fields[1].value, json!("hi"));
+    }
+
+    #[test]
+    fn test_rejects_out_of_range_bits_and_sizes() {
+        let bits = "name: t\nfields:\n  - { name: v, type: u64, bits: { x: \"60-70\" } }\n";
+        assert!(PacketSchema::parse(bits).is_err());
+        let bit = "name: t\nfields:\n  - { name: v, type: u8, bits: { x: 9 } }\n";
+        assert!(PacketSchema::parse(bit).is_err());
+
+        let huge = "name: t\nfields:\n  - { name: n, type: i64 }\n  - { name: d, type: bytes, length: \"n * 4\" }\n";
+        let schema = PacketSchema::parse(huge).unwrap();
+        let mut frame = i64::MAX.to_be_bytes().to_vec();
+        frame.push(0);
+        assert!(schema.decode(&frame, 0).is_err());
+        let mut frame = (i64::MAX / 4).to_be_bytes().to_vec();
+        frame.push(0);
+        assert!(schema.decode(&frame, 0).is_err());
+    }
+}
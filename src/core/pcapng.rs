@@ -0,0 +1,419 @@
+//! pcapng 导出
+//!
+//! 将 `CaptureRecord` 写成 Wireshark 可直接打开的 pcapng 文件：
+//! - 每个 端口/方向 对应一个接口 (IDB)，接口名如 "COM3 RX"
+//! - 时间戳精度为纳秒 (if_tsresol = 9)
+//! - 方向同时写入 epb_flags (inbound/outbound)
+//! - 用户标记写成带 opt_comment 的零长度数据包
+//!
+//! 链路类型默认使用 LINKTYPE_USER0 (147)，可在 Wireshark 的 "DLT_USER" 中为其指定解析器。
+
+use anyhow::{Result, anyhow, Context};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::capture::{CaptureEvent, CaptureRecord, CaptureSink, Direction};
+
+/// 用户自定义链路类型 (DLT_USER0)
+pub const LINKTYPE_USER0: u16 = 147;
+
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_ENDOFOPT: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+const EPB_FLAGS: u16 = 2;
+
+/// epb_flags 方向位
+const EPB_INBOUND: u32 = 0b01;
+const EPB_OUTBOUND: u32 = 0b10;
+
+/// pcapng 写入器
+pub struct PcapngWriter<W: Write> {
+    out: W,
+    link_type: u16,
+    /// (端口, 方向) -> 接口 ID
+    interfaces: HashMap<(String, Direction), u32>,
+}
+
+impl PcapngWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create pcapng file {:?}", path))?;
+        Self::new(BufWriter::new(file), LINKTYPE_USER0)
+    }
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// 创建写入器并立即写出 Section Header Block
+    pub fn new(out: W, link_type: u16) -> Result<Self> {
+        let mut writer = Self {
+            out,
+            link_type,
+            interfaces: HashMap::new(),
+        };
+        writer.write_section_header()?;
+        Ok(writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write_section_header(&mut self) -> Result<()> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes()); // major
+        body.extend_from_slice(&0u16.to_le_bytes()); // minor
+        body.extend_from_slice(&(-1i64).to_le_bytes()); // section length 未知
+        push_option(&mut body, SHB_USERAPPL, b"SerialUtil");
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(BLOCK_SHB, &body)
+    }
+
+    /// 获取 (或按需写出) 接口 ID
+    fn interface_id(&mut self, port: &str, direction: Direction) -> Result<u32> {
+        let key = (port.to_string(), direction);
+        if let Some(&id) = self.interfaces.get(&key) {
+            return Ok(id);
+        }
+
+        let id = self.interfaces.len() as u32;
+        let name = format!("{} {}", port, direction.as_str());
+        let description = format!("Serial port {} ({})", port, direction.as_str());
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&self.link_type.to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+        body.extend_from_slice(&0u32.to_le_bytes()); // snaplen: 不限制
+        push_text_option(&mut body, IF_NAME, &name);
+        push_text_option(&mut body, IF_DESCRIPTION, &description);
+        push_option(&mut body, IF_TSRESOL, &[9]); // 10^-9 秒
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+        self.write_block(BLOCK_IDB, &body)?;
+
+        self.interfaces.insert(key, id);
+        Ok(id)
+    }
+
+    fn write_packet(
+        &mut self,
+        interface_id: u32,
+        timestamp_ns: u64,
+        data: &[u8],
+        flags: Option<u32>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let mut body = Vec::with_capacity(data.len() + 48);
+        body.extend_from_slice(&interface_id.to_le_bytes());
+        body.extend_from_slice(&((timestamp_ns >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(timestamp_ns as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // captured
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes()); // original
+        body.extend_from_slice(data);
+        pad32(&mut body);
+
+        if let Some(comment) = comment {
+            push_text_option(&mut body, OPT_COMMENT, comment);
+        }
+        if let Some(flags) = flags {
+            push_option(&mut body, EPB_FLAGS, &flags.to_le_bytes());
+        }
+        if comment.is_some() || flags.is_some() {
+            push_option(&mut body, OPT_ENDOFOPT, &[]);
+        }
+        self.write_block(BLOCK_EPB, &body)
+    }
+
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<()> {
+        let total_len = (body.len() + 12) as u32;
+        self.out.write_all(&block_type.to_le_bytes())?;
+        self.out.write_all(&total_len.to_le_bytes())?;
+        self.out.write_all(body)?;
+        self.out.write_all(&total_len.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> CaptureSink for PcapngWriter<W> {
+    fn write_record(&mut self, record: &CaptureRecord) -> Result<()> {
+        match &record.event {
+            CaptureEvent::Data { direction, data } => {
+                let id = self.interface_id(&record.port, *direction)?;
+                let flags = match direction {
+                    Direction::Rx => EPB_INBOUND,
+                    Direction::Tx => EPB_OUTBOUND,
+                };
+                self.write_packet(id, record.timestamp_ns, data, Some(flags), None)
+            }
+            CaptureEvent::Marker { text } => {
+                // 标记不区分方向，挂在该端口的 RX 接口上
+                let id = self.interface_id(&record.port, Direction::Rx)?;
+                self.write_packet(id, record.timestamp_ns, &[], None, Some(text))
+            }
+            CaptureEvent::Decoded { protocol, direction, summary, .. } => {
+                // pcapng 中只保留摘要注释，导入后成为标记
+                let id = self.interface_id(&record.port, *direction)?;
+                let text = format!("{}: {}", protocol, summary);
+                self.write_packet(id, record.timestamp_ns, &[], None, Some(&text))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// 将已保存的 JSON Lines 抓包转换为 pcapng，返回转换的记录数
+pub fn convert_jsonl_to_pcapng(input: &Path, output: &Path) -> Result<usize> {
+    let records = super::capture::read_jsonl(input)?;
+    let mut writer = PcapngWriter::create(output)?;
+    for record in &records {
+        writer.write_record(record)?;
+    }
+    writer.flush()?;
+    Ok(records.len())
+}
+
+/// 读取 pcapng 文件 (仅支持本模块写出的小端文件)，还原为 `CaptureRecord`
+pub fn read_pcapng(path: &Path) -> Result<Vec<CaptureRecord>> {
+    let buf = std::fs::read(path)
+        .with_context(|| format!("Failed to open pcapng file {:?}", path))?;
+    parse_pcapng(&buf)
+}
+
+/// 解析 pcapng 字节流
+pub fn parse_pcapng(buf: &[u8]) -> Result<Vec<CaptureRecord>> {
+    // 接口: (端口名, 方向, 每秒的时间戳单位数)
+    let mut interfaces: Vec<(String, Direction, u64)> = Vec::new();
+    let mut records = Vec::new();
+    let mut offset = 0;
+
+    while offset + 12 <= buf.len() {
+        let block_type = le_u32(buf, offset);
+        let total_len = le_u32(buf, offset + 4) as usize;
+        if total_len < 12 || offset + total_len > buf.len() {
+            return Err(anyhow!("Truncated pcapng block at offset {}", offset));
+        }
+        let body = &buf[offset + 8..offset + total_len - 4];
+
+        match block_type {
+            BLOCK_SHB => {
+                if body.len() < 4 || le_u32(body, 0) != BYTE_ORDER_MAGIC {
+                    return Err(anyhow!("Unsupported pcapng byte order"));
+                }
+                interfaces.clear();
+            }
+            BLOCK_IDB => {
+                let mut name = format!("if{}", interfaces.len());
+                let mut units_per_sec = 1_000_000u64; // 默认微秒
+                for (code, value) in parse_options(body.get(8..).unwrap_or(&[])) {
+                    match code {
+                        IF_NAME => name = String::from_utf8_lossy(value).to_string(),
+                        IF_TSRESOL if !value.is_empty() => {
+                            let v = value[0];
+                            let units = if v & 0x80 != 0 {
+                                1u64.checked_shl((v & 0x7F) as u32)
+                            } else {
+                                10u64.checked_pow(v as u32)
+                            };
+                            units_per_sec = units.ok_or_else(|| anyhow!("Unsupported if_tsresol 0x{:02X}", v))?;
+                        }
+                        _ => {}
+                    }
+                }
+                // 接口名格式 "<端口> <RX|TX>"
+                let (port, direction) = match name.rsplit_once(' ') {
+                    Some((port, "TX")) => (port.to_string(), Direction::Tx),
+                    Some((port, "RX")) => (port.to_string(), Direction::Rx),
+                    _ => (name.clone(), Direction::Rx),
+                };
+                interfaces.push((port, direction, units_per_sec));
+            }
+            BLOCK_EPB if body.len() >= 20 => {
+                let if_id = le_u32(body, 0) as usize;
+                let (port, direction, units_per_sec) = interfaces.get(if_id)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Packet references unknown interface {}", if_id))?;
+                let ts = ((le_u32(body, 4) as u64) << 32) | le_u32(body, 8) as u64;
+                let timestamp_ns = (ts as u128 * 1_000_000_000 / units_per_sec as u128) as u64;
+                let cap_len = le_u32(body, 12) as usize;
+                let data = body.get(20..20 + cap_len)
+                    .ok_or_else(|| anyhow!("Truncated packet data"))?;
+                let opts_start = 20 + ((cap_len + 3) & !3);
+                let comment = parse_options(body.get(opts_start..).unwrap_or(&[]))
+                    .into_iter()
+                    .find(|(code, _)| *code == OPT_COMMENT)
+                    .map(|(_, v)| String::from_utf8_lossy(v).to_string());
+
+                let event = match comment {
+                    Some(text) if data.is_empty() => CaptureEvent::Marker { text },
+                    _ => CaptureEvent::Data { direction, data: data.to_vec() },
+                };
+                records.push(CaptureRecord { timestamp_ns, port, event });
+            }
+            _ => {}
+        }
+        offset += total_len;
+    }
+
+    Ok(records)
+}
+
+fn parse_options(mut buf: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut options = Vec::new();
+    while buf.len() >= 4 {
+        let code = u16::from_le_bytes([buf[0], buf[1]]);
+        let len = u16::from_le_bytes([buf[2], buf[3]]) as usize;
+        if code == OPT_ENDOFOPT || buf.len() < 4 + len {
+            break;
+        }
+        options.push((code, &buf[4..4 + len]));
+        buf = &buf[(4 + ((len + 3) & !3)).min(buf.len())..];
+    }
+    options
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]])
+}
+
+/// 选项长度字段为 16 位，超长的值截断，避免长度回绕写出损坏的块
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    let value = &value[..value.len().min(u16::MAX as usize)];
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    pad32(buf);
+}
+
+/// 文本选项：在字符边界处截断到选项长度上限
+fn push_text_option(buf: &mut Vec<u8>, code: u16, text: &str) {
+    let mut end = text.len().min(u16::MAX as usize);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    push_option(buf, code, &text.as_bytes()[..end]);
+}
+
+fn pad32(buf: &mut Vec<u8>) {
+    while buf.len() & 3 != 0 {
+        buf.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_u32(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    /// 遍历块，返回 (block_type, offset, total_len)
+    fn blocks(buf: &[u8]) -> Vec<(u32, usize, usize)> {
+        let mut result = Vec::new();
+        let mut offset = 0;
+        while offset < buf.len() {
+            let block_type = read_u32(buf, offset);
+            let len = read_u32(buf, offset + 4) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(read_u32(buf, offset + len - 4) as usize, len);
+            result.push((block_type, offset, len));
+            offset += len;
+        }
+        result
+    }
+
+    #[test]
+    fn test_interface_per_port_direction() {
+        let mut writer = PcapngWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        let mut rx = CaptureRecord::data("COM3", Direction::Rx, b"hello");
+        rx.timestamp_ns = 0x1_0000_0002;
+        writer.write_record(&rx).unwrap();
+        writer.write_record(&CaptureRecord::data("COM3", Direction::Tx, b"AT\r")).unwrap();
+        writer.write_record(&CaptureRecord::data("COM3", Direction::Rx, b"OK")).unwrap();
+        writer.write_record(&CaptureRecord::marker("COM3", "reset")).unwrap();
+        let buf = writer.into_inner();
+
+        let types: Vec<u32> = blocks(&buf).iter().map(|b| b.0).collect();
+        assert_eq!(types, vec![BLOCK_SHB, BLOCK_IDB, BLOCK_EPB, BLOCK_IDB, BLOCK_EPB, BLOCK_EPB, BLOCK_EPB]);
+
+        // 第一个 EPB: 接口 0，时间戳高/低 32 位
+        let (_, epb, _) = blocks(&buf)[2];
+        assert_eq!(read_u32(&buf, epb + 8), 0);
+        assert_eq!(read_u32(&buf, epb + 12), 1);
+        assert_eq!(read_u32(&buf, epb + 16), 2);
+        assert_eq!(read_u32(&buf, epb + 20), 5);
+        assert_eq!(&buf[epb + 28..epb + 33], b"hello");
+
+        // TX 数据使用新接口 1
+        let (_, epb_tx, _) = blocks(&buf)[4];
+        assert_eq!(read_u32(&buf, epb_tx + 8), 1);
+    }
+
+    #[test]
+    fn test_marker_comment() {
+        let mut writer = PcapngWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        writer.write_record(&CaptureRecord::marker("COM1", "boot")).unwrap();
+        let buf = writer.into_inner();
+        let (_, epb, len) = *blocks(&buf).last().unwrap();
+        assert_eq!(read_u32(&buf, epb + 20), 0); // 零长度数据
+        let block = &buf[epb..epb + len];
+        assert!(block.windows(4).any(|w| w == b"boot"));
+    }
+
+    #[test]
+    fn test_long_comment_is_truncated() {
+        let mut writer = PcapngWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        writer.write_record(&CaptureRecord::marker("COM1", &"é".repeat(40_000))).unwrap();
+        let buf = writer.into_inner();
+        // 块结构完整，注释在字符边界处截断
+        let (_, epb, _) = *blocks(&buf).last().unwrap();
+        assert_eq!(&buf[epb + 28..epb + 30], &OPT_COMMENT.to_le_bytes());
+        assert_eq!(&buf[epb + 30..epb + 32], &(u16::MAX - 1).to_le_bytes());
+    }
+
+    #[test]
+    fn test_out_of_range_tsresol() {
+        let mut writer = PcapngWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        writer.write_record(&CaptureRecord::data("COM1", Direction::Rx, b"x")).unwrap();
+        let buf = writer.into_inner();
+        let (_, idb, len) = blocks(&buf)[1];
+        // 选项 if_tsresol: 代码 9、长度 1、值 9
+        let at = idb + buf[idb..idb + len].windows(5).position(|w| w == [9, 0, 1, 0, 9]).unwrap() + 4;
+        for bad in [25u8, 0x80 | 0x50] {
+            let mut corrupt = buf.clone();
+            corrupt[at] = bad;
+            assert!(parse_pcapng(&corrupt).is_err());
+        }
+        let mut binary = buf.clone();
+        binary[at] = 0x80 | 20;
+        assert_eq!(parse_pcapng(&binary).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = PcapngWriter::new(Vec::new(), LINKTYPE_USER0).unwrap();
+        let records = vec![
+            CaptureRecord::data("COM3", Direction::Tx, b"AT\r"),
+            CaptureRecord::data("COM3", Direction::Rx, b"OK\r\n"),
+            CaptureRecord::marker("COM3", "after reset"),
+        ];
+        for r in &records {
+            writer.write_record(r).unwrap();
+        }
+        let parsed = parse_pcapng(&writer.into_inner()).unwrap();
+        assert_eq!(parsed, records);
+    }
+}
@@ -0,0 +1,179 @@
+//! 协议解码插件接口
+//!
+//! 各协议 (Modbus、NMEA、自定义报文描述等) 实现 `ProtocolDecoder`，按方向消费收发数据块，
+//! 输出带摘要行与字段树的 `DecodedMessage`。`DecoderRegistry` 按名称登记工厂函数，
+//! 会话可按名称启用/停用解码器，事件循环统一喂数据并转发结果。
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use super::capture::Direction;
+
+/// 一条解码结果 (`decoded-message` 事件载荷)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodedMessage {
+    /// 解码器名称
+    pub protocol: String,
+    pub direction: Direction,
+    /// 帧首字节时间 (Unix 纳秒)
+    pub timestamp_ns: u64,
+    pub raw: Vec<u8>,
+    /// 校验与格式均正确
+    pub valid: bool,
+    /// 单行摘要，供终端显示
+    pub summary: String,
+    /// 字段树
+    pub fields: Value,
+}
+
+/// 协议解码器
+pub trait ProtocolDecoder: Send {
+    /// 注册名称，同时作为 `DecodedMessage::protocol`
+    fn name(&self) -> &str;
+
+    /// 喂入一块收发数据，返回其中解出的完整消息
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage>;
+
+    /// 无数据时周期调用，输出按静默间隔收尾的消息
+    fn flush_idle(&mut self, _now_ns: u64) -> Vec<DecodedMessage> {
+        Vec::new()
+    }
+}
+
+/// 由启用参数创建解码器
+pub type DecoderFactory = Box<dyn Fn(&Value) -> Result<Box<dyn ProtocolDecoder>> + Send>;
+
+/// 解码器注册表
+pub struct DecoderRegistry {
+    factories: BTreeMap<String, DecoderFactory>,
+    /// 已启用的解码器，按启用顺序
+    active: Vec<Box<dyn ProtocolDecoder>>,
+}
+
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DecoderRegistry {
+    /// 登记内置解码器
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(super::modbus::sniffer::DECODER_NAME, Box::new(super::modbus::sniffer::decoder_factory));
+        registry.register(super::nmea::DECODER_NAME, Box::new(super::nmea::decoder_factory));
+        registry.register(super::mavlink::DECODER_NAME, Box::new(super::mavlink::decoder_factory));
+        registry.register(super::packet_schema::DECODER_NAME, Box::new(super::packet_schema::decoder_factory));
+        registry.register(super::slcan::DECODER_NAME, Box::new(super::slcan::decoder_factory));
+        registry
+    }
+
+    pub fn empty() -> Self {
+        Self { factories: BTreeMap::new(), active: Vec::new() }
+    }
+
+    pub fn register(&mut self, name: &str, factory: DecoderFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// 可启用的解码器名称
+    pub fn available(&self) -> Vec<String> {
+        self.factories.keys().cloned().collect()
+    }
+
+    /// 已启用的解码器名称
+    pub fn enabled(&self) -> Vec<String> {
+        self.active.iter().map(|d| d.name().to_string()).collect()
+    }
+
+    /// 按名称创建并启用，已启用的同名解码器被替换
+    pub fn enable(&mut self, name: &str, options: &Value) -> Result<()> {
+        let factory = self.factories.get(name).ok_or_else(|| anyhow!("Unknown protocol decoder: {}", name))?;
+        let decoder = factory(options)?;
+        self.insert(decoder);
+        Ok(())
+    }
+
+    /// 启用已构造好的解码器
+    pub fn insert(&mut self, decoder: Box<dyn ProtocolDecoder>) {
+        self.disable(decoder.name());
+        self.active.push(decoder);
+    }
+
+    pub fn disable(&mut self, name: &str) -> bool {
+        let before = self.active.len();
+        self.active.retain(|d| d.name() != name);
+        self.active.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    pub fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        self.active
+            .iter_mut()
+            .flat_map(|d| d.feed(direction, data, timestamp_ns))
+            .collect()
+    }
+
+    pub fn flush_idle(&mut self, now_ns: u64) -> Vec<DecodedMessage> {
+        self.active.iter_mut().flat_map(|d| d.flush_idle(now_ns)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 每个字节一条消息
+    struct ByteDecoder;
+
+    impl ProtocolDecoder for ByteDecoder {
+        fn name(&self) -> &str {
+            "bytes"
+        }
+
+        fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+            data.iter()
+                .map(|&b| DecodedMessage {
+                    protocol: self.name().to_string(),
+                    direction,
+                    timestamp_ns,
+                    raw: vec![b],
+                    valid: true,
+                    summary: format!("{:02X}", b),
+                    fields: json!({ "value": b }),
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_registry_enable_by_name() {
+        let mut registry = DecoderRegistry::new();
+        registry.register("bytes", Box::new(|_| Ok(Box::new(ByteDecoder))));
+        assert_eq!(registry.available(), vec!["bytes", "mavlink", "modbus-rtu", "nmea-0183", "packet-schema", "slcan"]);
+        assert!(registry.enable("nmea-2000", &Value::Null).is_err());
+
+        registry.enable("bytes", &Value::Null).unwrap();
+        registry.enable("bytes", &Value::Null).unwrap();
+        registry.enable("modbus-rtu", &json!({ "baudRate": 9600 })).unwrap();
+        assert_eq!(registry.enabled(), vec!["bytes", "modbus-rtu"]);
+
+        // Modbus 请求 01 03 0000 0001 + CRC
+        let messages = registry.feed(Direction::Rx, &[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A], 7);
+        assert_eq!(messages.len(), 9);
+        let modbus = messages.last().unwrap();
+        assert_eq!(modbus.protocol, "modbus-rtu");
+        assert!(modbus.valid);
+        assert_eq!(modbus.summary, "unit 1 FC03 request: read 1 @ 0");
+
+        assert!(registry.disable("bytes"));
+        assert!(!registry.disable("bytes"));
+        assert!(registry.feed(Direction::Tx, &[0xAA], 8).is_empty());
+    }
+}
@@ -0,0 +1,282 @@
+//! 抓包回放 (虚拟设备)
+//!
+//! 读取已保存的抓包文件，按原始 (或缩放后的) 时序重新发出其中的 RX 记录：
+//! - 送入应用的数据通道 (与 `SerialManager` 读线程共用同一个 `mpsc::Sender`)
+//! - 可选写入一个虚拟串口对的一端 (com0com) 或新建的 pty，供第三方工具读取
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use log::{info, error};
+
+use super::capture::{self, CaptureEvent, Direction};
+use super::endpoint::{Endpoint, OpenedEndpoint};
+use super::serial_manager::RxChunk;
+
+/// 最低回放倍速 (更慢时等待时间会溢出)
+const MIN_SPEED: f64 = 0.01;
+
+/// 回放参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayOptions {
+    /// 时间缩放: 1.0 为原速，2.0 为两倍速，0 表示不等待；其余须不低于 `MIN_SPEED`
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    /// 只回放指定端口的记录 (抓包中含多个端口时)
+    #[serde(default)]
+    pub port_filter: Option<String>,
+    /// 回放结束后从头循环
+    #[serde(default)]
+    pub repeat: bool,
+    /// 是否送入应用的数据通道
+    #[serde(default = "default_true")]
+    pub emit_to_app: bool,
+    /// 额外输出到虚拟串口对或 pty (None 表示仅送入应用)
+    #[serde(default)]
+    pub output: Option<Endpoint>,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 回放状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayStatus {
+    pub running: bool,
+    pub sent_records: usize,
+    pub total_records: usize,
+    /// 使用 pty 输出时，第三方工具应打开的设备路径
+    pub device_path: Option<String>,
+}
+
+/// 一条待回放的数据 (相对起点的偏移)
+struct ReplayItem {
+    offset: Duration,
+    data: Vec<u8>,
+}
+
+/// 回放管理器
+pub struct ReplayManager {
+    /// 每次启动新建，旧线程退出时只清除自己的标志
+    running: Arc<AtomicBool>,
+    sent: Arc<AtomicUsize>,
+    worker: Option<JoinHandle<()>>,
+    total: usize,
+    device_path: Option<String>,
+}
+
+impl Default for ReplayManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayManager {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            sent: Arc::new(AtomicUsize::new(0)),
+            worker: None,
+            total: 0,
+            device_path: None,
+        }
+    }
+
+    /// 启动回放
+    pub fn start(
+        &mut self,
+        path: &Path,
+        options: ReplayOptions,
+        app_tx: Option<mpsc::Sender<RxChunk>>,
+    ) -> Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(anyhow!("Replay already running"));
+        }
+        let speed = options.speed;
+        if !(speed == 0.0 || (speed.is_finite() && speed >= MIN_SPEED)) {
+            return Err(anyhow!("Invalid replay speed {} (use 0 or at least {})", speed, MIN_SPEED));
+        }
+        // 回收已自行退出的上一次线程
+        self.stop_worker();
+
+        let items = Self::load_items(path, options.port_filter.as_deref())?;
+        if items.is_empty() {
+            return Err(anyhow!("No RX records to replay in {:?}", path));
+        }
+
+        let output: Option<OpenedEndpoint> = match &options.output {
+            Some(endpoint) => Some(endpoint.open(Duration::from_millis(100))?),
+            None => None,
+        };
+        let device_path = output.as_ref().and_then(|o| o.device_path.clone());
+        let app_tx = if options.emit_to_app { app_tx } else { None };
+
+        self.total = items.len();
+        self.device_path = device_path;
+        self.running = Arc::new(AtomicBool::new(true));
+        self.sent = Arc::new(AtomicUsize::new(0));
+
+        let running = self.running.clone();
+        let sent = self.sent.clone();
+        let repeat = options.repeat;
+
+        info!("Replay started: {:?}, {} records, speed x{}", path, items.len(), speed);
+
+        self.worker = Some(std::thread::spawn(move || {
+            let mut output = output;
+            'outer: loop {
+                let start = Instant::now();
+                for item in &items {
+                    if speed > 0.0 {
+                        let due = item.offset.div_f64(speed);
+                        // 分段休眠，便于及时响应 stop
+                        while let Some(wait) = due.checked_sub(start.elapsed()) {
+                            if !running.load(Ordering::SeqCst) {
+                                break 'outer;
+                            }
+                            if wait.is_zero() {
+                                break;
+                            }
+                            std::thread::sleep(wait.min(Duration::from_millis(50)));
+                        }
+                    }
+                    if !running.load(Ordering::SeqCst) {
+                        break 'outer;
+                    }
+
+                    if let Some(tx) = &app_tx {
+                        if tx.blocking_send(RxChunk { data: item.data.clone(), received_ns: capture::now_ns() }).is_err() {
+                            break 'outer;
+                        }
+                    }
+                    if let Some(port) = output.as_mut().map(|o| &mut o.port) {
+                        if let Err(e) = port.write_all(&item.data).and_then(|_| port.flush()) {
+                            error!("Replay output write error: {}", e);
+                            break 'outer;
+                        }
+                    }
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+                if !repeat {
+                    break;
+                }
+                sent.store(0, Ordering::SeqCst);
+            }
+            running.store(false, Ordering::SeqCst);
+            info!("Replay thread exited");
+        }));
+
+        Ok(())
+    }
+
+    /// 停止回放并等待线程退出
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_worker();
+        self.device_path = None;
+        Ok(())
+    }
+
+    fn stop_worker(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        ReplayStatus {
+            running: self.running.load(Ordering::SeqCst),
+            sent_records: self.sent.load(Ordering::SeqCst),
+            total_records: self.total,
+            device_path: self.device_path.clone(),
+        }
+    }
+
+    /// 等待线程退出 (测试用)
+    #[cfg(test)]
+    fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    /// 读取抓包并提取 RX 记录，时间戳转换为相对第一条记录的偏移
+    fn load_items(path: &Path, port_filter: Option<&str>) -> Result<Vec<ReplayItem>> {
+        let records = capture::read_capture(path)?;
+        let mut items = Vec::new();
+        let mut first_ts = None;
+
+        for record in records {
+            if let Some(port) = port_filter {
+                if record.port != port {
+                    continue;
+                }
+            }
+            if let CaptureEvent::Data { direction: Direction::Rx, data } = record.event {
+                let base = *first_ts.get_or_insert(record.timestamp_ns);
+                let offset = Duration::from_nanos(record.timestamp_ns.saturating_sub(base));
+                items.push(ReplayItem { offset, data });
+            }
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::capture::{CaptureRecord, CaptureSink, JsonlCaptureWriter};
+
+    fn write_capture(path: &Path) {
+        let mut writer = JsonlCaptureWriter::create(path).unwrap();
+        let records = [
+            (1_000_000, Direction::Rx, b"one".as_slice()),
+            (2_000_000, Direction::Tx, b"skip".as_slice()),
+            (21_000_000, Direction::Rx, b"two".as_slice()),
+        ];
+        for (timestamp_ns, direction, data) in records {
+            let record = CaptureRecord { timestamp_ns, ..CaptureRecord::data("COM1", direction, data) };
+            writer.write_record(&record).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    fn options(speed: f64) -> ReplayOptions {
+        ReplayOptions { speed, port_filter: None, repeat: false, emit_to_app: true, output: None }
+    }
+
+    #[test]
+    fn test_replay_to_app_channel() {
+        let path = std::env::temp_dir().join(format!("replay_test_{}.jsonl", std::process::id()));
+        write_capture(&path);
+
+        let mut manager = ReplayManager::new();
+        for speed in [-1.0, f64::NAN, f64::INFINITY, 1e-300] {
+            assert!(manager.start(&path, options(speed), None).is_err());
+        }
+
+        let (tx, mut rx) = mpsc::channel(8);
+        let started = Instant::now();
+        manager.start(&path, options(2.0), Some(tx)).unwrap();
+        manager.join();
+        // 两条 RX 间隔 20 ms，两倍速约 10 ms
+        assert!(started.elapsed() >= Duration::from_millis(10));
+        assert_eq!(rx.blocking_recv().unwrap().data, b"one");
+        assert_eq!(rx.blocking_recv().unwrap().data, b"two");
+        assert!(rx.blocking_recv().is_none());
+        let status = manager.status();
+        assert!(!status.running);
+        assert_eq!((status.sent_records, status.total_records), (2, 2));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+//! SCPI 仪器事务层
+//!
+//! RS-232 台式仪器 (电源、万用表等) 的 SCPI 命令/查询：
+//! - 命令按配置的结束符发送，查询按结束符读取一行应答；`#<n><len>` 定长二进制块按长度读取
+//! - 每条命令 (或查询) 之后反复 `SYST:ERR?` 直到 `0,"No error"`，清空并返回仪器错误队列
+//! - 应答按类型解析：单个数值、逗号分隔的数值数组、字符串或二进制块
+//! - `*IDN?` 应答解析为厂商/型号/序列号/固件版本
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use std::time::{Duration, Instant};
+
+use super::session_link::SerialLink;
+
+/// 错误队列单次最多读取条数，防止仪器不支持 `SYST:ERR?` 时死循环
+const MAX_DRAINED_ERRORS: usize = 32;
+
+/// 事务参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScpiOptions {
+    /// 发送结束符
+    pub write_terminator: String,
+    /// 应答结束符 (单字节)
+    pub read_terminator: String,
+    /// 单次应答超时
+    pub timeout_ms: u64,
+    /// 每条命令后读取 `SYST:ERR?`
+    pub check_errors: bool,
+}
+
+impl Default for ScpiOptions {
+    fn default() -> Self {
+        Self {
+            write_terminator: "\n".to_string(),
+            read_terminator: "\n".to_string(),
+            timeout_ms: 2000,
+            check_errors: true,
+        }
+    }
+}
+
+/// `*IDN?` 应答
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScpiIdentity {
+    pub manufacturer: String,
+    pub model: String,
+    pub serial: String,
+    pub firmware: String,
+    pub raw: String,
+}
+
+impl ScpiIdentity {
+    pub fn parse(raw: &str) -> Self {
+        let mut fields = raw.splitn(4, ',').map(|f| f.trim().to_string());
+        let mut next = || fields.next().unwrap_or_default();
+        Self {
+            manufacturer: next(),
+            model: next(),
+            serial: next(),
+            firmware: next(),
+            raw: raw.to_string(),
+        }
+    }
+}
+
+/// 错误队列中的一项，如 `-222,"Data out of range"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScpiError {
+    pub code: i32,
+    pub message: String,
+}
+
+impl ScpiError {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let (code, message) = raw.split_once(',').unwrap_or((raw, ""));
+        let code = code.trim().parse().map_err(|_| anyhow!("Invalid SYST:ERR? response: {:?}", raw))?;
+        Ok(Self { code, message: message.trim().trim_matches('"').to_string() })
+    }
+}
+
+/// 类型化的应答
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ScpiValue {
+    Number(f64),
+    Numbers(Vec<f64>),
+    Text(String),
+    Block(Vec<u8>),
+}
+
+impl ScpiValue {
+    /// 解析文本应答：全部逗号分隔项都是数值时为数值 (数组)，否则为去掉引号的字符串
+    pub fn parse(text: &str) -> Self {
+        let numbers: Option<Vec<f64>> = text.split(',').map(|item| item.trim().parse::<f64>().ok()).collect();
+        match numbers {
+            Some(mut numbers) if numbers.len() == 1 => Self::Number(numbers.remove(0)),
+            Some(numbers) => Self::Numbers(numbers),
+            None => {
+                let text = text.trim();
+                let unquoted = text
+                    .strip_prefix('"')
+                    .and_then(|t| t.strip_suffix('"'))
+                    .unwrap_or(text);
+                Self::Text(unquoted.to_string())
+            }
+        }
+    }
+}
+
+/// 一次事务的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ScpiResponse {
+    pub command: String,
+    /// 查询的应答，命令为空
+    pub value: Option<ScpiValue>,
+    /// 应答原文 (二进制块为空)
+    pub raw: Option<String>,
+    /// 事务后从错误队列读出的错误
+    pub errors: Vec<ScpiError>,
+    pub elapsed_ms: u64,
+}
+
+/// 是否为查询：程序头 (第一个空白之前) 以 `?` 结尾；`;` 连接的多条中任意一条为查询即可
+pub fn is_query(command: &str) -> bool {
+    command
+        .split(';')
+        .any(|part| part.split_whitespace().next().is_some_and(|header| header.ends_with('?')))
+}
+
+/// SCPI 仪器
+pub struct ScpiInstrument<L: SerialLink> {
+    link: L,
+    options: ScpiOptions,
+    buffer: Vec<u8>,
+}
+
+impl<L: SerialLink> ScpiInstrument<L> {
+    pub fn new(link: L, options: ScpiOptions) -> Self {
+        Self { link, options, buffer: Vec::new() }
+    }
+
+    pub fn into_inner(self) -> L {
+        self.link
+    }
+
+    fn terminator(&self) -> u8 {
+        self.options.read_terminator.bytes().next().unwrap_or(b'\n')
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.options.timeout_ms)
+    }
+
+    fn write_line(&mut self, command: &str) -> Result<()> {
+        let command = command.trim_end_matches(['\r', '\n']);
+        self.link.write_all(format!("{}{}", command, self.options.write_terminator).as_bytes())
+    }
+
+    /// 缓冲区至少有 `n` 字节，超时返回错误
+    fn fill(&mut self, n: usize, deadline: Instant) -> Result<()> {
+        let mut chunk = [0u8; 256];
+        while self.buffer.len() < n {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                bail!("Timeout waiting for instrument response");
+            }
+            let read = self.link.read(&mut chunk, remaining)?;
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+        Ok(())
+    }
+
+    /// 读取到结束符为止 (不含结束符与其前的 `\r`)
+    fn read_until_terminator(&mut self, deadline: Instant) -> Result<Vec<u8>> {
+        let terminator = self.terminator();
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&b| b == terminator) {
+                let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+                line.pop();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                return Ok(line);
+            }
+            let n = self.buffer.len() + 1;
+            self.fill(n, deadline)?;
+        }
+    }
+
+    /// 读取一条应答，`#` 开头时按 IEEE 488.2 二进制块读取
+    fn read_response(&mut self) -> Result<(ScpiValue, Option<String>)> {
+        let deadline = Instant::now() + self.timeout();
+        self.fill(1, deadline)?;
+        // 跳过上条应答残留的空行
+        while matches!(self.buffer.first(), Some(b'\r' | b'\n')) {
+            self.buffer.remove(0);
+            self.fill(1, deadline)?;
+        }
+        if self.buffer[0] != b'#' {
+            let line = String::from_utf8_lossy(&self.read_until_terminator(deadline)?).trim().to_string();
+            return Ok((ScpiValue::parse(&line), Some(line)));
+        }
+
+        self.fill(2, deadline)?;
+        let digits = (self.buffer[1] as char)
+            .to_digit(10)
+            .ok_or_else(|| anyhow!("Invalid binary block header"))? as usize;
+        if digits == 0 {
+            // 不定长块：读到结束符
+            self.buffer.drain(..2);
+            return Ok((ScpiValue::Block(self.read_until_terminator(deadline)?), None));
+        }
+        self.fill(2 + digits, deadline)?;
+        let len: usize = std::str::from_utf8(&self.buffer[2..2 + digits])?
+            .parse()
+            .map_err(|_| anyhow!("Invalid binary block length"))?;
+        self.fill(2 + digits + len, deadline)?;
+        let data: Vec<u8> = self.buffer.drain(..2 + digits + len).skip(2 + digits).collect();
+        // 块后的结束符
+        let terminator = self.terminator();
+        if self.fill(1, Instant::now() + Duration::from_millis(50)).is_ok() && self.buffer[0] == terminator {
+            self.buffer.remove(0);
+        }
+        Ok((ScpiValue::Block(data), None))
+    }
+
+    /// 读出错误队列直到 `0,"No error"`
+    pub fn drain_errors(&mut self) -> Result<Vec<ScpiError>> {
+        let mut errors = Vec::new();
+        for _ in 0..MAX_DRAINED_ERRORS {
+            self.write_line("SYST:ERR?")?;
+            let (_, raw) = self.read_response()?;
+            let error = ScpiError::parse(raw.as_deref().unwrap_or_default())?;
+            if error.code == 0 {
+                break;
+            }
+            errors.push(error);
+        }
+        Ok(errors)
+    }
+
+    /// 发送命令或查询 (按 `?` 判断)，随后按配置读取错误队列
+    pub fn send(&mut self, command: &str) -> Result<ScpiResponse> {
+        let command = command.trim().to_string();
+        let started = Instant::now();
+        self.link.clear_input();
+        self.buffer.clear();
+        self.write_line(&command)?;
+        let (value, raw) = if is_query(&command) {
+            let (value, raw) = self.read_response()?;
+            (Some(value), raw)
+        } else {
+            (None, None)
+        };
+        let errors = if self.options.check_errors { self.drain_errors()? } else { Vec::new() };
+        Ok(ScpiResponse { command, value, raw, errors, elapsed_ms: started.elapsed().as_millis() as u64 })
+    }
+
+    /// `*IDN?`
+    pub fn identify(&mut self) -> Result<ScpiIdentity> {
+        self.link.clear_input();
+        self.buffer.clear();
+        self.write_line("*IDN?")?;
+        let (_, raw) = self.read_response()?;
+        let raw = raw.ok_or_else(|| anyhow!("Unexpected binary response to *IDN?"))?;
+        Ok(ScpiIdentity::parse(&raw))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(ScpiValue::parse("+1.23450000E+00"), ScpiValue::Number(1.2345));
+        assert_eq!(ScpiValue::parse("1.0,2.5,-3"), ScpiValue::Numbers(vec![1.0, 2.5, -3.0]));
+        assert_eq!(ScpiValue::parse("\"DC\""), ScpiValue::Text("DC".to_string()));
+        assert_eq!(ScpiValue::parse("VOLT,CURR"), ScpiValue::Text("VOLT,CURR".to_string()));
+        assert_eq!(ScpiError::parse("-222,\"Data out of range\"").unwrap().code, -222);
+        assert!(is_query("MEAS:VOLT:DC?"));
+        assert!(is_query("VOLT 5;:MEAS:CURR? CH1"));
+        assert!(!is_query("VOLT 5"));
+
+        let idn = ScpiIdentity::parse("Keysight Technologies,E36312A,MY12345678,2.1.0-1.0.4-1.12");
+        assert_eq!((idn.model.as_str(), idn.firmware.as_str()), ("E36312A", "2.1.0-1.0.4-1.12"));
+    }
+
+    #[test]
+    fn test_query_command_and_error_drain() {
+        let (mut device, link) = pipe_pair();
+        let device = std::thread::spawn(move || {
+            let mut errors = vec!["-222,\"Data out of range\""];
+            let mut buf = [0u8; 64];
+            let mut line = Vec::new();
+            loop {
+                let n = device.read(&mut buf, Duration::from_millis(300)).unwrap();
+                if n == 0 {
+                    break;
+                }
+                for &b in &buf[..n] {
+                    if b != b'\n' {
+                        line.push(b);
+                        continue;
+                    }
+                    let reply: Vec<u8> = match std::mem::take(&mut line).as_slice() {
+                        b"MEAS:VOLT?" => b"+5.0012E+00\r\n".to_vec(),
+                        b"TRAC:DATA?" => b"#14\x00\x01\x0A\xFF\n".to_vec(),
+                        b"SYST:ERR?" => format!("{}\n", errors.pop().unwrap_or("+0,\"No error\"")).into_bytes(),
+                        _ => continue,
+                    };
+                    device.write_all(&reply).unwrap();
+                }
+            }
+        });
+
+        let mut instrument = ScpiInstrument::new(link, ScpiOptions::default());
+        let measured = instrument.send("MEAS:VOLT?").unwrap();
+        assert_eq!(measured.value, Some(ScpiValue::Number(5.0012)));
+        assert_eq!(measured.errors, vec![ScpiError { code: -222, message: "Data out of range".to_string() }]);
+
+        let set = instrument.send("VOLT 5").unwrap();
+        assert!(set.value.is_none() && set.errors.is_empty());
+
+        let trace = instrument.send("TRAC:DATA?").unwrap();
+        assert_eq!(trace.value, Some(ScpiValue::Block(vec![0x00, 0x01, 0x0A, 0xFF])));
+        drop(instrument);
+        device.join().unwrap();
+    }
+}
@@ -6,14 +6,38 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 use log::{info, error};
 use std::io::{Read, Write};
+use std::path::Path;
+
+use super::capture::{self, CaptureFormat, CaptureRecord, CaptureSink, Direction};
+use super::protocol_decoder::DecodedMessage;
+use super::session_link::SessionLink;
+
+type SharedCapture = Arc<Mutex<Option<Box<dyn CaptureSink>>>>;
+type RxTaps = Arc<Mutex<Vec<std::sync::mpsc::Sender<Vec<u8>>>>>;
+
+/// A batch of received bytes sent to the UI data channel
+#[derive(Debug, Clone)]
+pub struct RxChunk {
+    pub data: Vec<u8>,
+    /// Arrival time of the first byte in the batch (Unix ns)
+    pub received_ns: u64,
+}
 
 pub struct SerialManager {
     port: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
-    tx: Option<mpsc::Sender<Vec<u8>>>,
+    tx: Option<mpsc::Sender<RxChunk>>,
     should_run: Arc<AtomicBool>,
     // Port sharing fields
     virtual_port: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
     sharing_active: Arc<AtomicBool>,
+    // Capture (timestamped RX/TX records)
+    port_name: Arc<Mutex<String>>,
+    capture: SharedCapture,
+    // Modem control lines (serialport cannot read back output levels)
+    dtr: bool,
+    rts: bool,
+    // RX taps for protocol clients running on this session (see SessionLink)
+    rx_taps: RxTaps,
 }
 
 impl SerialManager {
@@ -24,6 +48,11 @@ impl SerialManager {
             should_run: Arc::new(AtomicBool::new(false)),
             virtual_port: Arc::new(Mutex::new(None)),
             sharing_active: Arc::new(AtomicBool::new(false)),
+            port_name: Arc::new(Mutex::new(String::new())),
+            capture: Arc::new(Mutex::new(None)),
+            dtr: true,
+            rts: true,
+            rx_taps: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -51,16 +80,27 @@ impl SerialManager {
         self.should_run.store(true, Ordering::SeqCst);
         let should_run = self.should_run.clone();
         let virtual_port_handle = self.virtual_port.clone();
+        let capture_handle = self.capture.clone();
+        let capture_port = port_name.to_string();
+        let rx_taps = self.rx_taps.clone();
+        *self.port_name.lock().unwrap() = port_name.to_string();
 
         if let Some(tx) = self.tx.clone() {
             std::thread::spawn(move || {
                 let mut buf = [0u8; 4096];
                 let mut rx_buffer: Vec<u8> = Vec::with_capacity(4096);
+                let mut first_byte_ns: Option<u64> = None;
                 
                 while should_run.load(Ordering::SeqCst) {
                     match port_clone.read(&mut buf) {
                         Ok(n) if n > 0 => {
+                            first_byte_ns.get_or_insert_with(capture::now_ns);
                             rx_buffer.extend_from_slice(&buf[0..n]);
+                            record(&capture_handle, &capture_port, Direction::Rx, &buf[0..n]);
+                            // Forward immediately to protocol clients (no batching: they need timing)
+                            if let Ok(mut taps) = rx_taps.lock() {
+                                taps.retain(|tap| tap.send(buf[0..n].to_vec()).is_ok());
+                            }
                             
                             // Prevent buffer from growing too large (latency/memory safeguard)
                             if rx_buffer.len() >= 4096 {
@@ -68,7 +108,8 @@ impl SerialManager {
                                 rx_buffer.clear();
 
                                 // Send to UI
-                                if tx.blocking_send(data.clone()).is_err() {
+                                let received_ns = first_byte_ns.take().unwrap_or_else(capture::now_ns);
+                                if tx.blocking_send(RxChunk { data: data.clone(), received_ns }).is_err() {
                                     break;
                                 }
 
@@ -89,7 +130,8 @@ impl SerialManager {
                                 rx_buffer.clear();
 
                                 // Send to UI
-                                if tx.blocking_send(data.clone()).is_err() {
+                                let received_ns = first_byte_ns.take().unwrap_or_else(capture::now_ns);
+                                if tx.blocking_send(RxChunk { data: data.clone(), received_ns }).is_err() {
                                     break;
                                 }
 
@@ -112,18 +154,29 @@ impl SerialManager {
             });
         }
 
+        // Most drivers assert DTR/RTS on open
+        self.dtr = true;
+        self.rts = true;
+
         let mut guard = self.port.lock().unwrap();
         *guard = Some(port);
         info!("Opened serial port: {} at {}", port_name, baud_rate);
         Ok(())
     }
 
-    pub fn set_sender(&mut self, tx: mpsc::Sender<Vec<u8>>) {
+    pub fn set_sender(&mut self, tx: mpsc::Sender<RxChunk>) {
         self.tx = Some(tx);
     }
 
+    /// Clone of the UI data channel, for sources other than the read thread (e.g. replay)
+    pub fn sender(&self) -> Option<mpsc::Sender<RxChunk>> {
+        self.tx.clone()
+    }
+
     pub fn close(&mut self) -> Result<()> {
         self.should_run.store(false, Ordering::SeqCst);
+        // Disconnect protocol clients; their reads fail with "Port closed"
+        self.rx_taps.lock().unwrap().clear();
         // Do NOT stop sharing on close. Doing so breaks the "Persistent Sharing" feature
         // where the user expects the bridge to remain active even if the physical connection drops or is toggled.
         // let _ = self.stop_sharing(); 
@@ -141,12 +194,61 @@ impl SerialManager {
         if let Some(port) = guard.as_mut() {
             port.write_all(data).map_err(|e| anyhow!("Write error: {}", e))?;
             port.flush().map_err(|e| anyhow!("Flush error: {}", e))?;
+            let port_name = self.port_name.lock().unwrap().clone();
+            record(&self.capture, &port_name, Direction::Tx, data);
             Ok(())
         } else {
             Err(anyhow!("Port not open"))
         }
     }
 
+    /// Create a request/response link on the open port.
+    /// RX data keeps flowing to the UI; the link receives its own copy.
+    pub fn link(&self) -> Result<SessionLink> {
+        if self.port.lock().unwrap().is_none() {
+            return Err(anyhow!("Port not open"));
+        }
+        let (tap_tx, tap_rx) = std::sync::mpsc::channel();
+        self.rx_taps.lock().unwrap().push(tap_tx);
+        let port_name = self.port_name.lock().unwrap().clone();
+        Ok(SessionLink::new(self.port.clone(), tap_rx, port_name, self.capture.clone()))
+    }
+
+    /// Baud rate of the open port
+    pub fn baud_rate(&self) -> Result<u32> {
+        let guard = self.port.lock().unwrap();
+        let port = guard.as_ref().ok_or_else(|| anyhow!("Port not open"))?;
+        port.baud_rate().map_err(|e| anyhow!("Get baud rate error: {}", e))
+    }
+
+    /// Set the DTR line level
+    pub fn set_dtr(&mut self, level: bool) -> Result<()> {
+        let mut guard = self.port.lock().unwrap();
+        let port = guard.as_mut().ok_or_else(|| anyhow!("Port not open"))?;
+        port.write_data_terminal_ready(level).map_err(|e| anyhow!("Set DTR error: {}", e))?;
+        self.dtr = level;
+        Ok(())
+    }
+
+    /// Set the RTS line level
+    pub fn set_rts(&mut self, level: bool) -> Result<()> {
+        let mut guard = self.port.lock().unwrap();
+        let port = guard.as_mut().ok_or_else(|| anyhow!("Port not open"))?;
+        port.write_request_to_send(level).map_err(|e| anyhow!("Set RTS error: {}", e))?;
+        self.rts = level;
+        Ok(())
+    }
+
+    /// Last DTR level set through this manager
+    pub fn dtr(&self) -> bool {
+        self.dtr
+    }
+
+    /// Last RTS level set through this manager
+    pub fn rts(&self) -> bool {
+        self.rts
+    }
+
     /// Start sharing: attach virtual port and spawn reverse bridge thread
     pub fn start_sharing(&mut self, virtual_port_name: &str) -> Result<()> {
         let v_port = serialport::new(virtual_port_name, 115200)
@@ -170,6 +272,8 @@ impl SerialManager {
         // Spawn V->P thread with its own port handle (no mutex needed for read)
         let physical_port_handle = self.port.clone();
         let sharing_flag = self.sharing_active.clone();
+        let capture_handle = self.capture.clone();
+        let port_name_handle = self.port_name.clone();
 
         std::thread::spawn(move || {
             let mut buf = [0u8; 1024]; // Smaller buffer for lower latency
@@ -181,6 +285,9 @@ impl SerialManager {
                             if let Some(p_port) = p_guard.as_mut() {
                                 let _ = p_port.write_all(&buf[0..n]);
                                 let _ = p_port.flush();
+                                // Traffic injected by the sharing peer is TX from the device's point of view
+                                let port_name = port_name_handle.lock().unwrap().clone();
+                                record(&capture_handle, &port_name, Direction::Tx, &buf[0..n]);
                             }
                         }
                     }
@@ -205,4 +312,63 @@ impl SerialManager {
         info!("Sharing stopped");
         Ok(())
     }
+
+    /// Start recording timestamped RX/TX records to a capture file.
+    /// With `strip_escapes`, ANSI/VT escape sequences are removed from the recorded data.
+    pub fn start_capture(&mut self, path: &Path, format: CaptureFormat, strip_escapes: bool) -> Result<()> {
+        let mut sink = capture::create_sink(path, format)?;
+        if strip_escapes {
+            sink = Box::new(capture::StripEscapesSink::new(sink));
+        }
+        let mut guard = self.capture.lock().unwrap();
+        if let Some(mut old) = guard.take() {
+            let _ = old.flush();
+        }
+        *guard = Some(sink);
+        info!("Capture started: {:?} ({:?})", path, format);
+        Ok(())
+    }
+
+    /// Stop recording and flush the capture file
+    pub fn stop_capture(&mut self) -> Result<()> {
+        let mut guard = self.capture.lock().unwrap();
+        if let Some(mut sink) = guard.take() {
+            sink.flush()?;
+            info!("Capture stopped");
+        }
+        Ok(())
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.lock().unwrap().is_some()
+    }
+
+    /// Insert a user marker into the active capture
+    pub fn add_capture_marker(&self, text: &str) -> Result<()> {
+        let port_name = self.port_name.lock().unwrap().clone();
+        let mut guard = self.capture.lock().unwrap();
+        let sink = guard.as_mut().ok_or_else(|| anyhow!("Capture not active"))?;
+        sink.write_record(&CaptureRecord::marker(&port_name, text))
+    }
+
+    /// Store a protocol decode result in the active capture (no-op when not capturing)
+    pub fn record_decoded(&self, message: &DecodedMessage) -> Result<()> {
+        let port_name = self.port_name.lock().unwrap().clone();
+        let mut guard = self.capture.lock().unwrap();
+        match guard.as_mut() {
+            Some(sink) => sink.write_record(&CaptureRecord::decoded(&port_name, message)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Append a data record to the capture sink (if any). Errors are logged, never propagated to the data path.
+fn record(capture: &SharedCapture, port_name: &str, direction: Direction, data: &[u8]) {
+    if let Ok(mut guard) = capture.lock() {
+        if let Some(sink) = guard.as_mut() {
+            if let Err(e) = sink.write_record(&CaptureRecord::data(port_name, direction, data)) {
+                error!("Capture write error: {}", e);
+            }
+        }
+    }
 }
@@ -0,0 +1,237 @@
+//! 会话链路
+//!
+//! 协议客户端 (Modbus、文件传输、Bootloader 等) 需要在已打开的会话上"发送并等待应答"。
+//! `SessionLink` 复用 `SerialManager` 的端口句柄写入，并通过读线程的数据分流 (tap)
+//! 获得 RX 数据，因此协议交互期间终端监视与抓包照常进行，无需释放端口。
+
+use anyhow::{Result, anyhow};
+use serialport::{Parity, SerialPort};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use super::capture::{CaptureRecord, CaptureSink, Direction};
+
+/// 协议层使用的串口抽象
+pub trait SerialLink: Send {
+    /// 写入全部数据
+    fn write_all(&mut self, data: &[u8]) -> Result<()>;
+    /// 读取数据，超时返回 0
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize>;
+    /// 丢弃已接收但尚未读取的数据
+    fn clear_input(&mut self);
+    fn baud_rate(&self) -> Result<u32>;
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<()>;
+    fn parity(&self) -> Result<Parity>;
+    fn set_parity(&mut self, parity: Parity) -> Result<()>;
+    fn set_dtr(&mut self, level: bool) -> Result<()>;
+    fn set_rts(&mut self, level: bool) -> Result<()>;
+
+    /// 精确读取 `buf.len()` 字节，总超时为 `timeout`
+    fn read_exact(&mut self, buf: &mut [u8], timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut filled = 0;
+        while filled < buf.len() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(anyhow!("Timeout: received {} of {} bytes", filled, buf.len()));
+            }
+            filled += self.read(&mut buf[filled..], remaining)?;
+        }
+        Ok(())
+    }
+
+    /// 读取单个字节，超时返回 None
+    fn read_byte(&mut self, timeout: Duration) -> Result<Option<u8>> {
+        let mut b = [0u8; 1];
+        match self.read(&mut b, timeout)? {
+            0 => Ok(None),
+            _ => Ok(Some(b[0])),
+        }
+    }
+}
+
+/// 基于 `SerialManager` 活动会话的链路
+pub struct SessionLink {
+    port: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
+    rx: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+    port_name: String,
+    capture: Arc<Mutex<Option<Box<dyn CaptureSink>>>>,
+}
+
+impl SessionLink {
+    pub(crate) fn new(
+        port: Arc<Mutex<Option<Box<dyn SerialPort>>>>,
+        rx: Receiver<Vec<u8>>,
+        port_name: String,
+        capture: Arc<Mutex<Option<Box<dyn CaptureSink>>>>,
+    ) -> Self {
+        Self {
+            port,
+            rx,
+            pending: VecDeque::new(),
+            port_name,
+            capture,
+        }
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    /// 在会话抓包中插入标记 (未抓包时忽略)
+    pub fn mark(&self, text: &str) {
+        if let Ok(mut guard) = self.capture.lock() {
+            if let Some(sink) = guard.as_mut() {
+                let _ = sink.write_record(&CaptureRecord::marker(&self.port_name, text));
+            }
+        }
+    }
+
+    fn with_port<T>(&self, f: impl FnOnce(&mut Box<dyn SerialPort>) -> Result<T>) -> Result<T> {
+        let mut guard = self.port.lock().map_err(|_| anyhow!("Port lock poisoned"))?;
+        let port = guard.as_mut().ok_or_else(|| anyhow!("Port not open"))?;
+        f(port)
+    }
+}
+
+impl SerialLink for SessionLink {
+    fn write_all(&mut self, data: &[u8]) -> Result<()> {
+        self.with_port(|port| {
+            port.write_all(data).map_err(|e| anyhow!("Write error: {}", e))?;
+            port.flush().map_err(|e| anyhow!("Flush error: {}", e))
+        })?;
+        if let Ok(mut guard) = self.capture.lock() {
+            if let Some(sink) = guard.as_mut() {
+                let _ = sink.write_record(&CaptureRecord::data(&self.port_name, Direction::Tx, data));
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.pending.is_empty() {
+            match self.rx.recv_timeout(timeout) {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(RecvTimeoutError::Timeout) => return Ok(0),
+                Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("Port closed")),
+            }
+        }
+        // 顺带取走已到达的其它数据块
+        while let Ok(chunk) = self.rx.try_recv() {
+            self.pending.extend(chunk);
+        }
+        let n = buf.len().min(self.pending.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+
+    fn clear_input(&mut self) {
+        self.pending.clear();
+        while self.rx.try_recv().is_ok() {}
+    }
+
+    fn baud_rate(&self) -> Result<u32> {
+        self.with_port(|port| port.baud_rate().map_err(|e| anyhow!("Get baud rate error: {}", e)))
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> Result<()> {
+        self.with_port(|port| port.set_baud_rate(baud_rate).map_err(|e| anyhow!("Set baud rate error: {}", e)))
+    }
+
+    fn parity(&self) -> Result<Parity> {
+        self.with_port(|port| port.parity().map_err(|e| anyhow!("Get parity error: {}", e)))
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> Result<()> {
+        self.with_port(|port| port.set_parity(parity).map_err(|e| anyhow!("Set parity error: {}", e)))
+    }
+
+    fn set_dtr(&mut self, level: bool) -> Result<()> {
+        self.with_port(|port| port.write_data_terminal_ready(level).map_err(|e| anyhow!("Set DTR error: {}", e)))
+    }
+
+    fn set_rts(&mut self, level: bool) -> Result<()> {
+        self.with_port(|port| port.write_request_to_send(level).map_err(|e| anyhow!("Set RTS error: {}", e)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) use pipe::pipe_pair;
+
+/// 测试用：进程内互连的一对链路
+#[cfg(test)]
+mod pipe {
+    use super::*;
+    use std::sync::mpsc::{channel, Sender};
+
+    pub(crate) struct PipeLink {
+        tx: Sender<Vec<u8>>,
+        rx: Receiver<Vec<u8>>,
+        pending: VecDeque<u8>,
+    }
+
+    pub(crate) fn pipe_pair() -> (PipeLink, PipeLink) {
+        let (a_tx, b_rx) = channel();
+        let (b_tx, a_rx) = channel();
+        (
+            PipeLink { tx: a_tx, rx: a_rx, pending: VecDeque::new() },
+            PipeLink { tx: b_tx, rx: b_rx, pending: VecDeque::new() },
+        )
+    }
+
+    impl SerialLink for PipeLink {
+        fn write_all(&mut self, data: &[u8]) -> Result<()> {
+            let _ = self.tx.send(data.to_vec());
+            Ok(())
+        }
+
+        fn read(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+            if self.pending.is_empty() {
+                match self.rx.recv_timeout(timeout) {
+                    Ok(chunk) => self.pending.extend(chunk),
+                    Err(_) => return Ok(0),
+                }
+            }
+            let n = buf.len().min(self.pending.len());
+            for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+                *dst = src;
+            }
+            Ok(n)
+        }
+
+        fn clear_input(&mut self) {}
+
+        fn baud_rate(&self) -> Result<u32> {
+            Ok(115200)
+        }
+
+        fn set_baud_rate(&mut self, _baud_rate: u32) -> Result<()> {
+            Ok(())
+        }
+
+        fn parity(&self) -> Result<Parity> {
+            Ok(Parity::None)
+        }
+
+        fn set_parity(&mut self, _parity: Parity) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_dtr(&mut self, _level: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn set_rts(&mut self, _level: bool) -> Result<()> {
+            Ok(())
+        }
+    }
+}
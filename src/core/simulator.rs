@@ -0,0 +1,465 @@
+//! 设备模拟器 (自动应答)
+//!
+//! 通过 YAML 规则文件模拟一个串口设备 (AT 模组、Modbus 从站等)。
+//! 规则字段沿用指令文件 (`serial_commands.yaml`) 的命名风格：
+//!
+//! ```yaml
+//! variables:
+//!   signal: 23
+//!   echo: 1
+//! rules:
+//!   - name: CSQ
+//!     pattern: 'AT\+CSQ\r'
+//!     isRegex: true
+//!     response: "+CSQ: {{signal}},99\r\n\r\nOK\r\n"
+//!     delayMs: 20
+//!   - name: Echo
+//!     pattern: 'ATE(\d)\r'
+//!     isRegex: true
+//!     set: { echo: "{{1}}" }
+//!     response: "OK\r\n"
+//!   - name: Modbus read
+//!     pattern: "01 03 00 00 00 01 84 0A"
+//!     isHex: true
+//!     response: "01 03 02 00 {{counter}} 00 00"
+//!     increment: [counter]
+//! ```
+//!
+//! 模板支持 `{{变量名}}`、正则捕获组 `{{1}}` 以及内置的 `{{timestamp}}`；
+//! 十六进制应答中的数值变量按大端字节输出 (取能容纳该值的最少字节数)。
+
+use anyhow::{Result, anyhow, Context};
+use regex::bytes::Regex;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use log::{info, error};
+
+use super::endpoint::Endpoint;
+use super::hex::parse_hex;
+
+/// 接收缓冲上限，超出后丢弃旧数据
+const MAX_PENDING: usize = 4096;
+
+/// 模拟器规则文件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimulatorProfile {
+    #[serde(default)]
+    pub variables: HashMap<String, serde_yaml::Value>,
+    #[serde(default)]
+    pub rules: Vec<SimulatorRule>,
+}
+
+/// 单条应答规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatorRule {
+    pub name: String,
+    /// 请求匹配内容
+    pub pattern: String,
+    /// pattern/response 为十六进制
+    #[serde(default)]
+    pub is_hex: bool,
+    /// pattern 为正则表达式 (文本模式下有效)
+    #[serde(default)]
+    pub is_regex: bool,
+    /// 应答模板，为空则不应答
+    #[serde(default)]
+    pub response: Option<String>,
+    /// 应答前延时
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 命中后设置的变量 (值为模板)
+    #[serde(default)]
+    pub set: HashMap<String, String>,
+    /// 命中后自增的数值变量
+    #[serde(default)]
+    pub increment: Vec<String>,
+}
+
+impl SimulatorProfile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read simulator profile {:?}", path))?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        serde_yaml::from_str(content).map_err(|e| anyhow!("Invalid simulator profile: {}", e))
+    }
+}
+
+/// 一次应答
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorReply {
+    pub rule: String,
+    pub delay: Duration,
+    pub data: Vec<u8>,
+}
+
+struct CompiledRule {
+    rule: SimulatorRule,
+    regex: Regex,
+}
+
+/// 模拟器核心：喂入请求字节，产出应答 (不涉及 IO，便于测试)
+pub struct SimulatorEngine {
+    rules: Vec<CompiledRule>,
+    variables: HashMap<String, String>,
+    pending: Vec<u8>,
+}
+
+impl SimulatorEngine {
+    pub fn new(profile: &SimulatorProfile) -> Result<Self> {
+        let rules = profile.rules.iter()
+            .map(|rule| {
+                let source = if rule.is_hex {
+                    // 十六进制请求按字节精确匹配 (关闭 Unicode 以便 \xNN 表示任意字节)
+                    let bytes: String = parse_hex(&rule.pattern)?
+                        .iter()
+                        .map(|b| format!(r"\x{:02X}", b))
+                        .collect();
+                    format!("(?-u){}", bytes)
+                } else if rule.is_regex {
+                    rule.pattern.clone()
+                } else {
+                    regex::escape(&rule.pattern)
+                };
+                let regex = Regex::new(&source)
+                    .with_context(|| format!("Rule '{}': invalid pattern", rule.name))?;
+                Ok(CompiledRule { rule: rule.clone(), regex })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let variables = profile.variables.iter()
+            .map(|(k, v)| (k.clone(), yaml_to_string(v)))
+            .collect();
+
+        Ok(Self { rules, variables, pending: Vec::new() })
+    }
+
+    pub fn variables(&self) -> &HashMap<String, String> {
+        &self.variables
+    }
+
+    /// 喂入收到的数据，返回按顺序需要发送的应答
+    pub fn feed(&mut self, data: &[u8]) -> Vec<SimulatorReply> {
+        self.pending.extend_from_slice(data);
+        let mut replies = Vec::new();
+
+        // 反复匹配，取缓冲中最早出现的请求
+        loop {
+            let earliest = self.rules.iter()
+                .enumerate()
+                .filter_map(|(idx, c)| c.regex.captures(&self.pending).map(|caps| (idx, caps)))
+                .min_by_key(|(_, caps)| caps.get(0).map(|m| m.start()).unwrap_or(usize::MAX));
+
+            let (idx, groups, end) = match earliest {
+                Some((idx, caps)) => {
+                    let groups: Vec<String> = caps.iter()
+                        .map(|m| m.map(|m| String::from_utf8_lossy(m.as_bytes()).to_string()).unwrap_or_default())
+                        .collect();
+                    let end = caps.get(0).map(|m| m.end()).unwrap_or(0);
+                    (idx, groups, end)
+                }
+                None => break,
+            };
+            if end == 0 {
+                // 空匹配无法推进缓冲，避免死循环
+                break;
+            }
+            self.pending.drain(..end);
+
+            if let Some(reply) = self.apply(idx, &groups) {
+                replies.push(reply);
+            }
+        }
+
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+        }
+        replies
+    }
+
+    fn apply(&mut self, idx: usize, groups: &[String]) -> Option<SimulatorReply> {
+        let rule = self.rules[idx].rule.clone();
+
+        // 先渲染应答，使其看到命中前的状态
+        let response = rule.response.as_ref().map(|tpl| self.render(tpl, groups, rule.is_hex));
+
+        for (name, tpl) in &rule.set {
+            let value = self.render(tpl, groups, false);
+            self.variables.insert(name.clone(), value);
+        }
+        for name in &rule.increment {
+            let next = self.variables.get(name)
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(0) + 1;
+            self.variables.insert(name.clone(), next.to_string());
+        }
+
+        let text = response?;
+        let data = if rule.is_hex {
+            match parse_hex(&text) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!("Simulator rule '{}': bad hex response: {}", rule.name, e);
+                    return None;
+                }
+            }
+        } else {
+            text.into_bytes()
+        };
+
+        Some(SimulatorReply {
+            rule: rule.name,
+            delay: Duration::from_millis(rule.delay_ms),
+            data,
+        })
+    }
+
+    /// 替换模板中的 `{{...}}` 占位符
+    ///
+    /// 十六进制模板中，非负整数变量按大端字节输出，位宽取能容纳该值的最少字节数
+    /// (0..=255 为单字节，256 为 `01 00`)。
+    fn render(&self, template: &str, groups: &[String], hex: bool) -> String {
+        let mut out = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}") {
+                Some(end) => {
+                    let key = after[..end].trim();
+                    let value = self.lookup(key, groups);
+                    match value.parse::<u64>() {
+                        Ok(n) if hex => out.push_str(&hex_be_bytes(n)),
+                        _ => out.push_str(&value),
+                    }
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    out.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn lookup(&self, key: &str, groups: &[String]) -> String {
+        if let Ok(idx) = key.parse::<usize>() {
+            return groups.get(idx).cloned().unwrap_or_default();
+        }
+        if key == "timestamp" {
+            return chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        }
+        self.variables.get(key).cloned().unwrap_or_default()
+    }
+}
+
+/// 以最少字节数输出大端十六进制 (至少一个字节)
+fn hex_be_bytes(n: u64) -> String {
+    let bytes = n.to_be_bytes();
+    let skip = bytes.iter().take_while(|&&b| b == 0).count().min(bytes.len() - 1);
+    bytes[skip..].iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(" ")
+}
+
+fn yaml_to_string(v: &serde_yaml::Value) -> String {
+    match v {
+        serde_yaml::Value::String(s) => s.clone(),
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        serde_yaml::Value::Number(n) => n.to_string(),
+        serde_yaml::Value::Null => String::new(),
+        other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+    }
+}
+
+/// 模拟器运行状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatorStatus {
+    pub running: bool,
+    pub device_path: Option<String>,
+    pub replies_sent: usize,
+    pub variables: HashMap<String, String>,
+}
+
+/// 模拟器管理器：将规则引擎挂到串口 / 虚拟串口对 / pty 上运行
+pub struct SimulatorManager {
+    /// 每次启动新建，旧线程退出时只清除自己的标志
+    running: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    replies_sent: Arc<AtomicUsize>,
+    device_path: Option<String>,
+    variables: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl Default for SimulatorManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatorManager {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            worker: None,
+            replies_sent: Arc::new(AtomicUsize::new(0)),
+            device_path: None,
+            variables: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn start(&mut self, profile_path: &Path, endpoint: &Endpoint) -> Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(anyhow!("Simulator already running"));
+        }
+        // 回收已自行退出的上一次线程
+        self.stop_worker();
+
+        let profile = SimulatorProfile::load(profile_path)?;
+        let mut engine = SimulatorEngine::new(&profile)?;
+        let mut opened = endpoint.open(Duration::from_millis(20))?;
+
+        self.device_path = opened.device_path.clone();
+        self.running = Arc::new(AtomicBool::new(true));
+        self.replies_sent = Arc::new(AtomicUsize::new(0));
+        self.variables = Arc::new(Mutex::new(engine.variables().clone()));
+
+        let running = self.running.clone();
+        let replies_sent = self.replies_sent.clone();
+        let variables = self.variables.clone();
+
+        info!("Simulator started: {:?} ({} rules) on {:?}", profile_path, profile.rules.len(), self.device_path);
+
+        self.worker = Some(std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while running.load(Ordering::SeqCst) {
+                match opened.port.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        for reply in engine.feed(&buf[..n]) {
+                            if !reply.delay.is_zero() {
+                                std::thread::sleep(reply.delay);
+                            }
+                            if let Err(e) = opened.port.write_all(&reply.data).and_then(|_| opened.port.flush()) {
+                                error!("Simulator write error: {}", e);
+                                running.store(false, Ordering::SeqCst);
+                                break;
+                            }
+                            replies_sent.fetch_add(1, Ordering::SeqCst);
+                        }
+                        *variables.lock().unwrap() = engine.variables().clone();
+                    }
+                    Ok(_) => {}
+                    Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        error!("Simulator read error: {}", e);
+                        break;
+                    }
+                }
+            }
+            running.store(false, Ordering::SeqCst);
+            info!("Simulator thread exited");
+        }));
+
+        Ok(())
+    }
+
+    /// 停止并等待线程退出 (释放端点)
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_worker();
+        self.device_path = None;
+        Ok(())
+    }
+
+    fn stop_worker(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    pub fn status(&self) -> SimulatorStatus {
+        SimulatorStatus {
+            running: self.running.load(Ordering::SeqCst),
+            device_path: self.device_path.clone(),
+            replies_sent: self.replies_sent.load(Ordering::SeqCst),
+            variables: self.variables.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILE: &str = r#"
+variables:
+  signal: 23
+  counter: 0
+rules:
+  - name: AT
+    pattern: "AT\r"
+    response: "OK\r\n"
+  - name: CSQ
+    pattern: 'AT\+CSQ\r'
+    isRegex: true
+    response: "+CSQ: {{signal}},99\r\nOK\r\n"
+  - name: SetSignal
+    pattern: 'AT\+SIG=(\d+)\r'
+    isRegex: true
+    set: { signal: "{{1}}" }
+    response: "OK\r\n"
+  - name: Poll
+    pattern: "01 03"
+    isHex: true
+    response: "01 83 {{counter}}"
+    increment: [counter]
+"#;
+
+    #[test]
+    fn test_text_rules_and_state() {
+        let profile = SimulatorProfile::parse(PROFILE).unwrap();
+        let mut engine = SimulatorEngine::new(&profile).unwrap();
+
+        let replies = engine.feed(b"AT+CSQ\r");
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].data, b"+CSQ: 23,99\r\nOK\r\n");
+
+        // 分块到达 + 变量更新
+        assert!(engine.feed(b"AT+SIG=").is_empty());
+        assert_eq!(engine.feed(b"7\r").len(), 1);
+        let replies = engine.feed(b"AT+CSQ\rAT\r");
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0].data, b"+CSQ: 7,99\r\nOK\r\n");
+        assert_eq!(replies[1].data, b"OK\r\n");
+    }
+
+    #[test]
+    fn test_hex_rule_with_counter() {
+        let profile = SimulatorProfile::parse(PROFILE).unwrap();
+        let mut engine = SimulatorEngine::new(&profile).unwrap();
+        assert_eq!(engine.feed(&[0x01, 0x03])[0].data, vec![0x01, 0x83, 0x00]);
+        assert_eq!(engine.feed(&[0x01, 0x03])[0].data, vec![0x01, 0x83, 0x01]);
+        assert_eq!(engine.variables()["counter"], "2");
+    }
+
+    #[test]
+    fn test_hex_render_wide_values() {
+        let profile = SimulatorProfile::parse(PROFILE).unwrap();
+        let mut engine = SimulatorEngine::new(&profile).unwrap();
+        engine.variables.insert("counter".to_string(), "255".to_string());
+        assert_eq!(engine.feed(&[0x01, 0x03])[0].data, vec![0x01, 0x83, 0xFF]);
+        assert_eq!(engine.feed(&[0x01, 0x03])[0].data, vec![0x01, 0x83, 0x01, 0x00]);
+        engine.variables.insert("counter".to_string(), "70000".to_string());
+        assert_eq!(engine.feed(&[0x01, 0x03])[0].data, vec![0x01, 0x83, 0x01, 0x11, 0x70]);
+    }
+}
@@ -0,0 +1,516 @@
+//! SLCAN (Lawicel) CAN 串口适配器
+//!
+//! CANable、USBtin 等适配器在虚拟串口上以 ASCII 命令收发 CAN 帧：
+//! - `Sn` 设置波特率，`O`/`L` 打开通道 (正常/只听)，`C` 关闭，`Z1` 开启时间戳
+//! - 帧格式 `tiiiLdd..`、`TiiiiiiiiLdd..`，远程帧 `r`/`R`，可带 4 位 hex 毫秒时间戳
+//! - 命令以 `\r` 结束，适配器以 `\r` 应答成功、`\x07` 应答失败
+//!
+//! 通道打开后由 `slcan` 解码器在 RX 流中解出 CAN 帧；`CanBridge` 可把帧桥接到 Linux vcan 接口。
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use log::{info, error};
+
+use super::capture::Direction;
+use super::file_transfer::TransferGuard;
+use super::protocol_decoder::{DecodedMessage, ProtocolDecoder};
+use super::session_link::{SerialLink, SessionLink};
+use super::socketcan::CanSocket;
+
+/// 命令应答超时
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(500);
+const BELL: u8 = 0x07;
+/// 单行最大长度 (最长的扩展帧加时间戳为 30 字节)，超出部分丢弃
+const MAX_LINE: usize = 64;
+pub const MAX_STANDARD_ID: u32 = 0x7FF;
+pub const MAX_EXTENDED_ID: u32 = 0x1FFF_FFFF;
+
+/// `S0`..`S8` 对应的标准波特率
+const BITRATES: [u32; 9] = [10_000, 20_000, 50_000, 100_000, 125_000, 250_000, 500_000, 800_000, 1_000_000];
+
+/// 一帧 CAN (经典 CAN，最多 8 字节)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CanFrame {
+    pub id: u32,
+    #[serde(default)]
+    pub extended: bool,
+    /// 远程帧 (RTR)，无数据
+    #[serde(default)]
+    pub remote: bool,
+    /// 远程帧的请求长度；数据帧可省略，按数据长度
+    #[serde(default)]
+    pub dlc: Option<u8>,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    /// 适配器时间戳 (毫秒，0..59999 循环)，`Z1` 开启后才有
+    #[serde(default)]
+    pub adapter_timestamp_ms: Option<u16>,
+}
+
+impl CanFrame {
+    pub fn len(&self) -> u8 {
+        self.dlc.unwrap_or(self.data.len() as u8)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let max_id = if self.extended { MAX_EXTENDED_ID } else { MAX_STANDARD_ID };
+        if self.id > max_id {
+            bail!("CAN id 0x{:X} out of range (max 0x{:X})", self.id, max_id);
+        }
+        if self.len() > 8 || self.data.len() > 8 {
+            bail!("Classic CAN frames carry at most 8 bytes");
+        }
+        if self.remote && !self.data.is_empty() {
+            bail!("Remote frames carry no data");
+        }
+        if !self.remote && self.len() as usize != self.data.len() {
+            bail!("DLC {} does not match {} data bytes", self.len(), self.data.len());
+        }
+        Ok(())
+    }
+
+    /// 解析一行 SLCAN 帧 (不含 `\r`)
+    pub fn parse(line: &str) -> Result<Self> {
+        let (extended, remote) = match line.chars().next() {
+            Some('t') => (false, false),
+            Some('T') => (true, false),
+            Some('r') => (false, true),
+            Some('R') => (true, true),
+            _ => bail!("Not an SLCAN frame: {:?}", line),
+        };
+        let id_len = if extended { 8 } else { 3 };
+        let hex = |s: &str| -> Result<u32> {
+            u32::from_str_radix(s, 16).map_err(|_| anyhow!("Invalid hex {:?} in {:?}", s, line))
+        };
+        let body = &line[1..];
+        if !body.is_ascii() || body.len() < id_len + 1 {
+            bail!("Truncated SLCAN frame: {:?}", line);
+        }
+        let id = hex(&body[..id_len])?;
+        let dlc = hex(&body[id_len..id_len + 1])? as u8;
+        if dlc > 8 {
+            bail!("Invalid DLC {} in {:?}", dlc, line);
+        }
+        let mut rest = &body[id_len + 1..];
+        let mut data = Vec::new();
+        if !remote {
+            let data_len = dlc as usize * 2;
+            if rest.len() < data_len {
+                bail!("Truncated SLCAN frame: {:?}", line);
+            }
+            for i in (0..data_len).step_by(2) {
+                data.push(hex(&rest[i..i + 2])? as u8);
+            }
+            rest = &rest[data_len..];
+        }
+        let adapter_timestamp_ms = match rest.len() {
+            0 => None,
+            4 => Some(hex(rest)? as u16),
+            _ => bail!("Unexpected trailing data in {:?}", line),
+        };
+        let frame = Self { id, extended, remote, dlc: Some(dlc), data, adapter_timestamp_ms };
+        frame.validate()?;
+        Ok(frame)
+    }
+
+    /// 编码为 SLCAN 命令 (含 `\r`)
+    pub fn to_slcan(&self) -> Result<String> {
+        self.validate()?;
+        let kind = match (self.extended, self.remote) {
+            (false, false) => 't',
+            (true, false) => 'T',
+            (false, true) => 'r',
+            (true, true) => 'R',
+        };
+        let id = if self.extended { format!("{:08X}", self.id) } else { format!("{:03X}", self.id) };
+        let data: String = self.data.iter().map(|b| format!("{:02X}", b)).collect();
+        Ok(format!("{}{}{}{}\r", kind, id, self.len(), data))
+    }
+
+    /// 单行摘要，格式近似 candump
+    pub fn summary(&self) -> String {
+        let id = if self.extended { format!("{:08X}", self.id) } else { format!("{:03X}", self.id) };
+        if self.remote {
+            format!("{} [{}] remote request", id, self.len())
+        } else {
+            let data: Vec<String> = self.data.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("{} [{}] {}", id, self.len(), data.join(" ")).trim_end().to_string()
+        }
+    }
+}
+
+/// 波特率对应的 `Sn` 命令
+pub fn bitrate_command(bitrate: u32) -> Result<String> {
+    BITRATES
+        .iter()
+        .position(|&b| b == bitrate)
+        .map(|n| format!("S{}", n))
+        .ok_or_else(|| anyhow!("Unsupported SLCAN bitrate {} (supported: {:?})", bitrate, BITRATES))
+}
+
+/// 通道参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlcanOptions {
+    /// CAN 波特率 (bit/s)
+    pub bitrate: u32,
+    /// 只听模式 (`L`)，不应答 ACK
+    #[serde(default)]
+    pub listen_only: bool,
+    /// 开启适配器时间戳 (`Z1`)
+    #[serde(default)]
+    pub timestamps: bool,
+}
+
+/// 发送一条命令并等待应答，返回应答文本 (不含 `\r`)；期间到达的 CAN 帧被忽略
+pub fn command(link: &mut impl SerialLink, cmd: &str) -> Result<String> {
+    link.write_all(format!("{}\r", cmd).as_bytes())?;
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    let mut line = Vec::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let Some(b) = link.read_byte(remaining)? else {
+            bail!("SLCAN adapter did not answer {:?}", cmd);
+        };
+        match b {
+            BELL => bail!("SLCAN adapter rejected {:?}", cmd),
+            b'\r' => {
+                let text = String::from_utf8_lossy(&line).to_string();
+                if matches!(text.chars().next(), Some('t' | 'T' | 'r' | 'R')) {
+                    line.clear();
+                    continue;
+                }
+                return Ok(text);
+            }
+            b if line.len() < MAX_LINE => line.push(b),
+            _ => {}
+        }
+    }
+}
+
+/// 打开通道：关闭 → 设置波特率 → 时间戳 → 打开；返回适配器版本 (若支持 `V`)
+pub fn open_channel(link: &mut impl SerialLink, options: &SlcanOptions) -> Result<Option<String>> {
+    let bitrate = bitrate_command(options.bitrate)?;
+    link.clear_input();
+    // 通道已关闭时部分固件以 BELL 应答，忽略
+    let _ = command(link, "C");
+    let version = command(link, "V").ok().filter(|v| !v.is_empty());
+    command(link, &bitrate)?;
+    // 不支持时间戳的固件会拒绝 Z 命令
+    if let Err(e) = command(link, if options.timestamps { "Z1" } else { "Z0" }) {
+        if options.timestamps {
+            return Err(e);
+        }
+    }
+    command(link, if options.listen_only { "L" } else { "O" })?;
+    Ok(version)
+}
+
+pub fn close_channel(link: &mut impl SerialLink) -> Result<()> {
+    command(link, "C").map(|_| ())
+}
+
+/// 按 `\r` 切分 SLCAN 流
+#[derive(Debug, Default)]
+pub struct SlcanParser {
+    line: Vec<u8>,
+}
+
+/// 流中的一项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlcanItem {
+    Frame(CanFrame),
+    /// 其它应答行 (如 `z`/`Z` 发送确认、版本、状态)
+    Reply(String),
+    /// `\x07` 错误应答
+    Error,
+    /// 无法解析的帧行
+    Invalid(String, String),
+}
+
+impl SlcanParser {
+    pub fn feed(&mut self, data: &[u8]) -> Vec<SlcanItem> {
+        let mut items = Vec::new();
+        for &b in data {
+            match b {
+                BELL => {
+                    self.line.clear();
+                    items.push(SlcanItem::Error);
+                }
+                b'\r' | b'\n' => {
+                    let line = String::from_utf8_lossy(&std::mem::take(&mut self.line)).to_string();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    items.push(match line.chars().next() {
+                        Some('t' | 'T' | 'r' | 'R') => match CanFrame::parse(&line) {
+                            Ok(frame) => SlcanItem::Frame(frame),
+                            Err(e) => SlcanItem::Invalid(line, e.to_string()),
+                        },
+                        _ => SlcanItem::Reply(line),
+                    });
+                }
+                b if self.line.len() < MAX_LINE => self.line.push(b),
+                _ => {}
+            }
+        }
+        items
+    }
+}
+
+/// 注册表中的解码器名称
+pub const DECODER_NAME: &str = "slcan";
+
+/// SLCAN 解码器：RX 为适配器上报的帧，TX 为发出的命令
+#[derive(Debug, Default)]
+pub struct SlcanDecoder {
+    rx: SlcanParser,
+    tx: SlcanParser,
+}
+
+impl SlcanDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+pub fn decoder_factory(_options: &Value) -> Result<Box<dyn ProtocolDecoder>> {
+    Ok(Box::new(SlcanDecoder::new()))
+}
+
+impl ProtocolDecoder for SlcanDecoder {
+    fn name(&self) -> &str {
+        DECODER_NAME
+    }
+
+    fn feed(&mut self, direction: Direction, data: &[u8], timestamp_ns: u64) -> Vec<DecodedMessage> {
+        let parser = match direction {
+            Direction::Rx => &mut self.rx,
+            Direction::Tx => &mut self.tx,
+        };
+        parser
+            .feed(data)
+            .into_iter()
+            .filter_map(|item| {
+                let (valid, summary, fields, raw) = match item {
+                    SlcanItem::Frame(frame) => {
+                        let raw = frame.to_slcan().unwrap_or_default().into_bytes();
+                        (true, frame.summary(), serde_json::to_value(&frame).unwrap_or_default(), raw)
+                    }
+                    // 成功应答与发送确认不单独显示
+                    SlcanItem::Reply(line) if matches!(line.as_str(), "z" | "Z") => return None,
+                    SlcanItem::Reply(line) => {
+                        let label = if direction == Direction::Tx { "command" } else { "reply" };
+                        (true, format!("{} {}", label, line), Value::String(line.clone()), line.into_bytes())
+                    }
+                    SlcanItem::Error => (false, "error (BELL)".to_string(), Value::Null, vec![BELL]),
+                    SlcanItem::Invalid(line, error) => {
+                        (false, format!("invalid frame {}: {}", line, error), Value::String(error), line.into_bytes())
+                    }
+                };
+                Some(DecodedMessage {
+                    protocol: DECODER_NAME.to_string(),
+                    direction,
+                    timestamp_ns,
+                    raw,
+                    valid,
+                    summary,
+                    fields,
+                })
+            })
+            .collect()
+    }
+}
+
+/// 桥接状态
+#[derive(Debug, Clone, Serialize)]
+pub struct CanBridgeStatus {
+    pub running: bool,
+    pub interface: Option<String>,
+    /// 串口 → CAN 接口
+    pub to_interface: usize,
+    /// CAN 接口 → 串口
+    pub from_interface: usize,
+    pub errors: usize,
+}
+
+/// SLCAN ⇄ SocketCAN (vcan) 桥接
+pub struct CanBridge {
+    /// 每次启动新建，旧线程退出时只清除自己的标志
+    running: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+    interface: Option<String>,
+    to_interface: Arc<AtomicUsize>,
+    from_interface: Arc<AtomicUsize>,
+    errors: Arc<AtomicUsize>,
+}
+
+impl Default for CanBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CanBridge {
+    pub fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicBool::new(false)),
+            worker: None,
+            interface: None,
+            to_interface: Arc::new(AtomicUsize::new(0)),
+            from_interface: Arc::new(AtomicUsize::new(0)),
+            errors: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// 在 `link` 所属会话 (通道已打开) 与 `interface` (如 "vcan0") 之间转发帧；
+    /// `busy` 为会话链路的占用守卫，桥接线程退出时释放
+    pub fn start(&mut self, link: SessionLink, interface: &str, busy: Option<TransferGuard>) -> Result<()> {
+        if self.running.load(Ordering::SeqCst) {
+            return Err(anyhow!("CAN bridge already running"));
+        }
+        // 回收已自行退出的上一次线程
+        self.stop_worker();
+        let socket = CanSocket::open(interface)?;
+
+        self.interface = Some(interface.to_string());
+        self.to_interface = Arc::new(AtomicUsize::new(0));
+        self.from_interface = Arc::new(AtomicUsize::new(0));
+        self.errors = Arc::new(AtomicUsize::new(0));
+        self.running = Arc::new(AtomicBool::new(true));
+
+        let running = self.running.clone();
+        let counters = (self.to_interface.clone(), self.from_interface.clone(), self.errors.clone());
+        info!("SLCAN bridge to {} started", interface);
+        self.worker = Some(std::thread::spawn(move || {
+            let _busy = busy;
+            if let Err(e) = run_bridge(link, socket, &running, counters) {
+                error!("SLCAN bridge error: {}", e);
+            }
+            running.store(false, Ordering::SeqCst);
+            info!("SLCAN bridge stopped");
+        }));
+        Ok(())
+    }
+
+    /// 停止桥接并等待线程退出
+    pub fn stop(&mut self) -> Result<()> {
+        self.stop_worker();
+        self.interface = None;
+        Ok(())
+    }
+
+    /// 通知当前线程退出并等待其结束
+    fn stop_worker(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+
+    pub fn status(&self) -> CanBridgeStatus {
+        CanBridgeStatus {
+            running: self.running.load(Ordering::SeqCst),
+            interface: self.interface.clone(),
+            to_interface: self.to_interface.load(Ordering::SeqCst),
+            from_interface: self.from_interface.load(Ordering::SeqCst),
+            errors: self.errors.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn run_bridge(
+    mut link: SessionLink,
+    socket: CanSocket,
+    running: &AtomicBool,
+    (to_interface, from_interface, errors): (Arc<AtomicUsize>, Arc<AtomicUsize>, Arc<AtomicUsize>),
+) -> Result<()> {
+    let mut parser = SlcanParser::default();
+    let mut buf = [0u8; 256];
+    while running.load(Ordering::SeqCst) {
+        let n = link.read(&mut buf, Duration::from_millis(10))?;
+        for item in parser.feed(&buf[..n]) {
+            match item {
+                SlcanItem::Frame(frame) => match socket.send(&frame) {
+                    Ok(()) => {
+                        to_interface.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err(e) => {
+                        errors.fetch_add(1, Ordering::SeqCst);
+                        error!("SLCAN bridge send error: {}", e);
+                    }
+                },
+                SlcanItem::Error | SlcanItem::Invalid(..) => {
+                    errors.fetch_add(1, Ordering::SeqCst);
+                }
+                SlcanItem::Reply(_) => {}
+            }
+        }
+        while let Some(frame) = socket.recv()? {
+            link.write_all(frame.to_slcan()?.as_bytes())?;
+            from_interface.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_parse_and_encode() {
+        let frame = CanFrame::parse("t1233112233").unwrap();
+        assert_eq!((frame.id, frame.extended, frame.data.clone()), (0x123, false, vec![0x11, 0x22, 0x33]));
+        assert_eq!(frame.to_slcan().unwrap(), "t1233112233\r");
+        assert_eq!(frame.summary(), "123 [3] 11 22 33");
+
+        let stamped = CanFrame::parse("T1ABCDEF02BEEFEA5F").unwrap();
+        assert_eq!((stamped.id, stamped.extended, stamped.adapter_timestamp_ms), (0x1ABCDEF0, true, Some(0xEA5F)));
+        let remote = CanFrame::parse("r7FF4").unwrap();
+        assert!(remote.remote && remote.data.is_empty());
+        assert_eq!(remote.to_slcan().unwrap(), "r7FF4\r");
+
+        assert!(CanFrame::parse("t800100").is_err());
+        assert!(CanFrame::parse("t12390").is_err());
+        assert!(CanFrame { id: 0x800, extended: false, remote: false, dlc: None, data: vec![], adapter_timestamp_ms: None }
+            .to_slcan()
+            .is_err());
+        assert_eq!(bitrate_command(500_000).unwrap(), "S6");
+        assert!(bitrate_command(33_333).is_err());
+    }
+
+    #[test]
+    fn test_decoder_splits_stream() {
+        let mut decoder = SlcanDecoder::new();
+        let first = decoder.feed(Direction::Rx, b"t10020102\rz\rT0000", 1);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].summary, "100 [2] 01 02");
+        let rest = decoder.feed(Direction::Rx, b"00FF0\r\x07", 2);
+        assert_eq!(rest[0].summary, "000000FF [0]");
+        assert!(!rest[1].valid);
+
+        let tx = decoder.feed(Direction::Tx, b"S6\rO\r", 3);
+        assert_eq!(tx.iter().map(|m| m.summary.as_str()).collect::<Vec<_>>(), vec!["command S6", "command O"]);
+    }
+
+    #[test]
+    fn test_parser_caps_line() {
+        let mut parser = SlcanParser::default();
+        assert!(parser.feed(&[b'x'; 10_000]).is_empty());
+        assert_eq!(parser.line.len(), MAX_LINE);
+        let items = parser.feed(b"\rt1001AA\r");
+        assert_eq!(items[0], SlcanItem::Reply("x".repeat(MAX_LINE)));
+        assert!(matches!(items[1], SlcanItem::Frame(_)));
+    }
+}
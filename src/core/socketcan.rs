@@ -0,0 +1,142 @@
+//! SocketCAN 原始套接字 (仅 Linux)
+//!
+//! 供 SLCAN 桥接把帧转发到 vcan/can 接口，其它程序 (candump、cansend 等) 即可直接使用。
+//! 创建 vcan：`ip link add dev vcan0 type vcan && ip link set up vcan0`。
+
+use anyhow::Result;
+
+use super::slcan::CanFrame;
+
+#[cfg(target_os = "linux")]
+pub use linux::CanSocket;
+
+#[cfg(not(target_os = "linux"))]
+pub use unsupported::CanSocket;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use anyhow::anyhow;
+    use std::ffi::CString;
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    const CAN_EFF_FLAG: u32 = 0x8000_0000;
+    const CAN_RTR_FLAG: u32 = 0x4000_0000;
+    const CAN_ERR_FLAG: u32 = 0x2000_0000;
+    const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+    /// `struct can_frame` 大小
+    const FRAME_LEN: usize = 16;
+
+    /// 绑定到一个接口的 CAN_RAW 套接字 (非阻塞)
+    pub struct CanSocket {
+        fd: OwnedFd,
+    }
+
+    impl CanSocket {
+        pub fn open(interface: &str) -> Result<Self> {
+            let name = CString::new(interface)?;
+            let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+            if index == 0 {
+                return Err(anyhow!("CAN interface {} not found", interface));
+            }
+            let raw = unsafe {
+                libc::socket(libc::PF_CAN, libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, libc::CAN_RAW)
+            };
+            if raw < 0 {
+                return Err(anyhow!("Failed to create CAN socket: {}", io::Error::last_os_error()));
+            }
+            let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+            let mut addr: libc::sockaddr_can = unsafe { std::mem::zeroed() };
+            addr.can_family = libc::AF_CAN as libc::sa_family_t;
+            addr.can_ifindex = index as libc::c_int;
+            let rc = unsafe {
+                libc::bind(
+                    fd.as_raw_fd(),
+                    &addr as *const libc::sockaddr_can as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+                )
+            };
+            if rc < 0 {
+                return Err(anyhow!("Failed to bind CAN socket to {}: {}", interface, io::Error::last_os_error()));
+            }
+            Ok(Self { fd })
+        }
+
+        pub fn send(&self, frame: &CanFrame) -> Result<()> {
+            frame.validate()?;
+            let mut id = frame.id;
+            if frame.extended {
+                id |= CAN_EFF_FLAG;
+            }
+            if frame.remote {
+                id |= CAN_RTR_FLAG;
+            }
+            let mut raw = [0u8; FRAME_LEN];
+            raw[..4].copy_from_slice(&id.to_ne_bytes());
+            raw[4] = frame.len();
+            raw[8..8 + frame.data.len()].copy_from_slice(&frame.data);
+            let n = unsafe { libc::write(self.fd.as_raw_fd(), raw.as_ptr() as *const libc::c_void, FRAME_LEN) };
+            if n != FRAME_LEN as isize {
+                return Err(anyhow!("CAN write error: {}", io::Error::last_os_error()));
+            }
+            Ok(())
+        }
+
+        /// 读取一帧，无数据返回 None；错误帧被跳过
+        pub fn recv(&self) -> Result<Option<CanFrame>> {
+            loop {
+                let mut raw = [0u8; FRAME_LEN];
+                let n = unsafe { libc::read(self.fd.as_raw_fd(), raw.as_mut_ptr() as *mut libc::c_void, FRAME_LEN) };
+                if n < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() == io::ErrorKind::WouldBlock {
+                        return Ok(None);
+                    }
+                    return Err(anyhow!("CAN read error: {}", err));
+                }
+                if n as usize != FRAME_LEN {
+                    continue;
+                }
+                let id = u32::from_ne_bytes(raw[..4].try_into().unwrap());
+                if id & CAN_ERR_FLAG != 0 {
+                    continue;
+                }
+                let extended = id & CAN_EFF_FLAG != 0;
+                let remote = id & CAN_RTR_FLAG != 0;
+                let dlc = raw[4].min(8);
+                return Ok(Some(CanFrame {
+                    id: if extended { id & CAN_EFF_MASK } else { id & 0x7FF },
+                    extended,
+                    remote,
+                    dlc: Some(dlc),
+                    data: if remote { Vec::new() } else { raw[8..8 + dlc as usize].to_vec() },
+                    adapter_timestamp_ms: None,
+                }));
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod unsupported {
+    use super::*;
+    use anyhow::anyhow;
+
+    pub struct CanSocket;
+
+    impl CanSocket {
+        pub fn open(_interface: &str) -> Result<Self> {
+            Err(anyhow!("SocketCAN bridging is only available on Linux"))
+        }
+
+        pub fn send(&self, _frame: &CanFrame) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn recv(&self) -> Result<Option<CanFrame>> {
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,524 @@
+//! STM32 UART 系统 Bootloader (AN3155)
+//!
+//! 在已打开的会话上烧录 STM32：通过 DTR/RTS 控制 BOOT0/NRST 进入 Bootloader，
+//! 临时切换为 8E1 后发送 0x7F 自动波特率同步，再执行读/写/擦除/跳转命令。
+//! 结束时恢复原校验位设置，终端可直接继续监视。
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use serialport::Parity;
+use std::time::Duration;
+
+use super::checksum::ChecksumAlgorithm;
+use super::file_transfer::{TransferControl, TransferPhase, TransferProgress, TransferStats};
+use super::firmware_image::FirmwareImage;
+use super::session_link::SerialLink;
+
+pub const ACK: u8 = 0x79;
+pub const NACK: u8 = 0x1F;
+pub const SYNC: u8 = 0x7F;
+
+pub const CMD_GET: u8 = 0x00;
+pub const CMD_GET_VERSION: u8 = 0x01;
+pub const CMD_GET_ID: u8 = 0x02;
+pub const CMD_READ_MEMORY: u8 = 0x11;
+pub const CMD_GO: u8 = 0x21;
+pub const CMD_WRITE_MEMORY: u8 = 0x31;
+pub const CMD_ERASE: u8 = 0x43;
+pub const CMD_EXTENDED_ERASE: u8 = 0x44;
+
+/// 单次读写的最大字节数
+pub const MAX_CHUNK: usize = 256;
+
+const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+/// 整片擦除耗时较长 (大容量器件可达数十秒)
+const MASS_ERASE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 用于控制 BOOT0 / NRST 的串口信号
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlLine {
+    Dtr,
+    Rts,
+}
+
+/// 进入 Bootloader 的引脚控制 (与常见一键下载电路一致：DTR→NRST，RTS→BOOT0)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BootEntry {
+    /// 控制 NRST 的信号，None 表示手动复位
+    #[serde(default = "default_nrst")]
+    pub nrst: Option<ControlLine>,
+    /// 控制 BOOT0 的信号，None 表示 BOOT0 已由跳线拉高
+    #[serde(default = "default_boot0")]
+    pub boot0: Option<ControlLine>,
+    /// 信号有效电平取反 (电路中没有反相三极管时)
+    #[serde(default)]
+    pub invert: bool,
+}
+
+fn default_nrst() -> Option<ControlLine> {
+    Some(ControlLine::Dtr)
+}
+
+fn default_boot0() -> Option<ControlLine> {
+    Some(ControlLine::Rts)
+}
+
+impl Default for BootEntry {
+    fn default() -> Self {
+        Self { nrst: default_nrst(), boot0: default_boot0(), invert: false }
+    }
+}
+
+/// 烧录参数
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stm32FlashOptions {
+    #[serde(default)]
+    pub entry: BootEntry,
+    /// 二进制镜像的基地址 (默认 0x08000000)
+    #[serde(default)]
+    pub base_address: Option<u32>,
+    /// 跳过整片擦除 (目标区域已擦除时)
+    #[serde(default)]
+    pub no_erase: bool,
+    /// 写入后回读校验
+    #[serde(default)]
+    pub verify: bool,
+    /// 完成后从该地址运行 (默认镜像起始地址)；否则复位到用户程序
+    #[serde(default)]
+    pub go_address: Option<u32>,
+}
+
+/// Bootloader 信息
+#[derive(Debug, Clone, Serialize)]
+pub struct Stm32Info {
+    /// Bootloader 协议版本 (如 0x31 表示 3.1)
+    pub version: u8,
+    /// 产品 ID (如 0x410 为 STM32F10xxx 中密度)
+    pub product_id: u16,
+    /// 支持的命令码
+    pub commands: Vec<u8>,
+}
+
+/// 默认 Flash 起始地址
+pub const FLASH_BASE: u32 = 0x0800_0000;
+
+fn xor(data: &[u8]) -> u8 {
+    ChecksumAlgorithm::Xor8.compute(data) as u8
+}
+
+/// Bootloader 客户端
+pub struct Stm32Bootloader<L: SerialLink> {
+    link: L,
+    /// 进入前的校验位，结束时恢复
+    saved_parity: Option<Parity>,
+    commands: Vec<u8>,
+}
+
+impl<L: SerialLink> Stm32Bootloader<L> {
+    pub fn new(link: L) -> Self {
+        Self { link, saved_parity: None, commands: Vec::new() }
+    }
+
+    pub fn link(&self) -> &L {
+        &self.link
+    }
+
+    fn set_line(&mut self, line: ControlLine, active: bool, invert: bool) -> Result<()> {
+        let level = active != invert;
+        match line {
+            ControlLine::Dtr => self.link.set_dtr(level),
+            ControlLine::Rts => self.link.set_rts(level),
+        }
+    }
+
+    /// 拉高 BOOT0 并复位，使芯片从系统存储器启动
+    pub fn enter(&mut self, entry: &BootEntry) -> Result<()> {
+        if self.saved_parity.is_none() {
+            self.saved_parity = Some(self.link.parity()?);
+            self.link.set_parity(Parity::Even)?;
+        }
+        if let Some(boot0) = entry.boot0 {
+            self.set_line(boot0, true, entry.invert)?;
+        }
+        if let Some(nrst) = entry.nrst {
+            self.set_line(nrst, true, entry.invert)?;
+            std::thread::sleep(Duration::from_millis(50));
+            self.set_line(nrst, false, entry.invert)?;
+        }
+        // 等待 Bootloader 启动
+        std::thread::sleep(Duration::from_millis(100));
+        self.link.clear_input();
+        Ok(())
+    }
+
+    /// 释放 BOOT0 并复位，运行用户程序；恢复原校验位
+    pub fn exit(&mut self, entry: &BootEntry) -> Result<()> {
+        if let Some(boot0) = entry.boot0 {
+            self.set_line(boot0, false, entry.invert)?;
+        }
+        if let Some(nrst) = entry.nrst {
+            self.set_line(nrst, true, entry.invert)?;
+            std::thread::sleep(Duration::from_millis(50));
+            self.set_line(nrst, false, entry.invert)?;
+        }
+        self.restore_parity()
+    }
+
+    pub fn restore_parity(&mut self) -> Result<()> {
+        match self.saved_parity.take() {
+            Some(parity) => self.link.set_parity(parity),
+            None => Ok(()),
+        }
+    }
+
+    fn wait_ack(&mut self, timeout: Duration) -> Result<()> {
+        match self.link.read_byte(timeout)? {
+            Some(ACK) => Ok(()),
+            Some(NACK) => bail!("Bootloader NACK"),
+            Some(other) => bail!("Unexpected bootloader reply 0x{:02X}", other),
+            None => bail!("Bootloader did not respond"),
+        }
+    }
+
+    fn command(&mut self, cmd: u8) -> Result<()> {
+        self.link.write_all(&[cmd, !cmd])?;
+        self.wait_ack(ACK_TIMEOUT).map_err(|e| anyhow!("Command 0x{:02X}: {}", cmd, e))
+    }
+
+    fn send_address(&mut self, address: u32) -> Result<()> {
+        let bytes = address.to_be_bytes();
+        let mut frame = bytes.to_vec();
+        frame.push(xor(&bytes));
+        self.link.write_all(&frame)?;
+        self.wait_ack(ACK_TIMEOUT).map_err(|e| anyhow!("Address 0x{:08X}: {}", address, e))
+    }
+
+    /// 自动波特率同步；已同步的 Bootloader 会对 0x7F 回 NACK，同样视为成功
+    pub fn sync(&mut self) -> Result<()> {
+        for _ in 0..5 {
+            self.link.clear_input();
+            self.link.write_all(&[SYNC])?;
+            match self.link.read_byte(Duration::from_millis(500))? {
+                Some(ACK) | Some(NACK) => return Ok(()),
+                _ => {}
+            }
+        }
+        bail!("No response to bootloader sync (check BOOT0/NRST wiring)")
+    }
+
+    /// Get：协议版本与支持的命令
+    pub fn get(&mut self) -> Result<(u8, Vec<u8>)> {
+        self.command(CMD_GET)?;
+        let n = self.link.read_byte(ACK_TIMEOUT)?.ok_or_else(|| anyhow!("Get: no length"))? as usize;
+        let mut data = vec![0u8; n + 1];
+        self.link.read_exact(&mut data, ACK_TIMEOUT)?;
+        self.wait_ack(ACK_TIMEOUT)?;
+        self.commands = data[1..].to_vec();
+        Ok((data[0], data[1..].to_vec()))
+    }
+
+    /// Get Version：协议版本
+    pub fn get_version(&mut self) -> Result<u8> {
+        self.command(CMD_GET_VERSION)?;
+        let mut data = [0u8; 3];
+        self.link.read_exact(&mut data, ACK_TIMEOUT)?;
+        self.wait_ack(ACK_TIMEOUT)?;
+        Ok(data[0])
+    }
+
+    /// Get ID：产品 ID
+    pub fn get_id(&mut self) -> Result<u16> {
+        self.command(CMD_GET_ID)?;
+        let n = self.link.read_byte(ACK_TIMEOUT)?.ok_or_else(|| anyhow!("Get ID: no length"))? as usize;
+        let mut data = vec![0u8; n + 1];
+        self.link.read_exact(&mut data, ACK_TIMEOUT)?;
+        self.wait_ack(ACK_TIMEOUT)?;
+        if data.len() < 2 {
+            bail!("Get ID: short reply");
+        }
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    pub fn info(&mut self) -> Result<Stm32Info> {
+        let (version, commands) = self.get()?;
+        let product_id = self.get_id()?;
+        Ok(Stm32Info { version, product_id, commands })
+    }
+
+    /// 读取最多 256 字节
+    pub fn read_memory(&mut self, address: u32, len: usize) -> Result<Vec<u8>> {
+        if len == 0 || len > MAX_CHUNK {
+            bail!("Read length must be 1..={}", MAX_CHUNK);
+        }
+        self.command(CMD_READ_MEMORY)?;
+        self.send_address(address)?;
+        let n = (len - 1) as u8;
+        self.link.write_all(&[n, !n])?;
+        self.wait_ack(ACK_TIMEOUT)?;
+        let mut data = vec![0u8; len];
+        self.link.read_exact(&mut data, ACK_TIMEOUT)?;
+        Ok(data)
+    }
+
+    /// 写入最多 256 字节 (长度按 4 字节对齐，以 0xFF 补齐)
+    pub fn write_memory(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        if data.is_empty() || data.len() > MAX_CHUNK {
+            bail!("Write length must be 1..={}", MAX_CHUNK);
+        }
+        self.command(CMD_WRITE_MEMORY)?;
+        self.send_address(address)?;
+        let mut payload = data.to_vec();
+        payload.resize(data.len().div_ceil(4) * 4, 0xFF);
+        let mut frame = Vec::with_capacity(payload.len() + 2);
+        frame.push((payload.len() - 1) as u8);
+        frame.extend_from_slice(&payload);
+        frame.push(xor(&frame));
+        self.link.write_all(&frame)?;
+        self.wait_ack(ACK_TIMEOUT).map_err(|e| anyhow!("Write at 0x{:08X}: {}", address, e))
+    }
+
+    fn uses_extended_erase(&mut self) -> Result<bool> {
+        if self.commands.is_empty() {
+            self.get()?;
+        }
+        Ok(self.commands.contains(&CMD_EXTENDED_ERASE))
+    }
+
+    /// 整片擦除
+    pub fn erase_all(&mut self) -> Result<()> {
+        if self.uses_extended_erase()? {
+            self.command(CMD_EXTENDED_ERASE)?;
+            self.link.write_all(&[0xFF, 0xFF, 0x00])?;
+        } else {
+            self.command(CMD_ERASE)?;
+            self.link.write_all(&[0xFF, 0x00])?;
+        }
+        self.wait_ack(MASS_ERASE_TIMEOUT).map_err(|e| anyhow!("Mass erase: {}", e))
+    }
+
+    /// 擦除指定页 (页号)
+    pub fn erase_pages(&mut self, pages: &[u16]) -> Result<()> {
+        if pages.is_empty() {
+            return Ok(());
+        }
+        let mut frame = Vec::new();
+        if self.uses_extended_erase()? {
+            self.command(CMD_EXTENDED_ERASE)?;
+            frame.extend_from_slice(&((pages.len() - 1) as u16).to_be_bytes());
+            for page in pages {
+                frame.extend_from_slice(&page.to_be_bytes());
+            }
+        } else {
+            if pages.len() > 255 || pages.iter().any(|&p| p > 0xFF) {
+                bail!("Standard erase supports at most 255 pages below 256");
+            }
+            self.command(CMD_ERASE)?;
+            frame.push((pages.len() - 1) as u8);
+            frame.extend(pages.iter().map(|&p| p as u8));
+        }
+        frame.push(xor(&frame));
+        self.link.write_all(&frame)?;
+        self.wait_ack(MASS_ERASE_TIMEOUT).map_err(|e| anyhow!("Page erase: {}", e))
+    }
+
+    /// 从指定地址运行
+    pub fn go(&mut self, address: u32) -> Result<()> {
+        self.command(CMD_GO)?;
+        self.send_address(address)
+    }
+
+    /// 完整烧录流程：进入 → 同步 → 擦除 → 写入 (可选校验) → 运行
+    pub fn flash(
+        &mut self,
+        image: &FirmwareImage,
+        options: &Stm32FlashOptions,
+        control: &TransferControl,
+        progress: &mut dyn FnMut(&TransferProgress),
+    ) -> Result<Stm32Info> {
+        // Write Memory 要求起始地址按 4 字节对齐 (末尾不足 4 字节由 write_memory 补 0xFF)，在动设备之前检查
+        for segment in &image.segments {
+            if segment.address & 3 != 0 {
+                bail!("Segment at 0x{:08X} is not 4-byte aligned", segment.address);
+            }
+            if (segment.address as u64) + (segment.data.len() as u64) > 1 << 32 {
+                bail!("Segment at 0x{:08X} ({} bytes) exceeds the address space", segment.address, segment.data.len());
+            }
+        }
+        let result = self.flash_inner(image, options, control, progress);
+        if result.is_err() {
+            // 失败或取消时同样释放 BOOT0 并复位，不让芯片停在 Bootloader
+            let _ = self.exit(&options.entry);
+        }
+        result
+    }
+
+    fn flash_inner(
+        &mut self,
+        image: &FirmwareImage,
+        options: &Stm32FlashOptions,
+        control: &TransferControl,
+        progress: &mut dyn FnMut(&TransferProgress),
+    ) -> Result<Stm32Info> {
+        let total = image.total_len() as u64;
+        let mut stats = TransferStats::default();
+        let mut report = |phase: TransferPhase, bytes: u64, stats: &TransferStats| {
+            progress(&TransferProgress {
+                phase,
+                file_name: None,
+                bytes,
+                total: Some(total),
+                file_index: 0,
+                file_count: Some(1),
+                stats: stats.clone(),
+            });
+        };
+
+        report(TransferPhase::Waiting, 0, &stats);
+        self.enter(&options.entry)?;
+        self.sync()?;
+        let info = self.info()?;
+
+        if !options.no_erase {
+            self.erase_all()?;
+        }
+
+        let mut written = 0u64;
+        for segment in &image.segments {
+            for (i, chunk) in segment.data.chunks(MAX_CHUNK).enumerate() {
+                if control.is_cancelled() {
+                    report(TransferPhase::Cancelled, written, &stats);
+                    bail!("Transfer cancelled");
+                }
+                let address = u32::try_from(i * MAX_CHUNK)
+                    .ok()
+                    .and_then(|offset| segment.address.checked_add(offset))
+                    .ok_or_else(|| anyhow!("Segment at 0x{:08X} exceeds the address space", segment.address))?;
+                self.write_memory(address, chunk)?;
+                if options.verify {
+                    let readback = self.read_memory(address, chunk.len())?;
+                    if readback != chunk {
+                        report(TransferPhase::Failed, written, &stats);
+                        bail!("Verify failed at 0x{:08X}", address);
+                    }
+                }
+                written += chunk.len() as u64;
+                stats.blocks += 1;
+                stats.total_bytes = written;
+                report(TransferPhase::Transferring, written, &stats);
+            }
+        }
+
+        match options.go_address.or(image.start_address()) {
+            Some(address) if options.go_address.is_some() || options.entry.nrst.is_none() => {
+                self.go(address)?;
+                self.restore_parity()?;
+            }
+            _ => self.exit(&options.entry)?,
+        }
+        report(TransferPhase::Completed, written, &stats);
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+    use std::collections::HashMap;
+
+    fn read_n(link: &mut impl SerialLink, n: usize) -> Option<Vec<u8>> {
+        let mut buf = vec![0u8; n];
+        link.read_exact(&mut buf, Duration::from_millis(500)).ok().map(|_| buf)
+    }
+
+    /// 最小 Bootloader 模拟：支持 Get / Get ID / Write Memory / Read Memory，返回写入记录
+    fn fake_device(mut link: impl SerialLink) -> Vec<(u32, Vec<u8>)> {
+        let mut writes = Vec::new();
+        let mut memory: HashMap<u32, Vec<u8>> = HashMap::new();
+        while let Some(cmd) = read_n(&mut link, 1) {
+            if cmd[0] == SYNC {
+                link.write_all(&[ACK]).unwrap();
+                continue;
+            }
+            let check = read_n(&mut link, 1).unwrap();
+            assert_eq!(check[0], !cmd[0]);
+            match cmd[0] {
+                CMD_GET => link
+                    .write_all(&[ACK, 4, 0x31, CMD_GET, CMD_GET_ID, CMD_WRITE_MEMORY, CMD_READ_MEMORY, ACK])
+                    .unwrap(),
+                CMD_GET_ID => link.write_all(&[ACK, 1, 0x04, 0x10, ACK]).unwrap(),
+                CMD_WRITE_MEMORY | CMD_READ_MEMORY => {
+                    link.write_all(&[ACK]).unwrap();
+                    let addr = read_n(&mut link, 5).unwrap();
+                    assert_eq!(addr[4], xor(&addr[..4]));
+                    let address = u32::from_be_bytes([addr[0], addr[1], addr[2], addr[3]]);
+                    link.write_all(&[ACK]).unwrap();
+                    let n = read_n(&mut link, 1).unwrap()[0] as usize + 1;
+                    if cmd[0] == CMD_WRITE_MEMORY {
+                        let data = read_n(&mut link, n + 1).unwrap();
+                        let mut frame = vec![(n - 1) as u8];
+                        frame.extend_from_slice(&data[..n]);
+                        assert_eq!(data[n], xor(&frame));
+                        memory.insert(address, data[..n].to_vec());
+                        writes.push((address, data[..n].to_vec()));
+                        link.write_all(&[ACK]).unwrap();
+                    } else {
+                        let _complement = read_n(&mut link, 1).unwrap();
+                        let mut reply = vec![ACK];
+                        reply.extend_from_slice(&memory[&address][..n]);
+                        link.write_all(&reply).unwrap();
+                    }
+                }
+                _ => link.write_all(&[NACK]).unwrap(),
+            }
+        }
+        writes
+    }
+
+    #[test]
+    fn test_flash_with_verify() {
+        let (host, device) = pipe_pair();
+        let device = std::thread::spawn(move || fake_device(device));
+
+        let image = FirmwareImage::from_binary((0..300u32).map(|i| i as u8).collect(), FLASH_BASE);
+        let options = Stm32FlashOptions { no_erase: true, verify: true, ..Default::default() };
+        let mut bootloader = Stm32Bootloader::new(host);
+        let mut last = None;
+        let info = bootloader
+            .flash(&image, &options, &TransferControl::new(), &mut |p| last = Some((p.phase, p.bytes)))
+            .unwrap();
+        drop(bootloader);
+        let writes = device.join().unwrap();
+
+        assert_eq!(info.version, 0x31);
+        assert_eq!(info.product_id, 0x0410);
+        assert_eq!(last, Some((TransferPhase::Completed, 300)));
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].0, FLASH_BASE);
+        assert_eq!(writes[1].0, FLASH_BASE + 256);
+        // 300 = 256 + 44，末块已按 4 字节对齐
+        assert_eq!(writes[1].1.len(), 44);
+    }
+
+    #[test]
+    fn test_flash_rejects_unaligned_segment() {
+        let (host, device) = pipe_pair();
+        let device = std::thread::spawn(move || fake_device(device));
+
+        let options = Stm32FlashOptions { no_erase: true, ..Default::default() };
+        let mut bootloader = Stm32Bootloader::new(host);
+        let unaligned = FirmwareImage::from_binary(vec![0xAA; 8], FLASH_BASE + 2);
+        let err = bootloader.flash(&unaligned, &options, &TransferControl::new(), &mut |_| {}).unwrap_err();
+        assert!(err.to_string().contains("not 4-byte aligned"));
+
+        // 长度不是 4 的倍数时末尾补 0xFF
+        let odd = FirmwareImage::from_binary(vec![0xAA; 6], FLASH_BASE);
+        bootloader.flash(&odd, &options, &TransferControl::new(), &mut |_| {}).unwrap();
+        drop(bootloader);
+        let writes = device.join().unwrap();
+        assert_eq!(writes, vec![(FLASH_BASE, vec![0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xFF, 0xFF])]);
+    }
+}
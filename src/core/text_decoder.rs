@@ -0,0 +1,157 @@
+//! 接收数据的文本解码
+//!
+//! 读线程按 4096 字节 / 超时切块，多字节字符 (UTF-8、GBK 等) 可能被拆到两个
+//! `serial-data` 事件里。`StreamDecoder` 按会话编码流式解码，未完整的字节序列
+//! 会保留到下一块再拼接，保证输出的文本不出现被截断的字符。
+
+use anyhow::{Result, anyhow};
+use encoding_rs::{Decoder, Encoding};
+use serde::{Serialize, Deserialize};
+
+/// 支持的文本编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextEncoding {
+    Utf8,
+    Gbk,
+    Gb18030,
+    ShiftJis,
+    /// ISO-8859-1，每个字节直接映射为 U+0000..U+00FF
+    Latin1,
+    Utf16le,
+    Utf16be,
+}
+
+impl TextEncoding {
+    pub fn parse(s: &str) -> Result<Self> {
+        let normalized: String = s.to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        match normalized.as_str() {
+            "utf8" => Ok(TextEncoding::Utf8),
+            "gbk" | "gb2312" | "cp936" => Ok(TextEncoding::Gbk),
+            "gb18030" => Ok(TextEncoding::Gb18030),
+            "shiftjis" | "sjis" | "cp932" => Ok(TextEncoding::ShiftJis),
+            "latin1" | "iso88591" => Ok(TextEncoding::Latin1),
+            "utf16" | "utf16le" => Ok(TextEncoding::Utf16le),
+            "utf16be" => Ok(TextEncoding::Utf16be),
+            _ => Err(anyhow!("Unsupported text encoding: {}", s)),
+        }
+    }
+
+    fn encoding_rs(&self) -> Option<&'static Encoding> {
+        match self {
+            TextEncoding::Utf8 => Some(encoding_rs::UTF_8),
+            TextEncoding::Gbk => Some(encoding_rs::GBK),
+            TextEncoding::Gb18030 => Some(encoding_rs::GB18030),
+            TextEncoding::ShiftJis => Some(encoding_rs::SHIFT_JIS),
+            TextEncoding::Utf16le => Some(encoding_rs::UTF_16LE),
+            TextEncoding::Utf16be => Some(encoding_rs::UTF_16BE),
+            // encoding_rs 将 Latin-1 视为 windows-1252，这里自行实现
+            TextEncoding::Latin1 => None,
+        }
+    }
+}
+
+/// 流式解码器：跨块保留不完整的多字节序列
+pub struct StreamDecoder {
+    encoding: TextEncoding,
+    decoder: Option<Decoder>,
+}
+
+impl Default for StreamDecoder {
+    fn default() -> Self {
+        Self::new(TextEncoding::Utf8)
+    }
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: TextEncoding) -> Self {
+        Self {
+            encoding,
+            // 不剥离 BOM：串口数据流中间不应出现 BOM 语义
+            decoder: encoding.encoding_rs().map(|e| e.new_decoder_without_bom_handling()),
+        }
+    }
+
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// 切换编码 (丢弃尚未完成的字节序列)
+    pub fn set_encoding(&mut self, encoding: TextEncoding) {
+        *self = Self::new(encoding);
+    }
+
+    /// 解码一块数据；末尾不完整的序列留待下一块
+    pub fn decode(&mut self, chunk: &[u8]) -> String {
+        self.decode_inner(chunk, false)
+    }
+
+    /// 输出残留字节 (不完整序列以 U+FFFD 替代)，并重置状态
+    pub fn flush(&mut self) -> String {
+        let text = self.decode_inner(&[], true);
+        self.decoder = self.encoding.encoding_rs().map(|e| e.new_decoder_without_bom_handling());
+        text
+    }
+
+    fn decode_inner(&mut self, chunk: &[u8], last: bool) -> String {
+        let decoder = match self.decoder.as_mut() {
+            Some(d) => d,
+            None => return chunk.iter().map(|&b| b as char).collect(),
+        };
+
+        let capacity = decoder
+            .max_utf8_buffer_length(chunk.len())
+            .unwrap_or(chunk.len() * 3 + 16);
+        let mut out = String::with_capacity(capacity);
+        let (_, _, _) = decoder.decode_to_string(chunk, &mut out, last);
+        out
+    }
+}
+
+/// 解码后的文本事件载荷 (与原始字节一同下发)
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedText {
+    pub data: Vec<u8>,
+    pub text: String,
+    /// VT 解析后的样式片段 (未启用 VT 解析时为空)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<super::vt_parser::VtSegment>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_split_across_chunks() {
+        let bytes = "温度: 25℃".as_bytes();
+        let mut decoder = StreamDecoder::new(TextEncoding::Utf8);
+        // 在 "温" 的第 2 个字节处切开
+        let a = decoder.decode(&bytes[..1]);
+        let b = decoder.decode(&bytes[1..]);
+        assert_eq!(a, "");
+        assert_eq!(b, "温度: 25℃");
+    }
+
+    #[test]
+    fn test_gbk_split_across_chunks() {
+        // "你好" in GBK
+        let bytes = [0xC4, 0xE3, 0xBA, 0xC3];
+        let mut decoder = StreamDecoder::new(TextEncoding::Gbk);
+        let mut text = decoder.decode(&bytes[..3]);
+        assert_eq!(text, "你");
+        text.push_str(&decoder.decode(&bytes[3..]));
+        assert_eq!(text, "你好");
+    }
+
+    #[test]
+    fn test_latin1_and_flush() {
+        let mut decoder = StreamDecoder::new(TextEncoding::Latin1);
+        assert_eq!(decoder.decode(&[0x41, 0xE9, 0x80]), "A\u{e9}\u{80}");
+
+        let mut decoder = StreamDecoder::new(TextEncoding::Utf16le);
+        assert_eq!(decoder.decode(&[0x41]), "");
+        assert_eq!(decoder.decode(&[0x00, 0x42]), "A");
+        assert_eq!(decoder.flush(), "\u{FFFD}");
+    }
+}
@@ -0,0 +1,357 @@
+//! RX 触发规则引擎
+//!
+//! 在后端对接收数据流逐块匹配规则 (正则 / 十六进制序列 / 字节掩码)，
+//! 命中后产出 `TriggerHit`，由上层 (Tauri 事件循环) 执行对应动作。
+//! 规则按会话 (端口名) 保存，并可持久化为 JSON。
+
+use anyhow::{Result, anyhow, Context};
+use regex::bytes::Regex;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::capture::CaptureFormat;
+use super::hex::parse_hex;
+
+/// 跨块匹配时保留的历史字节数
+const CARRY_OVER_LEN: usize = 256;
+
+/// 默认规则集的键 (未单独配置的会话使用)
+pub const DEFAULT_SESSION: &str = "*";
+
+/// 匹配条件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerPattern {
+    /// 正则表达式 (按字节匹配，可匹配非 UTF-8 数据)
+    Regex { pattern: String },
+    /// 十六进制序列，如 "AA 55 01"
+    Hex { bytes: String },
+    /// 带掩码的字节序列: (data & mask) == (value & mask)
+    Mask { value: String, mask: String },
+}
+
+/// 命中后的动作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+    /// 在终端中高亮
+    Highlight { color: Option<String> },
+    /// 插入标记 (同时写入抓包)
+    Marker { text: String },
+    /// 自动回复
+    Reply {
+        data: String,
+        #[serde(default)]
+        hex: bool,
+    },
+    /// 开始记录抓包
+    StartLogging { path: String, format: String },
+    /// 停止记录抓包
+    StopLogging,
+    /// 设置 DTR 电平
+    SetDtr { level: bool },
+    /// 翻转 DTR 电平
+    ToggleDtr,
+    /// 桌面通知 (由前端展示)
+    Notify { message: String },
+}
+
+impl TriggerAction {
+    /// 自动回复实际发送的字节
+    pub fn reply_bytes(data: &str, hex: bool) -> Result<Vec<u8>> {
+        if hex {
+            parse_hex(data)
+        } else {
+            Ok(data.as_bytes().to_vec())
+        }
+    }
+
+    /// 保存规则时检查动作参数，避免命中时才失败
+    fn validate(&self) -> Result<()> {
+        match self {
+            TriggerAction::Reply { data, hex } => Self::reply_bytes(data, *hex).map(|_| ()),
+            TriggerAction::StartLogging { format, .. } => CaptureFormat::parse(format).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 触发规则
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerRule {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub pattern: TriggerPattern,
+    pub actions: Vec<TriggerAction>,
+    /// 两次触发之间的最小间隔 (ms)，0 表示不限制
+    #[serde(default)]
+    pub cooldown_ms: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 规则命中结果
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerHit {
+    pub rule: String,
+    pub matched: Vec<u8>,
+    pub actions: Vec<TriggerAction>,
+}
+
+/// 编译后的匹配器
+enum Matcher {
+    Regex(Regex),
+    Bytes { value: Vec<u8>, mask: Vec<u8> },
+}
+
+impl Matcher {
+    fn compile(pattern: &TriggerPattern) -> Result<Self> {
+        match pattern {
+            TriggerPattern::Regex { pattern } => Ok(Matcher::Regex(
+                Regex::new(pattern).map_err(|e| anyhow!("Invalid regex '{}': {}", pattern, e))?,
+            )),
+            TriggerPattern::Hex { bytes } => {
+                let value = parse_hex(bytes)?;
+                let mask = vec![0xFF; value.len()];
+                Ok(Matcher::Bytes { value, mask })
+            }
+            TriggerPattern::Mask { value, mask } => {
+                let value = parse_hex(value)?;
+                let mask = parse_hex(mask)?;
+                if value.len() != mask.len() {
+                    return Err(anyhow!("Mask length ({}) differs from value length ({})", mask.len(), value.len()));
+                }
+                Ok(Matcher::Bytes { value, mask })
+            }
+        }
+    }
+
+    /// 返回所有匹配的 (start, end)
+    fn find_all(&self, haystack: &[u8]) -> Vec<(usize, usize)> {
+        match self {
+            Matcher::Regex(re) => re.find_iter(haystack).map(|m| (m.start(), m.end())).collect(),
+            Matcher::Bytes { value, mask } => {
+                if value.is_empty() || haystack.len() < value.len() {
+                    return Vec::new();
+                }
+                (0..=haystack.len() - value.len())
+                    .filter(|&i| {
+                        haystack[i..i + value.len()]
+                            .iter()
+                            .zip(value.iter().zip(mask))
+                            .all(|(b, (v, m))| b & m == v & m)
+                    })
+                    .map(|i| (i, i + value.len()))
+                    .collect()
+            }
+        }
+    }
+}
+
+struct CompiledRule {
+    rule: TriggerRule,
+    matcher: Matcher,
+    last_fired: Option<Instant>,
+}
+
+/// 单个会话的规则引擎
+pub struct TriggerEngine {
+    rules: Vec<CompiledRule>,
+    /// 上一块的尾部数据，用于匹配跨块边界的模式
+    carry: Vec<u8>,
+}
+
+impl TriggerEngine {
+    pub fn new(rules: &[TriggerRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let matcher = Matcher::compile(&rule.pattern)
+                    .with_context(|| format!("Rule '{}'", rule.name))?;
+                for action in &rule.actions {
+                    action.validate().with_context(|| format!("Rule '{}'", rule.name))?;
+                }
+                Ok(CompiledRule { rule: rule.clone(), matcher, last_fired: None })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules, carry: Vec::new() })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 处理一块 RX 数据，返回本块内结束的所有命中
+    pub fn process(&mut self, chunk: &[u8]) -> Vec<TriggerHit> {
+        if self.rules.is_empty() {
+            return Vec::new();
+        }
+
+        let carry_len = self.carry.len();
+        let mut window = std::mem::take(&mut self.carry);
+        window.extend_from_slice(chunk);
+
+        let now = Instant::now();
+        let mut hits = Vec::new();
+        for compiled in self.rules.iter_mut().filter(|c| c.rule.enabled) {
+            for (start, end) in compiled.matcher.find_all(&window) {
+                // 完全位于历史数据中的匹配已在上一块报告过
+                if end <= carry_len {
+                    continue;
+                }
+                let cooldown = Duration::from_millis(compiled.rule.cooldown_ms);
+                if let Some(last) = compiled.last_fired {
+                    if now.duration_since(last) < cooldown {
+                        continue;
+                    }
+                }
+                compiled.last_fired = Some(now);
+                hits.push(TriggerHit {
+                    rule: compiled.rule.name.clone(),
+                    matched: window[start..end].to_vec(),
+                    actions: compiled.rule.actions.clone(),
+                });
+            }
+        }
+
+        let keep_from = window.len().saturating_sub(CARRY_OVER_LEN);
+        self.carry = window.split_off(keep_from);
+        hits
+    }
+
+    /// 清空跨块历史 (端口重新打开时调用)
+    pub fn reset(&mut self) {
+        self.carry.clear();
+    }
+}
+
+/// 规则管理器：按会话保存规则，并维护当前会话的引擎
+pub struct TriggerManager {
+    sessions: HashMap<String, Vec<TriggerRule>>,
+    active_session: String,
+    engine: TriggerEngine,
+}
+
+impl Default for TriggerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TriggerManager {
+    pub fn new() -> Self {
+        Self {
+            sessions: HashMap::new(),
+            active_session: DEFAULT_SESSION.to_string(),
+            engine: TriggerEngine { rules: Vec::new(), carry: Vec::new() },
+        }
+    }
+
+    /// 从 JSON 文件加载规则 (文件不存在时保持为空)
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        if !path.exists() {
+            return Ok(());
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read trigger rules {:?}", path))?;
+        self.sessions = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid trigger rules file {:?}", path))?;
+        let active = self.active_session.clone();
+        self.activate(&active)
+    }
+
+    /// 保存全部会话的规则
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = serde_json::to_string_pretty(&self.sessions)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write trigger rules {:?}", path))
+    }
+
+    /// 切换到指定会话 (端口名)，未单独配置时使用默认规则集
+    pub fn activate(&mut self, session: &str) -> Result<()> {
+        self.active_session = session.to_string();
+        self.engine = TriggerEngine::new(&self.rules(session))?;
+        Ok(())
+    }
+
+    pub fn rules(&self, session: &str) -> Vec<TriggerRule> {
+        self.sessions
+            .get(session)
+            .or_else(|| self.sessions.get(DEFAULT_SESSION))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// 设置会话规则 (先校验全部规则可编译)
+    pub fn set_rules(&mut self, session: &str, rules: Vec<TriggerRule>) -> Result<()> {
+        TriggerEngine::new(&rules)?;
+        self.sessions.insert(session.to_string(), rules);
+        if session == self.active_session || session == DEFAULT_SESSION {
+            let active = self.active_session.clone();
+            self.activate(&active)?;
+        }
+        Ok(())
+    }
+
+    pub fn process(&mut self, chunk: &[u8]) -> Vec<TriggerHit> {
+        self.engine.process(chunk)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, pattern: TriggerPattern) -> TriggerRule {
+        TriggerRule {
+            name: name.to_string(),
+            enabled: true,
+            pattern,
+            actions: vec![TriggerAction::Notify { message: name.to_string() }],
+            cooldown_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_regex_across_chunks() {
+        let mut engine = TriggerEngine::new(&[rule(
+            "panic",
+            TriggerPattern::Regex { pattern: r"Kernel panic".to_string() },
+        )]).unwrap();
+        assert!(engine.process(b"boot ok\r\nKernel pa").is_empty());
+        let hits = engine.process(b"nic - not syncing");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].matched, b"Kernel panic");
+        // 已报告的匹配不会在下一块重复出现
+        assert!(engine.process(b"\r\n").is_empty());
+    }
+
+    #[test]
+    fn test_hex_and_mask() {
+        let mut engine = TriggerEngine::new(&[
+            rule("sync", TriggerPattern::Hex { bytes: "AA 55".to_string() }),
+            rule("any_cmd", TriggerPattern::Mask { value: "01 F0".to_string(), mask: "FF F0".to_string() }),
+        ]).unwrap();
+        let hits = engine.process(&[0x00, 0xAA, 0x55, 0x01, 0xF3]);
+        let names: Vec<&str> = hits.iter().map(|h| h.rule.as_str()).collect();
+        assert_eq!(names, vec!["sync", "any_cmd"]);
+        assert_eq!(hits[1].matched, vec![0x01, 0xF3]);
+    }
+
+    #[test]
+    fn test_invalid_reply_rejected() {
+        let mut bad = rule("reply", TriggerPattern::Hex { bytes: "AA".to_string() });
+        bad.actions = vec![TriggerAction::Reply { data: "0G".to_string(), hex: true }];
+        let mut manager = TriggerManager::new();
+        assert!(manager.set_rules(DEFAULT_SESSION, vec![bad]).is_err());
+        assert!(manager.rules(DEFAULT_SESSION).is_empty());
+    }
+}
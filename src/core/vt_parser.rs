@@ -0,0 +1,384 @@
+//! ANSI/VT100 转义序列解析
+//!
+//! 设备控制台 (Zephyr、ESP-IDF、U-Boot 等) 会输出颜色与光标控制序列。
+//! `VtParser` 将解码后的文本切分为带样式的片段 (SGR 颜色/粗体等) 与控制操作
+//! (光标移动、清屏/清行)，未完成的序列跨块保留。
+//! `EscapeStripper` 在字节层面去除转义序列，用于保存日志时选择"去除转义"。
+
+use serde::Serialize;
+
+/// 颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Color {
+    /// 0-15 为标准/高亮色，16-255 为 256 色表
+    Indexed { index: u8 },
+    Rgb { r: u8, g: u8, b: u8 },
+}
+
+/// 文本样式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+}
+
+/// 光标/擦除操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum VtOp {
+    CursorUp { n: u16 },
+    CursorDown { n: u16 },
+    CursorForward { n: u16 },
+    CursorBack { n: u16 },
+    /// 1 起始的行列
+    CursorPosition { row: u16, col: u16 },
+    CursorColumn { col: u16 },
+    /// 0: 光标至屏尾, 1: 屏首至光标, 2/3: 全屏
+    EraseDisplay { mode: u8 },
+    /// 0: 光标至行尾, 1: 行首至光标, 2: 整行
+    EraseLine { mode: u8 },
+    SaveCursor,
+    RestoreCursor,
+    ShowCursor,
+    HideCursor,
+    Bell,
+}
+
+/// 解析结果片段
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VtSegment {
+    Text { text: String, style: Style },
+    Control { op: VtOp },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    /// ESC 后跟中间字节 (如 "ESC (" 字符集选择)，等待终结字节
+    EscapeIntermediate,
+    Csi,
+    /// OSC/DCS/APC 等字符串序列，直到 BEL 或 ST
+    String,
+    StringEscape,
+}
+
+const ESC: char = '\x1b';
+const BEL: char = '\x07';
+
+/// 流式 VT 解析器
+pub struct VtParser {
+    state: State,
+    params: String,
+    style: Style,
+}
+
+impl Default for VtParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VtParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: String::new(),
+            style: Style::default(),
+        }
+    }
+
+    /// 重置状态 (样式恢复默认)
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 解析一段文本
+    pub fn feed(&mut self, text: &str) -> Vec<VtSegment> {
+        let mut segments = Vec::new();
+        let mut current = String::new();
+
+        for c in text.chars() {
+            match self.state {
+                State::Ground => match c {
+                    ESC => self.state = State::Escape,
+                    BEL => {
+                        self.flush_text(&mut current, &mut segments);
+                        segments.push(VtSegment::Control { op: VtOp::Bell });
+                    }
+                    _ => current.push(c),
+                },
+                State::Escape => match c {
+                    '[' => {
+                        self.params.clear();
+                        self.state = State::Csi;
+                    }
+                    ']' | 'P' | '_' | '^' | 'X' => self.state = State::String,
+                    '7' => {
+                        self.flush_text(&mut current, &mut segments);
+                        segments.push(VtSegment::Control { op: VtOp::SaveCursor });
+                        self.state = State::Ground;
+                    }
+                    '8' => {
+                        self.flush_text(&mut current, &mut segments);
+                        segments.push(VtSegment::Control { op: VtOp::RestoreCursor });
+                        self.state = State::Ground;
+                    }
+                    'c' => {
+                        self.flush_text(&mut current, &mut segments);
+                        self.style = Style::default();
+                        segments.push(VtSegment::Control { op: VtOp::EraseDisplay { mode: 2 } });
+                        self.state = State::Ground;
+                    }
+                    ' '..='/' => self.state = State::EscapeIntermediate,
+                    _ => self.state = State::Ground, // 其它两字节序列忽略
+                },
+                State::EscapeIntermediate => {
+                    if !(' '..='/').contains(&c) {
+                        self.state = State::Ground;
+                    }
+                }
+                State::Csi => {
+                    if ('@'..='~').contains(&c) {
+                        self.flush_text(&mut current, &mut segments);
+                        self.dispatch_csi(c, &mut segments);
+                        self.state = State::Ground;
+                    } else if self.params.len() < 64 {
+                        self.params.push(c);
+                    }
+                }
+                State::String => match c {
+                    BEL => self.state = State::Ground,
+                    ESC => self.state = State::StringEscape,
+                    _ => {}
+                },
+                State::StringEscape => {
+                    self.state = if c == '\\' { State::Ground } else { State::String };
+                }
+            }
+        }
+
+        self.flush_text(&mut current, &mut segments);
+        segments
+    }
+
+    fn flush_text(&self, current: &mut String, segments: &mut Vec<VtSegment>) {
+        if current.is_empty() {
+            return;
+        }
+        let text = std::mem::take(current);
+        // 与上一个同样式片段合并
+        if let Some(VtSegment::Text { text: prev, style }) = segments.last_mut() {
+            if *style == self.style {
+                prev.push_str(&text);
+                return;
+            }
+        }
+        segments.push(VtSegment::Text { text, style: self.style });
+    }
+
+    fn dispatch_csi(&mut self, final_char: char, segments: &mut Vec<VtSegment>) {
+        let private = self.params.starts_with('?');
+        let params: Vec<u16> = self.params
+            .trim_start_matches(['?', '>', '='])
+            .split(';')
+            .map(|p| p.parse::<u16>().unwrap_or(0))
+            .collect();
+        let first = params.first().copied().unwrap_or(0);
+        let count = first.max(1);
+
+        let op = match final_char {
+            'm' => {
+                self.apply_sgr(&params);
+                None
+            }
+            'A' => Some(VtOp::CursorUp { n: count }),
+            'B' => Some(VtOp::CursorDown { n: count }),
+            'C' => Some(VtOp::CursorForward { n: count }),
+            'D' => Some(VtOp::CursorBack { n: count }),
+            'G' => Some(VtOp::CursorColumn { col: count }),
+            'H' | 'f' => Some(VtOp::CursorPosition {
+                row: count,
+                col: params.get(1).copied().unwrap_or(1).max(1),
+            }),
+            'J' => Some(VtOp::EraseDisplay { mode: first.min(3) as u8 }),
+            'K' => Some(VtOp::EraseLine { mode: first.min(2) as u8 }),
+            's' => Some(VtOp::SaveCursor),
+            'u' => Some(VtOp::RestoreCursor),
+            'h' if private && first == 25 => Some(VtOp::ShowCursor),
+            'l' if private && first == 25 => Some(VtOp::HideCursor),
+            _ => None,
+        };
+        if let Some(op) = op {
+            segments.push(VtSegment::Control { op });
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &[u16]) {
+        let mut i = 0;
+        while i < params.len() {
+            match params[i] {
+                0 => self.style = Style::default(),
+                1 => self.style.bold = true,
+                2 => self.style.dim = true,
+                3 => self.style.italic = true,
+                4 => self.style.underline = true,
+                5 | 6 => self.style.blink = true,
+                7 => self.style.inverse = true,
+                22 => {
+                    self.style.bold = false;
+                    self.style.dim = false;
+                }
+                23 => self.style.italic = false,
+                24 => self.style.underline = false,
+                25 => self.style.blink = false,
+                27 => self.style.inverse = false,
+                n @ 30..=37 => self.style.fg = Some(Color::Indexed { index: (n - 30) as u8 }),
+                39 => self.style.fg = None,
+                n @ 40..=47 => self.style.bg = Some(Color::Indexed { index: (n - 40) as u8 }),
+                49 => self.style.bg = None,
+                n @ 90..=97 => self.style.fg = Some(Color::Indexed { index: (n - 90 + 8) as u8 }),
+                n @ 100..=107 => self.style.bg = Some(Color::Indexed { index: (n - 100 + 8) as u8 }),
+                n @ (38 | 48) => {
+                    // 38;5;n / 38;2;r;g;b
+                    let (color, used) = match params.get(i + 1) {
+                        Some(5) => (params.get(i + 2).map(|&idx| Color::Indexed { index: idx as u8 }), 2),
+                        Some(2) => match (params.get(i + 2), params.get(i + 3), params.get(i + 4)) {
+                            (Some(&r), Some(&g), Some(&b)) => {
+                                (Some(Color::Rgb { r: r as u8, g: g as u8, b: b as u8 }), 4)
+                            }
+                            _ => (None, params.len()),
+                        },
+                        _ => (None, 0),
+                    };
+                    if n == 38 {
+                        self.style.fg = color;
+                    } else {
+                        self.style.bg = color;
+                    }
+                    i += used;
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+}
+
+/// 字节层面的转义序列过滤器 (流式，跨块保留状态)
+#[derive(Default)]
+pub struct EscapeStripper {
+    state: Option<State>,
+}
+
+impl EscapeStripper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut state = self.state.unwrap_or(State::Ground);
+        for &b in data {
+            state = match state {
+                State::Ground => match b {
+                    0x1B => State::Escape,
+                    0x07 => State::Ground,
+                    _ => {
+                        out.push(b);
+                        State::Ground
+                    }
+                },
+                State::Escape => match b {
+                    b'[' => State::Csi,
+                    b']' | b'P' | b'_' | b'^' | b'X' => State::String,
+                    0x20..=0x2F => State::EscapeIntermediate,
+                    _ => State::Ground,
+                },
+                State::EscapeIntermediate => {
+                    if (0x20..=0x2F).contains(&b) { State::EscapeIntermediate } else { State::Ground }
+                }
+                State::Csi => {
+                    if (0x40..=0x7E).contains(&b) { State::Ground } else { State::Csi }
+                }
+                State::String => match b {
+                    0x07 => State::Ground,
+                    0x1B => State::StringEscape,
+                    _ => State::String,
+                },
+                State::StringEscape => {
+                    if b == b'\\' { State::Ground } else { State::String }
+                }
+            };
+        }
+        self.state = Some(state);
+        out
+    }
+}
+
+/// 去除文本中的转义序列 (一次性)
+pub fn strip_escapes(text: &str) -> String {
+    let bytes = EscapeStripper::new().strip(text.as_bytes());
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(segments: &[VtSegment]) -> String {
+        segments.iter().filter_map(|s| match s {
+            VtSegment::Text { text, .. } => Some(text.as_str()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_sgr_colors() {
+        let mut parser = VtParser::new();
+        let segs = parser.feed("\x1b[1;31mE (123) wifi:\x1b[0m ok");
+        assert_eq!(segs.len(), 2);
+        match &segs[0] {
+            VtSegment::Text { text, style } => {
+                assert_eq!(text, "E (123) wifi:");
+                assert!(style.bold);
+                assert_eq!(style.fg, Some(Color::Indexed { index: 1 }));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        match &segs[1] {
+            VtSegment::Text { style, .. } => assert_eq!(*style, Style::default()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sequence_split_across_chunks() {
+        let mut parser = VtParser::new();
+        let a = parser.feed("uart:~$ \x1b[");
+        let b = parser.feed("2K\x1b[38;2;1;2;3mX");
+        assert_eq!(text(&a), "uart:~$ ");
+        assert_eq!(b[0], VtSegment::Control { op: VtOp::EraseLine { mode: 2 } });
+        match &b[1] {
+            VtSegment::Text { style, .. } => assert_eq!(style.fg, Some(Color::Rgb { r: 1, g: 2, b: 3 })),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_strip() {
+        assert_eq!(strip_escapes("\x1b[0;32mI (42) boot\x1b[0m\r\n\x1b]0;title\x07"), "I (42) boot\r\n");
+        let mut stripper = EscapeStripper::new();
+        let mut out = stripper.strip(b"A\x1b[3");
+        out.extend(stripper.strip(b"1mB"));
+        assert_eq!(out, b"AB");
+    }
+}
@@ -0,0 +1,655 @@
+//! XMODEM / YMODEM 文件传输
+//!
+//! 发送与接收状态机，运行在已打开会话的 `SerialLink` 上：
+//! - XMODEM: 128 字节块 + 8 位累加和
+//! - XMODEM-CRC: 128 字节块 + CRC16 (由接收方发送 'C' 启动)
+//! - XMODEM-1K: 1024 字节块 + CRC16
+//! - YMODEM: 1K 批量传输，块 0 携带文件名/大小，以空块 0 结束
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use std::time::{Duration, Instant};
+
+use super::file_transfer::{
+    OutgoingFile, ReceivedFile, TransferControl, TransferPhase, TransferProgress, TransferStats,
+};
+use super::session_link::SerialLink;
+use super::checksum::{ChecksumAlgorithm, crc16_xmodem};
+
+pub const SOH: u8 = 0x01;
+pub const STX: u8 = 0x02;
+pub const EOT: u8 = 0x04;
+pub const ACK: u8 = 0x06;
+pub const NAK: u8 = 0x15;
+pub const CAN: u8 = 0x18;
+pub const CRC_START: u8 = b'C';
+/// 末块填充字符
+pub const SUB: u8 = 0x1A;
+
+/// 接收方启动字符的重发间隔
+const START_POLL: Duration = Duration::from_secs(3);
+/// 等待块头 / 应答的超时
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(10);
+/// 块头之后剩余字节的超时
+const BLOCK_BODY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 协议变体
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XmodemProtocol {
+    Xmodem,
+    XmodemCrc,
+    Xmodem1k,
+    Ymodem,
+}
+
+impl XmodemProtocol {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "xmodem" => Ok(XmodemProtocol::Xmodem),
+            "xmodemcrc" => Ok(XmodemProtocol::XmodemCrc),
+            "xmodem1k" => Ok(XmodemProtocol::Xmodem1k),
+            "ymodem" => Ok(XmodemProtocol::Ymodem),
+            _ => Err(anyhow!("Unknown transfer protocol: {}", s)),
+        }
+    }
+
+    fn block_size(self) -> usize {
+        match self {
+            XmodemProtocol::Xmodem | XmodemProtocol::XmodemCrc => 128,
+            XmodemProtocol::Xmodem1k | XmodemProtocol::Ymodem => 1024,
+        }
+    }
+}
+
+/// 传输参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct XmodemOptions {
+    pub protocol: XmodemProtocol,
+    /// 单块最大重试次数
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// 等待对端启动的超时 (秒)
+    #[serde(default = "default_start_timeout")]
+    pub start_timeout_secs: u64,
+    /// XMODEM 接收时去掉末尾的 0x1A 填充 (二进制文件慎用)
+    #[serde(default)]
+    pub trim_padding: bool,
+}
+
+fn default_max_retries() -> u32 {
+    10
+}
+
+fn default_start_timeout() -> u64 {
+    60
+}
+
+impl XmodemOptions {
+    pub fn new(protocol: XmodemProtocol) -> Self {
+        Self {
+            protocol,
+            max_retries: default_max_retries(),
+            start_timeout_secs: default_start_timeout(),
+            trim_padding: false,
+        }
+    }
+}
+
+fn checksum8(data: &[u8]) -> u8 {
+    ChecksumAlgorithm::Sum8.compute(data) as u8
+}
+
+/// 组装一个数据块 (`data` 不足块长时按 `pad` 填充)
+pub fn build_block(num: u8, data: &[u8], size: usize, crc: bool, pad: u8) -> Vec<u8> {
+    let mut block = Vec::with_capacity(size + 5);
+    block.push(if size == 1024 { STX } else { SOH });
+    block.push(num);
+    block.push(!num);
+    block.extend_from_slice(data);
+    block.resize(3 + size, pad);
+    let payload = &block[3..];
+    if crc {
+        let crc = crc16_xmodem(payload);
+        block.extend_from_slice(&crc.to_be_bytes());
+    } else {
+        block.push(checksum8(payload));
+    }
+    block
+}
+
+/// YMODEM 块 0: "文件名\0大小 修改时间(八进制)"
+fn ymodem_header(file: Option<&OutgoingFile>) -> Vec<u8> {
+    let mut header = Vec::new();
+    if let Some(file) = file {
+        header.extend_from_slice(file.name.as_bytes());
+        header.push(0);
+        let mut info = file.data.len().to_string();
+        if let Some(modified) = file.modified {
+            info.push_str(&format!(" {:o}", modified));
+        }
+        header.extend_from_slice(info.as_bytes());
+    }
+    header
+}
+
+/// 组装 YMODEM 块 0；头部超过 128 字节 (长文件名) 时改用 1K 块
+fn ymodem_header_block(file: Option<&OutgoingFile>) -> Result<Vec<u8>> {
+    let header = ymodem_header(file);
+    let size = match header.len() {
+        0..=128 => 128,
+        129..=1024 => 1024,
+        _ => bail!("YMODEM header too long ({} bytes)", header.len()),
+    };
+    Ok(build_block(0, &header, size, true, 0))
+}
+
+/// 解析 YMODEM 块 0，返回 (文件名, 大小)；空文件名表示批量结束
+fn parse_ymodem_header(data: &[u8]) -> (String, Option<u64>) {
+    let name_end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let name = String::from_utf8_lossy(&data[..name_end]).to_string();
+    let info = data.get(name_end + 1..).unwrap_or(&[]);
+    let info_end = info.iter().position(|&b| b == 0).unwrap_or(info.len());
+    let size = String::from_utf8_lossy(&info[..info_end])
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse().ok());
+    (name, size)
+}
+
+/// 接收到的块
+enum Block {
+    Data { num: u8, data: Vec<u8> },
+    Eot,
+    Timeout,
+    /// 校验失败或无法识别
+    Bad,
+    Cancelled,
+}
+
+/// 一次传输会话 (发送或接收)
+struct Transfer<'a, L: SerialLink> {
+    link: &'a mut L,
+    options: &'a XmodemOptions,
+    control: &'a TransferControl,
+    progress: &'a mut dyn FnMut(&TransferProgress),
+    stats: TransferStats,
+    file_name: Option<String>,
+    bytes: u64,
+    total: Option<u64>,
+    file_index: usize,
+    file_count: Option<usize>,
+}
+
+impl<'a, L: SerialLink> Transfer<'a, L> {
+    fn new(
+        link: &'a mut L,
+        options: &'a XmodemOptions,
+        control: &'a TransferControl,
+        progress: &'a mut dyn FnMut(&TransferProgress),
+    ) -> Self {
+        Self {
+            link,
+            options,
+            control,
+            progress,
+            stats: TransferStats::default(),
+            file_name: None,
+            bytes: 0,
+            total: None,
+            file_index: 0,
+            file_count: None,
+        }
+    }
+
+    fn report(&mut self, phase: TransferPhase) {
+        let progress = TransferProgress {
+            phase,
+            file_name: self.file_name.clone(),
+            bytes: self.bytes,
+            total: self.total,
+            file_index: self.file_index,
+            file_count: self.file_count,
+            stats: self.stats.clone(),
+        };
+        (self.progress)(&progress);
+    }
+
+    /// 发送取消序列 (连续 CAN)
+    fn abort(&mut self) {
+        let _ = self.link.write_all(&[CAN; 5]);
+    }
+
+    fn check_cancel(&mut self) -> Result<()> {
+        if self.control.is_cancelled() {
+            self.abort();
+            self.report(TransferPhase::Cancelled);
+            bail!("Transfer cancelled");
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, message: &str) -> anyhow::Error {
+        self.abort();
+        self.report(TransferPhase::Failed);
+        anyhow!("{}", message)
+    }
+
+    /// 对端是否发送了连续两个 CAN
+    fn second_can(&mut self) -> Result<bool> {
+        Ok(self.link.read_byte(Duration::from_secs(1))? == Some(CAN))
+    }
+
+    /// 丢弃线路上的残留数据直到静默
+    fn purge(&mut self) -> Result<()> {
+        let mut discarded = 0;
+        while self.link.read_byte(Duration::from_millis(500))?.is_some() && discarded < 4096 {
+            discarded += 1;
+        }
+        Ok(())
+    }
+
+    // ---------- 发送 ----------
+
+    /// 等待接收方启动，返回是否使用 CRC
+    fn wait_start(&mut self) -> Result<bool> {
+        self.report(TransferPhase::Waiting);
+        let deadline = Instant::now() + Duration::from_secs(self.options.start_timeout_secs);
+        while Instant::now() < deadline {
+            self.check_cancel()?;
+            match self.link.read_byte(Duration::from_secs(1))? {
+                Some(CRC_START) => return Ok(true),
+                Some(NAK) if self.options.protocol != XmodemProtocol::Ymodem => return Ok(false),
+                Some(CAN) if self.second_can()? => return Err(self.fail("Cancelled by receiver")),
+                _ => {}
+            }
+        }
+        Err(self.fail("Timed out waiting for receiver"))
+    }
+
+    /// 发送一块并等待 ACK
+    fn send_block(&mut self, block: &[u8]) -> Result<()> {
+        for attempt in 0..=self.options.max_retries {
+            self.check_cancel()?;
+            if attempt > 0 {
+                self.stats.retries += 1;
+            }
+            self.link.clear_input();
+            self.link.write_all(block)?;
+
+            let deadline = Instant::now() + BLOCK_TIMEOUT;
+            let response = loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match self.link.read_byte(remaining)? {
+                    Some(ACK) => break Some(ACK),
+                    Some(NAK) => break Some(NAK),
+                    Some(CAN) if self.second_can()? => return Err(self.fail("Cancelled by receiver")),
+                    // 其它字节 (如接收方迟到的 'C') 忽略
+                    Some(_) if !remaining.is_zero() => continue,
+                    _ => break None,
+                }
+            };
+            match response {
+                Some(ACK) => {
+                    self.stats.blocks += 1;
+                    return Ok(());
+                }
+                Some(_) => self.stats.crc_errors += 1,
+                None => self.stats.timeouts += 1,
+            }
+        }
+        Err(self.fail("Too many retries"))
+    }
+
+    /// 发送 EOT 直到 ACK (YMODEM 接收方通常先 NAK 第一个 EOT)
+    fn send_eot(&mut self) -> Result<()> {
+        for attempt in 0..=self.options.max_retries {
+            self.check_cancel()?;
+            // 第一次 NAK 属于正常握手，不计入重试
+            if attempt > 1 {
+                self.stats.retries += 1;
+            }
+            self.link.write_all(&[EOT])?;
+            match self.link.read_byte(BLOCK_TIMEOUT)? {
+                Some(ACK) => return Ok(()),
+                Some(CAN) if self.second_can()? => return Err(self.fail("Cancelled by receiver")),
+                Some(_) => {}
+                None => self.stats.timeouts += 1,
+            }
+        }
+        Err(self.fail("No ACK for EOT"))
+    }
+
+    fn send_data(&mut self, data: &[u8], crc: bool) -> Result<()> {
+        let block_size = if crc { self.options.protocol.block_size() } else { 128 };
+        let mut num: u8 = 1;
+        let mut offset = 0;
+        self.bytes = 0;
+        self.total = Some(data.len() as u64);
+        self.report(TransferPhase::Transferring);
+        while offset < data.len() {
+            // 剩余不足 128 字节时改用短块，减少填充
+            let size = if data.len() - offset <= 128 { 128 } else { block_size };
+            let end = (offset + size).min(data.len());
+            let block = build_block(num, &data[offset..end], size, crc, SUB);
+            self.send_block(&block)?;
+            self.bytes = end as u64;
+            self.stats.total_bytes += (end - offset) as u64;
+            self.report(TransferPhase::Transferring);
+            offset = end;
+            num = num.wrapping_add(1);
+        }
+        self.send_eot()
+    }
+
+    // ---------- 接收 ----------
+
+    fn read_block(&mut self, crc: bool, timeout: Duration) -> Result<Block> {
+        let size = match self.link.read_byte(timeout)? {
+            None => return Ok(Block::Timeout),
+            Some(SOH) => 128,
+            Some(STX) => 1024,
+            Some(EOT) => return Ok(Block::Eot),
+            Some(CAN) if self.second_can()? => return Ok(Block::Cancelled),
+            Some(_) => return Ok(Block::Bad),
+        };
+        let mut rest = vec![0u8; 2 + size + if crc { 2 } else { 1 }];
+        if self.link.read_exact(&mut rest, BLOCK_BODY_TIMEOUT).is_err() {
+            return Ok(Block::Bad);
+        }
+        if rest[0] != !rest[1] {
+            return Ok(Block::Bad);
+        }
+        let payload = &rest[2..2 + size];
+        let valid = if crc {
+            crc16_xmodem(payload).to_be_bytes() == rest[2 + size..]
+        } else {
+            checksum8(payload) == rest[2 + size]
+        };
+        if !valid {
+            return Ok(Block::Bad);
+        }
+        Ok(Block::Data { num: rest[0], data: payload.to_vec() })
+    }
+
+    /// 接收数据块直到 EOT；`poke` 为尚未收到首块时重复发送的启动字符
+    fn receive_data(&mut self, mut crc: bool, mut poke: Option<u8>, double_eot: bool) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut expected: u8 = 1;
+        let mut errors = 0;
+        let mut start_polls = 0;
+        let start_deadline = Instant::now() + Duration::from_secs(self.options.start_timeout_secs);
+        self.bytes = 0;
+        if let Some(c) = poke {
+            self.link.write_all(&[c])?;
+        }
+        loop {
+            self.check_cancel()?;
+            let timeout = match poke {
+                Some(_) => START_POLL.min(start_deadline.saturating_duration_since(Instant::now())),
+                None => BLOCK_TIMEOUT,
+            };
+            match self.read_block(crc, timeout)? {
+                Block::Data { num, data: payload } if num == expected => {
+                    self.link.write_all(&[ACK])?;
+                    self.stats.blocks += 1;
+                    self.stats.total_bytes += payload.len() as u64;
+                    data.extend_from_slice(&payload);
+                    self.bytes = data.len() as u64;
+                    expected = expected.wrapping_add(1);
+                    errors = 0;
+                    poke = None;
+                    self.report(TransferPhase::Transferring);
+                }
+                // 发送方未收到上一块的 ACK 而重发
+                Block::Data { num, .. } if num == expected.wrapping_sub(1) => {
+                    self.stats.retries += 1;
+                    self.link.write_all(&[ACK])?;
+                }
+                Block::Data { .. } => return Err(self.fail("Block sequence error")),
+                Block::Eot => {
+                    if double_eot {
+                        self.link.write_all(&[NAK])?;
+                        match self.link.read_byte(BLOCK_TIMEOUT)? {
+                            Some(EOT) | None => {}
+                            Some(_) => return Err(self.fail("Unexpected data after EOT")),
+                        }
+                    }
+                    self.link.write_all(&[ACK])?;
+                    return Ok(data);
+                }
+                Block::Cancelled => {
+                    self.report(TransferPhase::Cancelled);
+                    bail!("Cancelled by sender");
+                }
+                Block::Timeout => {
+                    self.stats.timeouts += 1;
+                    // 等待首块期间按 start_timeout_secs 计时，不计入块错误
+                    if poke.is_some() {
+                        if Instant::now() >= start_deadline {
+                            return Err(self.fail("Timed out waiting for sender"));
+                        }
+                        start_polls += 1;
+                    } else {
+                        errors += 1;
+                    }
+                    // XMODEM-CRC 接收：发送方不支持 CRC 时退回累加和
+                    if poke == Some(CRC_START)
+                        && self.options.protocol == XmodemProtocol::XmodemCrc
+                        && start_polls == 3
+                    {
+                        crc = false;
+                        poke = Some(NAK);
+                    }
+                    self.link.write_all(&[poke.unwrap_or(NAK)])?;
+                }
+                Block::Bad => {
+                    self.stats.crc_errors += 1;
+                    errors += 1;
+                    self.purge()?;
+                    self.link.write_all(&[NAK])?;
+                }
+            }
+            if errors > self.options.max_retries {
+                return Err(self.fail("Too many errors"));
+            }
+        }
+    }
+}
+
+/// 发送文件；XMODEM 系列只发送第一个文件
+pub fn send<L: SerialLink>(
+    link: &mut L,
+    options: &XmodemOptions,
+    files: &[OutgoingFile],
+    control: &TransferControl,
+    progress: &mut dyn FnMut(&TransferProgress),
+) -> Result<TransferStats> {
+    if files.is_empty() {
+        bail!("No file to send");
+    }
+    let mut t = Transfer::new(link, options, control, progress);
+    if options.protocol == XmodemProtocol::Ymodem {
+        t.file_count = Some(files.len());
+        for (i, file) in files.iter().enumerate() {
+            t.file_index = i;
+            t.file_name = Some(file.name.clone());
+            t.total = Some(file.data.len() as u64);
+            t.bytes = 0;
+            t.wait_start()?;
+            t.send_block(&ymodem_header_block(Some(file))?)?;
+            t.wait_start()?;
+            t.send_data(&file.data, true)?;
+        }
+        // 空块 0 结束批量传输
+        t.wait_start()?;
+        t.send_block(&ymodem_header_block(None)?)?;
+    } else {
+        let file = &files[0];
+        t.file_name = Some(file.name.clone());
+        t.file_count = Some(1);
+        let crc = t.wait_start()?;
+        t.send_data(&file.data, crc)?;
+    }
+    t.report(TransferPhase::Completed);
+    Ok(t.stats)
+}
+
+/// 接收文件；XMODEM 系列返回单个无名文件
+pub fn receive<L: SerialLink>(
+    link: &mut L,
+    options: &XmodemOptions,
+    control: &TransferControl,
+    progress: &mut dyn FnMut(&TransferProgress),
+) -> Result<(Vec<ReceivedFile>, TransferStats)> {
+    let mut t = Transfer::new(link, options, control, progress);
+    let mut files = Vec::new();
+    t.report(TransferPhase::Waiting);
+
+    if options.protocol == XmodemProtocol::Ymodem {
+        loop {
+            // 请求块 0
+            let deadline = Instant::now() + Duration::from_secs(options.start_timeout_secs);
+            let header = loop {
+                t.check_cancel()?;
+                t.link.write_all(&[CRC_START])?;
+                let timeout = START_POLL.min(deadline.saturating_duration_since(Instant::now()));
+                match t.read_block(true, timeout)? {
+                    Block::Data { num: 0, data } => break data,
+                    Block::Cancelled => {
+                        t.report(TransferPhase::Cancelled);
+                        bail!("Cancelled by sender");
+                    }
+                    Block::Bad => {
+                        t.stats.crc_errors += 1;
+                        t.purge()?;
+                    }
+                    _ => t.stats.timeouts += 1,
+                }
+                if Instant::now() >= deadline {
+                    return Err(t.fail("Timed out waiting for sender"));
+                }
+            };
+            t.link.write_all(&[ACK])?;
+            let (name, size) = parse_ymodem_header(&header);
+            if name.is_empty() {
+                break;
+            }
+            t.file_index = files.len();
+            t.file_name = Some(name.clone());
+            t.total = size;
+            let mut data = t.receive_data(true, Some(CRC_START), true)?;
+            if let Some(size) = size {
+                data.truncate(size as usize);
+            }
+            files.push(ReceivedFile { name: Some(name), data });
+        }
+    } else {
+        let crc = options.protocol != XmodemProtocol::Xmodem;
+        let poke = if crc { CRC_START } else { NAK };
+        let mut data = t.receive_data(crc, Some(poke), false)?;
+        if options.trim_padding {
+            while data.last() == Some(&SUB) {
+                data.pop();
+            }
+        }
+        files.push(ReceivedFile { name: None, data });
+    }
+    t.report(TransferPhase::Completed);
+    Ok((files, t.stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+
+    fn round_trip(protocol: XmodemProtocol, files: Vec<OutgoingFile>) -> Vec<ReceivedFile> {
+        let (mut a, mut b) = pipe_pair();
+        let options = XmodemOptions::new(protocol);
+        let sender_options = options.clone();
+        let sender = std::thread::spawn(move || {
+            send(&mut a, &sender_options, &files, &TransferControl::new(), &mut |_| {}).unwrap()
+        });
+        let (received, _) = receive(&mut b, &options, &TransferControl::new(), &mut |_| {}).unwrap();
+        let stats = sender.join().unwrap();
+        assert_eq!(stats.retries, 0);
+        received
+    }
+
+    #[test]
+    fn test_xmodem_1k_round_trip() {
+        let data: Vec<u8> = (0..2100u32).map(|i| i as u8).collect();
+        let file = OutgoingFile { name: "a.bin".to_string(), data: data.clone(), modified: None };
+        let received = round_trip(XmodemProtocol::Xmodem1k, vec![file]);
+        // 2 个 1K 块 + 1 个 128 字节短块
+        assert_eq!(received[0].data.len(), 2048 + 128);
+        assert_eq!(&received[0].data[..2100], &data[..]);
+    }
+
+    #[test]
+    fn test_ymodem_batch_round_trip() {
+        let files = vec![
+            OutgoingFile { name: "one.txt".to_string(), data: b"hello".to_vec(), modified: None },
+            OutgoingFile { name: "two.bin".to_string(), data: vec![0x5A; 1500], modified: Some(1) },
+        ];
+        let received = round_trip(XmodemProtocol::Ymodem, files);
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].name.as_deref(), Some("one.txt"));
+        assert_eq!(received[0].data, b"hello");
+        assert_eq!(received[1].data, vec![0x5A; 1500]);
+    }
+
+    #[test]
+    fn test_receive_start_timeout() {
+        let (_sender, mut b) = pipe_pair();
+        for protocol in [XmodemProtocol::XmodemCrc, XmodemProtocol::Ymodem] {
+            let options = XmodemOptions { start_timeout_secs: 1, ..XmodemOptions::new(protocol) };
+            let started = Instant::now();
+            let err = receive(&mut b, &options, &TransferControl::new(), &mut |_| {}).unwrap_err();
+            assert!(err.to_string().contains("Timed out waiting for sender"));
+            assert!(started.elapsed() < Duration::from_secs(3));
+        }
+    }
+
+    #[test]
+    fn test_crc16_xmodem() {
+        assert_eq!(crc16_xmodem(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn test_build_block() {
+        let block = build_block(1, b"AB", 128, false, SUB);
+        assert_eq!(block.len(), 132);
+        assert_eq!(&block[..5], &[SOH, 0x01, 0xFE, b'A', b'B']);
+        assert_eq!(block[5], SUB);
+        assert_eq!(block[131], checksum8(&block[3..131]));
+
+        let block = build_block(2, &[0u8; 1024], 1024, true, SUB);
+        assert_eq!(block.len(), 1029);
+        assert_eq!(block[0], STX);
+    }
+
+    #[test]
+    fn test_ymodem_header() {
+        let file = OutgoingFile { name: "fw.bin".to_string(), data: vec![0; 1500], modified: Some(8) };
+        let header = ymodem_header(Some(&file));
+        assert_eq!(header, b"fw.bin\x001500 10".to_vec());
+        let block = build_block(0, &header, 128, true, 0);
+        assert_eq!(parse_ymodem_header(&block[3..131]), ("fw.bin".to_string(), Some(1500)));
+        assert_eq!(parse_ymodem_header(&[0u8; 128]).0, "");
+    }
+
+    #[test]
+    fn test_ymodem_long_file_name() {
+        let name = format!("{}.bin", "n".repeat(200));
+        let file = OutgoingFile { name: name.clone(), data: vec![1; 10], modified: None };
+        let block = ymodem_header_block(Some(&file)).unwrap();
+        assert_eq!((block[0], block.len()), (STX, 1029));
+        assert_eq!(ymodem_header_block(None).unwrap().len(), 133);
+
+        let received = round_trip(XmodemProtocol::Ymodem, vec![file]);
+        assert_eq!(received[0].name.as_deref(), Some(name.as_str()));
+        assert_eq!(received[0].data, vec![1; 10]);
+    }
+}
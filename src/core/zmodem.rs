@@ -0,0 +1,978 @@
+//! ZMODEM 文件传输
+//!
+//! 与嵌入式 Linux 上的 `sz`/`rz` (lrzsz) 互通：
+//! - 接收：应答 ZRINIT，按 ZRPOS 续传，数据写入目标目录
+//! - 发送：ZRQINIT → ZFILE → ZDATA 流式发送，接收方 ZRPOS 时从指定位置重发 (断点续传)
+//! - 对端支持时使用 CRC32 (ZBIN32) 帧，否则退回 CRC16
+//!
+//! `ZmodemDetector` 在 RX 流中识别自动启动序列 `**\x18B0`，供界面提示开始传输。
+
+use anyhow::{Result, anyhow, bail};
+use serde::{Serialize, Deserialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use super::file_transfer::{OutgoingFile, TransferControl, TransferPhase, TransferProgress, TransferStats};
+use super::session_link::SerialLink;
+use super::checksum::{crc16_xmodem, crc32};
+
+pub const ZPAD: u8 = b'*';
+pub const ZDLE: u8 = 0x18;
+pub const ZBIN: u8 = b'A';
+pub const ZHEX: u8 = b'B';
+pub const ZBIN32: u8 = b'C';
+const XON: u8 = 0x11;
+
+// 帧类型
+pub const ZRQINIT: u8 = 0;
+pub const ZRINIT: u8 = 1;
+pub const ZSINIT: u8 = 2;
+pub const ZACK: u8 = 3;
+pub const ZFILE: u8 = 4;
+pub const ZSKIP: u8 = 5;
+pub const ZNAK: u8 = 6;
+pub const ZABORT: u8 = 7;
+pub const ZFIN: u8 = 8;
+pub const ZRPOS: u8 = 9;
+pub const ZDATA: u8 = 10;
+pub const ZEOF: u8 = 11;
+pub const ZFERR: u8 = 12;
+pub const ZCHALLENGE: u8 = 14;
+pub const ZCAN: u8 = 16;
+
+// 数据子包结束符
+pub const ZCRCE: u8 = b'h';
+pub const ZCRCG: u8 = b'i';
+pub const ZCRCQ: u8 = b'j';
+pub const ZCRCW: u8 = b'k';
+const ZRUB0: u8 = b'l';
+const ZRUB1: u8 = b'm';
+
+// ZRINIT 能力标志 (ZF0)
+pub const CANFDX: u8 = 0x01;
+pub const CANOVIO: u8 = 0x02;
+pub const CANFC32: u8 = 0x20;
+pub const ESCCTL: u8 = 0x40;
+
+/// ZFILE 转换选项 (ZF0)：断点续传
+pub const ZCRESUM: u8 = 3;
+
+/// 子包最大长度 (接收端上限)
+const MAX_SUBPACKET: usize = 8192;
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 传输参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZmodemOptions {
+    /// 发送子包长度
+    #[serde(default = "default_subpacket")]
+    pub subpacket_size: usize,
+    /// 发送方每隔多少字节等待一次 ZACK (0 表示全程流式)
+    #[serde(default = "default_window")]
+    pub window: usize,
+    /// 对端支持时使用 CRC32
+    #[serde(default = "default_true")]
+    pub crc32: bool,
+    /// 断点续传：接收时追加到已有的部分文件，发送时请求对端续传
+    #[serde(default = "default_true")]
+    pub resume: bool,
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_subpacket() -> usize {
+    1024
+}
+
+fn default_window() -> usize {
+    16 * 1024
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_retries() -> u32 {
+    10
+}
+
+impl Default for ZmodemOptions {
+    fn default() -> Self {
+        Self {
+            subpacket_size: default_subpacket(),
+            window: default_window(),
+            crc32: true,
+            resume: true,
+            max_retries: default_max_retries(),
+        }
+    }
+}
+
+/// ZDLE 转义
+fn escape_into(out: &mut Vec<u8>, data: &[u8], escctl: bool) {
+    for &b in data {
+        match b {
+            ZDLE | 0x10 | 0x11 | 0x13 | 0x90 | 0x91 | 0x93 => out.extend_from_slice(&[ZDLE, b ^ 0x40]),
+            b if escctl && b & 0x60 == 0 => out.extend_from_slice(&[ZDLE, b ^ 0x40]),
+            b => out.push(b),
+        }
+    }
+}
+
+fn hex_nibble(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// 帧头
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: u8,
+    /// ZP0..ZP3 (位置为小端)；标志位时 data[3] 为 ZF0
+    pub data: [u8; 4],
+    /// 帧头以 ZBIN32 发送，后续数据子包使用 CRC32
+    pub crc32: bool,
+}
+
+impl Header {
+    pub fn new(frame_type: u8, data: [u8; 4]) -> Self {
+        Self { frame_type, data, crc32: false }
+    }
+
+    pub fn with_position(frame_type: u8, position: u64) -> Self {
+        Self::new(frame_type, (position as u32).to_le_bytes())
+    }
+
+    pub fn position(&self) -> u64 {
+        u32::from_le_bytes(self.data) as u64
+    }
+
+    /// ZF0 标志
+    pub fn zf0(&self) -> u8 {
+        self.data[3]
+    }
+
+    fn raw(&self) -> [u8; 5] {
+        [self.frame_type, self.data[0], self.data[1], self.data[2], self.data[3]]
+    }
+
+    /// 十六进制帧头
+    pub fn encode_hex(&self) -> Vec<u8> {
+        let raw = self.raw();
+        let crc = crc16_xmodem(&raw);
+        let mut out = vec![ZPAD, ZPAD, ZDLE, ZHEX];
+        for b in raw.iter().chain(crc.to_be_bytes().iter()) {
+            out.extend_from_slice(format!("{:02x}", b).as_bytes());
+        }
+        out.extend_from_slice(&[b'\r', 0x8A]);
+        if self.frame_type != ZACK && self.frame_type != ZFIN {
+            out.push(XON);
+        }
+        out
+    }
+
+    /// 二进制帧头 (CRC16 或 CRC32)
+    pub fn encode_binary(&self, use_crc32: bool, escctl: bool) -> Vec<u8> {
+        let raw = self.raw();
+        let mut out = vec![ZPAD, ZDLE, if use_crc32 { ZBIN32 } else { ZBIN }];
+        escape_into(&mut out, &raw, escctl);
+        if use_crc32 {
+            escape_into(&mut out, &crc32(&raw).to_le_bytes(), escctl);
+        } else {
+            escape_into(&mut out, &crc16_xmodem(&raw).to_be_bytes(), escctl);
+        }
+        out
+    }
+}
+
+/// 编码数据子包
+pub fn encode_subpacket(data: &[u8], end: u8, use_crc32: bool, escctl: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8 + 8);
+    escape_into(&mut out, data, escctl);
+    out.extend_from_slice(&[ZDLE, end]);
+    let mut covered = data.to_vec();
+    covered.push(end);
+    if use_crc32 {
+        escape_into(&mut out, &crc32(&covered).to_le_bytes(), escctl);
+    } else {
+        escape_into(&mut out, &crc16_xmodem(&covered).to_be_bytes(), escctl);
+    }
+    if end == ZCRCW {
+        out.push(XON);
+    }
+    out
+}
+
+/// ZDLE 解码后的字节
+enum ZByte {
+    Byte(u8),
+    /// 子包结束符
+    End(u8),
+}
+
+/// 读取数据子包的结果
+enum Subpacket {
+    Data { data: Vec<u8>, end: u8 },
+    /// CRC 错误、超长或超时
+    Error,
+}
+
+/// 自动启动检测结果 (本端应执行的动作)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZmodemStart {
+    /// 对端运行了 `sz` (ZRQINIT)，本端应接收
+    Receive,
+    /// 对端运行了 `rz` (ZRINIT)，本端应发送
+    Send,
+}
+
+/// RX 流中的 ZMODEM 自动启动检测
+#[derive(Debug, Default)]
+pub struct ZmodemDetector {
+    tail: Vec<u8>,
+}
+
+impl ZmodemDetector {
+    const PREFIX: &'static [u8] = b"**\x18B0";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> Option<ZmodemStart> {
+        let mut buf = std::mem::take(&mut self.tail);
+        buf.extend_from_slice(data);
+        let mut found = None;
+        let mut search_from = 0;
+        while let Some(pos) = buf[search_from..]
+            .windows(Self::PREFIX.len())
+            .position(|w| w == Self::PREFIX)
+        {
+            let at = search_from + pos + Self::PREFIX.len();
+            match buf.get(at) {
+                Some(b'0') => found = Some(ZmodemStart::Receive),
+                Some(b'1') => found = Some(ZmodemStart::Send),
+                Some(_) => {}
+                // 序列被切在块尾，留到下一块再判断
+                None => {
+                    self.tail = buf[search_from + pos..].to_vec();
+                    return found;
+                }
+            }
+            search_from = at;
+        }
+        let keep = buf.len().min(Self::PREFIX.len() - 1);
+        self.tail = buf[buf.len() - keep..].to_vec();
+        found
+    }
+}
+
+/// 一次 ZMODEM 会话
+struct Session<'a, L: SerialLink> {
+    link: &'a mut L,
+    options: &'a ZmodemOptions,
+    control: &'a TransferControl,
+    progress: &'a mut dyn FnMut(&TransferProgress),
+    stats: TransferStats,
+    use_crc32: bool,
+    escctl: bool,
+    file_name: Option<String>,
+    bytes: u64,
+    total: Option<u64>,
+    file_index: usize,
+    file_count: Option<usize>,
+}
+
+impl<'a, L: SerialLink> Session<'a, L> {
+    fn new(
+        link: &'a mut L,
+        options: &'a ZmodemOptions,
+        control: &'a TransferControl,
+        progress: &'a mut dyn FnMut(&TransferProgress),
+    ) -> Self {
+        Self {
+            link,
+            options,
+            control,
+            progress,
+            stats: TransferStats::default(),
+            use_crc32: false,
+            escctl: false,
+            file_name: None,
+            bytes: 0,
+            total: None,
+            file_index: 0,
+            file_count: None,
+        }
+    }
+
+    fn report(&mut self, phase: TransferPhase) {
+        let progress = TransferProgress {
+            phase,
+            file_name: self.file_name.clone(),
+            bytes: self.bytes,
+            total: self.total,
+            file_index: self.file_index,
+            file_count: self.file_count,
+            stats: self.stats.clone(),
+        };
+        (self.progress)(&progress);
+    }
+
+    /// 取消序列：8 个 CAN + 8 个退格
+    fn abort(&mut self) {
+        let mut seq = vec![ZDLE; 8];
+        seq.extend_from_slice(&[0x08; 8]);
+        let _ = self.link.write_all(&seq);
+    }
+
+    fn check_cancel(&mut self) -> Result<()> {
+        if self.control.is_cancelled() {
+            self.abort();
+            self.report(TransferPhase::Cancelled);
+            bail!("Transfer cancelled");
+        }
+        Ok(())
+    }
+
+    fn fail(&mut self, message: &str) -> anyhow::Error {
+        self.abort();
+        self.report(TransferPhase::Failed);
+        anyhow!("{}", message)
+    }
+
+    fn send_hex(&mut self, header: Header) -> Result<()> {
+        self.link.write_all(&header.encode_hex())
+    }
+
+    fn send_binary(&mut self, header: Header) -> Result<()> {
+        let encoded = header.encode_binary(self.use_crc32, self.escctl);
+        self.link.write_all(&encoded)
+    }
+
+    fn send_subpacket(&mut self, data: &[u8], end: u8) -> Result<()> {
+        let encoded = encode_subpacket(data, end, self.use_crc32, self.escctl);
+        self.link.write_all(&encoded)
+    }
+
+    // ---------- 解码 ----------
+
+    fn zdl_read(&mut self, timeout: Duration) -> Result<Option<ZByte>> {
+        loop {
+            match self.link.read_byte(timeout)? {
+                None => return Ok(None),
+                Some(ZDLE) => break,
+                // 流控字符
+                Some(XON) | Some(0x13) | Some(0x91) | Some(0x93) => continue,
+                Some(b) => return Ok(Some(ZByte::Byte(b))),
+            }
+        }
+        let mut cans = 1;
+        loop {
+            let c = match self.link.read_byte(timeout)? {
+                None => return Ok(None),
+                Some(c) => c,
+            };
+            return Ok(Some(match c {
+                ZDLE => {
+                    cans += 1;
+                    if cans >= 5 {
+                        self.report(TransferPhase::Cancelled);
+                        bail!("Cancelled by peer");
+                    }
+                    continue;
+                }
+                ZCRCE | ZCRCG | ZCRCQ | ZCRCW => ZByte::End(c),
+                ZRUB0 => ZByte::Byte(0x7F),
+                ZRUB1 => ZByte::Byte(0xFF),
+                XON | 0x13 | 0x91 | 0x93 => continue,
+                c if c & 0x60 == 0x40 => ZByte::Byte(c ^ 0x40),
+                // 非法转义：按原值交给 CRC 校验判定
+                c => ZByte::Byte(c),
+            }));
+        }
+    }
+
+    fn read_raw_bytes(&mut self, n: usize) -> Result<Option<Vec<u8>>> {
+        let mut out = Vec::with_capacity(n);
+        while out.len() < n {
+            match self.zdl_read(Duration::from_secs(1))? {
+                Some(ZByte::Byte(b)) => out.push(b),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(out))
+    }
+
+    /// 扫描并读取下一个帧头，超时返回 None；连续 5 个 CAN 视为对端取消
+    fn read_header(&mut self, timeout: Duration) -> Result<Option<Header>> {
+        let deadline = Instant::now() + timeout;
+        let mut cans = 0;
+        loop {
+            self.check_cancel()?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let b = match self.link.read_byte(remaining.min(Duration::from_millis(500)))? {
+                Some(b) => b,
+                None if remaining.is_zero() => return Ok(None),
+                None => continue,
+            };
+            if b == ZDLE {
+                cans += 1;
+                if cans >= 5 {
+                    self.report(TransferPhase::Cancelled);
+                    bail!("Cancelled by peer");
+                }
+                continue;
+            }
+            cans = 0;
+            if b & 0x7F != ZPAD {
+                continue;
+            }
+            // ZPAD [ZPAD] ZDLE 格式字符
+            let mut c = b;
+            while c & 0x7F == ZPAD {
+                c = match self.link.read_byte(Duration::from_secs(1))? {
+                    Some(c) => c,
+                    None => break,
+                };
+            }
+            if c != ZDLE {
+                continue;
+            }
+            let format = match self.link.read_byte(Duration::from_secs(1))? {
+                Some(f) => f,
+                None => continue,
+            };
+            let header = match format {
+                ZHEX => self.read_hex_header()?,
+                ZBIN => self.read_binary_header(false)?,
+                ZBIN32 => self.read_binary_header(true)?,
+                _ => None,
+            };
+            match header {
+                Some(h) => return Ok(Some(h)),
+                None => self.stats.crc_errors += 1,
+            }
+        }
+    }
+
+    fn read_hex_header(&mut self) -> Result<Option<Header>> {
+        let mut bytes = [0u8; 7];
+        for byte in bytes.iter_mut() {
+            let mut value = 0;
+            for _ in 0..2 {
+                let c = match self.link.read_byte(Duration::from_secs(1))? {
+                    Some(c) => c,
+                    None => return Ok(None),
+                };
+                match hex_nibble(c) {
+                    Some(n) => value = (value << 4) | n,
+                    None => return Ok(None),
+                }
+            }
+            *byte = value;
+        }
+        // CR LF (LF 可能带最高位)，可选 XON
+        for _ in 0..2 {
+            let _ = self.link.read_byte(Duration::from_millis(100))?;
+        }
+        if crc16_xmodem(&bytes[..5]).to_be_bytes() != bytes[5..] {
+            return Ok(None);
+        }
+        Ok(Some(Header::new(bytes[0], [bytes[1], bytes[2], bytes[3], bytes[4]])))
+    }
+
+    fn read_binary_header(&mut self, use_crc32: bool) -> Result<Option<Header>> {
+        let crc_len = if use_crc32 { 4 } else { 2 };
+        let bytes = match self.read_raw_bytes(5 + crc_len)? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+        let valid = if use_crc32 {
+            crc32(&bytes[..5]).to_le_bytes() == bytes[5..]
+        } else {
+            crc16_xmodem(&bytes[..5]).to_be_bytes() == bytes[5..]
+        };
+        if !valid {
+            return Ok(None);
+        }
+        Ok(Some(Header {
+            frame_type: bytes[0],
+            data: [bytes[1], bytes[2], bytes[3], bytes[4]],
+            crc32: use_crc32,
+        }))
+    }
+
+    fn read_subpacket(&mut self, use_crc32: bool) -> Result<Subpacket> {
+        let mut data = Vec::new();
+        let end = loop {
+            match self.zdl_read(Duration::from_secs(5))? {
+                Some(ZByte::Byte(b)) => {
+                    if data.len() >= MAX_SUBPACKET {
+                        return Ok(Subpacket::Error);
+                    }
+                    data.push(b);
+                }
+                Some(ZByte::End(end)) => break end,
+                None => {
+                    self.stats.timeouts += 1;
+                    return Ok(Subpacket::Error);
+                }
+            }
+        };
+        let crc_len = if use_crc32 { 4 } else { 2 };
+        let crc = match self.read_raw_bytes(crc_len)? {
+            Some(c) => c,
+            None => return Ok(Subpacket::Error),
+        };
+        let mut covered = data.clone();
+        covered.push(end);
+        let valid = if use_crc32 {
+            crc32(&covered).to_le_bytes() == crc[..]
+        } else {
+            crc16_xmodem(&covered).to_be_bytes() == crc[..]
+        };
+        if !valid {
+            self.stats.crc_errors += 1;
+            return Ok(Subpacket::Error);
+        }
+        Ok(Subpacket::Data { data, end })
+    }
+
+    /// 不阻塞地检查对端是否发来了帧头 (发送数据期间的 ZRPOS 等)
+    fn poll_header(&mut self) -> Result<Option<Header>> {
+        while let Some(b) = self.link.read_byte(Duration::ZERO)? {
+            if b & 0x7F == ZPAD {
+                return self.read_header(Duration::from_secs(1));
+            }
+        }
+        Ok(None)
+    }
+
+    // ---------- 发送 ----------
+
+    fn send_files(&mut self, files: &[OutgoingFile]) -> Result<()> {
+        self.report(TransferPhase::Waiting);
+        // 对端 shell 上启动 rz (已在运行时作为普通输入忽略)
+        self.link.write_all(b"rz\r")?;
+        self.send_hex(Header::new(ZRQINIT, [0; 4]))?;
+
+        let mut attempts = 0;
+        let rinit = loop {
+            match self.read_header(HEADER_TIMEOUT)? {
+                Some(h) if h.frame_type == ZRINIT => break h,
+                Some(h) if h.frame_type == ZCHALLENGE => self.send_hex(Header::new(ZACK, h.data))?,
+                Some(h) if h.frame_type == ZCAN || h.frame_type == ZABORT => {
+                    return Err(self.fail("Aborted by receiver"));
+                }
+                Some(_) => {}
+                None => {
+                    self.stats.timeouts += 1;
+                    attempts += 1;
+                    if attempts > self.options.max_retries {
+                        return Err(self.fail("Timed out waiting for receiver"));
+                    }
+                    self.send_hex(Header::new(ZRQINIT, [0; 4]))?;
+                }
+            }
+        };
+        self.use_crc32 = self.options.crc32 && rinit.zf0() & CANFC32 != 0;
+        self.escctl = rinit.zf0() & ESCCTL != 0;
+
+        self.file_count = Some(files.len());
+        for (i, file) in files.iter().enumerate() {
+            self.file_index = i;
+            self.send_file(file)?;
+        }
+
+        // 结束会话
+        for _ in 0..=self.options.max_retries {
+            self.send_hex(Header::new(ZFIN, [0; 4]))?;
+            match self.read_header(HEADER_TIMEOUT)? {
+                Some(h) if h.frame_type == ZFIN => {
+                    self.link.write_all(b"OO")?;
+                    return Ok(());
+                }
+                Some(_) => {}
+                None => self.stats.timeouts += 1,
+            }
+        }
+        // 对端未回应 ZFIN 时文件已全部确认，不视为失败
+        Ok(())
+    }
+
+    fn send_file(&mut self, file: &OutgoingFile) -> Result<()> {
+        let len = file.data.len() as u64;
+        self.file_name = Some(file.name.clone());
+        self.total = Some(len);
+        self.bytes = 0;
+
+        let mut info = file.name.as_bytes().to_vec();
+        info.push(0);
+        info.extend_from_slice(format!("{} {:o} 0 0 1 {}", len, file.modified.unwrap_or(0), len).as_bytes());
+        info.push(0);
+
+        let zf0 = if self.options.resume { ZCRESUM } else { 0 };
+        let mut attempts = 0;
+        let mut pos = loop {
+            self.check_cancel()?;
+            self.send_binary(Header::new(ZFILE, [0, 0, 0, zf0]))?;
+            self.send_subpacket(&info, ZCRCW)?;
+            let mut reply = self.read_header(HEADER_TIMEOUT)?;
+            // 对 ZRQINIT 的重复 ZRINIT 不代表 ZFILE 丢失，继续等待，避免对端重复应答 ZRPOS
+            if reply.is_some_and(|h| h.frame_type == ZRINIT) {
+                reply = self.read_header(HEADER_TIMEOUT)?;
+            }
+            match reply {
+                Some(h) if h.frame_type == ZRPOS => break h.position().min(len),
+                Some(h) if h.frame_type == ZSKIP => {
+                    self.report(TransferPhase::Completed);
+                    return Ok(());
+                }
+                Some(h) if h.frame_type == ZCAN || h.frame_type == ZABORT || h.frame_type == ZFERR => {
+                    return Err(self.fail("Aborted by receiver"));
+                }
+                _ => {
+                    attempts += 1;
+                    self.stats.retries += 1;
+                    if attempts > self.options.max_retries {
+                        return Err(self.fail("No response to ZFILE"));
+                    }
+                }
+            }
+        };
+
+        let mut errors = 0;
+        'frame: loop {
+            if errors > self.options.max_retries {
+                return Err(self.fail("Too many retries"));
+            }
+            self.check_cancel()?;
+            self.bytes = pos;
+            self.report(TransferPhase::Transferring);
+            self.send_binary(Header::with_position(ZDATA, pos))?;
+
+            let mut acked = pos;
+            while pos < len {
+                self.check_cancel()?;
+                let end = (pos + self.options.subpacket_size as u64).min(len);
+                let last = end == len;
+                let wait_ack = !last && self.options.window > 0 && end - acked >= self.options.window as u64;
+                let terminator = if last {
+                    ZCRCE
+                } else if wait_ack {
+                    ZCRCW
+                } else {
+                    ZCRCG
+                };
+                self.send_subpacket(&file.data[pos as usize..end as usize], terminator)?;
+                self.stats.blocks += 1;
+                self.stats.total_bytes += end - pos;
+                pos = end;
+                self.bytes = pos;
+                self.report(TransferPhase::Transferring);
+
+                let reply = if wait_ack {
+                    self.read_header(HEADER_TIMEOUT)?
+                } else {
+                    self.poll_header()?
+                };
+                match reply {
+                    // ZCRCW 结束了当前帧，确认后以新的 ZDATA 帧继续
+                    Some(h) if h.frame_type == ZACK && wait_ack => continue 'frame,
+                    Some(h) if h.frame_type == ZACK => acked = pos,
+                    Some(h) if h.frame_type == ZRPOS => {
+                        // 接收方出错，从其指定位置重发
+                        pos = h.position().min(len);
+                        self.stats.retries += 1;
+                        errors += 1;
+                        continue 'frame;
+                    }
+                    Some(h) if h.frame_type == ZCAN || h.frame_type == ZABORT || h.frame_type == ZFERR => {
+                        return Err(self.fail("Aborted by receiver"));
+                    }
+                    None if wait_ack => {
+                        self.stats.timeouts += 1;
+                        pos = acked;
+                        errors += 1;
+                        continue 'frame;
+                    }
+                    _ => {}
+                }
+            }
+
+            // 文件结束
+            for _ in 0..=self.options.max_retries {
+                self.send_binary(Header::with_position(ZEOF, len))?;
+                match self.read_header(HEADER_TIMEOUT)? {
+                    Some(h) if h.frame_type == ZRINIT => {
+                        self.report(TransferPhase::Completed);
+                        return Ok(());
+                    }
+                    Some(h) if h.frame_type == ZRPOS => {
+                        pos = h.position().min(len);
+                        self.stats.retries += 1;
+                        errors += 1;
+                        continue 'frame;
+                    }
+                    Some(h) if h.frame_type == ZCAN || h.frame_type == ZABORT || h.frame_type == ZFERR => {
+                        return Err(self.fail("Aborted by receiver"));
+                    }
+                    Some(_) => {}
+                    None => self.stats.timeouts += 1,
+                }
+            }
+            return Err(self.fail("No response to ZEOF"));
+        }
+    }
+
+    // ---------- 接收 ----------
+
+    fn send_rinit(&mut self) -> Result<()> {
+        let mut flags = CANFDX | CANOVIO;
+        if self.options.crc32 {
+            flags |= CANFC32;
+        }
+        self.send_hex(Header::new(ZRINIT, [0, 0, 0, flags]))
+    }
+
+    fn receive_files(&mut self, dest_dir: &Path) -> Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dest_dir)?;
+        let mut files = Vec::new();
+        let mut attempts = 0;
+        self.report(TransferPhase::Waiting);
+        self.send_rinit()?;
+        loop {
+            let header = match self.read_header(HEADER_TIMEOUT)? {
+                Some(h) => h,
+                None => {
+                    self.stats.timeouts += 1;
+                    attempts += 1;
+                    if attempts > self.options.max_retries {
+                        return Err(self.fail("Timed out waiting for sender"));
+                    }
+                    self.send_rinit()?;
+                    continue;
+                }
+            };
+            attempts = 0;
+            match header.frame_type {
+                ZRQINIT => self.send_rinit()?,
+                ZSINIT => {
+                    let _ = self.read_subpacket(header.crc32)?;
+                    self.send_hex(Header::new(ZACK, [0; 4]))?;
+                }
+                ZFILE => {
+                    if let Some(path) = self.receive_file(header, dest_dir)? {
+                        files.push(path);
+                        self.file_index += 1;
+                    }
+                    self.send_rinit()?;
+                }
+                ZFIN => {
+                    self.send_hex(Header::new(ZFIN, [0; 4]))?;
+                    // 对端的 "OO"，可忽略
+                    let _ = self.link.read_byte(Duration::from_millis(500))?;
+                    let _ = self.link.read_byte(Duration::from_millis(100))?;
+                    return Ok(files);
+                }
+                ZCAN | ZABORT => {
+                    self.report(TransferPhase::Cancelled);
+                    bail!("Aborted by sender");
+                }
+                // 上一个文件的残留帧
+                _ => {}
+            }
+        }
+    }
+
+    /// 接收一个文件；文件信息损坏时返回 None (由发送方重发 ZFILE)
+    fn receive_file(&mut self, header: Header, dest_dir: &Path) -> Result<Option<PathBuf>> {
+        let info = match self.read_subpacket(header.crc32)? {
+            Subpacket::Data { data, .. } => data,
+            Subpacket::Error => return Ok(None),
+        };
+        let name_end = info.iter().position(|&b| b == 0).unwrap_or(info.len());
+        let raw_name = String::from_utf8_lossy(&info[..name_end]).to_string();
+        // 只取文件名部分，避免写出目标目录
+        let name = Path::new(&raw_name)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "zmodem.bin".to_string());
+        let size: Option<u64> = info
+            .get(name_end + 1..)
+            .map(|rest| String::from_utf8_lossy(rest).to_string())
+            .and_then(|rest| rest.split([' ', '\0']).next().and_then(|s| s.parse().ok()));
+
+        let path = dest_dir.join(&name);
+        let existing = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let resume = self.options.resume && existing > 0 && size.is_some_and(|s| existing < s);
+        let mut out = if resume {
+            std::fs::OpenOptions::new().append(true).open(&path)?
+        } else {
+            std::fs::File::create(&path)?
+        };
+        let mut pos = if resume { existing } else { 0 };
+
+        self.file_name = Some(name);
+        self.total = size;
+        self.bytes = pos;
+        self.report(TransferPhase::Transferring);
+        self.send_hex(Header::with_position(ZRPOS, pos))?;
+
+        let mut errors = 0;
+        loop {
+            if errors > self.options.max_retries {
+                return Err(self.fail("Too many errors"));
+            }
+            let header = match self.read_header(HEADER_TIMEOUT)? {
+                Some(h) => h,
+                None => {
+                    self.stats.timeouts += 1;
+                    errors += 1;
+                    self.send_hex(Header::with_position(ZRPOS, pos))?;
+                    continue;
+                }
+            };
+            match header.frame_type {
+                ZDATA => {
+                    if header.position() != pos {
+                        self.stats.retries += 1;
+                        errors += 1;
+                        self.send_hex(Header::with_position(ZRPOS, pos))?;
+                        continue;
+                    }
+                    loop {
+                        self.check_cancel()?;
+                        match self.read_subpacket(header.crc32)? {
+                            Subpacket::Data { data, end } => {
+                                out.write_all(&data)?;
+                                pos += data.len() as u64;
+                                self.stats.blocks += 1;
+                                self.stats.total_bytes += data.len() as u64;
+                                self.bytes = pos;
+                                self.report(TransferPhase::Transferring);
+                                errors = 0;
+                                match end {
+                                    ZCRCW => {
+                                        self.send_hex(Header::with_position(ZACK, pos))?;
+                                        break;
+                                    }
+                                    ZCRCQ => self.send_hex(Header::with_position(ZACK, pos))?,
+                                    ZCRCE => break,
+                                    _ => {}
+                                }
+                            }
+                            Subpacket::Error => {
+                                self.stats.retries += 1;
+                                errors += 1;
+                                self.send_hex(Header::with_position(ZRPOS, pos))?;
+                                break;
+                            }
+                        }
+                    }
+                }
+                ZEOF if header.position() == pos => {
+                    out.flush()?;
+                    self.report(TransferPhase::Completed);
+                    return Ok(Some(path));
+                }
+                // 发送方未收到 ZRPOS 而重发 ZFILE
+                ZFILE => {
+                    let _ = self.read_subpacket(header.crc32)?;
+                    self.send_hex(Header::with_position(ZRPOS, pos))?;
+                }
+                ZCAN | ZABORT | ZFIN => {
+                    self.report(TransferPhase::Cancelled);
+                    bail!("Aborted by sender");
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// 发送文件 (对端运行 `rz` 或尚未启动时自动发送 "rz\r")
+pub fn send<L: SerialLink>(
+    link: &mut L,
+    options: &ZmodemOptions,
+    files: &[OutgoingFile],
+    control: &TransferControl,
+    progress: &mut dyn FnMut(&TransferProgress),
+) -> Result<TransferStats> {
+    if files.is_empty() {
+        bail!("No file to send");
+    }
+    let mut session = Session::new(link, options, control, progress);
+    session.send_files(files)?;
+    session.report(TransferPhase::Completed);
+    Ok(session.stats)
+}
+
+/// 接收文件到 `dest_dir` (对端运行 `sz`)，返回保存的文件路径
+pub fn receive<L: SerialLink>(
+    link: &mut L,
+    options: &ZmodemOptions,
+    dest_dir: &Path,
+    control: &TransferControl,
+    progress: &mut dyn FnMut(&TransferProgress),
+) -> Result<(Vec<PathBuf>, TransferStats)> {
+    let mut session = Session::new(link, options, control, progress);
+    let files = session.receive_files(dest_dir)?;
+    session.report(TransferPhase::Completed);
+    Ok((files, session.stats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::session_link::pipe_pair;
+
+    #[test]
+    fn test_hex_header() {
+        // sz 发出的 ZRQINIT
+        let encoded = Header::new(ZRQINIT, [0; 4]).encode_hex();
+        assert_eq!(&encoded[..], b"**\x18B00000000000000\r\x8a\x11");
+    }
+
+    #[test]
+    fn test_detector_split_sequence() {
+        let mut detector = ZmodemDetector::new();
+        assert_eq!(detector.feed(b"$ sz fw.bin\r\nrz\r**\x18"), None);
+        assert_eq!(detector.feed(b"B00000000000000\r\x8a\x11"), Some(ZmodemStart::Receive));
+        assert_eq!(detector.feed(b"**\x18B0100000023be50\r\x8a\x11"), Some(ZmodemStart::Send));
+        assert_eq!(detector.feed(b"plain text"), None);
+    }
+
+    #[test]
+    fn test_round_trip_with_resume() {
+        let dir = std::env::temp_dir().join(format!("zmodem_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 含需要转义的字节
+        let data: Vec<u8> = (0..40_000u32).map(|i| (i * 7) as u8).collect();
+        // 上次中断留下的部分文件
+        std::fs::write(dir.join("image.bin"), &data[..10_000]).unwrap();
+
+        let (mut a, mut b) = pipe_pair();
+        let files = vec![OutgoingFile { name: "image.bin".to_string(), data: data.clone(), modified: Some(0) }];
+        let sender = std::thread::spawn(move || {
+            send(&mut a, &ZmodemOptions::default(), &files, &TransferControl::new(), &mut |_| {}).unwrap()
+        });
+        let (paths, _) = receive(&mut b, &ZmodemOptions::default(), &dir, &TransferControl::new(), &mut |_| {}).unwrap();
+        let stats = sender.join().unwrap();
+
+        assert_eq!(paths, vec![dir.join("image.bin")]);
+        assert_eq!(std::fs::read(&paths[0]).unwrap(), data);
+        // 只发送了缺失的部分
+        assert_eq!(stats.total_bytes, 30_000);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
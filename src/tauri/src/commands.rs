@@ -1,6 +1,34 @@
 use serial_util::core::serial_manager::SerialManager;
 use serial_util::core::port_sharing_manager::{PortSharingManager, SharingStatus};
 use serial_util::core::com0com_manager::Com0comManager;
+use serial_util::core::capture::{now_ns, CaptureFormat, Direction};
+use serial_util::core::replay::{ReplayManager, ReplayOptions, ReplayStatus};
+use serial_util::core::trigger::{TriggerManager, TriggerRule};
+use serial_util::core::endpoint::Endpoint;
+use serial_util::core::simulator::{SimulatorManager, SimulatorStatus};
+use serial_util::core::text_decoder::{StreamDecoder, TextEncoding};
+use serial_util::core::vt_parser::{self, VtParser};
+use serial_util::core::line_assembler::{LineAssembler, LineAssemblerConfig};
+use serial_util::core::modbus::master::ModbusMaster;
+use serial_util::core::modbus::gateway::{GatewayStatus, ModbusGateway};
+use serial_util::core::modbus::slave::{ModbusSlaveManager, ModbusSlaveStatus, RegisterChange, RegisterMap, RegisterTable};
+use serial_util::core::session_link::SessionLink;
+use serial_util::core::checksum::{ChecksumAlgorithm, ChecksumSpec};
+use serial_util::core::file_transfer::{OutgoingFile, ReceivedFile, TransferControl, TransferGuard, TransferStats};
+use serial_util::core::xmodem::{self, XmodemOptions};
+use serial_util::core::zmodem::{self, ZmodemOptions};
+use serial_util::core::at_command::{AtEngine, AtResponse, SharedAtEngine, DEFAULT_AT_TIMEOUT};
+use serial_util::core::scpi::{ScpiError, ScpiIdentity, ScpiInstrument, ScpiOptions, ScpiResponse};
+use serial_util::core::firmware_image::{FirmwareImage, ImageFormat, ImageInfo};
+use serial_util::core::stm32_bootloader::{self, BootEntry, Stm32Bootloader, Stm32FlashOptions, Stm32Info};
+use serial_util::core::esp_loader::{EspChip, EspFlashOptions, EspLoader};
+use serial_util::core::packet_schema::{self, DecodedPacket, PacketSchema, SchemaDecoder};
+use serial_util::core::protocol_decoder::DecoderRegistry;
+use serial_util::core::nmea::{FixState, SharedFixTracker};
+use serial_util::core::mavlink::{self, MavlinkSendOptions, MavlinkSender, MavlinkStatsSnapshot, SharedMavlinkStats};
+use serial_util::core::slcan::{self, CanBridge, CanBridgeStatus, CanFrame, SlcanOptions};
+use serial_util::core::gcode_streamer::{self, GcodeProgram, GcodeProgress, GcodeStreamOptions, StreamControl};
+use tauri::{Emitter, Manager};
 use tauri::State;
 use tokio::sync::Mutex;
 use serde::{Deserialize, Serialize};
@@ -11,6 +39,11 @@ fn to_string_err(e: impl std::fmt::Display) -> String {
     e.to_string()
 }
 
+// Claim the session link for a transaction (transfers, flashing, AT/SCPI, Modbus, ...)
+fn acquire_link(control: &TransferControl) -> Result<TransferGuard, String> {
+    control.try_begin().ok_or_else(|| "Serial link is busy".to_string())
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SerialConfig {
     pub port_name: String,
@@ -21,6 +54,9 @@ pub struct SerialConfig {
     pub stop_bits: u8,
     #[serde(default = "default_timeout")]
     pub timeout: u64, // ms
+    /// RX text encoding for this session (utf8, gbk, gb18030, shift_jis, latin1, utf16le, utf16be)
+    #[serde(default)]
+    pub encoding: Option<String>,
 }
 
 fn default_timeout() -> u64 {
@@ -185,10 +221,16 @@ pub async fn get_ports() -> Result<Vec<PortInfo>, String> {
 #[tauri::command]
 pub async fn connect(
     state: State<'_, Mutex<SerialManager>>,
+    trigger_manager: State<'_, Mutex<TriggerManager>>,
+    text_decoder: State<'_, Mutex<StreamDecoder>>,
     config: SerialConfig,
 ) -> Result<(), String> {
     let mut manager = state.lock().await;
     let (data_bits, flow_control, parity, stop_bits) = config.to_params()?;
+    let encoding = match &config.encoding {
+        Some(name) => TextEncoding::parse(name).map_err(to_string_err)?,
+        None => TextEncoding::Utf8,
+    };
     
     // Use configured timeout, default to 10ms if not explicitly set (though serde default handles it)
     let timeout = if config.timeout == 0 { 10 } else { config.timeout };
@@ -202,6 +244,10 @@ pub async fn connect(
         stop_bits,
         std::time::Duration::from_millis(timeout)
     ).map_err(to_string_err)?;
+
+    // Switch trigger rules and text decoding to this session
+    trigger_manager.lock().await.activate(&config.port_name).map_err(to_string_err)?;
+    text_decoder.lock().await.set_encoding(encoding);
     Ok(())
 }
 
@@ -212,12 +258,38 @@ pub async fn disconnect(state: State<'_, Mutex<SerialManager>>) -> Result<(), St
     Ok(())
 }
 
+/// 发送数据，返回实际写出的字节 (含 TX Hook 修改与追加的校验)，供前端记录
 #[tauri::command]
-pub async fn send(state: State<'_, Mutex<SerialManager>>, script_manager: State<'_, crate::scripting::ScriptManager>, content: Vec<u8>) -> Result<(), String> {
-    let final_content = script_manager.run_pre_send(content)?;
-    let manager = state.lock().await;
-    manager.write(&final_content).await.map_err(to_string_err)?;
-    Ok(())
+pub async fn send(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    script_manager: State<'_, crate::scripting::ScriptManager>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    content: Vec<u8>,
+    checksum: Option<ChecksumSpec>
+) -> Result<Vec<u8>, String> {
+    let mut final_content = script_manager.run_pre_send(content)?;
+    // 校验在 TX Hook 之后计算，覆盖实际发出的字节
+    if let Some(spec) = checksum {
+        final_content = spec.append(&final_content);
+    }
+    state.lock().await.write(&final_content).await.map_err(to_string_err)?;
+    // 协议解码器同样解析发出的数据
+    let messages = decoders.lock().await.feed(Direction::Tx, &final_content, now_ns());
+    crate::emit_decoded_messages(&app, messages).await;
+    Ok(final_content)
+}
+
+/// 计算校验值 (按 `spec` 的字节序编码)，供指令编辑时预览
+#[tauri::command]
+pub async fn compute_checksum(data: Vec<u8>, spec: ChecksumSpec) -> Vec<u8> {
+    spec.checksum_bytes(&data)
+}
+
+/// 支持的校验算法名称
+#[tauri::command]
+pub async fn get_checksum_algorithms() -> Vec<&'static str> {
+    ChecksumAlgorithm::ALL.iter().map(|a| a.name()).collect()
 }
 
 #[tauri::command]
@@ -231,6 +303,41 @@ pub async fn set_script(state: State<'_, crate::scripting::ScriptManager>, scrip
     Ok(())
 }
 
+/// 切换当前会话的 RX 文本编码
+#[tauri::command]
+pub async fn set_rx_encoding(state: State<'_, Mutex<StreamDecoder>>, encoding: String) -> Result<(), String> {
+    let encoding = TextEncoding::parse(&encoding).map_err(to_string_err)?;
+    state.lock().await.set_encoding(encoding);
+    Ok(())
+}
+
+/// 启用/关闭 RX 文本的 ANSI/VT 解析
+#[tauri::command]
+pub async fn set_vt_parsing(state: State<'_, Mutex<Option<VtParser>>>, enabled: bool) -> Result<(), String> {
+    let mut parser = state.lock().await;
+    *parser = if enabled { Some(VtParser::new()) } else { None };
+    Ok(())
+}
+
+/// 去除文本中的 ANSI/VT 转义序列 (保存日志时使用)
+#[tauri::command]
+pub async fn strip_escapes(text: String) -> String {
+    vt_parser::strip_escapes(&text)
+}
+
+/// 获取行组装配置
+#[tauri::command]
+pub async fn get_line_assembly(state: State<'_, Mutex<LineAssembler>>) -> Result<LineAssemblerConfig, String> {
+    Ok(state.lock().await.config().clone())
+}
+
+/// 设置行组装配置 (结束符、空闲超时等)
+#[tauri::command]
+pub async fn set_line_assembly(state: State<'_, Mutex<LineAssembler>>, config: LineAssemblerConfig) -> Result<(), String> {
+    state.lock().await.set_config(config);
+    Ok(())
+}
+
 // ============== 端口共享功能 ==============
 
 /// 检测 com0com 是否已安装
@@ -357,3 +464,1086 @@ pub async fn stop_port_sharing(
     manager.stop_sharing_status_only().map_err(to_string_err)
 }
 
+
+// ============== 抓包功能 ==============
+
+/// 开始抓包 (format: "jsonl" | "pcapng")
+#[tauri::command]
+pub async fn start_capture(
+    state: State<'_, Mutex<SerialManager>>,
+    path: String,
+    format: String,
+    strip_escapes: Option<bool>
+) -> Result<(), String> {
+    let format = CaptureFormat::parse(&format).map_err(to_string_err)?;
+    let mut manager = state.lock().await;
+    manager.start_capture(std::path::Path::new(&path), format, strip_escapes.unwrap_or(false))
+        .map_err(to_string_err)
+}
+
+/// 停止抓包
+#[tauri::command]
+pub async fn stop_capture(state: State<'_, Mutex<SerialManager>>) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    manager.stop_capture().map_err(to_string_err)
+}
+
+/// 在当前抓包中插入标记
+#[tauri::command]
+pub async fn add_capture_marker(state: State<'_, Mutex<SerialManager>>, text: String) -> Result<(), String> {
+    let manager = state.lock().await;
+    manager.add_capture_marker(&text).map_err(to_string_err)
+}
+
+/// 将已保存的 JSON Lines 抓包转换为 pcapng，返回记录数
+#[tauri::command]
+pub async fn convert_capture_to_pcapng(input: String, output: String) -> Result<usize, String> {
+    serial_util::core::pcapng::convert_jsonl_to_pcapng(
+        std::path::Path::new(&input),
+        std::path::Path::new(&output)
+    ).map_err(to_string_err)
+}
+
+// ============== 抓包回放 ==============
+
+/// 开始回放抓包文件中的 RX 记录
+#[tauri::command]
+pub async fn start_replay(
+    state: State<'_, Mutex<ReplayManager>>,
+    serial_manager: State<'_, Mutex<SerialManager>>,
+    path: String,
+    options: ReplayOptions
+) -> Result<(), String> {
+    let app_tx = serial_manager.lock().await.sender();
+    let mut manager = state.lock().await;
+    manager.start(std::path::Path::new(&path), options, app_tx).map_err(to_string_err)
+}
+
+/// 停止回放
+#[tauri::command]
+pub async fn stop_replay(state: State<'_, Mutex<ReplayManager>>) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    manager.stop().map_err(to_string_err)
+}
+
+/// 获取回放状态
+#[tauri::command]
+pub async fn get_replay_status(state: State<'_, Mutex<ReplayManager>>) -> Result<ReplayStatus, String> {
+    let manager = state.lock().await;
+    Ok(manager.status())
+}
+
+// ============== 触发规则 ==============
+
+/// 触发规则持久化路径
+pub fn trigger_rules_path(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    let dir = app.path().app_config_dir().map_err(to_string_err)?;
+    Ok(dir.join("triggers.json"))
+}
+
+/// 获取会话 (端口名，"*" 为默认规则集) 的触发规则
+#[tauri::command]
+pub async fn get_trigger_rules(
+    state: State<'_, Mutex<TriggerManager>>,
+    session: String
+) -> Result<Vec<TriggerRule>, String> {
+    let manager = state.lock().await;
+    Ok(manager.rules(&session))
+}
+
+/// 设置会话的触发规则并保存
+#[tauri::command]
+pub async fn set_trigger_rules(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<TriggerManager>>,
+    session: String,
+    rules: Vec<TriggerRule>
+) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    manager.set_rules(&session, rules).map_err(to_string_err)?;
+    manager.save(&trigger_rules_path(&app)?).map_err(to_string_err)
+}
+
+// ============== 设备模拟器 ==============
+
+/// 加载 YAML 规则文件并在指定端点 (串口 / 虚拟串口对 / pty) 上启动模拟器
+#[tauri::command]
+pub async fn start_simulator(
+    state: State<'_, Mutex<SimulatorManager>>,
+    profile_path: String,
+    endpoint: Endpoint
+) -> Result<SimulatorStatus, String> {
+    let mut manager = state.lock().await;
+    manager.start(std::path::Path::new(&profile_path), &endpoint).map_err(to_string_err)?;
+    Ok(manager.status())
+}
+
+/// 停止模拟器
+#[tauri::command]
+pub async fn stop_simulator(state: State<'_, Mutex<SimulatorManager>>) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    manager.stop().map_err(to_string_err)
+}
+
+/// 获取模拟器状态 (含当前变量值)
+#[tauri::command]
+pub async fn get_simulator_status(state: State<'_, Mutex<SimulatorManager>>) -> Result<SimulatorStatus, String> {
+    let manager = state.lock().await;
+    Ok(manager.status())
+}
+
+// ============== Modbus ==============
+
+/// 在活动会话上执行一次 Modbus 事务 (阻塞读写放到独立线程，避免占用串口管理器锁)；
+/// 事务期间占用会话链路
+async fn with_modbus_master<T, F>(
+    state: &State<'_, Mutex<SerialManager>>,
+    control: &State<'_, TransferControl>,
+    timeout_ms: Option<u64>,
+    f: F
+) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut ModbusMaster<SessionLink>) -> anyhow::Result<T> + Send + 'static,
+{
+    let link = {
+        let manager = state.lock().await;
+        manager.link().map_err(to_string_err)?
+    };
+    let busy = acquire_link(control)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        let mut master = ModbusMaster::new(link)?;
+        if let Some(ms) = timeout_ms {
+            master.set_response_timeout(std::time::Duration::from_millis(ms));
+        }
+        f(&mut master)
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)
+}
+
+/// 读线圈 (FC 01)
+#[tauri::command]
+pub async fn modbus_read_coils(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    quantity: u16,
+    timeout_ms: Option<u64>
+) -> Result<Vec<bool>, String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.read_coils(unit, address, quantity)).await
+}
+
+/// 读离散输入 (FC 02)
+#[tauri::command]
+pub async fn modbus_read_discrete_inputs(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    quantity: u16,
+    timeout_ms: Option<u64>
+) -> Result<Vec<bool>, String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.read_discrete_inputs(unit, address, quantity)).await
+}
+
+/// 读保持寄存器 (FC 03)
+#[tauri::command]
+pub async fn modbus_read_holding_registers(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    quantity: u16,
+    timeout_ms: Option<u64>
+) -> Result<Vec<u16>, String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.read_holding_registers(unit, address, quantity)).await
+}
+
+/// 读输入寄存器 (FC 04)
+#[tauri::command]
+pub async fn modbus_read_input_registers(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    quantity: u16,
+    timeout_ms: Option<u64>
+) -> Result<Vec<u16>, String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.read_input_registers(unit, address, quantity)).await
+}
+
+/// 写单个线圈 (FC 05)
+#[tauri::command]
+pub async fn modbus_write_single_coil(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    value: bool,
+    timeout_ms: Option<u64>
+) -> Result<(), String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.write_single_coil(unit, address, value)).await
+}
+
+/// 写单个寄存器 (FC 06)
+#[tauri::command]
+pub async fn modbus_write_single_register(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    value: u16,
+    timeout_ms: Option<u64>
+) -> Result<(), String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.write_single_register(unit, address, value)).await
+}
+
+/// 写多个线圈 (FC 15)
+#[tauri::command]
+pub async fn modbus_write_multiple_coils(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    values: Vec<bool>,
+    timeout_ms: Option<u64>
+) -> Result<(), String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.write_multiple_coils(unit, address, &values)).await
+}
+
+/// 写多个寄存器 (FC 16)
+#[tauri::command]
+pub async fn modbus_write_multiple_registers(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    address: u16,
+    values: Vec<u16>,
+    timeout_ms: Option<u64>
+) -> Result<(), String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| m.write_multiple_registers(unit, address, &values)).await
+}
+
+/// 读写多个寄存器 (FC 23)
+#[tauri::command]
+pub async fn modbus_read_write_registers(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    unit: u8,
+    read_address: u16,
+    read_quantity: u16,
+    write_address: u16,
+    values: Vec<u16>,
+    timeout_ms: Option<u64>
+) -> Result<Vec<u16>, String> {
+    with_modbus_master(&state, &control, timeout_ms, move |m| {
+        m.read_write_multiple_registers(unit, read_address, read_quantity, write_address, &values)
+    })
+    .await
+}
+
+// ============== Modbus 从站模拟器 ==============
+
+/// 加载寄存器表 (YAML/CSV) 并在端点上启动从站，主站写入通过 `modbus-slave-change` 事件下发
+#[tauri::command]
+pub async fn start_modbus_slave(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<ModbusSlaveManager>>,
+    map_path: String,
+    unit: Option<u8>,
+    endpoint: Endpoint
+) -> Result<ModbusSlaveStatus, String> {
+    let mut manager = state.lock().await;
+    let on_change = Box::new(move |change: &RegisterChange| {
+        if let Err(e) = app.emit("modbus-slave-change", change) {
+            log::error!("Failed to emit modbus-slave-change: {}", e);
+        }
+    });
+    manager
+        .start(std::path::Path::new(&map_path), unit, &endpoint, Some(on_change))
+        .map_err(to_string_err)?;
+    Ok(manager.status())
+}
+
+/// 停止从站模拟器
+#[tauri::command]
+pub async fn stop_modbus_slave(state: State<'_, Mutex<ModbusSlaveManager>>) -> Result<(), String> {
+    let mut manager = state.lock().await;
+    manager.stop().map_err(to_string_err)
+}
+
+/// 获取从站模拟器状态
+#[tauri::command]
+pub async fn get_modbus_slave_status(state: State<'_, Mutex<ModbusSlaveManager>>) -> Result<ModbusSlaveStatus, String> {
+    let manager = state.lock().await;
+    Ok(manager.status())
+}
+
+/// 获取当前寄存器表
+#[tauri::command]
+pub async fn get_modbus_slave_registers(state: State<'_, Mutex<ModbusSlaveManager>>) -> Result<RegisterMap, String> {
+    let manager = state.lock().await;
+    Ok(manager.registers())
+}
+
+/// 修改寄存器值 (运行中立即生效)，与主站写入一样下发 `modbus-slave-change` 事件
+#[tauri::command]
+pub async fn set_modbus_slave_register(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<ModbusSlaveManager>>,
+    table: RegisterTable,
+    address: u16,
+    value: u16
+) -> Result<(), String> {
+    let change = state.lock().await.set_register(table, address, value);
+    app.emit("modbus-slave-change", change).map_err(to_string_err)
+}
+
+// ============== Modbus TCP 网关 ==============
+
+/// 在本地 TCP 端口启动 Modbus TCP → RTU 网关，事务在当前会话串口上串行执行 (运行期间占用会话链路)
+#[tauri::command]
+pub async fn start_modbus_gateway(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    gateway: State<'_, Mutex<ModbusGateway>>,
+    bind_addr: String,
+    timeout_ms: Option<u64>
+) -> Result<GatewayStatus, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let timeout = std::time::Duration::from_millis(timeout_ms.unwrap_or(1000));
+    let mut gateway = gateway.lock().await;
+    // 网关运行期间一直占用会话链路
+    let busy = acquire_link(&control)?;
+    gateway.start(link, &bind_addr, timeout, Some(busy)).map_err(to_string_err)?;
+    Ok(gateway.status())
+}
+
+/// 停止网关
+#[tauri::command]
+pub async fn stop_modbus_gateway(gateway: State<'_, Mutex<ModbusGateway>>) -> Result<(), String> {
+    let mut gateway = gateway.lock().await;
+    gateway.stop().map_err(to_string_err)
+}
+
+/// 获取网关状态
+#[tauri::command]
+pub async fn get_modbus_gateway_status(gateway: State<'_, Mutex<ModbusGateway>>) -> Result<GatewayStatus, String> {
+    let gateway = gateway.lock().await;
+    Ok(gateway.status())
+}
+
+// ============== 文件传输 ==============
+
+/// 保存接收到的文件，无文件名时 (XMODEM) 按时间生成
+fn save_received_files(dest_dir: &str, prefix: &str, files: Vec<ReceivedFile>) -> Result<Vec<String>, String> {
+    let dir = std::path::Path::new(dest_dir);
+    std::fs::create_dir_all(dir).map_err(to_string_err)?;
+    let mut saved = Vec::new();
+    for file in files {
+        let name = match file.name {
+            // 只取文件名部分，避免对端路径写出目标目录
+            Some(name) => std::path::Path::new(&name)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or(name),
+            None => format!("{}_{}.bin", prefix, now_ns() / 1_000_000),
+        };
+        let path = dir.join(name);
+        std::fs::write(&path, &file.data).map_err(to_string_err)?;
+        saved.push(path.to_string_lossy().to_string());
+    }
+    Ok(saved)
+}
+
+/// 用 XMODEM / XMODEM-1K / YMODEM 发送文件 (进度通过 `transfer-progress` 事件下发)
+#[tauri::command]
+pub async fn xmodem_send(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    paths: Vec<String>,
+    options: XmodemOptions
+) -> Result<TransferStats, String> {
+    let files = paths
+        .iter()
+        .map(|p| OutgoingFile::load(std::path::Path::new(p)))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(to_string_err)?;
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = xmodem::send(&mut link, &options, &files, &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+/// 用 XMODEM / YMODEM 接收文件到 `dest_dir`，返回保存的文件路径
+#[tauri::command]
+pub async fn xmodem_receive(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    options: XmodemOptions,
+    dest_dir: String
+) -> Result<Vec<String>, String> {
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = xmodem::receive(&mut link, &options, &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    let (files, _stats) = result.map_err(to_string_err)?;
+    save_received_files(&dest_dir, "xmodem", files)
+}
+
+/// 用 ZMODEM 发送文件 (对端 `rz`)，支持断点续传
+#[tauri::command]
+pub async fn zmodem_send(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    paths: Vec<String>,
+    options: Option<ZmodemOptions>
+) -> Result<TransferStats, String> {
+    let files = paths
+        .iter()
+        .map(|p| OutgoingFile::load(std::path::Path::new(p)))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(to_string_err)?;
+    let options = options.unwrap_or_default();
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = zmodem::send(&mut link, &options, &files, &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+/// 用 ZMODEM 接收文件到 `dest_dir` (对端 `sz`)，已有的部分文件会续传，返回保存的文件路径
+#[tauri::command]
+pub async fn zmodem_receive(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    options: Option<ZmodemOptions>,
+    dest_dir: String
+) -> Result<Vec<String>, String> {
+    let options = options.unwrap_or_default();
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let result = zmodem::receive(&mut link, &options, std::path::Path::new(&dest_dir), &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    let (paths, _stats) = result.map_err(to_string_err)?;
+    Ok(paths.iter().map(|p| p.to_string_lossy().to_string()).collect())
+}
+
+/// 取消正在进行的文件传输
+#[tauri::command]
+pub async fn cancel_transfer(control: State<'_, TransferControl>) -> Result<(), String> {
+    control.cancel();
+    Ok(())
+}
+
+// ============== AT 指令 ==============
+
+/// 发送 AT 指令并等待最终结果码，返回中间响应行与结果；
+/// 期间及两次指令之间收到的 URC 额外通过 `at-urc` 事件下发
+#[tauri::command]
+pub async fn send_at(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    at_engine: State<'_, SharedAtEngine>,
+    command: String,
+    timeout_ms: Option<u64>,
+    urc_prefixes: Option<Vec<String>>
+) -> Result<AtResponse, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let timeout = timeout_ms.map(std::time::Duration::from_millis).unwrap_or(DEFAULT_AT_TIMEOUT);
+    let shared = at_engine.inner().clone();
+    let busy = acquire_link(&control)?;
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        let mut emit = |urc: &str| {
+            let _ = app.emit("at-urc", urc);
+        };
+        let mut guard = shared.lock().unwrap_or_else(|e| e.into_inner());
+        // 沿用已有引擎；换了端口或旧链路已断开时改用新链路
+        let reusable = guard
+            .as_mut()
+            .is_some_and(|engine| engine.link().port_name() == link.port_name() && engine.poll_urcs(&mut emit).is_ok());
+        let engine = if reusable {
+            guard.as_mut().unwrap()
+        } else {
+            guard.insert(AtEngine::new(link))
+        };
+        if let Some(prefixes) = urc_prefixes {
+            engine.set_urc_prefixes(prefixes);
+        }
+        engine.send(&command, timeout, &mut emit)
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+// ============== SCPI ==============
+
+/// 发送 SCPI 命令或查询 (以 `?` 判断)，查询应答按数值/数组/字符串/二进制块解析；
+/// 默认随后读取 `SYST:ERR?` 清空错误队列
+#[tauri::command]
+pub async fn scpi_send(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    command: String,
+    options: Option<ScpiOptions>
+) -> Result<ScpiResponse, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let busy = acquire_link(&control)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        ScpiInstrument::new(link, options.unwrap_or_default()).send(&command)
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)
+}
+
+/// `*IDN?` 识别仪器
+#[tauri::command]
+pub async fn scpi_identify(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    options: Option<ScpiOptions>
+) -> Result<ScpiIdentity, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let busy = acquire_link(&control)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        ScpiInstrument::new(link, options.unwrap_or_default()).identify()
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)
+}
+
+/// 读出仪器错误队列
+#[tauri::command]
+pub async fn scpi_drain_errors(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    options: Option<ScpiOptions>
+) -> Result<Vec<ScpiError>, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let busy = acquire_link(&control)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        ScpiInstrument::new(link, options.unwrap_or_default()).drain_errors()
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)
+}
+
+// ============== 固件镜像 ==============
+
+/// 解析固件镜像 (Intel HEX / S-record / ELF / 二进制)，返回各数据段与 CRC32；
+/// 整体 CRC 以 `fill` (默认 0xFF) 填充段间空隙后计算 (跨度过大时省略)
+#[tauri::command]
+pub async fn inspect_firmware_image(path: String, base_address: Option<u32>, fill: Option<u8>) -> Result<ImageInfo, String> {
+    let path = std::path::Path::new(&path);
+    let data = std::fs::read(path).map_err(to_string_err)?;
+    let format = ImageFormat::detect(path, &data);
+    let image = FirmwareImage::from_bytes(format, data, base_address).map_err(to_string_err)?;
+    Ok(image.info(format, fill.unwrap_or(0xFF)))
+}
+
+// ============== STM32 Bootloader ==============
+
+/// 通过 UART Bootloader 烧录 STM32 (HEX 或二进制)，进度通过 `transfer-progress` 事件下发
+#[tauri::command]
+pub async fn stm32_flash(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    path: String,
+    options: Option<Stm32FlashOptions>
+) -> Result<Stm32Info, String> {
+    let options = options.unwrap_or_default();
+    let image = FirmwareImage::load(std::path::Path::new(&path), Some(options.base_address.unwrap_or(stm32_bootloader::FLASH_BASE)))
+        .map_err(to_string_err)?;
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut bootloader = Stm32Bootloader::new(link);
+        let result = bootloader.flash(&image, &options, &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+/// 进入 Bootloader 读取版本与产品 ID，随后复位回用户程序
+#[tauri::command]
+pub async fn stm32_get_info(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    entry: Option<BootEntry>
+) -> Result<Stm32Info, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let entry = entry.unwrap_or_default();
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut bootloader = Stm32Bootloader::new(link);
+        let info = bootloader.enter(&entry).and_then(|_| bootloader.sync()).and_then(|_| bootloader.info());
+        let exited = bootloader.exit(&entry);
+        control.finish();
+        info.and_then(|info| exited.map(|_| info))
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+// ============== ESP ROM Bootloader ==============
+
+/// 待烧录的镜像：二进制文件写入 `offset`，HEX 文件按其自身地址
+#[derive(Debug, Deserialize)]
+pub struct EspFlashImage {
+    pub path: String,
+    pub offset: u32,
+}
+
+/// 通过 ROM Bootloader 烧录 ESP32/ESP8266，完成后复位并恢复会话波特率；
+/// 进度通过 `transfer-progress` 事件下发
+#[tauri::command]
+pub async fn esp_flash(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    images: Vec<EspFlashImage>,
+    options: Option<EspFlashOptions>
+) -> Result<EspChip, String> {
+    let mut segments = Vec::new();
+    for image in &images {
+        let loaded = FirmwareImage::load(std::path::Path::new(&image.path), Some(image.offset)).map_err(to_string_err)?;
+        segments.extend(loaded.segments);
+    }
+    let options = options.unwrap_or_default();
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut loader = EspLoader::new(link);
+        let result = loader.flash(&segments, &options, &control, &mut |progress| {
+            let _ = app.emit("transfer-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+/// 进入下载模式识别芯片型号，随后复位回用户程序
+#[tauri::command]
+pub async fn esp_detect_chip(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    auto_reset: Option<bool>
+) -> Result<EspChip, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A file transfer is already running".to_string());
+    }
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut loader = EspLoader::new(link);
+        let entered = if auto_reset.unwrap_or(true) { loader.enter_download_mode() } else { Ok(()) };
+        let chip = entered.and_then(|_| loader.sync()).and_then(|_| loader.detect_chip());
+        let reset = loader.hard_reset();
+        control.finish();
+        chip.and_then(|chip| reset.map(|_| chip))
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+// ============== 协议解码 ==============
+
+/// 可用与已启用的协议解码器
+#[derive(Debug, Serialize)]
+pub struct ProtocolDecoderList {
+    pub available: Vec<String>,
+    pub enabled: Vec<String>,
+}
+
+#[tauri::command]
+pub async fn get_protocol_decoders(decoders: State<'_, Mutex<DecoderRegistry>>) -> Result<ProtocolDecoderList, String> {
+    let registry = decoders.lock().await;
+    Ok(ProtocolDecoderList { available: registry.available(), enabled: registry.enabled() })
+}
+
+/// 按名称启用解码器 (结果通过 `decoded-message` 事件下发并写入抓包)；
+/// 未指定 `baudRate` 时取当前端口波特率
+#[tauri::command]
+pub async fn enable_protocol_decoder(
+    state: State<'_, Mutex<SerialManager>>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    name: String,
+    options: Option<serde_json::Value>
+) -> Result<(), String> {
+    let mut options = options.unwrap_or_else(|| serde_json::json!({}));
+    if let Some(object) = options.as_object_mut() {
+        if !object.contains_key("baudRate") {
+            if let Ok(baud_rate) = state.lock().await.baud_rate() {
+                object.insert("baudRate".to_string(), baud_rate.into());
+            }
+        }
+    }
+    decoders.lock().await.enable(&name, &options).map_err(to_string_err)
+}
+
+#[tauri::command]
+pub async fn disable_protocol_decoder(decoders: State<'_, Mutex<DecoderRegistry>>, name: String) -> Result<bool, String> {
+    Ok(decoders.lock().await.disable(&name))
+}
+
+// ============== GNSS ==============
+
+/// 当前定位状态 (启用 `nmea-0183` 解码器后更新，变化时另有 `gnss-fix` 事件)
+#[tauri::command]
+pub async fn get_gnss_fix(tracker: State<'_, SharedFixTracker>) -> Result<FixState, String> {
+    let tracker = tracker.lock().map_err(to_string_err)?;
+    Ok(tracker.state().clone())
+}
+
+/// 清空定位状态 (如更换模块后)
+#[tauri::command]
+pub async fn reset_gnss_fix(tracker: State<'_, SharedFixTracker>) -> Result<(), String> {
+    tracker.lock().map_err(to_string_err)?.reset();
+    Ok(())
+}
+
+// ============== MAVLink ==============
+
+/// 各 系统/组件 的收包、丢包与消息频率 (启用 `mavlink` 解码器后更新)
+#[tauri::command]
+pub async fn get_mavlink_stats(stats: State<'_, SharedMavlinkStats>) -> Result<MavlinkStatsSnapshot, String> {
+    let mut stats = stats.lock().map_err(to_string_err)?;
+    Ok(stats.snapshot(now_ns()))
+}
+
+#[tauri::command]
+pub async fn reset_mavlink_stats(stats: State<'_, SharedMavlinkStats>) -> Result<(), String> {
+    stats.lock().map_err(to_string_err)?.reset();
+    Ok(())
+}
+
+/// 可发送的消息名
+#[tauri::command]
+pub async fn get_mavlink_messages() -> Result<Vec<String>, String> {
+    Ok(mavlink::common().message_names().into_iter().map(String::from).collect())
+}
+
+/// 由消息名与字段值 (如 HEARTBEAT、COMMAND_LONG) 组帧发送，返回发出的帧
+#[tauri::command]
+pub async fn send_mavlink(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    sender: State<'_, Mutex<MavlinkSender>>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    message: String,
+    fields: serde_json::Value,
+    options: Option<MavlinkSendOptions>
+) -> Result<Vec<u8>, String> {
+    let frame = sender
+        .lock()
+        .await
+        .frame(&message, &fields, &options.unwrap_or_default())
+        .map_err(to_string_err)?;
+    state.lock().await.write(&frame).await.map_err(to_string_err)?;
+    let messages = decoders.lock().await.feed(Direction::Tx, &frame, now_ns());
+    crate::emit_decoded_messages(&app, messages).await;
+    Ok(frame)
+}
+
+// ============== SLCAN ==============
+
+/// 在当前会话 (SLCAN 适配器) 上按波特率打开 CAN 通道并启用 `slcan` 解码器；返回适配器版本
+#[tauri::command]
+pub async fn slcan_open(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    options: SlcanOptions
+) -> Result<Option<String>, String> {
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let busy = acquire_link(&control)?;
+    let version = tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        slcan::open_channel(&mut link, &options)
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)?;
+    decoders
+        .lock()
+        .await
+        .enable(slcan::DECODER_NAME, &serde_json::Value::Null)
+        .map_err(to_string_err)?;
+    Ok(version)
+}
+
+/// 关闭 CAN 通道，同时停止桥接与解码
+#[tauri::command]
+pub async fn slcan_close(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    bridge: State<'_, Mutex<CanBridge>>
+) -> Result<(), String> {
+    // 先停桥接，释放其占用的会话链路
+    bridge.lock().await.stop().map_err(to_string_err)?;
+    decoders.lock().await.disable(slcan::DECODER_NAME);
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let busy = acquire_link(&control)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        slcan::close_channel(&mut link)
+    })
+    .await
+    .map_err(to_string_err)?
+    .map_err(to_string_err)
+}
+
+/// 发送一帧 CAN (通道需已打开)
+#[tauri::command]
+pub async fn send_can_frame(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    frame: CanFrame
+) -> Result<(), String> {
+    let command = frame.to_slcan().map_err(to_string_err)?;
+    state.lock().await.write(command.as_bytes()).await.map_err(to_string_err)?;
+    let messages = decoders.lock().await.feed(Direction::Tx, command.as_bytes(), now_ns());
+    crate::emit_decoded_messages(&app, messages).await;
+    Ok(())
+}
+
+/// 把会话上的 CAN 帧与 Linux CAN 接口 (如 vcan0) 双向桥接
+#[tauri::command]
+pub async fn start_can_bridge(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, TransferControl>,
+    bridge: State<'_, Mutex<CanBridge>>,
+    interface: String
+) -> Result<CanBridgeStatus, String> {
+    let link = state.lock().await.link().map_err(to_string_err)?;
+    let mut bridge = bridge.lock().await;
+    // 桥接运行期间一直占用会话链路
+    let busy = acquire_link(&control)?;
+    bridge.start(link, &interface, Some(busy)).map_err(to_string_err)?;
+    Ok(bridge.status())
+}
+
+#[tauri::command]
+pub async fn stop_can_bridge(bridge: State<'_, Mutex<CanBridge>>) -> Result<(), String> {
+    bridge.lock().await.stop().map_err(to_string_err)
+}
+
+#[tauri::command]
+pub async fn get_can_bridge_status(bridge: State<'_, Mutex<CanBridge>>) -> Result<CanBridgeStatus, String> {
+    Ok(bridge.lock().await.status())
+}
+
+// ============== G-code ==============
+
+/// 流式发送 G-code 文件直到完成/报警/取消/应答超时，进度通过 `gcode-progress` 事件上报；
+/// `options` 缺省时按 GRBL 字符计数
+#[tauri::command]
+pub async fn start_gcode_stream(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, StreamControl>,
+    link_control: State<'_, TransferControl>,
+    path: String,
+    options: Option<GcodeStreamOptions>
+) -> Result<GcodeProgress, String> {
+    let options = options.unwrap_or_default();
+    let program = GcodeProgram::load(std::path::Path::new(&path)).map_err(to_string_err)?;
+    let mut link = state.lock().await.link().map_err(to_string_err)?;
+    let control = control.inner().clone();
+    if !control.begin() {
+        return Err("A G-code stream is already running".to_string());
+    }
+    // 发送期间占用会话链路
+    let busy = match acquire_link(&link_control) {
+        Ok(busy) => busy,
+        Err(e) => {
+            control.finish();
+            return Err(e);
+        }
+    };
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let _busy = busy;
+        let result = gcode_streamer::stream(&mut link, &program, &options, &control, &mut |progress| {
+            let _ = app.emit("gcode-progress", progress);
+        });
+        control.finish();
+        result
+    })
+    .await
+    .map_err(to_string_err)?;
+    result.map_err(to_string_err)
+}
+
+/// 暂停送行 (GRBL 同时进给保持)
+#[tauri::command]
+pub async fn pause_gcode_stream(control: State<'_, StreamControl>) -> Result<(), String> {
+    control.pause();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_gcode_stream(control: State<'_, StreamControl>) -> Result<(), String> {
+    control.resume();
+    Ok(())
+}
+
+/// 取消发送 (GRBL 同时软复位)
+#[tauri::command]
+pub async fn cancel_gcode_stream(control: State<'_, StreamControl>) -> Result<(), String> {
+    control.cancel();
+    Ok(())
+}
+
+/// 发送过程中插入实时字节 (如 `?` 状态查询)；未在发送时直接写入会话
+#[tauri::command]
+pub async fn send_gcode_realtime(
+    state: State<'_, Mutex<SerialManager>>,
+    control: State<'_, StreamControl>,
+    byte: u8
+) -> Result<(), String> {
+    if control.is_active() {
+        control.send_realtime(byte);
+        Ok(())
+    } else {
+        state.lock().await.write(&[byte]).await.map_err(to_string_err)
+    }
+}
+
+// ============== 报文描述 ==============
+
+/// 加载 YAML 报文描述并启用 `packet-schema` 解码器，`path` 为空时停用；返回报文描述名
+#[tauri::command]
+pub async fn set_packet_schema(
+    schema: State<'_, Mutex<Option<PacketSchema>>>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    path: Option<String>
+) -> Result<Option<String>, String> {
+    let loaded = match path {
+        Some(path) => Some(PacketSchema::load(std::path::Path::new(&path)).map_err(to_string_err)?),
+        None => None,
+    };
+    let mut registry = decoders.lock().await;
+    match &loaded {
+        Some(loaded) => registry.insert(Box::new(SchemaDecoder::new(loaded.clone()))),
+        None => {
+            registry.disable(packet_schema::DECODER_NAME);
+        }
+    }
+    let name = loaded.as_ref().map(|s| s.name.clone());
+    *schema.lock().await = loaded;
+    Ok(name)
+}
+
+/// 按当前报文描述解码一段完整的帧
+#[tauri::command]
+pub async fn decode_packet(
+    schema: State<'_, Mutex<Option<PacketSchema>>>,
+    data: Vec<u8>
+) -> Result<DecodedPacket, String> {
+    let guard = schema.lock().await;
+    let schema = guard.as_ref().ok_or("No packet schema loaded")?;
+    schema.decode(&data, now_ns()).map_err(to_string_err)
+}
+
+/// 由字段值编码一帧 (长度与校验字段自动回填)，`send` 为 true 时直接发出
+#[tauri::command]
+pub async fn encode_packet(
+    app: tauri::AppHandle,
+    state: State<'_, Mutex<SerialManager>>,
+    schema: State<'_, Mutex<Option<PacketSchema>>>,
+    decoders: State<'_, Mutex<DecoderRegistry>>,
+    values: serde_json::Value,
+    send: Option<bool>
+) -> Result<Vec<u8>, String> {
+    let packet = {
+        let guard = schema.lock().await;
+        let schema = guard.as_ref().ok_or("No packet schema loaded")?;
+        schema.encode(&values).map_err(to_string_err)?
+    };
+    if send.unwrap_or(false) {
+        state.lock().await.write(&packet).await.map_err(to_string_err)?;
+        let messages = decoders.lock().await.feed(Direction::Tx, &packet, now_ns());
+        crate::emit_decoded_messages(&app, messages).await;
+    }
+    Ok(packet)
+}
@@ -1,12 +1,34 @@
 mod commands;
 pub mod scripting;
 
-use serial_util::core::serial_manager::SerialManager;
+use serial_util::core::serial_manager::{RxChunk, SerialManager};
 use serial_util::core::port_sharing_manager::PortSharingManager;
+use serial_util::core::replay::ReplayManager;
+use serial_util::core::trigger::{TriggerAction, TriggerHit, TriggerManager};
+use serial_util::core::simulator::SimulatorManager;
+use serial_util::core::text_decoder::{DecodedText, StreamDecoder};
+use serial_util::core::vt_parser::VtParser;
+use serial_util::core::line_assembler::LineAssembler;
+use serial_util::core::capture::{now_ns, Direction};
+use serial_util::core::modbus::slave::ModbusSlaveManager;
+use serial_util::core::modbus::gateway::ModbusGateway;
+use serial_util::core::file_transfer::TransferControl;
+use serial_util::core::zmodem::ZmodemDetector;
+use serial_util::core::packet_schema::PacketSchema;
+use serial_util::core::protocol_decoder::{DecodedMessage, DecoderRegistry};
+use serial_util::core::nmea::{self, NmeaDecoder, SharedFixTracker};
+use serial_util::core::mavlink::{self, MavlinkSender, SharedMavlinkStats};
+use serial_util::core::slcan::CanBridge;
+use serial_util::core::gcode_streamer::StreamControl;
+use serial_util::core::at_command::SharedAtEngine;
+use std::time::Duration;
 use scripting::ScriptManager;
 use tauri::{Emitter, Manager};
 use tokio::sync::Mutex;
 
+/// Poll interval of the event loop when no data arrives (drives line idle flush)
+const LINE_IDLE_POLL: Duration = Duration::from_millis(50);
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Check for admin-service flag BEFORE loading Tauri
@@ -54,16 +76,43 @@ pub fn run() {
         std::process::exit(0);
     }
 
+    // The NMEA decoder shares its fix state with the GNSS commands/events
+    let fix_tracker = SharedFixTracker::default();
+    let mut decoders = DecoderRegistry::new();
+    let decoder_tracker = fix_tracker.clone();
+    decoders.register(nmea::DECODER_NAME, Box::new(move |_| Ok(Box::new(NmeaDecoder::new(decoder_tracker.clone())))));
+    // Likewise the MAVLink decoder feeds the per-system/component rate stats
+    let mavlink_stats = SharedMavlinkStats::default();
+    decoders.register(mavlink::DECODER_NAME, mavlink::shared_decoder_factory(mavlink_stats.clone()));
+
     tauri::Builder::default()
         .plugin(tauri_plugin_log::Builder::default().build())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .manage(Mutex::new(SerialManager::new()))
         .manage(Mutex::new(PortSharingManager::new()))
+        .manage(Mutex::new(ReplayManager::new()))
+        .manage(Mutex::new(TriggerManager::new()))
+        .manage(Mutex::new(SimulatorManager::new()))
+        .manage(Mutex::new(StreamDecoder::default()))
+        .manage(Mutex::new(Some(VtParser::new())))
+        .manage(Mutex::new(LineAssembler::default()))
+        .manage(Mutex::new(ModbusSlaveManager::new()))
+        .manage(Mutex::new(ModbusGateway::new()))
+        .manage(TransferControl::new())
+        .manage(Mutex::new(ZmodemDetector::new()))
+        .manage(Mutex::new(decoders))
+        .manage(fix_tracker)
+        .manage(mavlink_stats)
+        .manage(Mutex::new(MavlinkSender::new()))
+        .manage(Mutex::new(CanBridge::new()))
+        .manage(StreamControl::new())
+        .manage(SharedAtEngine::default())
+        .manage(Mutex::new(None::<PacketSchema>))
         .manage(ScriptManager::new())
         .setup(|app| {
             let app_handle = app.handle().clone();
-            let (tx, mut rx) = tokio::sync::mpsc::channel::<Vec<u8>>(100);
+            let (tx, mut rx) = tokio::sync::mpsc::channel::<RxChunk>(100);
 
             // Configure SerialManager with sender
             let state = app.state::<Mutex<SerialManager>>();
@@ -71,14 +120,48 @@ pub fn run() {
             manager.set_sender(tx);
             drop(manager); // Release lock
 
+            // Load persisted trigger rules
+            match commands::trigger_rules_path(app.handle()) {
+                Ok(path) => {
+                    let triggers = app.state::<Mutex<TriggerManager>>();
+                    if let Err(e) = triggers.blocking_lock().load(&path) {
+                        log::error!("Failed to load trigger rules: {}", e);
+                    }
+                }
+                Err(e) => log::error!("Failed to resolve trigger rules path: {}", e),
+            }
+
             // Retrieve ScriptManager state (it is managed, so we can get it from app)
             // We use app.state().clone() below directly
             let script_manager = (*app.state::<ScriptManager>()).clone();
 
             // Spawn event loop
             tauri::async_runtime::spawn(async move {
-                while let Some(data) = rx.recv().await {
+                loop {
+                    // Wake up periodically so unterminated lines can be flushed on idle
+                    let RxChunk { data, received_ns } = match tokio::time::timeout(LINE_IDLE_POLL, rx.recv()).await {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(_) => {
+                            let line = app_handle.state::<Mutex<LineAssembler>>().lock().await.flush_idle(now_ns());
+                            if let Some(line) = line {
+                                if let Err(e) = app_handle.emit("serial-line", line) {
+                                    log::error!("Failed to emit serial-line: {}", e);
+                                }
+                            }
+                            let messages = app_handle.state::<Mutex<DecoderRegistry>>().lock().await.flush_idle(now_ns());
+                            emit_decoded_messages(&app_handle, messages).await;
+                            poll_at_urcs(&app_handle);
+                            continue;
+                        }
+                    };
                     println!("[Backend-Debug] Raw Received {} bytes: {:?}", data.len(), data);
+
+                    // Evaluate trigger rules on the raw stream
+                    let hits = app_handle.state::<Mutex<TriggerManager>>().lock().await.process(&data);
+                    if !hits.is_empty() {
+                        run_trigger_actions(&app_handle, hits).await;
+                    }
                     
                     // Run Rx Hook
                     let final_data = match script_manager.run_rx_script(data.clone()) {
@@ -96,10 +179,46 @@ pub fn run() {
                          continue;
                     }
 
+                    // Decode text with the session encoding (multibyte sequences carried across chunks)
+                    let text = app_handle.state::<Mutex<StreamDecoder>>().lock().await.decode(&final_data);
+                    let segments = app_handle.state::<Mutex<Option<VtParser>>>().lock().await
+                        .as_mut()
+                        .map(|parser| parser.feed(&text));
+
                     // Emit event to frontend
-                    if let Err(e) = app_handle.emit("serial-data", final_data) {
+                    if let Err(e) = app_handle.emit("serial-data", final_data.clone()) {
                         log::error!("Failed to emit serial-data: {}", e);
                     }
+                    // Protocol decoders enabled for this session (Modbus RTU, packet schemas, NMEA, ...)
+                    let messages = app_handle.state::<Mutex<DecoderRegistry>>().lock().await
+                        .feed(Direction::Rx, &final_data, received_ns);
+                    emit_decoded_messages(&app_handle, messages).await;
+                    let fix = app_handle.state::<SharedFixTracker>().lock().ok().and_then(|mut t| t.take_update());
+                    if let Some(fix) = fix {
+                        if let Err(e) = app_handle.emit("gnss-fix", fix) {
+                            log::error!("Failed to emit gnss-fix: {}", e);
+                        }
+                    }
+                    poll_at_urcs(&app_handle);
+                    // ZMODEM auto-start (`sz`/`rz` run on the remote shell), ignored during a transfer
+                    if !app_handle.state::<TransferControl>().is_active() {
+                        let start = app_handle.state::<Mutex<ZmodemDetector>>().lock().await.feed(&final_data);
+                        if let Some(start) = start {
+                            if let Err(e) = app_handle.emit("zmodem-detected", start) {
+                                log::error!("Failed to emit zmodem-detected: {}", e);
+                            }
+                        }
+                    }
+                    // Assemble complete lines from the decoded text
+                    let lines = app_handle.state::<Mutex<LineAssembler>>().lock().await.feed(&text, received_ns);
+                    if let Err(e) = app_handle.emit("serial-text", DecodedText { data: final_data, text, segments }) {
+                        log::error!("Failed to emit serial-text: {}", e);
+                    }
+                    for line in lines {
+                        if let Err(e) = app_handle.emit("serial-line", line) {
+                            log::error!("Failed to emit serial-line: {}", e);
+                        }
+                    }
                 }
             });
 
@@ -110,7 +229,14 @@ pub fn run() {
             commands::connect,
             commands::disconnect,
             commands::send,
+            commands::compute_checksum,
+            commands::get_checksum_algorithms,
             commands::set_script,
+            commands::set_rx_encoding,
+            commands::set_vt_parsing,
+            commands::strip_escapes,
+            commands::get_line_assembly,
+            commands::set_line_assembly,
             // 端口共享命令
             commands::check_com0com_installed,
 
@@ -120,7 +246,92 @@ pub fn run() {
             commands::rename_virtual_pair,
             commands::get_sharing_status,
             commands::start_port_sharing,
-            commands::stop_port_sharing
+            commands::stop_port_sharing,
+            // 抓包命令
+            commands::start_capture,
+            commands::stop_capture,
+            commands::add_capture_marker,
+            commands::convert_capture_to_pcapng,
+            // 回放命令
+            commands::start_replay,
+            commands::stop_replay,
+            commands::get_replay_status,
+            // 触发规则命令
+            commands::get_trigger_rules,
+            commands::set_trigger_rules,
+            // 模拟器命令
+            commands::start_simulator,
+            commands::stop_simulator,
+            commands::get_simulator_status,
+            // Modbus 命令
+            commands::modbus_read_coils,
+            commands::modbus_read_discrete_inputs,
+            commands::modbus_read_holding_registers,
+            commands::modbus_read_input_registers,
+            commands::modbus_write_single_coil,
+            commands::modbus_write_single_register,
+            commands::modbus_write_multiple_coils,
+            commands::modbus_write_multiple_registers,
+            commands::modbus_read_write_registers,
+            // Modbus 从站模拟器命令
+            commands::start_modbus_slave,
+            commands::stop_modbus_slave,
+            commands::get_modbus_slave_status,
+            commands::get_modbus_slave_registers,
+            commands::set_modbus_slave_register,
+            // Modbus TCP 网关命令
+            commands::start_modbus_gateway,
+            commands::stop_modbus_gateway,
+            commands::get_modbus_gateway_status,
+            // 文件传输命令
+            commands::xmodem_send,
+            commands::xmodem_receive,
+            commands::zmodem_send,
+            commands::zmodem_receive,
+            commands::cancel_transfer,
+            // AT 指令命令
+            commands::send_at,
+            // SCPI 命令
+            commands::scpi_send,
+            commands::scpi_identify,
+            commands::scpi_drain_errors,
+            // 固件镜像命令
+            commands::inspect_firmware_image,
+            // STM32 Bootloader 命令
+            commands::stm32_flash,
+            commands::stm32_get_info,
+            // ESP ROM Bootloader 命令
+            commands::esp_flash,
+            commands::esp_detect_chip,
+            // 协议解码命令
+            commands::get_protocol_decoders,
+            commands::enable_protocol_decoder,
+            commands::disable_protocol_decoder,
+            // GNSS 命令
+            commands::get_gnss_fix,
+            commands::reset_gnss_fix,
+            // MAVLink 命令
+            commands::get_mavlink_stats,
+            commands::reset_mavlink_stats,
+            commands::get_mavlink_messages,
+            commands::send_mavlink,
+            // SLCAN 命令
+            commands::slcan_open,
+            commands::slcan_close,
+            commands::send_can_frame,
+            commands::start_can_bridge,
+            commands::stop_can_bridge,
+            commands::get_can_bridge_status,
+            // G-code 命令
+            commands::start_gcode_stream,
+            commands::pause_gcode_stream,
+            commands::resume_gcode_stream,
+            commands::cancel_gcode_stream,
+            commands::send_gcode_realtime,
+            // 报文描述命令
+            commands::set_packet_schema,
+            commands::decode_packet,
+            commands::encode_packet
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
@@ -139,3 +350,90 @@ pub fn run() {
             }
         });
 }
+
+/// Route URCs that reach the AT engine between `send_at` calls (skipped while a command is running)
+fn poll_at_urcs(app_handle: &tauri::AppHandle) {
+    let shared = app_handle.state::<SharedAtEngine>();
+    let Ok(mut guard) = shared.try_lock() else { return };
+    if let Some(engine) = guard.as_mut() {
+        let result = engine.poll_urcs(&mut |urc| {
+            if let Err(e) = app_handle.emit("at-urc", urc) {
+                log::error!("Failed to emit at-urc: {}", e);
+            }
+        });
+        // Session closed: the next send_at starts a new engine
+        if result.is_err() {
+            *guard = None;
+        }
+    }
+}
+
+/// Forward protocol decoder output to the frontend and store it in the active capture
+async fn emit_decoded_messages(app_handle: &tauri::AppHandle, messages: Vec<DecodedMessage>) {
+    if messages.is_empty() {
+        return;
+    }
+    let manager = app_handle.state::<Mutex<SerialManager>>();
+    let manager = manager.lock().await;
+    for message in messages {
+        if let Err(e) = manager.record_decoded(&message) {
+            log::error!("Failed to capture decoded message: {}", e);
+        }
+        if let Err(e) = app_handle.emit("decoded-message", message) {
+            log::error!("Failed to emit decoded-message: {}", e);
+        }
+    }
+}
+
+/// Execute backend-side trigger actions and forward the hit to the frontend
+async fn run_trigger_actions(app_handle: &tauri::AppHandle, hits: Vec<TriggerHit>) {
+    let serial_state = app_handle.state::<Mutex<SerialManager>>();
+
+    for hit in hits {
+        for action in &hit.actions {
+            let result = match action {
+                TriggerAction::Marker { text } => {
+                    let manager = serial_state.lock().await;
+                    if manager.is_capturing() {
+                        manager.add_capture_marker(text)
+                    } else {
+                        Ok(())
+                    }
+                }
+                TriggerAction::Reply { data, hex } => {
+                    match TriggerAction::reply_bytes(data, *hex) {
+                        Ok(bytes) => serial_state.lock().await.write(&bytes).await,
+                        Err(e) => Err(e),
+                    }
+                }
+                TriggerAction::StartLogging { path, format } => {
+                    match serial_util::core::capture::CaptureFormat::parse(format) {
+                        Ok(format) => serial_state.lock().await.start_capture(std::path::Path::new(path), format, false),
+                        Err(e) => Err(e),
+                    }
+                }
+                TriggerAction::StopLogging => serial_state.lock().await.stop_capture(),
+                TriggerAction::SetDtr { level } => serial_state.lock().await.set_dtr(*level),
+                TriggerAction::ToggleDtr => {
+                    let mut manager = serial_state.lock().await;
+                    let level = !manager.dtr();
+                    manager.set_dtr(level)
+                }
+                TriggerAction::Notify { message } => {
+                    app_handle
+                        .emit("trigger-notify", serde_json::json!({ "rule": hit.rule, "message": message }))
+                        .map_err(|e| anyhow::anyhow!(e.to_string()))
+                }
+                // Rendered by the frontend from the trigger-hit event
+                TriggerAction::Highlight { .. } => Ok(()),
+            };
+            if let Err(e) = result {
+                log::error!("Trigger '{}' action {:?} failed: {}", hit.rule, action, e);
+            }
+        }
+
+        if let Err(e) = app_handle.emit("trigger-hit", &hit) {
+            log::error!("Failed to emit trigger-hit: {}", e);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 import { Component, ErrorInfo, ReactNode } from 'react';
 import { Layout } from './components/Layout';
 import { useEffect, useState, useCallback, useRef } from 'react';
-import { SerialService, type SerialConfig } from './services/ipc';
+import { SerialService, type ChecksumSpec, type SerialConfig } from './services/ipc';
 import { TerminalContainer } from './components/Terminal/TerminalContainer';
 import { type LogData } from './components/Terminal/LogEntry';
 import { ControlPanel } from './components/ControlPanel';
@@ -213,19 +213,20 @@ function App() {
     }
   };
 
-  const handleSend = async (data: Uint8Array | number[]) => {
+  const handleSend = async (data: Uint8Array | number[], checksum?: ChecksumSpec) => {
     try {
       // Apply JS Tx Hook (if any)
       // If external hook is active in backend, this will likely be pass-through (empty js script)
       const processedData = ScriptService.runTxHook(data);
 
-      await SerialService.send(processedData);
+      // Log what the backend actually wrote (backend hook + appended checksum)
+      const written = await SerialService.send(processedData, checksum);
 
       setLogs(prev => [...prev, {
         id: Date.now() + Math.random(),
         timestamp: Date.now(),
         type: 'TX',
-        data: processedData
+        data: new Uint8Array(written)
       }]);
     } catch (e: any) {
       console.error(e);
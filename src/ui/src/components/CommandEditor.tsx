@@ -1,14 +1,15 @@
 import { useEffect, useRef, useCallback, useState } from 'react';
 import Editor, { OnMount } from '@monaco-editor/react';
 import { useScriptRunner } from '../hooks/useScriptRunner';
-import { SerialService } from '../services/ipc';
+import { SerialService, type ChecksumSpec } from '../services/ipc';
+import { hexToBytes, parseChecksum, parseHexCommand } from '../utils/commandFormat';
 import { Loader2, Square, HelpCircle, X, ChevronDown, ChevronRight } from 'lucide-react';
 import { createRoot } from 'react-dom/client';
 
 interface CommandEditorProps {
     content: string;
     setContent: (val: string) => void;
-    onSend: (data: Uint8Array | number[]) => void;
+    onSend: (data: Uint8Array | number[], checksum?: ChecksumSpec) => void;
     onLog?: (msg: string) => void;
     connected: boolean;
     wordWrap?: 'on' | 'off';
@@ -24,8 +25,8 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
     const foldWidgetsRef = useRef<any[]>([]); // Store { widget, root }
 
     const { run: runScript, terminate: stopScript, feedData, status: scriptStatus } = useScriptRunner({
-        onSend: (data) => {
-            if (connected) onSend(data);
+        onSend: (data, checksum) => {
+            if (connected) onSend(data, checksum);
         },
         onLog: (msg) => {
             onLog?.(msg);
@@ -55,7 +56,7 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
     type ParseResult =
         | { type: 'noop'; data: null }
         | { type: 'error'; data: string }
-        | { type: 'hex'; data: Uint8Array }
+        | { type: 'hex'; data: Uint8Array; checksum?: ChecksumSpec }
         | { type: 'text'; data: Uint8Array }
         | { type: 'script'; data: string };
 
@@ -124,17 +125,16 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
             }
         }
 
-        // 2. Hex
-        if (trimmed.startsWith('*') && trimmed.endsWith('*') && trimmed.length > 1) {
-            const hexStr = trimmed.substring(1, trimmed.length - 1).trim();
-            const cleanHex = hexStr.replace(/[^0-9A-Fa-f]/g, '');
-            if (cleanHex.length % 2 !== 0) return { type: 'error', data: 'Invalid Hex Length' };
-
-            const bytes = new Uint8Array(cleanHex.length / 2);
-            for (let i = 0; i < cleanHex.length; i += 2) {
-                bytes[i / 2] = parseInt(cleanHex.substring(i, i + 2), 16);
+        // 2. Hex (optionally with a checksum definition after the closing '*')
+        const hexCommand = parseHexCommand(trimmed);
+        if (hexCommand) {
+            const bytes = hexToBytes(hexCommand.hex);
+            if (!bytes) return { type: 'error', data: 'Invalid Hex Length' };
+            try {
+                return { type: 'hex', data: bytes, checksum: parseChecksum(hexCommand.checksum) };
+            } catch (e) {
+                return { type: 'error', data: String(e) };
             }
-            return { type: 'hex', data: bytes };
         }
 
         // 3. Fallback: Text
@@ -193,7 +193,7 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
         const result = parseCommandAtLine(model, lineNumber);
 
         if (result.type === 'hex') {
-            onSend(result.data);
+            onSend(result.data, result.checksum);
         } else if (result.type === 'text') {
             onSend(result.data);
         } else if (result.type === 'script') {
@@ -311,7 +311,7 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
             if (inBlock) continue;
 
             if (trimmed && !trimmed.startsWith('#')) {
-                const isHex = trimmed.startsWith('*') && trimmed.endsWith('*') && trimmed.length > 1;
+                const isHex = parseHexCommand(trimmed) !== null;
 
                 newDecorations.push({
                     range: new monacoRef.current.Range(i, 1, i, 1),
@@ -533,6 +533,7 @@ export function CommandEditor({ content, setContent, onSend, onLog, connected, w
                                     <li><strong># Name</strong>: Command Header</li>
                                     <li><strong>Hello</strong>: Plain Text Command</li>
                                     <li><strong>*AA BB*</strong>: Hex Command (wrapped in asterisks)</li>
+                                    <li><strong>*01 03 00 00 00 01* crc16-modbus:le</strong>: Hex Command with a checksum appended at send time (<code>:be</code> / <code>:le</code> byte order, <code>:skip=2</code> to exclude leading bytes)</li>
                                 </ul>
                             </div>
 
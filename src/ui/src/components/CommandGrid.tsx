@@ -2,12 +2,13 @@ import { useState, useEffect, useCallback, useRef } from 'react';
 import { Play, Trash2, Plus, Loader2, Square, ChevronDown, ChevronUp } from 'lucide-react';
 import { HexSwitch } from './ui/HexSwitch';
 import { useScriptRunner } from '../hooks/useScriptRunner';
-import { SerialService } from '../services/ipc';
+import { SerialService, type ChecksumSpec } from '../services/ipc';
+import { formatHexCommand, hexToBytes, parseChecksum, parseHexCommand } from '../utils/commandFormat';
 
 interface CommandGridProps {
     content: string;
     setContent: (val: string) => void;
-    onSend: (data: Uint8Array | number[]) => void;
+    onSend: (data: Uint8Array | number[], checksum?: ChecksumSpec) => void;
     onLog: (msg: string) => void;
     connected: boolean;
     onAdd?: () => void;
@@ -20,6 +21,8 @@ interface SavedCommand {
     command: string;
     isHex: boolean;
     isScript?: boolean;
+    // Checksum definition appended at send time, e.g. 'crc16-modbus:le' (hex commands only)
+    checksum?: string;
 }
 
 // Sub-component for individual card logic (Textarea resizing)
@@ -95,10 +98,22 @@ const CommandItem = ({
                         {isExpanded ? <ChevronUp className="w-3.5 h-3.5" /> : <ChevronDown className="w-3.5 h-3.5" />}
                     </button>
                 ) : (
-                    <HexSwitch
-                        checked={cmd.isHex}
-                        onChange={(val) => onUpdate(cmd.id, 'isHex', val)}
-                    />
+                    <>
+                        {cmd.isHex && (
+                            <input
+                                className="w-24 bg-transparent border-b border-transparent hover:border-border focus:border-primary focus:outline-none px-1 py-0.5 font-mono text-[10px] transition-colors"
+                                value={cmd.checksum ?? ''}
+                                onChange={(e) => onUpdate(cmd.id, 'checksum', e.target.value)}
+                                placeholder="checksum"
+                                title="Checksum appended at send time, e.g. crc16-modbus:le"
+                                spellCheck={false}
+                            />
+                        )}
+                        <HexSwitch
+                            checked={cmd.isHex}
+                            onChange={(val) => onUpdate(cmd.id, 'isHex', val)}
+                        />
+                    </>
                 )}
 
                 <button
@@ -143,8 +158,8 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
 
     // --- Script Runner ---
     const { run: runScript, terminate: stopScript, feedData, status: scriptStatus } = useScriptRunner({
-        onSend: (data) => {
-            if (connected) onSend(data);
+        onSend: (data, checksum) => {
+            if (connected) onSend(data, checksum);
         },
         onLog: (msg) => onLog(msg),
         onError: (err) => console.error("Script Error:", err)
@@ -216,10 +231,13 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
             } else {
                 let isHex = false;
                 let cmdText = trimmed;
+                let checksum: string | undefined;
 
-                if (trimmed.startsWith('*') && trimmed.endsWith('*') && trimmed.length > 1) {
+                const hexCommand = parseHexCommand(trimmed);
+                if (hexCommand) {
                     isHex = true;
-                    cmdText = trimmed.substring(1, trimmed.length - 1).trim();
+                    cmdText = hexCommand.hex;
+                    checksum = hexCommand.checksum;
                 } else if (trimmed.toLowerCase().startsWith('hex:')) {
                     // Legacy fallback just in case
                     isHex = true;
@@ -231,7 +249,8 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
                     name: currentName,
                     command: cmdText,
                     isHex,
-                    isScript: false
+                    isScript: false,
+                    checksum
                 });
                 currentName = 'Cmd';
             }
@@ -246,7 +265,8 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
                 if (newItems[i].name !== commands[i].name ||
                     newItems[i].command !== commands[i].command ||
                     newItems[i].isHex !== commands[i].isHex ||
-                    newItems[i].isScript !== commands[i].isScript) {
+                    newItems[i].isScript !== commands[i].isScript ||
+                    newItems[i].checksum !== commands[i].checksum) {
                     changed = true;
                     break;
                 }
@@ -261,7 +281,8 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
                 oldItem.name === newItem.name &&
                 oldItem.command === newItem.command &&
                 oldItem.isHex === newItem.isHex &&
-                oldItem.isScript === newItem.isScript) {
+                oldItem.isScript === newItem.isScript &&
+                oldItem.checksum === newItem.checksum) {
                 return { ...newItem, id: oldItem.id };
             }
             return { ...newItem, id: Math.random().toString(36).substr(2, 9) };
@@ -284,7 +305,7 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
                 parts.push(c.command);
                 parts.push('```');
             } else if (c.isHex) {
-                parts.push(`*${c.command}*`);
+                parts.push(formatHexCommand(c.command, c.checksum));
             } else {
                 parts.push(c.command);
             }
@@ -313,27 +334,26 @@ export function CommandGrid({ content, setContent, onSend, onLog, connected, onA
         // Scripts: Run via runner
         if (cmd.isScript) {
             // Terminate existing if any? Hook handles restart.
-            runScript(cmd.command, commands.map(c => c.isHex ? `*${c.command}*` : c.command));
+            runScript(cmd.command, commands.map(c => c.isHex ? formatHexCommand(c.command, c.checksum) : c.command));
             return;
         }
 
         // Normal commands
         if (!connected) return;
         try {
-            let data: Uint8Array;
             if (cmd.isHex) {
-                const cleanHex = cmd.command.replace(/[^0-9A-Fa-f]/g, '');
-                const bytes = new Uint8Array(cleanHex.length / 2);
-                for (let i = 0; i < cleanHex.length; i += 2) {
-                    bytes[i / 2] = parseInt(cleanHex.substring(i, i + 2), 16);
+                const bytes = hexToBytes(cmd.command);
+                if (!bytes) {
+                    onLog(`Invalid hex in command '${cmd.name}'`);
+                    return;
                 }
-                data = bytes;
+                onSend(bytes, parseChecksum(cmd.checksum));
             } else {
-                data = new TextEncoder().encode(cmd.command);
+                onSend(new TextEncoder().encode(cmd.command));
             }
-            onSend(data);
         } catch (e) {
             console.error("Command Execution Error", e);
+            onLog(`Command '${cmd.name}' failed: ${e}`);
         }
     };
 
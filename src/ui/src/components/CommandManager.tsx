@@ -8,6 +8,7 @@ import yaml from 'js-yaml';
 import { useDebounce } from '../hooks/useDebounce';
 import { CommandEditor } from './CommandEditor';
 import { CommandGrid } from './CommandGrid';
+import type { ChecksumSpec } from '../services/ipc';
 
 // Legacy Types for Migration
 interface StoredCommand {
@@ -17,7 +18,7 @@ interface StoredCommand {
 }
 
 interface CommandManagerProps {
-    onSend: (data: Uint8Array | number[]) => void;
+    onSend: (data: Uint8Array | number[], checksum?: ChecksumSpec) => void;
     onLog: (msg: string) => void;
     connected: boolean;
     filePath: string;
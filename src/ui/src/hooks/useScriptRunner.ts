@@ -1,9 +1,11 @@
 import { useState, useRef, useCallback, useEffect } from 'react';
+import type { ChecksumSpec } from '../services/ipc';
+import { parseChecksum, parseHexCommand } from '../utils/commandFormat';
 
 type ScriptStatus = 'idle' | 'running' | 'stopping' | 'error';
 
 interface UseScriptRunnerProps {
-    onSend: (data: Uint8Array) => void;
+    onSend: (data: Uint8Array, checksum?: ChecksumSpec) => void;
     onLog?: (msg: string) => void;
     onError?: (err: string) => void;
 }
@@ -126,12 +128,19 @@ export function useScriptRunner({ onSend, onLog, onError }: UseScriptRunnerProps
                 // Convert data to Uint8Array if likely
                 // Worker sends whatever 'send' got.
                 let payload: Uint8Array;
+                let checksum: ChecksumSpec | undefined;
 
                 if (typeof data === 'string') {
-                    const trimmed = data.trim();
-                    if (trimmed.startsWith('*') && trimmed.endsWith('*')) {
-                        // Hex String Parse (*AA BB*)
-                        const hex = trimmed.substring(1, trimmed.length - 1).replace(/[^0-9A-Fa-f]/g, '');
+                    const hexCommand = parseHexCommand(data);
+                    if (hexCommand) {
+                        // Hex String Parse (*AA BB*, optionally followed by a checksum definition)
+                        try {
+                            checksum = parseChecksum(hexCommand.checksum);
+                        } catch (err) {
+                            onError?.(String(err));
+                            return;
+                        }
+                        const hex = hexCommand.hex.replace(/[^0-9A-Fa-f]/g, '');
                         payload = new Uint8Array(Math.ceil(hex.length / 2));
                         for (let i = 0; i < hex.length; i += 2) {
                             // Handle potential odd length by grabbing 2 or 1 char
@@ -146,7 +155,7 @@ export function useScriptRunner({ onSend, onLog, onError }: UseScriptRunnerProps
                 } else {
                     payload = new Uint8Array(data); // Assume it's array-like or buffer
                 }
-                onSend(payload);
+                onSend(payload, checksum);
             } else if (type === 'log') {
                 onLog?.(data);
             } else if (type === 'error') {
@@ -16,6 +16,14 @@ export interface SerialConfig {
     parity: string;
     stop_bits: number;
     timeout?: number; // ms
+    encoding?: string; // RX text encoding, e.g. 'utf8' | 'gbk' | 'shift_jis'
+}
+
+// Checksum appended at send time, e.g. { algorithm: 'crc16-modbus', byteOrder: 'little' }
+export interface ChecksumSpec {
+    algorithm: string;
+    byteOrder?: 'big' | 'little';
+    skip?: number; // leading bytes excluded from the calculation
 }
 
 // Helper to check if running in Tauri
@@ -53,12 +61,13 @@ export class SerialService {
         return invoke('disconnect');
     }
 
-    static async send(content: Uint8Array | number[]): Promise<void> {
+    // Resolves with the bytes actually written (after the backend TX hook and checksum)
+    static async send(content: Uint8Array | number[], checksum?: ChecksumSpec): Promise<number[]> {
         if (!isTauri()) {
-            console.log("Mock Send:", content);
-            return Promise.resolve();
+            console.log("Mock Send:", content, checksum);
+            return Promise.resolve(Array.from(content));
         }
-        return invoke('send', { content: Array.from(content) });
+        return invoke<number[]>('send', { content: Array.from(content), checksum });
     }
 
     static async listen(callback: (data: Uint8Array) => void): Promise<UnlistenFn> {
@@ -0,0 +1,58 @@
+import type { ChecksumSpec } from '../services/ipc';
+
+// Hex command line, optionally followed by a send-time checksum definition:
+//   *01 03 00 00 00 01*                  plain hex
+//   *01 03 00 00 00 01* crc16-modbus:le  checksum appended by the backend
+//   *AA 55 01 02* sum8:skip=2             first two bytes excluded from the checksum
+// The definition is `<algorithm>[:be|:le][:skip=<n>]` (big-endian, no skip by default);
+// algorithm names are those returned by `get_checksum_algorithms`.
+const HEX_COMMAND = /^\*(.*)\*(?:\s+(\S+))?$/;
+
+export interface HexCommand {
+    hex: string;
+    checksum?: string;
+}
+
+export function parseHexCommand(line: string): HexCommand | null {
+    const match = HEX_COMMAND.exec(line.trim());
+    if (!match) return null;
+    return { hex: match[1].trim(), checksum: match[2] };
+}
+
+export function formatHexCommand(hex: string, checksum?: string): string {
+    const definition = checksum?.trim();
+    return definition ? `*${hex}* ${definition}` : `*${hex}*`;
+}
+
+// Converts a checksum definition such as `crc16-modbus:le` to the backend spec;
+// throws on an unknown option so a typo is not silently sent as big-endian
+export function parseChecksum(definition?: string): ChecksumSpec | undefined {
+    const trimmed = definition?.trim();
+    if (!trimmed) return undefined;
+    const [algorithm, ...options] = trimmed.split(':');
+    if (!algorithm) throw new Error(`Missing checksum algorithm in '${trimmed}'`);
+    const spec: ChecksumSpec = { algorithm, byteOrder: 'big' };
+    for (const option of options.map((o) => o.trim().toLowerCase())) {
+        const skip = /^skip=(\d+)$/.exec(option);
+        if (option === 'be' || option === 'big') {
+            spec.byteOrder = 'big';
+        } else if (option === 'le' || option === 'little') {
+            spec.byteOrder = 'little';
+        } else if (skip) {
+            spec.skip = parseInt(skip[1], 10);
+        } else {
+            throw new Error(`Invalid checksum option '${option}' in '${trimmed}'`);
+        }
+    }
+    return spec;
+}
+
+export function hexToBytes(hex: string): Uint8Array | null {
+    const cleanHex = hex.replace(/[^0-9A-Fa-f]/g, '');
+    if (cleanHex.length % 2 !== 0) return null;
+    const bytes = new Uint8Array(cleanHex.length / 2);
+    for (let i = 0; i < cleanHex.length; i += 2) {
+        bytes[i / 2] = parseInt(cleanHex.substring(i, i + 2), 16);
+    }
+    return bytes;
+}